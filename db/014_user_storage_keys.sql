@@ -0,0 +1,18 @@
+-- keys used to encrypt the stored media of a user. each key is wrapped by the
+-- server master key named in master_key. retired keys are no longer used for
+-- new objects but are kept so objects written with them can still be read
+create table user_storage_keys (
+    id integer primary key generated always as identity,
+
+    owner integer,
+
+    master_key varchar not null,
+    wrapped_key bytea not null,
+
+    created timestamp with time zone not null,
+    retired timestamp with time zone,
+
+    constraint owner_fk foreign key (owner) references users (id) on delete set null
+);
+
+create unique index user_storage_keys_active_idx on user_storage_keys (owner) where retired is null;
@@ -1,14 +1,19 @@
 create table entries (
     id integer primary key generated always as identity,
 
-    day timestamp with time zone not null default CURRENT_DATE,
+    day timestamp with time zone not null default CURRENT_TIMESTAMP,
 
     created timestamp with time zone not null default CURRENT_DATE,
     updated timestamp with time zone,
     deleted timestamp with time zone,
 
+    version integer not null default 1,
+
     owner integer not null,
 
-    constraint unique_day_owner_key unique (day, owner),
     constraint owner_fk foreign key (owner) references users (id)
 );
+
+create index entries_owner_day_idx on entries (owner, day);
+
+create index entries_deleted_idx on entries (deleted) where deleted is not null;
@@ -0,0 +1,50 @@
+-- uploaded media stored by the blake3 hash of its contents. blobs are kept per
+-- user so the same data uploaded by two users is stored twice and an upload
+-- never shows that another user already has the data. ref_count is kept up to
+-- date by triggers on the media tables that reference a blob and a blob is
+-- removed once nothing references it
+create table blobs (
+    owner integer not null,
+    hash varchar not null,
+
+    size bigint not null,
+    extension varchar not null,
+
+    ref_count integer not null default 0,
+
+    created timestamp with time zone not null,
+
+    primary key (owner, hash),
+
+    constraint owner_fk foreign key (owner) references users (id)
+);
+
+-- media rows reference a blob by its hash and the blob of the entry owner is
+-- used. a missing blob is rejected the same as a foreign key would
+create function blobs_count_refs() returns trigger as $$
+begin
+    if TG_OP = 'UPDATE' or TG_OP = 'DELETE' then
+        if OLD.blob is not null then
+            update blobs set ref_count = ref_count - 1
+            where owner = (select owner from entries where id = OLD.entry) and
+                  hash = OLD.blob;
+        end if;
+    end if;
+
+    if TG_OP = 'INSERT' or TG_OP = 'UPDATE' then
+        if NEW.blob is not null then
+            update blobs set ref_count = ref_count + 1
+            where owner = (select owner from entries where id = NEW.entry) and
+                  hash = NEW.blob;
+
+            if not found then
+                raise exception using
+                    errcode = 'foreign_key_violation',
+                    message = format('blob %s does not exist for the owner of entry %s', NEW.blob, NEW.entry);
+            end if;
+        end if;
+    end if;
+
+    return null;
+end;
+$$ language plpgsql;
@@ -2,10 +2,13 @@ create table text_entries (
     id integer primary key generated always as identity,
 
     thought text not null,
+    thought_search tsvector generated always as (to_tsvector('english', thought)) stored,
 
     private boolean not null default false,
 
     entry integer not null,
 
     constraint entry_fk foreign key (entry) references entries (id)
-);
\ No newline at end of file
+);
+
+create index text_entries_thought_search_idx on text_entries using gin (thought_search);
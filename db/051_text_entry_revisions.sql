@@ -0,0 +1,23 @@
+-- revisions are kept when their text entry is removed from an entry so the
+-- history of what was written is never lost. text_entry is not a foreign key
+-- since the text entry may be gone and the last revision of a removed text
+-- entry is marked as removed
+create table text_entry_revisions (
+    id integer primary key generated always as identity,
+
+    entry integer not null,
+    text_entry integer not null,
+
+    thought text not null,
+    private boolean not null,
+    removed boolean not null default false,
+
+    created timestamp with time zone not null,
+    edited_by integer not null,
+
+    constraint entry_fk foreign key (entry) references entries (id),
+    constraint edited_by_fk foreign key (edited_by) references users (id)
+);
+
+create index text_entry_revisions_entry_idx on text_entry_revisions (entry);
+create index text_entry_revisions_text_entry_idx on text_entry_revisions (text_entry);
@@ -12,5 +12,17 @@ create table audio_entries (
 
     file_size bigint default 0,
 
+    duration_ms bigint,
+    codec varchar,
+    sample_rate integer,
+    channels integer,
+
+    blob varchar,
+
     constraint entry_fk foreign key (entry) references entries (id)
 );
+
+create index audio_entries_blob_idx on audio_entries (blob);
+
+create trigger audio_entries_blob_refs after insert or delete or update of blob on audio_entries
+    for each row execute function blobs_count_refs();
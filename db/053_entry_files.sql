@@ -0,0 +1,27 @@
+create table entry_files (
+    id integer primary key generated always as identity,
+
+    private boolean not null default false,
+
+    comment varchar,
+    name varchar not null,
+
+    entry integer not null,
+
+    mime_type varchar not null,
+    mime_subtype varchar not null,
+
+    file_size bigint not null default 0,
+
+    created timestamp with time zone not null,
+
+    blob varchar,
+
+    constraint entry_fk foreign key (entry) references entries (id)
+);
+
+create index entry_files_entry_idx on entry_files (entry);
+create index entry_files_blob_idx on entry_files (blob);
+
+create trigger entry_files_blob_refs after insert or delete or update of blob on entry_files
+    for each row execute function blobs_count_refs();
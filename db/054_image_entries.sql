@@ -0,0 +1,31 @@
+create table image_entries (
+    id integer primary key generated always as identity,
+
+    private boolean not null default false,
+
+    comment varchar,
+
+    entry integer not null,
+
+    mime_type varchar not null,
+    mime_subtype varchar not null,
+
+    file_size bigint not null default 0,
+
+    width integer not null,
+    height integer not null,
+
+    thumbnail_mime_subtype varchar not null,
+
+    created timestamp with time zone not null,
+
+    blob varchar,
+
+    constraint entry_fk foreign key (entry) references entries (id)
+);
+
+create index image_entries_entry_idx on image_entries (entry);
+create index image_entries_blob_idx on image_entries (blob);
+
+create trigger image_entries_blob_refs after insert or delete or update of blob on image_entries
+    for each row execute function blobs_count_refs();
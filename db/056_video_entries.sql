@@ -0,0 +1,26 @@
+create table video_entries (
+    id integer primary key generated always as identity,
+
+    private boolean not null default false,
+
+    comment varchar,
+
+    entry integer not null,
+
+    mime_type varchar not null,
+    mime_subtype varchar not null,
+
+    file_size bigint not null default 0,
+
+    created timestamp with time zone not null,
+
+    blob varchar,
+
+    constraint entry_fk foreign key (entry) references entries (id)
+);
+
+create index video_entries_entry_idx on video_entries (entry);
+create index video_entries_blob_idx on video_entries (blob);
+
+create trigger video_entries_blob_refs after insert or delete or update of blob on video_entries
+    for each row execute function blobs_count_refs();
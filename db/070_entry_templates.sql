@@ -0,0 +1,16 @@
+create table entry_templates (
+    id integer primary key generated always as identity,
+
+    name varchar not null,
+    owner integer not null,
+
+    comment varchar,
+
+    data json not null,
+
+    created timestamp with time zone not null,
+    updated timestamp with time zone,
+
+    constraint unique_template_name_owner unique (name, owner),
+    constraint owner_fk foreign key (owner) references users (id)
+);
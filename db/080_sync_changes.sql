@@ -0,0 +1,89 @@
+create table sync_changes (
+    id bigint primary key generated always as identity,
+
+    owner integer not null,
+
+    kind varchar not null,
+    record bigint not null,
+
+    tx bigint not null default txid_current(),
+    changed timestamp with time zone not null default CURRENT_TIMESTAMP,
+
+    constraint owner_fk foreign key (owner) references users (id)
+);
+
+create index sync_changes_owner_tx_idx on sync_changes (owner, tx);
+create index sync_changes_record_idx on sync_changes (kind, record);
+
+-- the newest transaction with changes that have been removed from the feed.
+-- tokens at or before it may have missed a deletion
+create table sync_horizon (
+    id boolean primary key default true,
+
+    tx bigint not null default 0,
+
+    constraint single_row check (id)
+);
+
+insert into sync_horizon (id, tx) values (true, 0);
+
+-- records that a row was inserted, updated, or deleted for the sync feed.
+-- tags and custom field values are part of an entry so they are recorded as
+-- a change to the entry they belong to
+create function sync_record_change() returns trigger as $$
+declare
+    row_data record;
+    change_owner integer;
+    change_kind varchar;
+    change_record bigint;
+begin
+    if TG_OP = 'DELETE' then
+        row_data := OLD;
+    else
+        row_data := NEW;
+    end if;
+
+    case TG_TABLE_NAME
+        when 'entries' then
+            change_kind := 'entries';
+            change_record := row_data.id;
+            change_owner := row_data.owner;
+        when 'tags', 'custom_fields' then
+            change_kind := TG_TABLE_NAME;
+            change_record := row_data.id;
+            change_owner := row_data.owner;
+        when 'entries2tags', 'custom_field_entries' then
+            change_kind := 'entries';
+            change_record := row_data.entry;
+            select owner into change_owner from entries where id = row_data.entry;
+        else
+            change_kind := TG_TABLE_NAME;
+            change_record := row_data.id;
+            select owner into change_owner from entries where id = row_data.entry;
+    end case;
+
+    if change_owner is not null then
+        insert into sync_changes (owner, kind, record)
+        values (change_owner, change_kind, change_record);
+    end if;
+
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger entries_sync after insert or update or delete on entries
+    for each row execute function sync_record_change();
+create trigger tags_sync after insert or update or delete on tags
+    for each row execute function sync_record_change();
+create trigger custom_fields_sync after insert or update or delete on custom_fields
+    for each row execute function sync_record_change();
+create trigger entries2tags_sync after insert or update or delete on entries2tags
+    for each row execute function sync_record_change();
+create trigger custom_field_entries_sync after insert or update or delete on custom_field_entries
+    for each row execute function sync_record_change();
+create trigger text_entries_sync after insert or update or delete on text_entries
+    for each row execute function sync_record_change();
+create trigger entry_markers_sync after insert or update or delete on entry_markers
+    for each row execute function sync_record_change();
+create trigger entry_comments_sync after insert or update or delete on entry_comments
+    for each row execute function sync_record_change();
@@ -0,0 +1,25 @@
+create table image_entries (
+    id integer primary key generated always as identity,
+
+    private boolean not null default false,
+
+    comment varchar,
+
+    entry integer not null,
+
+    mime_type varchar not null,
+    mime_subtype varchar not null,
+
+    file_size bigint not null default 0,
+
+    width integer not null,
+    height integer not null,
+
+    thumbnail_mime_subtype varchar not null,
+
+    created timestamp with time zone not null,
+
+    constraint entry_fk foreign key (entry) references entries (id)
+);
+
+create index image_entries_entry_idx on image_entries (entry);
@@ -0,0 +1,20 @@
+create table video_entries (
+    id integer primary key generated always as identity,
+
+    private boolean not null default false,
+
+    comment varchar,
+
+    entry integer not null,
+
+    mime_type varchar not null,
+    mime_subtype varchar not null,
+
+    file_size bigint not null default 0,
+
+    created timestamp with time zone not null,
+
+    constraint entry_fk foreign key (entry) references entries (id)
+);
+
+create index video_entries_entry_idx on video_entries (entry);
@@ -0,0 +1,5 @@
+alter table audio_entries
+    add column duration_ms bigint,
+    add column codec varchar,
+    add column sample_rate integer,
+    add column channels integer;
@@ -0,0 +1,25 @@
+create table processing_jobs (
+    id bigint primary key generated always as identity,
+
+    audio integer not null,
+
+    command varchar not null,
+
+    status varchar not null default 'pending',
+    attempts integer not null default 0,
+    next_attempt timestamp with time zone not null,
+
+    output text,
+    error varchar,
+
+    text_entry integer,
+
+    created timestamp with time zone not null,
+    updated timestamp with time zone,
+
+    constraint audio_fk foreign key (audio) references audio_entries (id) on delete cascade,
+    constraint text_entry_fk foreign key (text_entry) references text_entries (id) on delete set null
+);
+
+create index processing_jobs_audio_idx on processing_jobs (audio);
+create index processing_jobs_pending_idx on processing_jobs (next_attempt) where status = 'pending';
@@ -0,0 +1,13 @@
+create index sync_changes_record_idx on sync_changes (kind, record);
+
+-- the newest transaction with changes that have been removed from the feed.
+-- tokens at or before it may have missed a deletion
+create table sync_horizon (
+    id boolean primary key default true,
+
+    tx bigint not null default 0,
+
+    constraint single_row check (id)
+);
+
+insert into sync_horizon (id, tx) values (true, 0);
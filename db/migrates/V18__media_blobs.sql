@@ -0,0 +1,23 @@
+alter table image_entries
+    add column blob varchar;
+
+create index image_entries_blob_idx on image_entries (blob);
+
+create trigger image_entries_blob_refs after insert or delete or update of blob on image_entries
+    for each row execute function blobs_count_refs();
+
+alter table video_entries
+    add column blob varchar;
+
+create index video_entries_blob_idx on video_entries (blob);
+
+create trigger video_entries_blob_refs after insert or delete or update of blob on video_entries
+    for each row execute function blobs_count_refs();
+
+alter table entry_files
+    add column blob varchar;
+
+create index entry_files_blob_idx on entry_files (blob);
+
+create trigger entry_files_blob_refs after insert or delete or update of blob on entry_files
+    for each row execute function blobs_count_refs();
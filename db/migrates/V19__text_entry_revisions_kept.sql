@@ -0,0 +1,19 @@
+-- revisions are kept when their text entry is removed from an entry so the
+-- history of what was written is never lost. the last revision of a removed
+-- text entry is marked as removed and revisions are only deleted along with
+-- their entry
+alter table text_entry_revisions
+    add column entry integer,
+    add column removed boolean not null default false;
+
+update text_entry_revisions
+set entry = text_entries.entry
+from text_entries
+where text_entry_revisions.text_entry = text_entries.id;
+
+alter table text_entry_revisions
+    alter column entry set not null,
+    drop constraint text_entry_fk,
+    add constraint entry_fk foreign key (entry) references entries (id);
+
+create index text_entry_revisions_entry_idx on text_entry_revisions (entry);
@@ -0,0 +1,4 @@
+alter table text_entries
+    add column thought_search tsvector generated always as (to_tsvector('english', thought)) stored;
+
+create index text_entries_thought_search_idx on text_entries using gin (thought_search);
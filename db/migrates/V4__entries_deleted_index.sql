@@ -0,0 +1 @@
+create index entries_deleted_idx on entries (deleted) where deleted is not null;
@@ -0,0 +1,26 @@
+create table text_entry_revisions (
+    id integer primary key generated always as identity,
+
+    text_entry integer not null,
+
+    thought text not null,
+    private boolean not null,
+
+    created timestamp with time zone not null,
+    edited_by integer not null,
+
+    constraint text_entry_fk foreign key (text_entry) references text_entries (id) on delete cascade,
+    constraint edited_by_fk foreign key (edited_by) references users (id)
+);
+
+create index text_entry_revisions_text_entry_idx on text_entry_revisions (text_entry);
+
+-- existing text entries start with a single revision from the entry owner
+insert into text_entry_revisions (text_entry, thought, private, created, edited_by)
+select text_entries.id,
+       text_entries.thought,
+       text_entries.private,
+       coalesce(entries.updated, entries.created),
+       entries.owner
+from text_entries
+join entries on text_entries.entry = entries.id;
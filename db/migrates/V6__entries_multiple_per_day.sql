@@ -0,0 +1,7 @@
+alter table entries
+    drop constraint unique_day_owner_key;
+
+create index entries_owner_day_idx on entries (owner, day);
+
+alter table entries
+    alter column day set default CURRENT_TIMESTAMP;
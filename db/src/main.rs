@@ -3,6 +3,7 @@ mod error;
 mod postgres;
 mod migrate;
 mod gen_test;
+mod storage;
 
 fn commands() -> clap::Command {
     use clap::{Command, Arg, ArgAction};
@@ -57,6 +58,36 @@ fn commands() -> clap::Command {
                 .arg(postgres::args::host())
                 .arg(postgres::args::port())
                 .arg(postgres::args::dbname())))
+        .subcommand(Command::new("storage")
+            .about("storage directory operations")
+            .subcommand_required(true)
+            .subcommand(Command::new("fsck")
+                .about("checks the storage directory against the database. only the file system backend is supported")
+                .arg(Arg::new("directory")
+                    .short('d')
+                    .long("directory")
+                    .action(ArgAction::Set)
+                    .required(true)
+                    .help("storage directory of the server"))
+                .arg(Arg::new("repair")
+                    .long("repair")
+                    .action(ArgAction::SetTrue)
+                    .help("removes rows with missing files, updates recorded sizes, and recounts blob references"))
+                .arg(Arg::new("quarantine")
+                    .short('q')
+                    .long("quarantine")
+                    .action(ArgAction::Set)
+                    .help("moves orphaned files into the given directory"))
+                .arg(Arg::new("min-age")
+                    .long("min-age")
+                    .action(ArgAction::Set)
+                    .help("seconds since a file was modified before it can be called an orphan. defaults to 3600"))
+                .arg(postgres::args::connect())
+                .arg(postgres::args::user())
+                .arg(postgres::args::password())
+                .arg(postgres::args::host())
+                .arg(postgres::args::port())
+                .arg(postgres::args::dbname())))
         .subcommand(Command::new("gen-test")
             .about("generates test data for the connected database"))
 }
@@ -66,6 +97,7 @@ fn main() -> () {
 
     let result = match matches.subcommand() {
         Some(("migrate", migrate_matches)) => migrate::run(migrate_matches),
+        Some(("storage", storage_matches)) => storage::run(storage_matches),
         Some(("gen-test", gen_test_matches)) => gen_test::run(gen_test_matches),
         _ => unreachable!()
     };
@@ -0,0 +1,677 @@
+//! checks the storage directory of the server against the database
+//!
+//! only the file system backend can be checked since the objects need to be
+//! listed. the paths are built the same way as the server does in
+//! `StorageState` so the two need to be kept in sync.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use clap::ArgMatches;
+use postgres::Client;
+
+use crate::error;
+use crate::postgres::create_client;
+
+/// files modified more recently than this are left alone by default since
+/// they may still be in the middle of an upload
+const DEFAULT_MIN_AGE: u64 = 60 * 60;
+
+/// the start of an object encrypted by the server. matches the format in
+/// src/storage/crypt.rs
+const CRYPT_MAGIC: &[u8; 4] = b"TSE1";
+const CRYPT_HEADER_LEN: u64 = 4 + 4 + 60 + 7;
+const CRYPT_CHUNK_SIZE: u64 = 64 * 1024;
+const CRYPT_TAG_LEN: u64 = 16;
+
+/// the row that a stored file belongs to
+#[derive(Debug, Clone)]
+enum Owner {
+    Blob(i32, String),
+    BlobThumbnail(i32, String),
+    Audio(i32),
+    Image(i32),
+    Thumbnail(i32),
+    Video(i32),
+    File(i32),
+}
+
+impl Owner {
+    fn describe(&self) -> String {
+        match self {
+            Owner::Blob(user, hash) => format!("blob {} of user {}", hash, user),
+            Owner::BlobThumbnail(user, hash) => format!("blob thumbnail {} of user {}", hash, user),
+            Owner::Audio(id) => format!("audio {}", id),
+            Owner::Image(id) => format!("image {}", id),
+            Owner::Thumbnail(id) => format!("image thumbnail {}", id),
+            Owner::Video(id) => format!("video {}", id),
+            Owner::File(id) => format!("file {}", id),
+        }
+    }
+}
+
+/// a file that the database says should exist
+struct Expected {
+    owner: Owner,
+    /// the recorded size if there is one
+    size: Option<i64>,
+}
+
+#[derive(Default)]
+struct Report {
+    orphans: Vec<(PathBuf, u64)>,
+    missing: Vec<(PathBuf, Owner)>,
+    size_mismatches: Vec<(PathBuf, Owner, i64, u64)>,
+    /// blobs whose ref_count does not match the media using them
+    ref_counts: Vec<(i32, String, i32, i64)>,
+    /// blobs that nothing references
+    unreferenced: Vec<(i32, String)>,
+    /// files too new to be checked
+    recent: usize,
+}
+
+fn audio_extension(mime_subtype: &str) -> &'static str {
+    // matches AudioKind::extension_for
+    match mime_subtype {
+        "ogg" => "ogg",
+        "mpeg" => "mp3",
+        "wav" => "wav",
+        "mp4" => "m4a",
+        _ => "webm",
+    }
+}
+
+fn blob_path(owner: i32, hash: &str, extension: &str) -> PathBuf {
+    let mut rtn = PathBuf::from("users");
+    rtn.push(owner.to_string());
+    rtn.push("blobs");
+    rtn.push(hash.get(..2).unwrap_or(hash));
+    rtn.push(format!("{}.{}", hash, extension));
+    rtn
+}
+
+fn entry_path(owner: i32, entry: i32) -> PathBuf {
+    let mut rtn = PathBuf::from("users");
+    rtn.push(owner.to_string());
+    rtn.push("entries");
+    rtn.push(entry.to_string());
+    rtn
+}
+
+/// counts the rows of every media table that reference a blob. matches the
+/// tables with the blobs_count_refs trigger. blobs belong to a user so only
+/// the entries of that user are counted
+const BLOB_REFS: &str = "\
+(select count(*) from audio_entries join entries on audio_entries.entry = entries.id where entries.owner = blobs.owner and audio_entries.blob = blobs.hash) + \
+(select count(*) from image_entries join entries on image_entries.entry = entries.id where entries.owner = blobs.owner and image_entries.blob = blobs.hash) + \
+(select count(*) from video_entries join entries on video_entries.entry = entries.id where entries.owner = blobs.owner and video_entries.blob = blobs.hash) + \
+(select count(*) from entry_files join entries on entry_files.entry = entries.id where entries.owner = blobs.owner and entry_files.blob = blobs.hash)";
+
+/// collects the files that should exist along with files that are allowed to
+/// exist but are not required such as cached peaks
+fn expected_files(client: &mut Client, report: &mut Report) -> error::Result<(HashMap<PathBuf, Expected>, HashSet<PathBuf>)> {
+    let mut expected = HashMap::new();
+    let mut optional = HashSet::new();
+
+    let blobs = client.query(
+        format!("\
+        select blobs.hash, \
+               blobs.size, \
+               blobs.extension, \
+               blobs.ref_count, \
+               {}, \
+               exists(\
+                   select 1 \
+                   from image_entries \
+                       join entries on image_entries.entry = entries.id \
+                   where entries.owner = blobs.owner and \
+                         image_entries.blob = blobs.hash\
+               ), \
+               blobs.owner \
+        from blobs",
+            BLOB_REFS
+        ).as_str(),
+        &[]
+    )?;
+
+    for row in blobs {
+        let hash: String = row.get(0);
+        let extension: &str = row.get(2);
+        let ref_count: i32 = row.get(3);
+        let used: i64 = row.get(4);
+        let has_image: bool = row.get(5);
+        let user: i32 = row.get(6);
+
+        if ref_count as i64 != used {
+            report.ref_counts.push((user, hash.clone(), ref_count, used));
+        }
+
+        if used == 0 {
+            report.unreferenced.push((user, hash));
+            continue;
+        }
+
+        optional.insert(blob_path(user, &hash, "peaks"));
+
+        // a thumbnail can be left from an image that has been removed while
+        // the same data is still stored as a file
+        if has_image {
+            expected.insert(blob_path(user, &hash, "thumb"), Expected {
+                owner: Owner::BlobThumbnail(user, hash.clone()),
+                size: None,
+            });
+        } else {
+            optional.insert(blob_path(user, &hash, "thumb"));
+        }
+
+        expected.insert(blob_path(user, &hash, extension), Expected {
+            owner: Owner::Blob(user, hash),
+            size: Some(row.get(1)),
+        });
+    }
+
+    let audio = client.query(
+        "\
+        select audio_entries.id, \
+               entries.owner, \
+               entries.id, \
+               audio_entries.mime_subtype, \
+               audio_entries.file_size \
+        from audio_entries \
+            join entries on audio_entries.entry = entries.id \
+        where audio_entries.blob is null",
+        &[]
+    )?;
+
+    for row in audio {
+        let id: i32 = row.get(0);
+        let dir = entry_path(row.get(1), row.get(2));
+
+        optional.insert(dir.join(format!("{}.peaks", id)));
+        expected.insert(dir.join(format!("{}.{}", id, audio_extension(row.get(3)))), Expected {
+            owner: Owner::Audio(id),
+            size: row.get(4),
+        });
+    }
+
+    let images = client.query(
+        "\
+        select image_entries.id, \
+               entries.owner, \
+               entries.id, \
+               image_entries.file_size \
+        from image_entries \
+            join entries on image_entries.entry = entries.id \
+        where image_entries.blob is null",
+        &[]
+    )?;
+
+    for row in images {
+        let id: i32 = row.get(0);
+        let dir = entry_path(row.get(1), row.get(2)).join("images");
+
+        expected.insert(dir.join(id.to_string()), Expected {
+            owner: Owner::Image(id),
+            size: Some(row.get(3)),
+        });
+        expected.insert(dir.join(format!("{}.thumb", id)), Expected {
+            owner: Owner::Thumbnail(id),
+            size: None,
+        });
+    }
+
+    let videos = client.query(
+        "\
+        select video_entries.id, \
+               entries.owner, \
+               entries.id, \
+               video_entries.mime_subtype, \
+               video_entries.file_size \
+        from video_entries \
+            join entries on video_entries.entry = entries.id \
+        where video_entries.blob is null",
+        &[]
+    )?;
+
+    for row in videos {
+        let id: i32 = row.get(0);
+        let subtype: &str = row.get(3);
+        let dir = entry_path(row.get(1), row.get(2)).join("video");
+
+        expected.insert(dir.join(format!("{}.{}", id, subtype)), Expected {
+            owner: Owner::Video(id),
+            size: Some(row.get(4)),
+        });
+    }
+
+    let files = client.query(
+        "\
+        select entry_files.id, \
+               entries.owner, \
+               entries.id, \
+               entry_files.file_size \
+        from entry_files \
+            join entries on entry_files.entry = entries.id \
+        where entry_files.blob is null",
+        &[]
+    )?;
+
+    for row in files {
+        let id: i32 = row.get(0);
+        let dir = entry_path(row.get(1), row.get(2)).join("files");
+
+        expected.insert(dir.join(id.to_string()), Expected {
+            owner: Owner::File(id),
+            size: Some(row.get(3)),
+        });
+    }
+
+    Ok((expected, optional))
+}
+
+/// the size of the data in a file that may have been encrypted by the server
+fn plain_size(path: &Path, stored: u64) -> error::Result<u64> {
+    if stored < CRYPT_HEADER_LEN {
+        return Ok(stored);
+    }
+
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path)?.read_exact(&mut magic)?;
+
+    if &magic != CRYPT_MAGIC {
+        return Ok(stored);
+    }
+
+    let body = stored - CRYPT_HEADER_LEN;
+    let sealed_chunk = CRYPT_CHUNK_SIZE + CRYPT_TAG_LEN;
+    let chunks = ((body + sealed_chunk - 1) / sealed_chunk).max(1);
+
+    Ok(body.saturating_sub(chunks * CRYPT_TAG_LEN))
+}
+
+/// lists every file under the directory relative to it
+fn walk(root: &Path, skip: Option<&Path>) -> error::Result<Vec<(PathBuf, std::fs::Metadata)>> {
+    let mut rtn = Vec::new();
+    let mut dirs = vec![root.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        for item in std::fs::read_dir(&dir)? {
+            let item = item?;
+            let path = item.path();
+            let metadata = item.metadata()?;
+
+            if metadata.is_dir() {
+                if skip != Some(path.as_path()) {
+                    dirs.push(path);
+                }
+            } else if metadata.is_file() {
+                let Ok(relative) = path.strip_prefix(root) else {
+                    continue;
+                };
+
+                rtn.push((relative.to_owned(), metadata));
+            }
+        }
+    }
+
+    rtn.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(rtn)
+}
+
+fn check(
+    client: &mut Client,
+    directory: &Path,
+    quarantine: Option<&Path>,
+    min_age: Duration,
+) -> error::Result<Report> {
+    let mut report = Report::default();
+    let (mut expected, optional) = expected_files(client, &mut report)?;
+    let now = SystemTime::now();
+
+    for (relative, metadata) in walk(directory, quarantine)? {
+        let Some(found) = expected.remove(&relative) else {
+            if optional.contains(&relative) {
+                continue;
+            }
+
+            let recent = metadata.modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .map(|age| age < min_age)
+                .unwrap_or(false);
+
+            if recent {
+                report.recent += 1;
+            } else {
+                report.orphans.push((relative, metadata.len()));
+            }
+
+            continue;
+        };
+
+        let Some(recorded) = found.size else {
+            continue;
+        };
+
+        // everything is written through the storage backend so it may be
+        // encrypted
+        let actual = plain_size(&directory.join(&relative), metadata.len())?;
+
+        if recorded < 0 || recorded as u64 != actual {
+            report.size_mismatches.push((relative, found.owner, recorded, actual));
+        }
+    }
+
+    let mut missing: Vec<(PathBuf, Owner)> = expected.into_iter()
+        .map(|(path, found)| (path, found.owner))
+        .collect();
+    missing.sort_by(|a, b| a.0.cmp(&b.0));
+
+    report.missing = missing;
+
+    Ok(report)
+}
+
+fn print_report(report: &Report) {
+    for (path, size) in &report.orphans {
+        println!("orphan: {} ({} bytes)", path.display(), size);
+    }
+
+    for (path, owner) in &report.missing {
+        println!("missing: {} {}", owner.describe(), path.display());
+    }
+
+    for (path, owner, recorded, actual) in &report.size_mismatches {
+        println!(
+            "size mismatch: {} {} recorded: {} actual: {}",
+            owner.describe(),
+            path.display(),
+            recorded,
+            actual
+        );
+    }
+
+    for (user, hash, recorded, actual) in &report.ref_counts {
+        println!("ref count mismatch: blob {} of user {} recorded: {} actual: {}", hash, user, recorded, actual);
+    }
+
+    for (user, hash) in &report.unreferenced {
+        println!("unreferenced: blob {} of user {}", hash, user);
+    }
+
+    println!(
+        "orphans: {} missing: {} size mismatches: {} ref count mismatches: {} unreferenced blobs: {} skipped recent: {}",
+        report.orphans.len(),
+        report.missing.len(),
+        report.size_mismatches.len(),
+        report.ref_counts.len(),
+        report.unreferenced.len(),
+        report.recent
+    );
+}
+
+/// fixes the database to match what is in the storage directory
+///
+/// rows whose files are missing are removed, recorded sizes are updated, and
+/// blob reference counts are recounted. blob sizes are not changed since a
+/// blob with the wrong size no longer matches its hash
+fn repair(client: &mut Client, report: &Report) -> error::Result<()> {
+    let mut transaction = client.transaction()?;
+
+    for (path, owner) in &report.missing {
+        let removed = match owner {
+            Owner::Blob(user, hash) => {
+                for table in ["audio_entries", "image_entries", "video_entries", "entry_files"] {
+                    transaction.execute(
+                        format!("\
+                        delete from {0} \
+                        using entries \
+                        where {0}.entry = entries.id and \
+                              entries.owner = $1 and \
+                              {0}.blob = $2",
+                            table
+                        ).as_str(),
+                        &[user, hash]
+                    )?;
+                }
+
+                transaction.execute("delete from blobs where owner = $1 and hash = $2", &[user, hash])?
+            },
+            Owner::Audio(id) => transaction.execute("delete from audio_entries where id = $1", &[id])?,
+            Owner::Image(id) => transaction.execute("delete from image_entries where id = $1", &[id])?,
+            Owner::Video(id) => transaction.execute("delete from video_entries where id = $1", &[id])?,
+            Owner::File(id) => transaction.execute("delete from entry_files where id = $1", &[id])?,
+            Owner::Thumbnail(_) | Owner::BlobThumbnail(..) => {
+                println!("cannot repair missing thumbnail: {}", path.display());
+                continue;
+            }
+        };
+
+        if removed > 0 {
+            println!("removed row: {}", owner.describe());
+        }
+    }
+
+    for (path, owner, _recorded, actual) in &report.size_mismatches {
+        let size = *actual as i64;
+
+        match owner {
+            Owner::Audio(id) => transaction.execute("update audio_entries set file_size = $2 where id = $1", &[id, &size])?,
+            Owner::Image(id) => transaction.execute("update image_entries set file_size = $2 where id = $1", &[id, &size])?,
+            Owner::Video(id) => transaction.execute("update video_entries set file_size = $2 where id = $1", &[id, &size])?,
+            Owner::File(id) => transaction.execute("update entry_files set file_size = $2 where id = $1", &[id, &size])?,
+            Owner::Blob(..) | Owner::BlobThumbnail(..) | Owner::Thumbnail(_) => {
+                println!("cannot repair size of {}: {}", owner.describe(), path.display());
+                continue;
+            }
+        };
+
+        println!("updated size: {}", owner.describe());
+    }
+
+    if !report.ref_counts.is_empty() || !report.unreferenced.is_empty() {
+        transaction.execute(
+            format!("\
+            update blobs \
+            set ref_count = counted.total \
+            from (\
+                select blobs.owner, blobs.hash, ({})::integer as total \
+                from blobs\
+            ) counted \
+            where blobs.owner = counted.owner and \
+                  blobs.hash = counted.hash and \
+                  blobs.ref_count != counted.total",
+                BLOB_REFS
+            ).as_str(),
+            &[]
+        )?;
+
+        let removed = transaction.execute("delete from blobs where ref_count = 0", &[])?;
+
+        println!("recounted blob references. removed unreferenced blobs: {}", removed);
+    }
+
+    transaction.commit()?;
+
+    Ok(())
+}
+
+/// moves orphaned files into the quarantine directory keeping their path
+fn quarantine_orphans(directory: &Path, quarantine: &Path, report: &Report) -> error::Result<()> {
+    for (relative, _size) in &report.orphans {
+        let from = directory.join(relative);
+        let to = quarantine.join(relative);
+
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // the quarantine may be on a different file system
+        if std::fs::rename(&from, &to).is_err() {
+            std::fs::copy(&from, &to)?;
+            std::fs::remove_file(&from)?;
+        }
+
+        println!("quarantined: {}", relative.display());
+    }
+
+    Ok(())
+}
+
+fn fsck(args: &ArgMatches) -> error::Result<()> {
+    let Some(directory) = args.get_one::<String>("directory") else {
+        return Err(error::Error::new()
+            .with_message("storage directory was not provided"));
+    };
+    let directory = std::fs::canonicalize(directory)?;
+    let quarantine = match args.get_one::<String>("quarantine") {
+        Some(given) => {
+            std::fs::create_dir_all(given)?;
+
+            Some(std::fs::canonicalize(given)?)
+        },
+        None => None
+    };
+    let min_age = match args.get_one::<String>("min-age") {
+        Some(given) => {
+            let Ok(secs) = u64::from_str(given) else {
+                return Err(error::Error::new()
+                    .with_message("invalid min age provided"));
+            };
+
+            Duration::from_secs(secs)
+        },
+        None => Duration::from_secs(DEFAULT_MIN_AGE)
+    };
+
+    let mut client = create_client(args)?;
+    let report = check(&mut client, &directory, quarantine.as_deref(), min_age)?;
+
+    print_report(&report);
+
+    if args.get_flag("repair") {
+        repair(&mut client, &report)?;
+    }
+
+    if let Some(quarantine) = &quarantine {
+        quarantine_orphans(&directory, quarantine, &report)?;
+    }
+
+    client.close()?;
+
+    Ok(())
+}
+
+pub fn run(args: &ArgMatches) -> error::Result<()> {
+    match args.subcommand() {
+        Some(("fsck", opts)) => fsck(opts),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("fsck_{}_{}", name, std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn write_file(root: &Path, relative: &str, data: &[u8]) {
+        let path = root.join(relative);
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn paths_match_the_server() {
+        assert_eq!(blob_path(1, "abcdef", "webm"), PathBuf::from("users/1/blobs/ab/abcdef.webm"));
+        assert_eq!(blob_path(1, "abcdef", "thumb"), PathBuf::from("users/1/blobs/ab/abcdef.thumb"));
+        assert_eq!(entry_path(1, 2), PathBuf::from("users/1/entries/2"));
+
+        assert_eq!(audio_extension("mpeg"), "mp3");
+        assert_eq!(audio_extension("mp4"), "m4a");
+        assert_eq!(audio_extension("unknown"), "webm");
+    }
+
+    #[test]
+    fn plain_sizes() {
+        let dir = test_dir("plain_sizes");
+        let sealed_chunk = (CRYPT_CHUNK_SIZE + CRYPT_TAG_LEN) as usize;
+
+        let mut one_chunk = CRYPT_MAGIC.to_vec();
+        one_chunk.resize(CRYPT_HEADER_LEN as usize + 10 + CRYPT_TAG_LEN as usize, 0);
+
+        let mut two_chunks = CRYPT_MAGIC.to_vec();
+        two_chunks.resize(CRYPT_HEADER_LEN as usize + sealed_chunk + 5 + CRYPT_TAG_LEN as usize, 0);
+
+        let plain = vec![1u8; CRYPT_HEADER_LEN as usize + 10];
+
+        for (name, data, expected) in [
+            ("one_chunk", one_chunk, 10),
+            ("two_chunks", two_chunks, CRYPT_CHUNK_SIZE + 5),
+            ("plain", plain, CRYPT_HEADER_LEN + 10),
+            ("short", CRYPT_MAGIC.to_vec(), 4),
+        ] {
+            let path = dir.join(name);
+            std::fs::write(&path, &data).unwrap();
+
+            assert_eq!(plain_size(&path, data.len() as u64).unwrap(), expected, "{}", name);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_skips_quarantine() {
+        let dir = test_dir("walk");
+
+        write_file(&dir, "users/1/entries/2/files/3", b"file");
+        write_file(&dir, "users/1/blobs/ab/abcdef.webm", b"blob");
+        write_file(&dir, "quarantine/blobs/ab/old.webm", b"old");
+
+        let found: Vec<PathBuf> = walk(&dir, Some(&dir.join("quarantine")))
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+
+        assert_eq!(found, vec![
+            PathBuf::from("users/1/blobs/ab/abcdef.webm"),
+            PathBuf::from("users/1/entries/2/files/3"),
+        ]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn orphans_keep_their_path() {
+        let dir = test_dir("quarantine");
+        let storage = dir.join("storage");
+        let quarantine = dir.join("quarantine");
+
+        write_file(&storage, "users/1/blobs/ab/abcdef.webm", b"orphan");
+
+        let report = Report {
+            orphans: vec![(PathBuf::from("users/1/blobs/ab/abcdef.webm"), 6)],
+            ..Report::default()
+        };
+
+        quarantine_orphans(&storage, &quarantine, &report).unwrap();
+
+        assert!(!storage.join("users/1/blobs/ab/abcdef.webm").exists());
+        assert_eq!(std::fs::read(quarantine.join("users/1/blobs/ab/abcdef.webm")).unwrap(), b"orphan");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
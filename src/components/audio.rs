@@ -0,0 +1,167 @@
+//! reading stored audio files
+//!
+//! the metadata and peaks need random access to the whole file. audio kept in
+//! a remote backend is downloaded to the tmp directory while it is read. files
+//! stored as blobs are checked against their hash when they are read.
+
+use actix_web::web;
+use tokio_postgres::GenericClient;
+
+use crate::db::tables::audio_entries::AudioEntry;
+use crate::components::{blobs, files::Removal};
+use crate::net::http::error;
+use crate::state::StorageState;
+use crate::storage;
+use crate::util::audio::{self, AudioMetadata};
+use crate::util::peaks::{self, Peaks, PeaksError};
+use crate::util::sniff::AudioKind;
+
+/// where the file of an audio entry is stored
+pub struct AudioFile {
+    /// the user the file is stored for
+    pub owner: i32,
+    pub key: String,
+    pub peaks_key: String,
+    /// the blob hash of the file. None for files that have not been moved to
+    /// blobs yet
+    pub hash: Option<String>,
+}
+
+impl AudioFile {
+    pub fn locate(
+        storage: &StorageState,
+        owner: &i32,
+        entry_id: &i32,
+        audio_id: &i32,
+        mime_subtype: &str,
+        blob: Option<&str>,
+    ) -> AudioFile {
+        let extension = AudioKind::extension_for(mime_subtype);
+
+        match blob {
+            Some(hash) => AudioFile {
+                owner: *owner,
+                key: storage.get_blob_key(owner, hash, extension),
+                peaks_key: storage.get_blob_peaks_key(owner, hash),
+                hash: Some(hash.to_owned()),
+            },
+            None => AudioFile {
+                owner: *owner,
+                key: storage.get_audio_key(owner, entry_id, audio_id, extension),
+                peaks_key: storage.get_audio_peaks_key(owner, entry_id, audio_id),
+                hash: None,
+            }
+        }
+    }
+
+    pub fn from_entry(storage: &StorageState, owner: &i32, audio: &AudioEntry) -> AudioFile {
+        AudioFile::locate(
+            storage,
+            owner,
+            &audio.entry,
+            &audio.id,
+            &audio.mime_subtype,
+            audio.blob.as_deref()
+        )
+    }
+}
+
+/// the objects to remove for audio files of the owner that are no longer
+/// referenced
+///
+/// should be called in the same transaction after the rows have been deleted
+/// or changed. files that have not been moved to blobs are always removed
+/// while blobs are only removed if nothing else references them
+pub async fn release_files(
+    conn: &impl GenericClient,
+    storage: &StorageState,
+    owner: &i32,
+    files: Vec<AudioFile>,
+) -> error::Result<Removal> {
+    let mut removal = Removal::default();
+    let mut hashes = Vec::new();
+
+    for file in files {
+        match file.hash {
+            Some(hash) => hashes.push(hash),
+            None => removal.objects.extend([file.key, file.peaks_key])
+        }
+    }
+
+    removal.extend(blobs::release(conn, storage, owner, &hashes).await?);
+
+    Ok(removal)
+}
+
+/// reads the metadata of a stored audio file
+///
+/// a file that passed sniffing is still accepted if its headers cannot be
+/// read, it will just be missing the metadata
+pub async fn read_metadata(
+    storage: &StorageState,
+    key: &str,
+    hash: Option<&str>,
+    kind: AudioKind,
+) -> AudioMetadata {
+    let local = match storage.get_local_file(key, hash).await {
+        Ok(local) => local,
+        Err(err) => {
+            log::warn!("failed to retrieve audio for metadata: {} {}", key, err);
+
+            return AudioMetadata::default();
+        }
+    };
+    let path = local.path().to_owned();
+
+    match web::block(move || audio::read_metadata(path, kind)).await {
+        Ok(Ok(metadata)) => metadata,
+        Ok(Err(err)) => {
+            log::warn!("failed to read audio metadata: {}", err);
+
+            AudioMetadata::default()
+        },
+        Err(err) => {
+            log::warn!("failed to read audio metadata: {}", err);
+
+            AudioMetadata::default()
+        }
+    }
+}
+
+/// reads the cached peaks or computes and caches them if missing
+///
+/// a cache that cannot be decoded is replaced
+pub async fn load_peaks(
+    storage: &StorageState,
+    file: &AudioFile,
+    kind: AudioKind,
+) -> Result<Peaks, PeaksError> {
+    if let Some(cached) = storage::read_bytes(storage.backend(), &file.peaks_key).await? {
+        if let Ok(found) = Peaks::from_bytes(&cached) {
+            return Ok(found);
+        }
+    }
+
+    let local = storage.get_local_file(&file.key, file.hash.as_deref()).await?;
+    let path = local.path().to_owned();
+
+    let computed = web::block(move || peaks::compute(path, kind))
+        .await
+        .map_err(|e| PeaksError::Io(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))??;
+
+    storage::put_bytes(storage.writer(&file.owner, &file.peaks_key).await?, computed.to_bytes()).await?;
+
+    Ok(computed)
+}
+
+/// computes the waveform peaks of a new audio file in the background
+///
+/// failures are only logged since the peaks will be computed again when they
+/// are first requested
+pub fn cache_peaks(storage: web::Data<StorageState>, file: AudioFile, kind: AudioKind) {
+    actix_web::rt::spawn(async move {
+        if let Err(err) = load_peaks(&storage, &file, kind).await {
+            log::warn!("failed to compute audio peaks: {}", err);
+        }
+    });
+}
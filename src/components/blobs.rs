@@ -0,0 +1,112 @@
+//! content addressed storage for uploaded media
+//!
+//! uploads are written to a temporary key while they are hashed and then
+//! moved to a key made from their blake3 hash. uploading the same data again
+//! only adds a reference to the blob that is already stored. references are
+//! counted by triggers on the media tables and a blob is removed along with
+//! the last row that references it.
+//!
+//! blobs belong to a single user. the same data uploaded by two users is kept
+//! twice so an upload never shows whether another user has the same data and
+//! every object stays encrypted with the key of the user it belongs to.
+
+use tokio_postgres::GenericClient;
+
+use crate::db::tables::blobs;
+use crate::net::http::error;
+use crate::state::StorageState;
+use crate::storage;
+use crate::components::files::Removal;
+
+/// a blob that an upload was stored as
+pub struct PlacedBlob {
+    pub hash: String,
+    pub key: String,
+    /// the upload was moved into place instead of matching an existing blob
+    moved: bool,
+}
+
+impl PlacedBlob {
+    /// removes the object if the upload was moved into place
+    ///
+    /// used when the transaction the blob was placed in fails. the blob row
+    /// is rolled back with it
+    pub async fn undo(self, storage: &StorageState) {
+        if self.moved {
+            storage::discard(storage.backend(), &self.key).await;
+        }
+    }
+}
+
+/// stores an upload as a blob of the owner
+///
+/// should be called inside of the transaction that will reference the blob.
+/// if the owner already has the blob then the upload is discarded, otherwise
+/// it is moved to the key of the blob. the upload is discarded on any error.
+///
+/// the same data can be uploaded as different kinds of entries so the
+/// extension should match [sniff::extension](crate::util::sniff::extension)
+/// for the data to always be given the same key
+pub async fn place(
+    conn: &impl GenericClient,
+    storage: &StorageState,
+    owner: &i32,
+    upload_key: &str,
+    hash: &str,
+    size: &i64,
+    extension: &str,
+) -> error::Result<PlacedBlob> {
+    let backend = storage.backend();
+    let key = storage.get_blob_key(owner, hash, extension);
+
+    let result = async {
+        let created = blobs::create(conn, owner, hash, size, extension, &chrono::Utc::now()).await?;
+
+        // a blob that was recorded but lost its object is given the upload
+        if created || backend.size(&key).await?.is_none() {
+            backend.rename(upload_key, &key).await?;
+
+            Ok::<bool, error::Error>(true)
+        } else {
+            storage::discard(backend, upload_key).await;
+
+            Ok(false)
+        }
+    }.await;
+
+    match result {
+        Ok(moved) => Ok(PlacedBlob {
+            hash: hash.to_owned(),
+            key,
+            moved,
+        }),
+        Err(err) => {
+            storage::discard(backend, upload_key).await;
+
+            Err(err)
+        }
+    }
+}
+
+/// finds the objects of blobs of the owner that are no longer referenced
+///
+/// should be called in the same transaction after the rows referencing the
+/// blobs have been deleted or changed. the unused blob rows are deleted and
+/// their objects should be given to
+/// [files::commit_removing](super::files::commit_removing)
+pub async fn release(
+    conn: &impl GenericClient,
+    storage: &StorageState,
+    owner: &i32,
+    hashes: &[String],
+) -> error::Result<Removal> {
+    let mut objects = Vec::new();
+
+    for unused in blobs::remove_unused(conn, owner, hashes).await? {
+        objects.push(storage.get_blob_key(owner, &unused.hash, &unused.extension));
+        objects.push(storage.get_blob_peaks_key(owner, &unused.hash));
+        objects.push(storage.get_blob_thumbnail_key(owner, &unused.hash));
+    }
+
+    Ok(Removal::objects(objects))
+}
@@ -1,3 +1,157 @@
+use actix_web::http::header::EntityTag;
+use tokio_postgres::GenericClient;
+
+use crate::db::tables::{
+    entries,
+    entries2tags,
+    entry_markers,
+    custom_field_entries,
+    text_entries,
+    audio_entries,
+    image_entries,
+    video_entries,
+    entry_files,
+};
+use crate::net::http::error;
+use crate::components::{audio::AudioFile, files::Removal, media::Released};
+use crate::state::StorageState;
+
+/// retrieves an entry with everything attached to it
+pub async fn find_entry(
+    conn: &impl GenericClient,
+    owner: &i32,
+    entry_id: &i32,
+    is_private: &Option<bool>,
+) -> error::Result<Option<schema::Entry>> {
+    let Some(record) = entries::from_user_and_id(conn, owner, entry_id).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(schema::Entry {
+        id: record.id,
+        day: record.day,
+        created: record.created,
+        updated: record.updated,
+        deleted: record.deleted,
+        owner: record.owner,
+        version: record.version,
+        tags: entries2tags::find_id_from_entry(conn, entry_id).await?,
+        markers: entry_markers::find_from_entry(conn, entry_id).await?
+            .into_iter()
+            .map(|m| m.into())
+            .collect(),
+        fields: custom_field_entries::find_from_entry(conn, entry_id).await?
+            .into_iter()
+            .map(|f| f.into())
+            .collect(),
+        text: text_entries::find_from_entry(conn, entry_id, is_private).await?
+            .into_iter()
+            .map(|t| t.into())
+            .collect(),
+        audio: audio_entries::find_from_entry(conn, entry_id, is_private).await?
+            .into_iter()
+            .map(|a| a.into())
+            .collect(),
+        images: image_entries::find_from_entry(conn, entry_id, is_private).await?
+            .into_iter()
+            .map(|i| i.into())
+            .collect(),
+        videos: video_entries::find_from_entry(conn, entry_id, is_private).await?
+            .into_iter()
+            .map(|v| v.into())
+            .collect(),
+        files: entry_files::find_from_entry(conn, entry_id, is_private).await?
+            .into_iter()
+            .map(|f| f.into())
+            .collect(),
+    }))
+}
+
+/// creates the etag for a given version of an entry
+pub fn etag(entry_id: &i32, version: &i32) -> EntityTag {
+    EntityTag::new_strong(format!("{}.{}", entry_id, version))
+}
+
+/// checks that an entry can be changed and locks it
+///
+/// entries in the trash cannot be changed until they are restored. when
+/// called inside of a transaction the entry cannot be trashed or purged
+/// until the transaction is done
+pub async fn lock_for_changes(conn: &impl GenericClient, entry_id: &i32) -> error::Result<()> {
+    let Some(record) = conn.query_opt(
+        "select deleted from entries where id = $1 for update",
+        &[entry_id]
+    ).await? else {
+        return Err(error::build::entry_not_found(entry_id));
+    };
+
+    let deleted: Option<chrono::DateTime<chrono::Utc>> = record.get(0);
+
+    if deleted.is_some() {
+        return Err(error::build::entry_in_trash(entry_id));
+    }
+
+    Ok(())
+}
+
+/// increments the version of an entry
+///
+/// used when something attached to the entry changes outside of updating
+/// the entry itself so that previously retrieved copies fail If-Match checks
+pub async fn bump_version(conn: &impl GenericClient, entry_id: &i32) -> error::Result<()> {
+    conn.execute(
+        "update entries set version = version + 1 where id = $1",
+        &[entry_id]
+    ).await?;
+
+    Ok(())
+}
+
+/// permanently removes an entry and everything attached to it
+///
+/// should be called inside of a transaction. the returned objects
+/// should be given to [files::commit_removing](super::files::commit_removing)
+/// so a failed commit does not leave entries without their files
+pub async fn purge(
+    conn: &impl GenericClient,
+    storage: &StorageState,
+    owner: &i32,
+    entry_id: &i32,
+) -> error::Result<Removal> {
+    let is_private = None;
+    let mut released: Vec<Released> = Vec::new();
+    let audio_files: Vec<AudioFile> = audio_entries::find_from_entry(conn, entry_id, &is_private).await?
+        .iter()
+        .map(|a| AudioFile::from_entry(storage, owner, a))
+        .collect();
+    released.extend(image_entries::find_from_entry(conn, entry_id, &is_private).await?
+        .into_iter()
+        .map(|i| Released::image(storage, owner, entry_id, &i.id, i.blob)));
+    released.extend(video_entries::find_from_entry(conn, entry_id, &is_private).await?
+        .into_iter()
+        .map(|v| Released::video(storage, owner, entry_id, &v.id, &v.mime_subtype, v.blob)));
+    released.extend(entry_files::find_from_entry(conn, entry_id, &is_private).await?
+        .into_iter()
+        .map(|f| Released::entry_file(storage, owner, entry_id, &f.id, f.blob)));
+
+    conn.execute("delete from entry_comments where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from audio_entries where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from image_entries where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from video_entries where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from entry_files where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from text_entries where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from text_entry_revisions where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from custom_field_entries where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from entries2tags where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from entry_markers where entry = $1", &[entry_id]).await?;
+    conn.execute("delete from entries where id = $1", &[entry_id]).await?;
+
+    let mut removal = super::media::release(conn, storage, owner, released).await?;
+    removal.extend(super::audio::release_files(conn, storage, owner, audio_files).await?);
+
+    Ok(removal)
+}
+
 pub mod schema {
     use chrono::{DateTime, Utc};
     use serde::Serialize;
@@ -5,9 +159,14 @@ pub mod schema {
     use crate::db::tables::{
         custom_field_entries::{CustomFieldEntry, CustomFieldEntryType},
         audio_entries::AudioEntry,
+        image_entries::ImageEntry,
+        video_entries::VideoEntry,
+        entry_files::EntryFile,
         text_entries::TextEntry,
+        text_entry_revisions::TextEntryRevision,
         entry_markers::EntryMarker,
     };
+    use crate::util::diff::Change;
 
     /// full data for an entry marker
     #[derive(Serialize)]
@@ -70,6 +229,10 @@ pub mod schema {
         pub private: bool,
         pub mime: String,
         pub size: i64,
+        pub duration_ms: Option<i64>,
+        pub codec: Option<String>,
+        pub sample_rate: Option<i32>,
+        pub channels: Option<i32>,
     }
 
     impl From<AudioEntry> for Audio {
@@ -79,6 +242,76 @@ pub mod schema {
                 private: v.private,
                 mime: format!("{}/{}", v.mime_type, v.mime_subtype),
                 size: v.file_size,
+                duration_ms: v.duration_ms,
+                codec: v.codec,
+                sample_rate: v.sample_rate,
+                channels: v.channels,
+            }
+        }
+    }
+
+    /// full data for an image entry
+    #[derive(Serialize)]
+    pub struct Image {
+        pub id: i32,
+        pub private: bool,
+        pub mime: String,
+        pub size: i64,
+        pub width: i32,
+        pub height: i32,
+    }
+
+    impl From<ImageEntry> for Image {
+        fn from(v: ImageEntry) -> Image {
+            Image {
+                id: v.id,
+                private: v.private,
+                mime: format!("{}/{}", v.mime_type, v.mime_subtype),
+                size: v.file_size,
+                width: v.width,
+                height: v.height,
+            }
+        }
+    }
+
+    /// full data for a video entry
+    #[derive(Serialize)]
+    pub struct Video {
+        pub id: i32,
+        pub private: bool,
+        pub mime: String,
+        pub size: i64,
+    }
+
+    impl From<VideoEntry> for Video {
+        fn from(v: VideoEntry) -> Video {
+            Video {
+                id: v.id,
+                private: v.private,
+                mime: format!("{}/{}", v.mime_type, v.mime_subtype),
+                size: v.file_size,
+            }
+        }
+    }
+
+    /// full data for an entry file
+    #[derive(Serialize)]
+    pub struct File {
+        pub id: i32,
+        pub name: String,
+        pub private: bool,
+        pub mime: String,
+        pub size: i64,
+    }
+
+    impl From<EntryFile> for File {
+        fn from(v: EntryFile) -> File {
+            File {
+                id: v.id,
+                name: v.name,
+                private: v.private,
+                mime: format!("{}/{}", v.mime_type, v.mime_subtype),
+                size: v.file_size,
             }
         }
     }
@@ -92,11 +325,15 @@ pub mod schema {
         pub updated: Option<DateTime<Utc>>,
         pub deleted: Option<DateTime<Utc>>,
         pub owner: i32,
+        pub version: i32,
         pub tags: Vec<i32>,
         pub markers: Vec<Marker>,
         pub fields: Vec<CustomField>,
         pub text: Vec<Text>,
         pub audio: Vec<Audio>,
+        pub images: Vec<Image>,
+        pub videos: Vec<Video>,
+        pub files: Vec<File>,
     }
 
     /// partial data for list custom field entry
@@ -131,6 +368,33 @@ pub mod schema {
         }
     }
 
+    /// word level diff between two revisions of a text entry
+    #[derive(Serialize)]
+    pub struct TextRevisionDiff {
+        pub from: TextEntryRevision,
+        pub to: TextEntryRevision,
+        pub changes: Vec<Change>,
+    }
+
+    /// single text entry that matched a search query
+    ///
+    /// the snippet is html escaped with the matched words wrapped in <mark>
+    #[derive(Serialize)]
+    pub struct ListSearchMatch {
+        pub id: i32,
+        pub rank: f32,
+        pub snippet: String,
+    }
+
+    /// search results for a list entry
+    ///
+    /// rank is the highest rank from the matched text entries
+    #[derive(Serialize)]
+    pub struct ListSearch {
+        pub rank: f32,
+        pub matches: Vec<ListSearchMatch>,
+    }
+
     /// partial data for list entry
     #[derive(Serialize)]
     pub struct ListEntry {
@@ -145,8 +409,10 @@ pub mod schema {
         pub fields: Vec<ListCustomField>,
         pub text: i64,
         pub audio: i64,
+        pub images: i64,
         pub video: i64,
         pub files: i64,
+        pub search: Option<ListSearch>,
     }
 
 }
@@ -0,0 +1,96 @@
+use tokio_postgres::GenericClient;
+
+use crate::db::{self, tables::entry_templates};
+use crate::components;
+use crate::net::http::error;
+
+/// retrieves a template that is owned by the given user
+pub async fn get_via_id(
+    conn: &impl GenericClient,
+    id: &i32,
+    owner: &i32,
+) -> error::Result<entry_templates::EntryTemplate> {
+    if let Some(template) = entry_templates::find_from_id(conn, id).await? {
+        if template.owner != *owner {
+            return Err(error::build::entry_template_not_found(id));
+        }
+
+        Ok(template)
+    } else {
+        Err(error::build::entry_template_not_found(id))
+    }
+}
+
+/// checks that everything in the template data belongs to the owner and that
+/// custom field defaults are valid for their fields
+pub async fn verify_data(
+    conn: &impl GenericClient,
+    owner: &i32,
+    data: &entry_templates::EntryTemplateData,
+) -> error::Result<()> {
+    if !data.tags.is_empty() {
+        let found = conn.query(
+            "select id from tags where id = any($1) and owner = $2",
+            &[&data.tags, owner]
+        ).await?;
+
+        for tag_id in &data.tags {
+            if !found.iter().any(|row| row.get::<usize, i32>(0) == *tag_id) {
+                return Err(error::build::tag_not_found(tag_id));
+            }
+        }
+    }
+
+    for custom_field_entry in &data.custom_field_entries {
+        let field = components::custom_fields::get_via_id(
+            conn,
+            &custom_field_entry.field,
+            Some(owner)
+        ).await?;
+
+        db::validation::verifiy_custom_field_entry(&field.config, &custom_field_entry.value)?;
+    }
+
+    Ok(())
+}
+
+/// removes the tags and custom fields from the template data that no longer
+/// exist for the owner
+///
+/// tags and fields can be deleted after a template was saved. the stale ids
+/// are skipped when the template is expanded instead of failing to create
+/// the entry
+pub async fn drop_missing(
+    conn: &impl GenericClient,
+    owner: &i32,
+    data: &mut entry_templates::EntryTemplateData,
+) -> error::Result<()> {
+    if !data.tags.is_empty() {
+        let found: Vec<i32> = conn.query(
+            "select id from tags where id = any($1) and owner = $2",
+            &[&data.tags, owner]
+        ).await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        data.tags.retain(|tag_id| found.contains(tag_id));
+    }
+
+    if !data.custom_field_entries.is_empty() {
+        let ids: Vec<i32> = data.custom_field_entries.iter()
+            .map(|custom_field_entry| custom_field_entry.field)
+            .collect();
+        let found: Vec<i32> = conn.query(
+            "select id from custom_fields where id = any($1) and owner = $2",
+            &[&ids, owner]
+        ).await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        data.custom_field_entries.retain(|custom_field_entry| found.contains(&custom_field_entry.field));
+    }
+
+    Ok(())
+}
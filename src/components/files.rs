@@ -0,0 +1,129 @@
+//! removing stored objects alongside the database rows that reference them
+//!
+//! objects are first renamed next to where they are so that nothing can read
+//! them while the transaction commits. if the commit fails the objects are
+//! renamed back, otherwise the renamed objects are deleted. a rename in the
+//! same directory of the local backend does not cross file systems so it will
+//! not partially copy the file.
+
+use tokio_postgres::Transaction;
+
+use crate::net::http::error;
+use crate::state::StorageState;
+
+/// extension added to a file that is waiting to be removed
+pub const REMOVING_EXT: &str = "removing";
+
+fn removing_key(key: &str) -> String {
+    format!("{}.{}", key, REMOVING_EXT)
+}
+
+/// storage objects to remove
+#[derive(Default)]
+pub struct Removal {
+    pub objects: Vec<String>,
+}
+
+impl Removal {
+    pub fn objects(objects: Vec<String>) -> Removal {
+        Removal {
+            objects,
+        }
+    }
+
+    pub fn extend(&mut self, other: Removal) {
+        self.objects.extend(other.objects);
+    }
+}
+
+/// objects that have been moved aside and are waiting on a transaction
+pub struct StagedRemoval {
+    moved_objects: Vec<(String, String)>,
+}
+
+impl StagedRemoval {
+    /// moves the given objects aside
+    ///
+    /// anything that does not exist is skipped. if one of them cannot be
+    /// moved then the ones that already were are put back before returning
+    /// the error
+    pub async fn stage(storage: &StorageState, removal: Removal) -> std::io::Result<StagedRemoval> {
+        let mut staged = StagedRemoval {
+            moved_objects: Vec::with_capacity(removal.objects.len()),
+        };
+
+        let backend = storage.backend();
+
+        for key in removal.objects {
+            let removing = removing_key(&key);
+            let result = match backend.size(&key).await {
+                Ok(Some(_)) => backend.rename(&key, &removing).await.map(|_| true),
+                Ok(None) => Ok(false),
+                Err(err) => Err(err)
+            };
+
+            match result {
+                Ok(true) => staged.moved_objects.push((key, removing)),
+                Ok(false) => {},
+                Err(err) => {
+                    staged.restore(storage).await;
+
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(staged)
+    }
+
+    /// puts the objects back to where they were
+    pub async fn restore(self, storage: &StorageState) {
+        let backend = storage.backend();
+
+        for (original, removing) in self.moved_objects.into_iter().rev() {
+            if let Err(err) = backend.rename(&removing, &original).await {
+                log::error!("failed to restore object: {} -> {} {}", removing, original, err);
+            }
+        }
+    }
+
+    /// deletes the objects that were moved aside
+    pub async fn finish(self, storage: &StorageState) {
+        let backend = storage.backend();
+
+        for (_original, removing) in self.moved_objects {
+            if let Err(err) = backend.delete(&removing).await {
+                log::error!("failed to remove object: {} {}", removing, err);
+            }
+        }
+    }
+}
+
+/// commits the transaction and removes the given objects
+///
+/// if they cannot be moved aside the transaction is rolled back and if the
+/// commit fails they are restored so the rows and objects stay in sync
+pub async fn commit_removing(
+    transaction: Transaction<'_>,
+    storage: &StorageState,
+    removal: Removal,
+) -> error::Result<()> {
+    let staged = match StagedRemoval::stage(storage, removal).await {
+        Ok(staged) => staged,
+        Err(err) => {
+            transaction.rollback().await?;
+
+            return Err(err.into());
+        }
+    };
+
+    if let Err(err) = transaction.commit().await {
+        staged.restore(storage).await;
+
+        return Err(err.into());
+    }
+
+    staged.finish(storage).await;
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+//! locating stored images, video, and files
+//!
+//! media uploaded before blobs were added is still kept under the entry it
+//! belongs to while anything uploaded since is kept as a blob of the entry
+//! owner. an image blob has its thumbnail stored next to it.
+
+use tokio_postgres::GenericClient;
+
+use crate::db::tables::{
+    blobs as blobs_table,
+    image_entries::ImageEntry,
+    video_entries::VideoEntry,
+    entry_files::EntryFile,
+};
+use crate::components::{blobs, files::Removal};
+use crate::net::http::error;
+use crate::state::StorageState;
+use crate::util::images::ImageKind;
+
+/// the storage key of an image
+pub fn image_key(storage: &StorageState, owner: &i32, image: &ImageEntry) -> String {
+    match &image.blob {
+        Some(hash) => {
+            let extension = ImageKind::from_mime_subtype(&image.mime_subtype)
+                .map(|kind| kind.extension())
+                .unwrap_or("bin");
+
+            storage.get_blob_key(owner, hash, extension)
+        },
+        None => storage.get_image_key(owner, &image.entry, &image.id)
+    }
+}
+
+/// the storage key of the thumbnail of an image
+pub fn image_thumbnail_key(storage: &StorageState, owner: &i32, image: &ImageEntry) -> String {
+    match &image.blob {
+        Some(hash) => storage.get_blob_thumbnail_key(owner, hash),
+        None => storage.get_image_thumbnail_key(owner, &image.entry, &image.id)
+    }
+}
+
+/// the storage key of a video
+pub fn video_key(storage: &StorageState, owner: &i32, video: &VideoEntry) -> String {
+    match &video.blob {
+        Some(hash) => storage.get_blob_key(owner, hash, &video.mime_subtype),
+        None => storage.get_video_key(owner, &video.entry, &video.id, &video.mime_subtype)
+    }
+}
+
+/// the storage key of an entry file
+///
+/// the mime type of a file is given by the client so the extension of its
+/// blob is looked up instead
+pub async fn entry_file_key(
+    conn: &impl GenericClient,
+    storage: &StorageState,
+    owner: &i32,
+    file: &EntryFile,
+) -> error::Result<String> {
+    let Some(hash) = &file.blob else {
+        return Ok(storage.get_entry_file_key(owner, &file.entry, &file.id));
+    };
+
+    let Some(extension) = blobs_table::find_extension(conn, owner, hash).await? else {
+        return Err(error::Error::new()
+            .set_message(format!("blob is missing for entry file: {}", file.id)));
+    };
+
+    Ok(storage.get_blob_key(owner, hash, &extension))
+}
+
+/// stored media whose row has been deleted
+pub enum Released {
+    /// the hash of the blob the row referenced
+    Blob(String),
+    /// the objects of media that had not been moved to a blob
+    Objects(Vec<String>),
+}
+
+impl Released {
+    pub fn image(
+        storage: &StorageState,
+        owner: &i32,
+        entry_id: &i32,
+        image_id: &i32,
+        blob: Option<String>,
+    ) -> Released {
+        match blob {
+            Some(hash) => Released::Blob(hash),
+            None => Released::Objects(vec![
+                storage.get_image_key(owner, entry_id, image_id),
+                storage.get_image_thumbnail_key(owner, entry_id, image_id),
+            ])
+        }
+    }
+
+    pub fn video(
+        storage: &StorageState,
+        owner: &i32,
+        entry_id: &i32,
+        video_id: &i32,
+        mime_subtype: &str,
+        blob: Option<String>,
+    ) -> Released {
+        match blob {
+            Some(hash) => Released::Blob(hash),
+            None => Released::Objects(vec![
+                storage.get_video_key(owner, entry_id, video_id, mime_subtype),
+            ])
+        }
+    }
+
+    pub fn entry_file(
+        storage: &StorageState,
+        owner: &i32,
+        entry_id: &i32,
+        file_id: &i32,
+        blob: Option<String>,
+    ) -> Released {
+        match blob {
+            Some(hash) => Released::Blob(hash),
+            None => Released::Objects(vec![
+                storage.get_entry_file_key(owner, entry_id, file_id),
+            ])
+        }
+    }
+}
+
+/// the objects to remove for media of the owner whose rows have been deleted
+///
+/// should be called in the same transaction after the rows have been deleted.
+/// media that has not been moved to blobs is always removed while blobs are
+/// only removed if nothing else references them
+pub async fn release(
+    conn: &impl GenericClient,
+    storage: &StorageState,
+    owner: &i32,
+    released: Vec<Released>,
+) -> error::Result<Removal> {
+    let mut removal = Removal::default();
+    let mut hashes = Vec::new();
+
+    for media in released {
+        match media {
+            Released::Blob(hash) => hashes.push(hash),
+            Released::Objects(objects) => removal.objects.extend(objects)
+        }
+    }
+
+    removal.extend(blobs::release(conn, storage, owner, &hashes).await?);
+
+    Ok(removal)
+}
@@ -4,6 +4,13 @@
 //! not sure if this is the best way to handle this but it is what I am doing
 //! for now.
 
+pub mod audio;
+pub mod blobs;
 pub mod entries;
+pub mod files;
+pub mod media;
 pub mod groups;
+pub mod quota;
 pub mod custom_fields;
+pub mod entry_templates;
+pub mod sync;
@@ -0,0 +1,351 @@
+//! storage usage and quotas
+//!
+//! usage is the total size of the media attached to the entries of a user
+//! including entries in the trash. audio that is shared with another entry
+//! through a blob still counts towards each entry since removing one does not
+//! free the space. thumbnails, peaks, and processing results are not counted.
+
+use serde::Serialize;
+use tokio_postgres::GenericClient;
+
+use crate::config::QuotaConfig;
+use crate::net::http::error;
+
+/// how much is stored by media type in bytes
+#[derive(Serialize, Default)]
+pub struct StorageUsage {
+    pub audio: i64,
+    pub images: i64,
+    pub video: i64,
+    pub files: i64,
+    pub total: i64,
+}
+
+impl StorageUsage {
+    fn from_row(row: &tokio_postgres::Row, offset: usize) -> StorageUsage {
+        let audio: i64 = row.get(offset);
+        let images: i64 = row.get(offset + 1);
+        let video: i64 = row.get(offset + 2);
+        let files: i64 = row.get(offset + 3);
+
+        StorageUsage {
+            audio,
+            images,
+            video,
+            files,
+            total: audio + images + video + files,
+        }
+    }
+}
+
+/// the combined usage of the given users
+pub async fn usage_for(conn: &impl GenericClient, owners: &[i32]) -> error::Result<StorageUsage> {
+    let row = conn.query_one(
+        "\
+        select (\
+                select coalesce(sum(audio_entries.file_size), 0)::bigint \
+                from audio_entries \
+                    join entries on audio_entries.entry = entries.id \
+                where entries.owner = any($1)\
+            ), (\
+                select coalesce(sum(image_entries.file_size), 0)::bigint \
+                from image_entries \
+                    join entries on image_entries.entry = entries.id \
+                where entries.owner = any($1)\
+            ), (\
+                select coalesce(sum(video_entries.file_size), 0)::bigint \
+                from video_entries \
+                    join entries on video_entries.entry = entries.id \
+                where entries.owner = any($1)\
+            ), (\
+                select coalesce(sum(entry_files.file_size), 0)::bigint \
+                from entry_files \
+                    join entries on entry_files.entry = entries.id \
+                where entries.owner = any($1)\
+            )",
+        &[&owners]
+    ).await?;
+
+    Ok(StorageUsage::from_row(&row, 0))
+}
+
+/// the usage of a single user
+pub struct UserUsage {
+    pub id: i32,
+    pub username: String,
+    pub usage: StorageUsage,
+}
+
+/// the usage of every user ordered by username
+pub async fn usage_by_user(conn: &impl GenericClient) -> error::Result<Vec<UserUsage>> {
+    let rows = conn.query(
+        "\
+        select users.id, \
+               users.username, \
+               (\
+                   select coalesce(sum(audio_entries.file_size), 0)::bigint \
+                   from audio_entries \
+                       join entries on audio_entries.entry = entries.id \
+                   where entries.owner = users.id\
+               ), (\
+                   select coalesce(sum(image_entries.file_size), 0)::bigint \
+                   from image_entries \
+                       join entries on image_entries.entry = entries.id \
+                   where entries.owner = users.id\
+               ), (\
+                   select coalesce(sum(video_entries.file_size), 0)::bigint \
+                   from video_entries \
+                       join entries on video_entries.entry = entries.id \
+                   where entries.owner = users.id\
+               ), (\
+                   select coalesce(sum(entry_files.file_size), 0)::bigint \
+                   from entry_files \
+                       join entries on entry_files.entry = entries.id \
+                   where entries.owner = users.id\
+               ) \
+        from users \
+        order by users.username",
+        &[]
+    ).await?;
+
+    Ok(rows.iter()
+        .map(|row| UserUsage {
+            id: row.get(0),
+            username: row.get(1),
+            usage: StorageUsage::from_row(row, 2),
+        })
+        .collect())
+}
+
+/// the members of a group
+async fn group_members(conn: &impl GenericClient, group_id: &i32) -> error::Result<Vec<i32>> {
+    Ok(conn.query(
+        "select users_id from group_users where group_id = $1",
+        &[group_id]
+    ).await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect())
+}
+
+/// a limit that applies to a user
+#[derive(Serialize)]
+pub struct QuotaLimit {
+    /// the group the limit belongs to. None for the limit of the user
+    pub group: Option<i32>,
+    pub limit: u64,
+    /// the usage counted against the limit
+    pub used: i64,
+}
+
+impl QuotaLimit {
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used.max(0) as u64)
+    }
+}
+
+/// the usage of a user and the limits that apply to it
+pub struct Quota {
+    pub usage: StorageUsage,
+    pub limits: Vec<QuotaLimit>,
+}
+
+impl Quota {
+    /// the amount that can still be stored. None if there is no limit
+    pub fn remaining(&self) -> Option<u64> {
+        self.limits.iter()
+            .map(QuotaLimit::remaining)
+            .min()
+    }
+}
+
+/// the limit of a user without the limits of their groups
+pub fn user_limit(config: &QuotaConfig, owner: &i32) -> Option<u64> {
+    config.users.get(owner).copied().or(config.default)
+}
+
+/// if any quota is configured
+fn has_limits(config: &QuotaConfig) -> bool {
+    config.default.is_some() || !config.users.is_empty() || !config.groups.is_empty()
+}
+
+/// finds the usage and limits of a user
+pub async fn find(conn: &impl GenericClient, config: &QuotaConfig, owner: &i32) -> error::Result<Quota> {
+    let usage = usage_for(conn, std::slice::from_ref(owner)).await?;
+    let mut limits = Vec::new();
+
+    if let Some(limit) = user_limit(config, owner) {
+        limits.push(QuotaLimit {
+            group: None,
+            limit,
+            used: usage.total,
+        });
+    }
+
+    if !config.groups.is_empty() {
+        let groups = conn.query(
+            "select group_id from group_users where users_id = $1",
+            &[owner]
+        ).await?;
+
+        for row in groups {
+            let group_id: i32 = row.get(0);
+
+            let Some(limit) = config.groups.get(&group_id) else {
+                continue;
+            };
+
+            let members = group_members(conn, &group_id).await?;
+
+            limits.push(QuotaLimit {
+                group: Some(group_id),
+                limit: *limit,
+                used: usage_for(conn, &members).await?.total,
+            });
+        }
+    }
+
+    Ok(Quota { usage, limits })
+}
+
+/// the amount a user can still store. None if there is no limit
+///
+/// skips looking up the usage when no quotas are configured
+pub async fn remaining(conn: &impl GenericClient, config: &QuotaConfig, owner: &i32) -> error::Result<Option<u64>> {
+    if !has_limits(config) {
+        return Ok(None);
+    }
+
+    Ok(find(conn, config, owner).await?.remaining())
+}
+
+/// the first key of the advisory locks taken by [reserve]. the second key is
+/// the id of the user or group being locked
+const USER_LOCK: i32 = 0x71756f75;
+const GROUP_LOCK: i32 = 0x71756f67;
+
+/// checks that an upload fits in the quota of a user
+///
+/// should be called in the transaction that records the upload before the
+/// row is added or changed. transaction level advisory locks are taken for
+/// the user and each of their groups with a quota so uploads counted against
+/// the same limit are checked one at a time and every upload committed
+/// before is counted. freed is the size of anything the upload replaces
+pub async fn reserve(
+    conn: &impl GenericClient,
+    config: &QuotaConfig,
+    owner: &i32,
+    size: i64,
+    freed: i64,
+) -> error::Result<()> {
+    if !has_limits(config) {
+        return Ok(());
+    }
+
+    conn.execute("select pg_advisory_xact_lock($1, $2)", &[&USER_LOCK, owner]).await?;
+
+    if !config.groups.is_empty() {
+        // always locked in the same order so two uploads cannot wait on each
+        // other
+        let mut groups: Vec<i32> = conn.query(
+            "select group_id from group_users where users_id = $1",
+            &[owner]
+        ).await?
+            .iter()
+            .map(|row| row.get(0))
+            .filter(|group_id| config.groups.contains_key(group_id))
+            .collect();
+        groups.sort_unstable();
+
+        for group_id in &groups {
+            conn.execute("select pg_advisory_xact_lock($1, $2)", &[&GROUP_LOCK, group_id]).await?;
+        }
+    }
+
+    let Some(remaining) = find(conn, config, owner).await?.remaining() else {
+        return Ok(());
+    };
+
+    let needed = size.saturating_sub(freed).max(0) as u64;
+
+    if needed > remaining {
+        return Err(error::build::storage_quota_exceeded(remaining));
+    }
+
+    Ok(())
+}
+
+/// the combined usage of the members of each group with a quota
+pub async fn group_usage(conn: &impl GenericClient, config: &QuotaConfig) -> error::Result<Vec<QuotaLimit>> {
+    let mut rtn = Vec::with_capacity(config.groups.len());
+
+    for (group_id, limit) in &config.groups {
+        let members = group_members(conn, group_id).await?;
+
+        rtn.push(QuotaLimit {
+            group: Some(*group_id),
+            limit: *limit,
+            used: usage_for(conn, &members).await?.total,
+        });
+    }
+
+    rtn.sort_by_key(|limit| limit.group);
+
+    Ok(rtn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn limit(group: Option<i32>, limit: u64, used: i64) -> QuotaLimit {
+        QuotaLimit { group, limit, used }
+    }
+
+    #[test]
+    fn no_limits() {
+        let quota = Quota {
+            usage: StorageUsage::default(),
+            limits: Vec::new(),
+        };
+
+        assert_eq!(quota.remaining(), None);
+    }
+
+    #[test]
+    fn smallest_limit_applies() {
+        let quota = Quota {
+            usage: StorageUsage::default(),
+            limits: vec![
+                limit(None, 1000, 100),
+                limit(Some(1), 500, 300),
+                limit(Some(2), 2000, 300),
+            ],
+        };
+
+        assert_eq!(quota.remaining(), Some(200));
+    }
+
+    #[test]
+    fn over_limit_has_nothing_remaining() {
+        assert_eq!(limit(Some(1), 500, 800).remaining(), 0);
+        assert_eq!(limit(None, 500, -10).remaining(), 500);
+    }
+
+    #[test]
+    fn user_limit_falls_back_to_default() {
+        let mut config = QuotaConfig {
+            default: Some(100),
+            ..QuotaConfig::default()
+        };
+        config.users.insert(2, 50);
+        config.groups.insert(2, 10);
+
+        assert_eq!(user_limit(&config, &1), Some(100));
+        assert_eq!(user_limit(&config, &2), Some(50));
+
+        config.default = None;
+
+        assert_eq!(user_limit(&config, &1), None);
+    }
+}
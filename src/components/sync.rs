@@ -0,0 +1,549 @@
+//! collecting changes for the sync feed
+//!
+//! every insert, update, and delete of the synced tables is recorded in
+//! sync_changes by a trigger along with the id of the transaction that made
+//! the change. a token is the oldest transaction that was still running when
+//! the changes were collected so anything committed afterwards will be picked
+//! up by the next request. this can send the same record twice but will not
+//! miss one.
+//!
+//! changes older than the retention window are compacted by [compact]. only
+//! the newest change of a record is kept and the changes of records that no
+//! longer exist are removed. a token from before the removed changes is
+//! rejected so the client knows to sync everything again.
+
+use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::GenericClient;
+
+use crate::db;
+use crate::db::tables::{
+    tags::Tag,
+    custom_fields::CustomField,
+    custom_field_entries::CustomFieldEntry,
+    text_entries::TextEntry,
+    entry_markers::EntryMarker,
+};
+use crate::net::http::error;
+
+/// position in the change feed given back to the client
+pub struct SyncToken(i64);
+
+impl SyncToken {
+    /// encodes the token into a url safe string
+    pub fn encode(&self) -> String {
+        base64::encode_config(format!("t.{}", self.0).as_bytes(), base64::URL_SAFE)
+    }
+}
+
+impl FromStr for SyncToken {
+    type Err = db::error::Error;
+
+    /// decodes a token created by [SyncToken::encode]
+    fn from_str(given: &str) -> db::error::Result<SyncToken> {
+        let invalid = || db::error::Error::Validation("invalid sync token given".to_owned());
+
+        let Ok(bytes) = base64::decode_config(given, base64::URL_SAFE) else {
+            return Err(invalid());
+        };
+        let Ok(plain) = String::from_utf8(bytes) else {
+            return Err(invalid());
+        };
+        let Some(Ok(tx)) = plain.strip_prefix("t.").map(i64::from_str) else {
+            return Err(invalid());
+        };
+
+        Ok(SyncToken(tx))
+    }
+}
+
+/// records of a single kind that changed along with the ids of records that
+/// no longer exist
+#[derive(Serialize)]
+pub struct Changes<T> {
+    pub changed: Vec<T>,
+    pub deleted: Vec<i64>,
+}
+
+impl<T> Changes<T> {
+    fn empty() -> Changes<T> {
+        Changes {
+            changed: Vec::new(),
+            deleted: Vec::new(),
+        }
+    }
+}
+
+/// entry data for the sync feed
+///
+/// text entries, markers, and comments are sent separately
+#[derive(Serialize)]
+pub struct SyncEntry {
+    pub id: i32,
+    pub day: DateTime<Utc>,
+    pub created: DateTime<Utc>,
+    pub updated: Option<DateTime<Utc>>,
+    pub deleted: Option<DateTime<Utc>>,
+    pub tags: Vec<i32>,
+    pub fields: Vec<CustomFieldEntry>,
+}
+
+/// comment data for the sync feed
+#[derive(Serialize)]
+pub struct SyncComment {
+    pub id: i64,
+    pub entry: i32,
+    pub owner: i32,
+    pub comment: String,
+    pub created: DateTime<Utc>,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct SyncResult {
+    pub token: String,
+    pub entries: Changes<SyncEntry>,
+    pub text_entries: Changes<TextEntry>,
+    pub markers: Changes<EntryMarker>,
+    pub comments: Changes<SyncComment>,
+    pub tags: Changes<Tag>,
+    pub custom_fields: Changes<CustomField>,
+}
+
+/// builds the list of changes for the ids that were requested
+///
+/// if no ids are given then everything that was found is considered changed
+fn to_changes<T>(found: Vec<T>, requested: Option<&Vec<i64>>, get_id: impl Fn(&T) -> i64) -> Changes<T> {
+    let deleted = if let Some(ids) = requested {
+        let found_ids: HashSet<i64> = found.iter().map(&get_id).collect();
+
+        ids.iter()
+            .filter(|id| !found_ids.contains(id))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    Changes {
+        changed: found,
+        deleted,
+    }
+}
+
+/// the ids to fetch for a kind
+///
+/// when no token was given every kind is fetched in full. otherwise only the
+/// ids recorded for that kind are fetched and kinds without changes are skipped
+fn requested<'a>(
+    changed: &'a Option<HashMap<String, Vec<i64>>>,
+    kind: &str
+) -> Option<Option<&'a Vec<i64>>> {
+    match changed {
+        Some(kinds) => kinds.get(kind).map(Some),
+        None => Some(None),
+    }
+}
+
+async fn find_entries(
+    conn: &impl GenericClient,
+    owner: &i32,
+    ids: Option<&Vec<i64>>,
+) -> error::Result<Vec<SyncEntry>> {
+    let mut rtn = Vec::new();
+    let mut lookup: HashMap<i32, usize> = HashMap::new();
+
+    for row in conn.query(
+        "\
+        select id, day, created, updated, deleted \
+        from entries \
+        where owner = $1 and ($2::bigint[] is null or id = any($2)) \
+        order by id",
+        &[owner, &ids]
+    ).await? {
+        lookup.insert(row.get(0), rtn.len());
+        rtn.push(SyncEntry {
+            id: row.get(0),
+            day: row.get(1),
+            created: row.get(2),
+            updated: row.get(3),
+            deleted: row.get(4),
+            tags: Vec::new(),
+            fields: Vec::new(),
+        });
+    }
+
+    if rtn.is_empty() {
+        return Ok(rtn);
+    }
+
+    let entry_ids: Vec<i32> = rtn.iter().map(|e| e.id).collect();
+
+    for row in conn.query(
+        "select entry, tag from entries2tags where entry = any($1) order by tag",
+        &[&entry_ids]
+    ).await? {
+        let entry: i32 = row.get(0);
+
+        if let Some(index) = lookup.get(&entry) {
+            rtn[*index].tags.push(row.get(1));
+        }
+    }
+
+    for row in conn.query(
+        "\
+        select field, value, comment, entry \
+        from custom_field_entries \
+        where entry = any($1) \
+        order by field",
+        &[&entry_ids]
+    ).await? {
+        let entry: i32 = row.get(3);
+
+        if let Some(index) = lookup.get(&entry) {
+            rtn[*index].fields.push(CustomFieldEntry {
+                field: row.get(0),
+                value: serde_json::from_value(row.get(1)).unwrap(),
+                comment: row.get(2),
+                entry,
+            });
+        }
+    }
+
+    Ok(rtn)
+}
+
+async fn find_text_entries(
+    conn: &impl GenericClient,
+    owner: &i32,
+    ids: Option<&Vec<i64>>,
+) -> error::Result<Vec<TextEntry>> {
+    Ok(conn.query(
+        "\
+        select text_entries.id, \
+               text_entries.thought, \
+               text_entries.private, \
+               text_entries.entry \
+        from text_entries \
+        join entries on text_entries.entry = entries.id \
+        where entries.owner = $1 and \
+              ($2::bigint[] is null or text_entries.id = any($2)) \
+        order by text_entries.id",
+        &[owner, &ids]
+    )
+        .await?
+        .iter()
+        .map(|row| TextEntry {
+            id: row.get(0),
+            thought: row.get(1),
+            private: row.get(2),
+            entry: row.get(3),
+        })
+        .collect())
+}
+
+async fn find_markers(
+    conn: &impl GenericClient,
+    owner: &i32,
+    ids: Option<&Vec<i64>>,
+) -> error::Result<Vec<EntryMarker>> {
+    Ok(conn.query(
+        "\
+        select entry_markers.id, \
+               entry_markers.title, \
+               entry_markers.comment, \
+               entry_markers.entry \
+        from entry_markers \
+        join entries on entry_markers.entry = entries.id \
+        where entries.owner = $1 and \
+              ($2::bigint[] is null or entry_markers.id = any($2)) \
+        order by entry_markers.id",
+        &[owner, &ids]
+    )
+        .await?
+        .iter()
+        .map(|row| EntryMarker {
+            id: row.get(0),
+            title: row.get(1),
+            comment: row.get(2),
+            entry: row.get(3),
+        })
+        .collect())
+}
+
+async fn find_comments(
+    conn: &impl GenericClient,
+    owner: &i32,
+    ids: Option<&Vec<i64>>,
+) -> error::Result<Vec<SyncComment>> {
+    Ok(conn.query(
+        "\
+        select entry_comments.id, \
+               entry_comments.entry, \
+               entry_comments.owner, \
+               entry_comments.comment, \
+               entry_comments.created, \
+               entry_comments.updated \
+        from entry_comments \
+        join entries on entry_comments.entry = entries.id \
+        where entries.owner = $1 and \
+              ($2::bigint[] is null or entry_comments.id = any($2)) \
+        order by entry_comments.id",
+        &[owner, &ids]
+    )
+        .await?
+        .iter()
+        .map(|row| SyncComment {
+            id: row.get(0),
+            entry: row.get(1),
+            owner: row.get(2),
+            comment: row.get(3),
+            created: row.get(4),
+            updated: row.get(5),
+        })
+        .collect())
+}
+
+async fn find_tags(
+    conn: &impl GenericClient,
+    owner: &i32,
+    ids: Option<&Vec<i64>>,
+) -> error::Result<Vec<Tag>> {
+    Ok(conn.query(
+        "\
+        select id, title, color, owner, comment \
+        from tags \
+        where owner = $1 and ($2::bigint[] is null or id = any($2)) \
+        order by id",
+        &[owner, &ids]
+    )
+        .await?
+        .iter()
+        .map(|row| Tag {
+            id: row.get(0),
+            title: row.get(1),
+            color: row.get(2),
+            owner: row.get(3),
+            comment: row.get(4),
+        })
+        .collect())
+}
+
+async fn find_custom_fields(
+    conn: &impl GenericClient,
+    owner: &i32,
+    ids: Option<&Vec<i64>>,
+) -> error::Result<Vec<CustomField>> {
+    Ok(conn.query(
+        "\
+        select id, \
+               name, \
+               owner, \
+               config, \
+               comment, \
+               \"order\", \
+               issued_by \
+        from custom_fields \
+        where owner = $1 and ($2::bigint[] is null or id = any($2)) \
+        order by id",
+        &[owner, &ids]
+    )
+        .await?
+        .iter()
+        .map(|row| CustomField {
+            id: row.get(0),
+            name: row.get(1),
+            owner: row.get(2),
+            config: serde_json::from_value(row.get(3)).unwrap(),
+            comment: row.get(4),
+            order: row.get(5),
+            issued_by: row.get(6),
+        })
+        .collect())
+}
+
+/// collects all changes for the owner since the given token
+///
+/// if no token is given then every record is returned with no deletions.
+/// should be called inside of a repeatable read transaction so that the token
+/// and records come from the same snapshot. a token at or before the sync
+/// horizon is an error since deletions after it may have been compacted
+pub async fn collect(
+    conn: &impl GenericClient,
+    owner: &i32,
+    since: Option<SyncToken>,
+) -> error::Result<SyncResult> {
+    if let Some(since) = since.as_ref() {
+        let horizon: i64 = conn.query_opt("select tx from sync_horizon", &[])
+            .await?
+            .map(|row| row.get(0))
+            .unwrap_or(0);
+
+        if since.0 <= horizon {
+            return Err(error::build::sync_resync_required());
+        }
+    }
+
+    let token_row = conn.query_one(
+        "select txid_snapshot_xmin(txid_current_snapshot())",
+        &[]
+    ).await?;
+    let token = SyncToken(token_row.get(0));
+
+    let changed = if let Some(since) = since {
+        let mut kinds: HashMap<String, Vec<i64>> = HashMap::new();
+
+        for row in conn.query(
+            "\
+            select kind, array_agg(distinct record) \
+            from sync_changes \
+            where owner = $1 and tx >= $2 \
+            group by kind",
+            &[owner, &since.0]
+        ).await? {
+            kinds.insert(row.get(0), row.get(1));
+        }
+
+        Some(kinds)
+    } else {
+        None
+    };
+
+    let entries = match requested(&changed, "entries") {
+        Some(ids) => to_changes(find_entries(conn, owner, ids).await?, ids, |v| v.id as i64),
+        None => Changes::empty(),
+    };
+    let text_entries = match requested(&changed, "text_entries") {
+        Some(ids) => to_changes(find_text_entries(conn, owner, ids).await?, ids, |v| v.id as i64),
+        None => Changes::empty(),
+    };
+    let markers = match requested(&changed, "entry_markers") {
+        Some(ids) => to_changes(find_markers(conn, owner, ids).await?, ids, |v| v.id as i64),
+        None => Changes::empty(),
+    };
+    let comments = match requested(&changed, "entry_comments") {
+        Some(ids) => to_changes(find_comments(conn, owner, ids).await?, ids, |v| v.id),
+        None => Changes::empty(),
+    };
+    let tags = match requested(&changed, "tags") {
+        Some(ids) => to_changes(find_tags(conn, owner, ids).await?, ids, |v| v.id as i64),
+        None => Changes::empty(),
+    };
+    let custom_fields = match requested(&changed, "custom_fields") {
+        Some(ids) => to_changes(find_custom_fields(conn, owner, ids).await?, ids, |v| v.id as i64),
+        None => Changes::empty(),
+    };
+
+    Ok(SyncResult {
+        token: token.encode(),
+        entries,
+        text_entries,
+        markers,
+        comments,
+        tags,
+        custom_fields,
+    })
+}
+
+/// the kinds of records in the feed. each is also the name of its table
+const KINDS: [&str; 6] = [
+    "entries",
+    "text_entries",
+    "entry_markers",
+    "entry_comments",
+    "tags",
+    "custom_fields",
+];
+
+/// the number of changes removed by [compact]
+pub struct Compacted {
+    /// changes with a newer change for the same record
+    pub superseded: u64,
+    /// changes for records that no longer exist
+    pub removed: u64,
+}
+
+/// removes changes from before the given time that are no longer needed
+///
+/// for each record only the change from the newest transaction is kept so a
+/// token will still pick up anything that changed after it. changes for
+/// records that no longer exist are removed and the horizon is moved up to
+/// the newest of their transactions. should be called inside of a
+/// transaction
+pub async fn compact(conn: &impl GenericClient, before: &DateTime<Utc>) -> error::Result<Compacted> {
+    let superseded = conn.execute(
+        "\
+        delete from sync_changes \
+        where changed < $1 and \
+              exists (\
+                  select 1 \
+                  from sync_changes newer \
+                  where newer.kind = sync_changes.kind and \
+                        newer.record = sync_changes.record and \
+                        newer.owner = sync_changes.owner and \
+                        (newer.tx > sync_changes.tx or \
+                         (newer.tx = sync_changes.tx and newer.id > sync_changes.id))\
+              )",
+        &[before]
+    ).await?;
+    let mut removed = 0;
+    let mut horizon: Option<i64> = None;
+
+    for kind in KINDS {
+        let row = conn.query_one(
+            format!(
+                "\
+                with removed as (\
+                    delete from sync_changes \
+                    where kind = $1 and \
+                          changed < $2 and \
+                          not exists (select 1 from {0} where {0}.id = sync_changes.record) \
+                    returning tx\
+                ) \
+                select count(*), max(tx) from removed",
+                kind
+            ).as_str(),
+            &[&kind, before]
+        ).await?;
+        let count: i64 = row.get(0);
+        let max_tx: Option<i64> = row.get(1);
+
+        removed += count as u64;
+        horizon = horizon.max(max_tx);
+    }
+
+    if let Some(tx) = horizon {
+        conn.execute(
+            "update sync_horizon set tx = greatest(tx, $1)",
+            &[&tx]
+        ).await?;
+    }
+
+    Ok(Compacted { superseded, removed })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_round_trip() {
+        let token = SyncToken(8_123_456);
+        let decoded = SyncToken::from_str(&token.encode()).unwrap();
+
+        assert_eq!(decoded.0, 8_123_456);
+    }
+
+    #[test]
+    fn invalid_tokens() {
+        let wrong_prefix = base64::encode_config("x.1", base64::URL_SAFE);
+        let list: [&str; 4] = ["", "t.1", "!!!", &wrong_prefix];
+
+        for given in list {
+            assert!(SyncToken::from_str(given).is_err(), "\"{}\" should not decode", given);
+        }
+    }
+}
@@ -256,10 +256,207 @@ impl TryFrom<Option<shapes::FileServingConfigShape>> for FileServingConfig {
 // StorageConfig
 // ----------------------------------------------------------------------------
 
+/// the smallest part size allowed by s3 for multipart uploads
+pub const S3_MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// connection details for an s3 compatible object store
+///
+/// path_style puts the bucket in the path of the url instead of the host
+/// which is what most self hosted stores expect. prefix is prepended to every
+/// key. part_size is the amount of an upload held in memory before it is sent
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub path_style: bool,
+    pub prefix: Option<String>,
+    pub part_size: usize,
+}
+
+impl TryFrom<shapes::S3ConfigShape> for S3Config {
+    type Error = error::Error;
+
+    fn try_from(value: shapes::S3ConfigShape) -> Result<Self, Self::Error> {
+        let missing = |name: &str| error::Error::InvalidConfig(
+            format!("missing s3 {} for storage (conf.storage.s3.{})", name, name)
+        );
+
+        let endpoint = value.endpoint.ok_or_else(|| missing("endpoint"))?;
+
+        if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+            return Err(error::Error::InvalidConfig(
+                format!("s3 endpoint must be an http or https url. given: {}", endpoint)
+            ));
+        }
+
+        let part_size = value.part_size.unwrap_or(8 * 1024 * 1024);
+
+        if part_size < S3_MIN_PART_SIZE {
+            return Err(error::Error::InvalidConfig(
+                format!("s3 part_size must be at least {} bytes", S3_MIN_PART_SIZE)
+            ));
+        }
+
+        Ok(S3Config {
+            endpoint: endpoint.trim_end_matches('/').to_owned(),
+            region: value.region.unwrap_or(String::from("us-east-1")),
+            bucket: value.bucket.ok_or_else(|| missing("bucket"))?,
+            access_key: value.access_key.ok_or_else(|| missing("access_key"))?,
+            secret_key: value.secret_key.ok_or_else(|| missing("secret_key"))?,
+            path_style: value.path_style.unwrap_or(true),
+            prefix: value.prefix
+                .map(|p| p.trim_matches('/').to_owned())
+                .filter(|p| !p.is_empty()),
+            part_size,
+        })
+    }
+}
+
+/// the length in bytes of an encryption master key
+pub const MASTER_KEY_LEN: usize = 32;
+
+/// a key used to wrap the storage keys of users
+#[derive(Clone)]
+pub struct MasterKey(pub [u8; MASTER_KEY_LEN]);
+
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+/// master keys for encrypting stored media
+///
+/// new user keys are wrapped with the current key. older keys are kept so
+/// user keys wrapped with them can still be read until they are rotated
+#[derive(Debug, Clone)]
+pub struct EncryptionConfig {
+    pub current: String,
+    pub keys: HashMap<String, MasterKey>,
+}
+
+impl TryFrom<shapes::EncryptionConfigShape> for EncryptionConfig {
+    type Error = error::Error;
+
+    fn try_from(value: shapes::EncryptionConfigShape) -> Result<Self, Self::Error> {
+        let given = value.keys.unwrap_or_default();
+        let mut keys = HashMap::with_capacity(given.len());
+
+        for (name, encoded) in given {
+            let decoded = base64::decode(encoded.trim()).map_err(|_| error::Error::InvalidConfig(
+                format!("storage encryption key \"{}\" is not valid base64", name)
+            ))?;
+            let Ok(key) = <[u8; MASTER_KEY_LEN]>::try_from(decoded.as_slice()) else {
+                return Err(error::Error::InvalidConfig(format!(
+                    "storage encryption key \"{}\" must be {} bytes",
+                    name,
+                    MASTER_KEY_LEN
+                )));
+            };
+
+            keys.insert(name, MasterKey(key));
+        }
+
+        let Some(current) = value.current else {
+            return Err(error::Error::InvalidConfig(
+                String::from("missing current key for storage encryption (conf.storage.encryption.current)")
+            ));
+        };
+
+        if !keys.contains_key(&current) {
+            return Err(error::Error::InvalidConfig(format!(
+                "current storage encryption key \"{}\" was not found in conf.storage.encryption.keys",
+                current
+            )));
+        }
+
+        Ok(EncryptionConfig { current, keys })
+    }
+}
+
+/// limits on how much a user can store in bytes
+///
+/// a user is limited by their own entry in users or the default if they do
+/// not have one. each entry in groups limits the combined usage of all the
+/// members of that group. no limit is applied when nothing is given
+#[derive(Debug, Clone, Default)]
+pub struct QuotaConfig {
+    pub default: Option<u64>,
+    pub users: HashMap<i32, u64>,
+    pub groups: HashMap<i32, u64>,
+}
+
+impl From<shapes::QuotaConfigShape> for QuotaConfig {
+    fn from(value: shapes::QuotaConfigShape) -> Self {
+        QuotaConfig {
+            default: value.default,
+            users: value.users.unwrap_or_default(),
+            groups: value.groups.unwrap_or_default(),
+        }
+    }
+}
+
+/// removal of files left behind by uploads that never finished
+///
+/// max_age is the number of seconds a file in the tmp directory can go
+/// without being modified before it is removed with 0 disabling the
+/// janitor. interval is the number of seconds between each sweep
+#[derive(Debug, Clone)]
+pub struct JanitorConfig {
+    pub max_age: u64,
+    pub interval: u64,
+}
+
+impl TryFrom<Option<shapes::JanitorConfigShape>> for JanitorConfig {
+    type Error = error::Error;
+
+    fn try_from(value: Option<shapes::JanitorConfigShape>) -> Result<Self, Self::Error> {
+        if let Some(janitor) = value {
+            let interval = janitor.interval.unwrap_or(60 * 60);
+
+            if interval == 0 {
+                return Err(error::Error::InvalidConfig(
+                    String::from("janitor interval must be greater than 0")
+                ));
+            }
+
+            Ok(JanitorConfig {
+                max_age: janitor.max_age.unwrap_or(60 * 60 * 24),
+                interval,
+            })
+        } else {
+            Ok(JanitorConfig {
+                max_age: 60 * 60 * 24,
+                interval: 60 * 60,
+            })
+        }
+    }
+}
+
+/// where stored media is kept
+#[derive(Debug, Clone)]
+pub enum StorageBackendConfig {
+    /// in the storage directory
+    Fs,
+    S3(S3Config),
+}
+
+/// storage locations for the server
+///
+/// uploaded media is kept in the chosen backend. the directory holds the
+/// media when the backend is the file system. objects in the backend are
+/// encrypted if encryption is given
 #[derive(Debug, Clone)]
 pub struct StorageConfig {
     pub directory: PathBuf,
-    pub temp: PathBuf
+    pub temp: PathBuf,
+    pub backend: StorageBackendConfig,
+    pub encryption: Option<EncryptionConfig>,
+    pub quota: QuotaConfig,
+    pub janitor: JanitorConfig,
 }
 
 impl TryFrom<Option<shapes::StorageConfigShape>> for StorageConfig {
@@ -272,19 +469,230 @@ impl TryFrom<Option<shapes::StorageConfigShape>> for StorageConfig {
         default_temp.push("temp");
 
         if let Some(storage) = value {
+            let backend = match storage.backend.as_deref() {
+                None | Some("fs") => StorageBackendConfig::Fs,
+                Some("s3") => {
+                    let Some(s3) = storage.s3 else {
+                        return Err(error::Error::InvalidConfig(
+                            String::from("storage backend is s3 but no s3 config was given (conf.storage.s3)")
+                        ));
+                    };
+
+                    StorageBackendConfig::S3(s3.try_into()?)
+                },
+                Some(unknown) => return Err(error::Error::InvalidConfig(
+                    format!("unknown storage backend: \"{}\". expect: fs | s3", unknown)
+                ))
+            };
+
+            let encryption = match storage.encryption {
+                Some(encryption) => Some(encryption.try_into()?),
+                None => None
+            };
+
             Ok(StorageConfig {
                 directory: storage.directory.unwrap_or(default_dir),
-                temp: storage.temp.unwrap_or(default_temp)
+                temp: storage.temp.unwrap_or(default_temp),
+                backend,
+                encryption,
+                quota: storage.quota.map(QuotaConfig::from).unwrap_or_default(),
+                janitor: storage.janitor.try_into()?,
             })
         } else {
             Ok(StorageConfig {
                 directory: default_dir,
-                temp: default_temp
+                temp: default_temp,
+                backend: StorageBackendConfig::Fs,
+                encryption: None,
+                quota: QuotaConfig::default(),
+                janitor: JanitorConfig::try_from(None)?,
+            })
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// TrashConfig
+// ----------------------------------------------------------------------------
+
+/// how long trashed entries are kept before being purged
+///
+/// purge_after is in days with 0 disabling the purge. interval is the number
+/// of seconds between each sweep of the trash
+#[derive(Debug, Clone)]
+pub struct TrashConfig {
+    pub purge_after: u32,
+    pub interval: u64
+}
+
+impl TryFrom<Option<shapes::TrashConfigShape>> for TrashConfig {
+    type Error = error::Error;
+
+    fn try_from(value: Option<shapes::TrashConfigShape>) -> Result<Self, Self::Error> {
+        if let Some(trash) = value {
+            let interval = trash.interval.unwrap_or(60 * 60);
+
+            if interval == 0 {
+                return Err(error::Error::InvalidConfig(
+                    String::from("trash interval must be greater than 0")
+                ));
+            }
+
+            Ok(TrashConfig {
+                purge_after: trash.purge_after.unwrap_or(30),
+                interval
+            })
+        } else {
+            Ok(TrashConfig {
+                purge_after: 30,
+                interval: 60 * 60
             })
         }
     }
 }
 
+// ----------------------------------------------------------------------------
+// SyncConfig
+// ----------------------------------------------------------------------------
+
+/// how long the sync feed keeps the full history of changes
+///
+/// retention is in days with 0 keeping everything. changes older than it are
+/// compacted on the same interval as the trash sweep
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub retention: u32,
+}
+
+impl From<Option<shapes::SyncConfigShape>> for SyncConfig {
+    fn from(value: Option<shapes::SyncConfigShape>) -> Self {
+        SyncConfig {
+            retention: value.and_then(|sync| sync.retention).unwrap_or(90),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// ProcessingConfig
+// ----------------------------------------------------------------------------
+
+/// a local command that is run on uploaded media
+///
+/// media is a list of either top level types (audio) or full mime types
+/// (audio/wav) the command applies to. the args can contain {file} and {mime}
+/// which are replaced with the path and mime type of the uploaded file.
+/// timeout is in seconds
+#[derive(Debug, Clone)]
+pub struct ProcessCommand {
+    pub name: String,
+    pub media: Vec<String>,
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub timeout: u64,
+    pub text_entry: bool,
+}
+
+impl ProcessCommand {
+    /// checks if the command applies to the given mime type
+    pub fn matches(&self, mime_type: &str, mime_subtype: &str) -> bool {
+        self.media.iter().any(|media| {
+            match media.split_once('/') {
+                Some((top, sub)) => top == mime_type && sub == mime_subtype,
+                None => media == mime_type
+            }
+        })
+    }
+}
+
+/// local commands to run on uploaded media
+///
+/// interval is the number of seconds between checks for pending jobs.
+/// max_attempts is the number of times a job is run before it is marked as
+/// failed
+#[derive(Debug, Clone)]
+pub struct ProcessingConfig {
+    pub interval: u64,
+    pub max_attempts: u32,
+    pub commands: Vec<ProcessCommand>,
+}
+
+impl TryFrom<Option<shapes::ProcessingConfigShape>> for ProcessingConfig {
+    type Error = error::Error;
+
+    fn try_from(value: Option<shapes::ProcessingConfigShape>) -> Result<Self, Self::Error> {
+        let Some(processing) = value else {
+            return Ok(ProcessingConfig {
+                interval: 10,
+                max_attempts: 3,
+                commands: Vec::new(),
+            });
+        };
+
+        let interval = processing.interval.unwrap_or(10);
+        let max_attempts = processing.max_attempts.unwrap_or(3);
+
+        if interval == 0 {
+            return Err(error::Error::InvalidConfig(
+                String::from("processing interval must be greater than 0")
+            ));
+        }
+
+        if max_attempts == 0 {
+            return Err(error::Error::InvalidConfig(
+                String::from("processing max_attempts must be greater than 0")
+            ));
+        }
+
+        let mut commands = Vec::new();
+
+        for (name, value) in processing.commands.unwrap_or_default() {
+            let Some(command) = value else {
+                continue;
+            };
+
+            let Some(program) = command.program else {
+                return Err(error::Error::InvalidConfig(
+                    format!("missing program for processing command \"{}\"", name)
+                ));
+            };
+
+            let media = command.media.unwrap_or_default();
+
+            if media.is_empty() {
+                return Err(error::Error::InvalidConfig(
+                    format!("no media types given for processing command \"{}\"", name)
+                ));
+            }
+
+            let timeout = command.timeout.unwrap_or(60 * 5);
+
+            if timeout == 0 {
+                return Err(error::Error::InvalidConfig(
+                    format!("timeout for processing command \"{}\" must be greater than 0", name)
+                ));
+            }
+
+            commands.push(ProcessCommand {
+                name,
+                media,
+                program,
+                args: command.args.unwrap_or_else(|| vec![String::from("{file}")]),
+                timeout,
+                text_entry: command.text_entry.unwrap_or(true),
+            });
+        }
+
+        // keeps the order jobs are created in the same between restarts
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(ProcessingConfig {
+            interval,
+            max_attempts,
+            commands,
+        })
+    }
+}
+
 // ----------------------------------------------------------------------------
 // SslConfig
 // ----------------------------------------------------------------------------
@@ -333,6 +741,9 @@ pub struct ServerConfig {
     pub template: TemplateConfig,
     pub file_serving: FileServingConfig,
     pub storage: StorageConfig,
+    pub trash: TrashConfig,
+    pub sync: SyncConfig,
+    pub processing: ProcessingConfig,
 }
 
 impl TryFrom<shapes::ServerConfigShape> for ServerConfig {
@@ -469,7 +880,10 @@ impl TryFrom<shapes::ServerConfigShape> for ServerConfig {
             info: value.info.try_into()?,
             template: value.template.try_into()?,
             file_serving: value.file_serving.try_into()?,
-            storage: value.storage.try_into()?
+            storage: value.storage.try_into()?,
+            trash: value.trash.try_into()?,
+            sync: value.sync.into(),
+            processing: value.processing.try_into()?
         })
     }
 }
@@ -183,16 +183,187 @@ impl MapShape for FileServingConfigShape {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct S3ConfigShape {
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub bucket: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub path_style: Option<bool>,
+    pub prefix: Option<String>,
+    pub part_size: Option<usize>,
+}
+
+impl MapShape for S3ConfigShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.endpoint.map_shape(rhs.endpoint);
+        self.region.map_shape(rhs.region);
+        self.bucket.map_shape(rhs.bucket);
+        self.access_key.map_shape(rhs.access_key);
+        self.secret_key.map_shape(rhs.secret_key);
+        self.path_style.map_shape(rhs.path_style);
+        self.prefix.map_shape(rhs.prefix);
+        self.part_size.map_shape(rhs.part_size);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EncryptionConfigShape {
+    pub current: Option<String>,
+    pub keys: Option<HashMap<String, String>>,
+}
+
+impl MapShape for EncryptionConfigShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.current.map_shape(rhs.current);
+
+        if let Some(map) = self.keys.as_mut() {
+            if let Some(rhs_map) = rhs.keys {
+                for (name, key) in rhs_map {
+                    map.insert(name, key);
+                }
+            }
+        } else if let Some(rhs_map) = rhs.keys {
+            self.keys = Some(rhs_map);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuotaConfigShape {
+    pub default: Option<u64>,
+    pub users: Option<HashMap<i32, u64>>,
+    pub groups: Option<HashMap<i32, u64>>,
+}
+
+impl MapShape for QuotaConfigShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.default.map_shape(rhs.default);
+
+        if let Some(map) = self.users.as_mut() {
+            if let Some(rhs_map) = rhs.users {
+                map.extend(rhs_map);
+            }
+        } else if let Some(rhs_map) = rhs.users {
+            self.users = Some(rhs_map);
+        }
+
+        if let Some(map) = self.groups.as_mut() {
+            if let Some(rhs_map) = rhs.groups {
+                map.extend(rhs_map);
+            }
+        } else if let Some(rhs_map) = rhs.groups {
+            self.groups = Some(rhs_map);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JanitorConfigShape {
+    pub max_age: Option<u64>,
+    pub interval: Option<u64>,
+}
+
+impl MapShape for JanitorConfigShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.max_age.map_shape(rhs.max_age);
+        self.interval.map_shape(rhs.interval);
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct StorageConfigShape {
     pub directory: Option<PathBuf>,
-    pub temp: Option<PathBuf>
+    pub temp: Option<PathBuf>,
+    pub backend: Option<String>,
+    pub s3: Option<S3ConfigShape>,
+    pub encryption: Option<EncryptionConfigShape>,
+    pub quota: Option<QuotaConfigShape>,
+    pub janitor: Option<JanitorConfigShape>,
 }
 
 impl MapShape for StorageConfigShape {
     fn map_shape(&mut self, rhs: Self) {
         self.directory.map_shape(rhs.directory);
         self.temp.map_shape(rhs.temp);
+        self.backend.map_shape(rhs.backend);
+
+        assign_map_struct(&mut self.s3, rhs.s3);
+        assign_map_struct(&mut self.encryption, rhs.encryption);
+        assign_map_struct(&mut self.quota, rhs.quota);
+        assign_map_struct(&mut self.janitor, rhs.janitor);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrashConfigShape {
+    pub purge_after: Option<u32>,
+    pub interval: Option<u64>
+}
+
+impl MapShape for TrashConfigShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.purge_after.map_shape(rhs.purge_after);
+        self.interval.map_shape(rhs.interval);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncConfigShape {
+    pub retention: Option<u32>,
+}
+
+impl MapShape for SyncConfigShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.retention.map_shape(rhs.retention);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProcessCommandShape {
+    pub media: Option<Vec<String>>,
+    pub program: Option<PathBuf>,
+    pub args: Option<Vec<String>>,
+    pub timeout: Option<u64>,
+    pub text_entry: Option<bool>,
+}
+
+impl MapShape for ProcessCommandShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.media.map_shape(rhs.media);
+        self.program.map_shape(rhs.program);
+        self.args.map_shape(rhs.args);
+        self.timeout.map_shape(rhs.timeout);
+        self.text_entry.map_shape(rhs.text_entry);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProcessingConfigShape {
+    pub interval: Option<u64>,
+    pub max_attempts: Option<u32>,
+    pub commands: Option<HashMap<String, Option<ProcessCommandShape>>>,
+}
+
+impl MapShape for ProcessingConfigShape {
+    fn map_shape(&mut self, rhs: Self) {
+        self.interval.map_shape(rhs.interval);
+        self.max_attempts.map_shape(rhs.max_attempts);
+
+        if let Some(lhs_map) = self.commands.as_mut() {
+            if let Some(rhs_map) = rhs.commands {
+                for (rhs_key, rhs_value) in rhs_map {
+                    if let Some(lhs_value) = lhs_map.get_mut(&rhs_key) {
+                        assign_map_struct(lhs_value, rhs_value);
+                    } else {
+                        lhs_map.insert(rhs_key, rhs_value);
+                    }
+                }
+            }
+        } else {
+            self.commands = rhs.commands;
+        }
     }
 }
 
@@ -243,6 +414,9 @@ pub struct ServerConfigShape {
     pub template: Option<TemplateConfigShape>,
     pub file_serving: Option<FileServingConfigShape>,
     pub storage: Option<StorageConfigShape>,
+    pub trash: Option<TrashConfigShape>,
+    pub sync: Option<SyncConfigShape>,
+    pub processing: Option<ProcessingConfigShape>,
 }
 
 impl MapShape for ServerConfigShape {
@@ -275,6 +449,9 @@ impl MapShape for ServerConfigShape {
         assign_map_struct(&mut self.template, rhs.template);
         assign_map_struct(&mut self.file_serving, rhs.file_serving);
         assign_map_struct(&mut self.storage, rhs.storage);
+        assign_map_struct(&mut self.trash, rhs.trash);
+        assign_map_struct(&mut self.sync, rhs.sync);
+        assign_map_struct(&mut self.processing, rhs.processing);
     }
 }
 
@@ -299,6 +476,9 @@ impl Default for ServerConfigShape {
             template: None,
             file_serving: None,
             storage: None,
+            trash: None,
+            sync: None,
+            processing: None,
         }
     }
 }
@@ -448,6 +628,42 @@ pub fn validate_server_config_shape(conf_dir: &Path, mut conf: ServerConfigShape
         None
     };
 
+    conf.processing = if let Some(mut processing) = conf.processing {
+        processing.commands = if let Some(commands) = processing.commands {
+            let mut verified_map = HashMap::with_capacity(commands.len());
+
+            for (key, value) in commands {
+                let Some(mut command) = value else {
+                    verified_map.insert(key, None);
+                    continue;
+                };
+
+                // a program without a directory is found with PATH when run
+                command.program = match command.program {
+                    Some(program) if program.components().count() > 1 => {
+                        let mut name = String::from("processing command program (conf.processing.commands.\"");
+                        name.reserve(key.len() + 10);
+                        name.push_str(&key);
+                        name.push_str("\".program)");
+
+                        Some(validate_path_buf(conf_dir, &name, false, program)?)
+                    },
+                    program => program
+                };
+
+                verified_map.insert(key, Some(command));
+            }
+
+            Some(verified_map)
+        } else {
+            None
+        };
+
+        Some(processing)
+    } else {
+        None
+    };
+
     conf.bind = if let Some(bind) = conf.bind {
         let mut verified_map = HashMap::with_capacity(bind.len());
 
@@ -0,0 +1,898 @@
+//! typed filters for searching entries
+//!
+//! filters are compiled into a where clause with all user provided values
+//! pushed into a [QueryParams] so nothing given by a client is written
+//! directly into the sql.
+
+use std::fmt::Write;
+use std::str::FromStr;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::db::{error, query::QueryParams};
+
+/// the longest tag expression that will be parsed
+pub const MAX_TAG_EXPR_LEN: usize = 1024;
+
+/// how deep `not` and parenthesis can be nested in a tag expression
+pub const MAX_TAG_DEPTH: usize = 32;
+
+/// boolean expression of tag ids
+///
+/// a flat comma separated list of tag ids is treated as `Or` to keep the
+/// original `tags=1,2,3` format working.
+#[derive(Debug, Clone)]
+pub enum TagExpr {
+    Tag(i32),
+    And(Vec<TagExpr>),
+    Or(Vec<TagExpr>),
+    Not(Box<TagExpr>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum TagToken {
+    Id(i32),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tag_tokens(given: &str) -> error::Result<Vec<TagToken>> {
+    let mut rtn = Vec::new();
+    let mut chars: Peekable<Chars> = given.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '(' => rtn.push(TagToken::Open),
+            ')' => rtn.push(TagToken::Close),
+            '&' => rtn.push(TagToken::And),
+            '|' | ',' => rtn.push(TagToken::Or),
+            '!' => rtn.push(TagToken::Not),
+            '0'..='9' => {
+                let mut number = String::from(ch);
+
+                while let Some(next) = chars.peek() {
+                    if !next.is_ascii_digit() {
+                        break;
+                    }
+
+                    number.push(chars.next().unwrap());
+                }
+
+                let Ok(id) = i32::from_str(&number) else {
+                    return Err(error::Error::Validation(
+                        format!("invalid tag id given in tag expression: {}", number)
+                    ));
+                };
+
+                rtn.push(TagToken::Id(id));
+            },
+            'a'..='z' | 'A'..='Z' => {
+                let mut word = String::from(ch);
+
+                while let Some(next) = chars.peek() {
+                    if !next.is_ascii_alphabetic() {
+                        break;
+                    }
+
+                    word.push(chars.next().unwrap());
+                }
+
+                match word.to_ascii_lowercase().as_str() {
+                    "and" => rtn.push(TagToken::And),
+                    "or" => rtn.push(TagToken::Or),
+                    "not" => rtn.push(TagToken::Not),
+                    _ => return Err(error::Error::Validation(
+                        format!("unknown word in tag expression: {}", word)
+                    ))
+                }
+            },
+            _ if ch.is_whitespace() => {},
+            _ => return Err(error::Error::Validation(
+                format!("unknown character in tag expression: {}", ch)
+            ))
+        }
+    }
+
+    Ok(rtn)
+}
+
+struct TagParser {
+    tokens: Vec<TagToken>,
+    index: usize,
+    depth: usize,
+}
+
+impl TagParser {
+    fn peek(&self) -> Option<&TagToken> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<TagToken> {
+        let rtn = self.tokens.get(self.index).copied();
+        self.index += 1;
+        rtn
+    }
+
+    fn parse_or(&mut self) -> error::Result<TagExpr> {
+        let mut list = vec![self.parse_and()?];
+
+        while self.peek() == Some(&TagToken::Or) {
+            self.index += 1;
+            list.push(self.parse_and()?);
+        }
+
+        if list.len() == 1 {
+            Ok(list.pop().unwrap())
+        } else {
+            Ok(TagExpr::Or(list))
+        }
+    }
+
+    fn parse_and(&mut self) -> error::Result<TagExpr> {
+        let mut list = vec![self.parse_unary()?];
+
+        while self.peek() == Some(&TagToken::And) {
+            self.index += 1;
+            list.push(self.parse_unary()?);
+        }
+
+        if list.len() == 1 {
+            Ok(list.pop().unwrap())
+        } else {
+            Ok(TagExpr::And(list))
+        }
+    }
+
+    /// tracks nesting so deep expressions are rejected before they can
+    /// overflow the stack
+    fn descend(&mut self) -> error::Result<()> {
+        self.depth += 1;
+
+        if self.depth > MAX_TAG_DEPTH {
+            Err(error::Error::Validation(
+                format!("tag expression is nested more than {} levels deep", MAX_TAG_DEPTH)
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn parse_unary(&mut self) -> error::Result<TagExpr> {
+        match self.next() {
+            Some(TagToken::Not) => {
+                self.descend()?;
+
+                let expr = self.parse_unary()?;
+
+                self.depth -= 1;
+
+                Ok(TagExpr::Not(Box::new(expr)))
+            },
+            Some(TagToken::Id(id)) => Ok(TagExpr::Tag(id)),
+            Some(TagToken::Open) => {
+                self.descend()?;
+
+                let expr = self.parse_or()?;
+
+                if self.next() != Some(TagToken::Close) {
+                    return Err(error::Error::Validation(
+                        "missing closing parenthesis in tag expression".to_owned()
+                    ));
+                }
+
+                self.depth -= 1;
+
+                Ok(expr)
+            },
+            _ => Err(error::Error::Validation(
+                "expected tag id, \"not\", or \"(\" in tag expression".to_owned()
+            ))
+        }
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = error::Error;
+
+    /// parses a tag expression
+    ///
+    /// `and`/`&`, `or`/`|`/`,`, `not`/`!` and parenthesis are accepted with
+    /// `not` binding tightest followed by `and` then `or`. example:
+    /// `(1 and 2) or not 3`. the length and nesting of the expression are
+    /// limited by [MAX_TAG_EXPR_LEN] and [MAX_TAG_DEPTH]
+    fn from_str(given: &str) -> error::Result<TagExpr> {
+        if given.len() > MAX_TAG_EXPR_LEN {
+            return Err(error::Error::Validation(
+                format!("tag expression is longer than {} characters", MAX_TAG_EXPR_LEN)
+            ));
+        }
+
+        let mut parser = TagParser {
+            tokens: tag_tokens(given)?,
+            index: 0,
+            depth: 0,
+        };
+        let expr = parser.parse_or()?;
+
+        if parser.peek().is_some() {
+            return Err(error::Error::Validation(
+                "unexpected token at end of tag expression".to_owned()
+            ));
+        }
+
+        Ok(expr)
+    }
+}
+
+impl TagExpr {
+    fn compile<'a>(&'a self, query: &mut String, params: &mut QueryParams<'a>) -> error::Result<()> {
+        match self {
+            TagExpr::Tag(id) => {
+                write!(
+                    query,
+                    "exists (\
+                        select 1 \
+                        from entries2tags \
+                        where entries2tags.entry = entries.id and \
+                              entries2tags.tag = ${}\
+                    )",
+                    params.push(id)
+                )?;
+            },
+            TagExpr::And(list) | TagExpr::Or(list) => {
+                let joiner = if let TagExpr::And(_) = self { " and " } else { " or " };
+                let mut first = true;
+
+                query.push('(');
+
+                for expr in list {
+                    if first {
+                        first = false;
+                    } else {
+                        query.push_str(joiner);
+                    }
+
+                    expr.compile(query, params)?;
+                }
+
+                query.push(')');
+            },
+            TagExpr::Not(expr) => {
+                query.push_str("not ");
+                expr.compile(query, params)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// comparison operator for a custom field filter
+#[derive(Debug, Clone)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Compare {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Compare::Eq => "=",
+            Compare::Ne => "<>",
+            Compare::Lt => "<",
+            Compare::Le => "<=",
+            Compare::Gt => ">",
+            Compare::Ge => ">=",
+        }
+    }
+}
+
+/// the part of a custom field entry value to compare against
+#[derive(Debug, Clone)]
+pub enum FieldKey {
+    Value,
+    Low,
+    High,
+}
+
+impl FieldKey {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            FieldKey::Value => "value",
+            FieldKey::Low => "low",
+            FieldKey::High => "high",
+        }
+    }
+}
+
+/// filters entries on the numeric value of a custom field entry
+///
+/// only Integer, IntegerRange, Float, and FloatRange entries can be compared.
+/// range entries use the low or high key, single values use value.
+#[derive(Debug, Clone)]
+pub struct FieldFilter {
+    pub field: i32,
+    pub key: FieldKey,
+    pub compare: Compare,
+    pub value: f64,
+}
+
+impl FromStr for FieldFilter {
+    type Err = error::Error;
+
+    /// parses a single field filter in the format of
+    /// `{field_id}[.value|.low|.high]{op}{number}` with op being one of
+    /// `=`, `!=`, `<`, `<=`, `>`, `>=`. example: `3>5` or `4.low>=2`
+    fn from_str(given: &str) -> error::Result<FieldFilter> {
+        let given = given.trim();
+        let Some(op_index) = given.find(|c: char| c == '<' || c == '>' || c == '=' || c == '!') else {
+            return Err(error::Error::Validation(
+                format!("missing comparison operator in field filter: {}", given)
+            ));
+        };
+        let (lhs, rest) = given.split_at(op_index);
+
+        let (compare, rhs) = if let Some(rhs) = rest.strip_prefix("<=") {
+            (Compare::Le, rhs)
+        } else if let Some(rhs) = rest.strip_prefix(">=") {
+            (Compare::Ge, rhs)
+        } else if let Some(rhs) = rest.strip_prefix("!=") {
+            (Compare::Ne, rhs)
+        } else if let Some(rhs) = rest.strip_prefix('<') {
+            (Compare::Lt, rhs)
+        } else if let Some(rhs) = rest.strip_prefix('>') {
+            (Compare::Gt, rhs)
+        } else if let Some(rhs) = rest.strip_prefix('=') {
+            (Compare::Eq, rhs)
+        } else {
+            return Err(error::Error::Validation(
+                format!("invalid comparison operator in field filter: {}", given)
+            ));
+        };
+
+        let (field_str, key) = match lhs.trim().split_once('.') {
+            Some((field_str, "value")) => (field_str, FieldKey::Value),
+            Some((field_str, "low")) => (field_str, FieldKey::Low),
+            Some((field_str, "high")) => (field_str, FieldKey::High),
+            Some((_, unknown)) => return Err(error::Error::Validation(
+                format!("unknown field key in field filter: {}", unknown)
+            )),
+            None => (lhs.trim(), FieldKey::Value)
+        };
+
+        let Ok(field) = i32::from_str(field_str) else {
+            return Err(error::Error::Validation(
+                format!("invalid field id in field filter: {}", field_str)
+            ));
+        };
+        // nan and infinity parse as floats but cannot be compared against
+        // the stored values
+        let Some(value) = f64::from_str(rhs.trim()).ok().filter(|v| v.is_finite()) else {
+            return Err(error::Error::Validation(
+                format!("invalid number in field filter: {}", rhs)
+            ));
+        };
+
+        Ok(FieldFilter { field, key, compare, value })
+    }
+}
+
+impl FieldFilter {
+    /// parses a comma separated list of field filters
+    pub fn from_list(given: &str) -> error::Result<Vec<FieldFilter>> {
+        let mut rtn = Vec::new();
+
+        for split in given.split(',') {
+            if split.trim().is_empty() {
+                continue;
+            }
+
+            rtn.push(FieldFilter::from_str(split)?);
+        }
+
+        Ok(rtn)
+    }
+
+    fn compile<'a>(&'a self, query: &mut String, params: &mut QueryParams<'a>) -> error::Result<()> {
+        let field_index = params.push(&self.field);
+        let value_index = params.push(&self.value);
+
+        // the case makes sure the cast only happens for numeric entries
+        write!(
+            query,
+            "exists (\
+                select 1 \
+                from custom_field_entries \
+                where custom_field_entries.entry = entries.id and \
+                      custom_field_entries.field = ${} and \
+                      case when custom_field_entries.value->>'type' in ('Integer', 'IntegerRange', 'Float', 'FloatRange') \
+                           then (custom_field_entries.value->>'{}')::float8 \
+                           else null \
+                      end {} ${}\
+            )",
+            field_index,
+            self.key.as_sql(),
+            self.compare.as_sql(),
+            value_index
+        )?;
+
+        Ok(())
+    }
+}
+
+/// which side of a cursor to retrieve entries from
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorDirection {
+    /// entries older than the cursor
+    Next,
+    /// entries newer than the cursor
+    Prev,
+}
+
+/// position in a list of entries ordered by day and id
+///
+/// when searching the list is ordered by rank first so the rank of the entry
+/// is kept as well. given to clients as an opaque url safe string
+#[derive(Debug, Clone)]
+pub struct EntriesCursor {
+    pub direction: CursorDirection,
+    pub day: DateTime<Utc>,
+    pub id: i32,
+    pub rank: Option<f32>,
+}
+
+impl EntriesCursor {
+    pub fn new(direction: CursorDirection, day: DateTime<Utc>, id: i32) -> EntriesCursor {
+        EntriesCursor { direction, day, id, rank: None }
+    }
+
+    /// creates a cursor for a list ordered by search rank
+    pub fn with_rank(direction: CursorDirection, rank: f32, day: DateTime<Utc>, id: i32) -> EntriesCursor {
+        EntriesCursor { direction, day, id, rank: Some(rank) }
+    }
+
+    /// encodes the cursor into a url safe string
+    pub fn encode(&self) -> String {
+        let dir = match self.direction {
+            CursorDirection::Next => 'n',
+            CursorDirection::Prev => 'p',
+        };
+        let mut plain = format!(
+            "{}.{}.{}.{}",
+            dir,
+            self.day.timestamp(),
+            self.day.timestamp_subsec_nanos(),
+            self.id
+        );
+
+        // the bits are kept so the rank compares exactly with the database
+        if let Some(rank) = self.rank {
+            let _ = write!(&mut plain, ".{}", rank.to_bits());
+        }
+
+        base64::encode_config(plain.as_bytes(), base64::URL_SAFE)
+    }
+}
+
+impl FromStr for EntriesCursor {
+    type Err = error::Error;
+
+    /// decodes a cursor created by [EntriesCursor::encode]
+    fn from_str(given: &str) -> error::Result<EntriesCursor> {
+        let invalid = || error::Error::Validation("invalid cursor given".to_owned());
+
+        let Ok(bytes) = base64::decode_config(given, base64::URL_SAFE) else {
+            return Err(invalid());
+        };
+        let Ok(plain) = String::from_utf8(bytes) else {
+            return Err(invalid());
+        };
+        let mut split = plain.split('.');
+
+        let direction = match split.next() {
+            Some("n") => CursorDirection::Next,
+            Some("p") => CursorDirection::Prev,
+            _ => return Err(invalid())
+        };
+        let Some(Ok(secs)) = split.next().map(i64::from_str) else {
+            return Err(invalid());
+        };
+        let Some(Ok(nanos)) = split.next().map(u32::from_str) else {
+            return Err(invalid());
+        };
+        let Some(Ok(id)) = split.next().map(i32::from_str) else {
+            return Err(invalid());
+        };
+        let rank = match split.next().map(u32::from_str) {
+            Some(Ok(bits)) => Some(f32::from_bits(bits)),
+            Some(Err(_)) => return Err(invalid()),
+            None => None
+        };
+
+        if split.next().is_some() {
+            return Err(invalid());
+        }
+
+        let Some(naive) = NaiveDateTime::from_timestamp_opt(secs, nanos) else {
+            return Err(invalid());
+        };
+
+        if rank.map(|r| !r.is_finite()).unwrap_or(false) {
+            return Err(invalid());
+        }
+
+        Ok(EntriesCursor {
+            direction,
+            day: DateTime::from_utc(naive, Utc),
+            id,
+            rank,
+        })
+    }
+}
+
+/// the compiled where clause with indexes of params other queries may want
+/// to reference
+///
+/// rank is an expression for the search rank of an entry when searching. it
+/// uses the same params as the clause
+pub struct EntriesWhere {
+    pub clause: String,
+    pub search: Option<usize>,
+    pub rank: Option<String>,
+}
+
+/// filter for searching entries of a given owner
+pub struct EntriesFilter {
+    pub owner: i32,
+    pub is_private: Option<bool>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// inclusive lower bound on the day and id of an entry
+    pub from_entry: Option<(DateTime<Utc>, i32)>,
+    /// inclusive upper bound on the day and id of an entry
+    pub to_entry: Option<(DateTime<Utc>, i32)>,
+    pub tags: Option<TagExpr>,
+    pub fields: Vec<FieldFilter>,
+    pub has_audio: Option<bool>,
+    pub has_text: Option<bool>,
+    pub search: Option<String>,
+    pub cursor: Option<EntriesCursor>,
+    /// only show entries that are in the trash
+    pub trashed: bool,
+}
+
+fn private_sql(table: &str, is_private: &Option<bool>) -> String {
+    if let Some(private) = is_private {
+        format!(" and {}.private = {}", table, if *private { "true" } else { "false" })
+    } else {
+        String::new()
+    }
+}
+
+impl EntriesFilter {
+    pub fn new(owner: i32) -> EntriesFilter {
+        EntriesFilter {
+            owner,
+            is_private: None,
+            from: None,
+            to: None,
+            from_entry: None,
+            to_entry: None,
+            tags: None,
+            fields: Vec::new(),
+            has_audio: None,
+            has_text: None,
+            search: None,
+            cursor: None,
+            trashed: false,
+        }
+    }
+
+    /// compiles the filter into a where clause for the entries table
+    ///
+    /// the clause can be used in any query that has the entries table
+    /// available. any query using the clause must also be given the params.
+    pub fn compile<'a>(&'a self, params: &mut QueryParams<'a>) -> error::Result<EntriesWhere> {
+        let mut clause = String::new();
+        let mut search = None;
+        let mut rank = None;
+
+        write!(&mut clause, "entries.owner = ${}", params.push(&self.owner))?;
+
+        if self.trashed {
+            clause.push_str(" and entries.deleted is not null");
+        } else {
+            clause.push_str(" and entries.deleted is null");
+        }
+
+        if let Some(from) = self.from.as_ref() {
+            write!(&mut clause, " and entries.day >= ${}", params.push(from))?;
+        }
+
+        if let Some(to) = self.to.as_ref() {
+            write!(&mut clause, " and entries.day <= ${}", params.push(to))?;
+        }
+
+        if let Some((day, id)) = self.from_entry.as_ref() {
+            write!(
+                &mut clause,
+                " and (entries.day, entries.id) >= (${}, ${})",
+                params.push(day),
+                params.push(id)
+            )?;
+        }
+
+        if let Some((day, id)) = self.to_entry.as_ref() {
+            write!(
+                &mut clause,
+                " and (entries.day, entries.id) <= (${}, ${})",
+                params.push(day),
+                params.push(id)
+            )?;
+        }
+
+        if let Some(tags) = self.tags.as_ref() {
+            clause.push_str(" and ");
+            tags.compile(&mut clause, params)?;
+        }
+
+        for field in &self.fields {
+            clause.push_str(" and ");
+            field.compile(&mut clause, params)?;
+        }
+
+        if let Some(has_audio) = self.has_audio {
+            write!(
+                &mut clause,
+                " and {}exists (select 1 from audio_entries where audio_entries.entry = entries.id{})",
+                if has_audio { "" } else { "not " },
+                private_sql("audio_entries", &self.is_private)
+            )?;
+        }
+
+        if let Some(has_text) = self.has_text {
+            write!(
+                &mut clause,
+                " and {}exists (select 1 from text_entries where text_entries.entry = entries.id{})",
+                if has_text { "" } else { "not " },
+                private_sql("text_entries", &self.is_private)
+            )?;
+        }
+
+        if let Some(q) = self.search.as_ref() {
+            let index = params.push(q);
+
+            write!(
+                &mut clause,
+                " and exists (\
+                    select 1 \
+                    from text_entries \
+                    where text_entries.entry = entries.id and \
+                          text_entries.thought_search @@ websearch_to_tsquery('english', ${}){}\
+                )",
+                index,
+                private_sql("text_entries", &self.is_private)
+            )?;
+
+            // the highest rank of the text entries that match
+            rank = Some(format!(
+                "(\
+                    select max(ts_rank(text_entries.thought_search, websearch_to_tsquery('english', ${0}))) \
+                    from text_entries \
+                    where text_entries.entry = entries.id and \
+                          text_entries.thought_search @@ websearch_to_tsquery('english', ${0}){1}\
+                )",
+                index,
+                private_sql("text_entries", &self.is_private)
+            ));
+            search = Some(index);
+        }
+
+        if let Some(cursor) = self.cursor.as_ref() {
+            let compare = if cursor.direction == CursorDirection::Next { "<" } else { ">" };
+
+            match (rank.as_ref(), cursor.rank.as_ref()) {
+                (Some(rank_sql), Some(cursor_rank)) => write!(
+                    &mut clause,
+                    " and ({}, entries.day, entries.id) {} (${}, ${}, ${})",
+                    rank_sql,
+                    compare,
+                    params.push(cursor_rank),
+                    params.push(&cursor.day),
+                    params.push(&cursor.id)
+                )?,
+                (None, None) => write!(
+                    &mut clause,
+                    " and (entries.day, entries.id) {} (${}, ${})",
+                    compare,
+                    params.push(&cursor.day),
+                    params.push(&cursor.id)
+                )?,
+                _ => return Err(error::Error::Validation(
+                    "cursor given was not created for the current search".to_owned()
+                ))
+            }
+        }
+
+        Ok(EntriesWhere { clause, search, rank })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(given: &str) -> TagExpr {
+        match TagExpr::from_str(given) {
+            Ok(expr) => expr,
+            Err(err) => panic!("failed to parse \"{}\" {:?}", given, err)
+        }
+    }
+
+    fn compile_tags(expr: &TagExpr) -> (String, usize) {
+        let mut query = String::new();
+        let mut params = QueryParams::with_capacity(4);
+
+        expr.compile(&mut query, &mut params).unwrap();
+
+        (query, params.slice().len())
+    }
+
+    #[test]
+    fn comma_list_is_or() {
+        let TagExpr::Or(list) = parse("1,2,3") else {
+            panic!("expected or");
+        };
+
+        assert_eq!(list.len(), 3);
+        assert!(matches!(list[2], TagExpr::Tag(3)));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_then_or() {
+        let TagExpr::Or(list) = parse("1 and not 2 or 3") else {
+            panic!("expected or at the root");
+        };
+
+        assert!(matches!(list[1], TagExpr::Tag(3)));
+
+        let TagExpr::And(and) = &list[0] else {
+            panic!("expected and on the left");
+        };
+
+        assert!(matches!(and[0], TagExpr::Tag(1)));
+        assert!(matches!(&and[1], TagExpr::Not(inner) if matches!(**inner, TagExpr::Tag(2))));
+    }
+
+    #[test]
+    fn parenthesis_group() {
+        let TagExpr::And(list) = parse("(1 | 2) & !(3)") else {
+            panic!("expected and at the root");
+        };
+
+        assert!(matches!(list[0], TagExpr::Or(_)));
+        assert!(matches!(list[1], TagExpr::Not(_)));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for given in ["1 and", "(1 or 2", "1 2", "1 xor 2", "1 # 2", ")", "99999999999"] {
+            assert!(TagExpr::from_str(given).is_err(), "\"{}\" should not parse", given);
+        }
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let allowed = format!("{}1{}", "(".repeat(MAX_TAG_DEPTH), ")".repeat(MAX_TAG_DEPTH));
+        let parens = format!("{}1{}", "(".repeat(MAX_TAG_DEPTH + 1), ")".repeat(MAX_TAG_DEPTH + 1));
+        let nots = format!("{}1", "!".repeat(MAX_TAG_DEPTH + 1));
+
+        parse(&allowed);
+
+        assert!(TagExpr::from_str(&parens).is_err());
+        assert!(TagExpr::from_str(&nots).is_err());
+    }
+
+    #[test]
+    fn depth_is_not_cumulative_across_siblings() {
+        let group = format!("{}1{}", "(".repeat(MAX_TAG_DEPTH), ")".repeat(MAX_TAG_DEPTH));
+        let given = format!("{} or {}", group, group);
+
+        parse(&given);
+    }
+
+    #[test]
+    fn length_is_limited() {
+        let given = vec!["1"; MAX_TAG_EXPR_LEN / 2 + 1].join(",");
+
+        assert!(given.len() > MAX_TAG_EXPR_LEN);
+        assert!(TagExpr::from_str(&given).is_err());
+    }
+
+    #[test]
+    fn tags_compile_to_params() {
+        let (query, count) = compile_tags(&parse("(1 and 2) or not 3"));
+
+        assert_eq!(count, 3);
+        assert!(query.starts_with("((exists"));
+        assert!(query.contains("entries2tags.tag = $1"));
+        assert!(query.contains("not exists"));
+        assert!(query.contains("entries2tags.tag = $3"));
+    }
+
+    #[test]
+    fn field_filters() {
+        let list = FieldFilter::from_list("3>5, 4.low>=2,,5.high!=1.5").unwrap();
+
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].field, 3);
+        assert!(matches!(list[0].compare, Compare::Gt));
+        assert!(matches!(list[0].key, FieldKey::Value));
+        assert!(matches!(list[1].compare, Compare::Ge));
+        assert!(matches!(list[1].key, FieldKey::Low));
+        assert!(matches!(list[2].compare, Compare::Ne));
+        assert_eq!(list[2].value, 1.5);
+
+        for given in ["3", "a>1", "3.mid>1", "3>x", "3=>1"] {
+            assert!(FieldFilter::from_str(given).is_err(), "\"{}\" should not parse", given);
+        }
+    }
+
+    #[test]
+    fn field_filters_must_be_finite() {
+        for given in ["3>NaN", "3<inf", "3>=-inf", "3.low=infinity", "3!=1e999"] {
+            assert!(FieldFilter::from_str(given).is_err(), "\"{}\" should not parse", given);
+        }
+
+        assert_eq!(FieldFilter::from_str("3<1e300").unwrap().value, 1e300);
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let day = DateTime::from_utc(NaiveDateTime::from_timestamp_opt(1_600_000_000, 500).unwrap(), Utc);
+        let cursor = EntriesCursor::new(CursorDirection::Prev, day, 42);
+        let decoded = EntriesCursor::from_str(&cursor.encode()).unwrap();
+
+        assert_eq!(decoded.direction, CursorDirection::Prev);
+        assert_eq!(decoded.day, day);
+        assert_eq!(decoded.id, 42);
+        assert_eq!(decoded.rank, None);
+
+        let ranked = EntriesCursor::with_rank(CursorDirection::Next, 0.0607927, day, 7);
+        let decoded = EntriesCursor::from_str(&ranked.encode()).unwrap();
+
+        assert_eq!(decoded.direction, CursorDirection::Next);
+        assert_eq!(decoded.rank.map(f32::to_bits), Some(0.0607927f32.to_bits()));
+
+        assert!(EntriesCursor::from_str("not a cursor").is_err());
+        assert!(EntriesCursor::from_str(&base64::encode_config("x.1.0.1", base64::URL_SAFE)).is_err());
+    }
+
+    #[test]
+    fn search_cursor_must_match_search() {
+        let day = Utc::now();
+        let mut filter = EntriesFilter::new(1);
+        filter.cursor = Some(EntriesCursor::new(CursorDirection::Next, day, 1));
+        filter.search = Some("word".to_owned());
+
+        let mut params = QueryParams::with_capacity(4);
+        assert!(filter.compile(&mut params).is_err());
+
+        filter.cursor = Some(EntriesCursor::with_rank(CursorDirection::Next, 0.5, day, 1));
+
+        let mut params = QueryParams::with_capacity(4);
+        let compiled = filter.compile(&mut params).unwrap();
+
+        assert!(compiled.rank.is_some());
+        assert!(compiled.clause.contains(", entries.day, entries.id) < ("));
+    }
+}
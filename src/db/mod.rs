@@ -1,6 +1,7 @@
 pub mod error;
 
 pub mod query;
+pub mod filter;
 
 pub mod tables;
 
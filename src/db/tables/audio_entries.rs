@@ -14,6 +14,15 @@ pub struct AudioEntry {
     pub mime_type: String,
     pub mime_subtype: String,
     pub file_size: i64,
+    /// length of the audio in milliseconds
+    pub duration_ms: Option<i64>,
+    pub codec: Option<String>,
+    pub sample_rate: Option<i32>,
+    pub channels: Option<i32>,
+    /// hash of the stored file. None for files that have not been moved to
+    /// blobs yet
+    #[serde(skip)]
+    pub blob: Option<String>,
 }
 
 pub async fn find_from_id(
@@ -24,10 +33,16 @@ pub async fn find_from_id(
     let mut query_str = format!("\
     select id, \
            private, \
+           comment, \
            entry, \
            mime_type, \
            mime_subtype, \
-           file_size \
+           file_size, \
+           duration_ms, \
+           codec, \
+           sample_rate, \
+           channels, \
+           blob \
     from audio_entries \
     where id = $1");
     let mut query_slice = query::QueryParams::with_capacity(2);
@@ -49,6 +64,11 @@ pub async fn find_from_id(
             mime_type: row.get(4),
             mime_subtype: row.get(5),
             file_size: row.get(6),
+            duration_ms: row.get(7),
+            codec: row.get(8),
+            sample_rate: row.get(9),
+            channels: row.get(10),
+            blob: row.get(11),
         }))
     } else {
         Ok(None)
@@ -67,9 +87,14 @@ pub async fn find_from_entry(
            entry, \
            mime_type, \
            mime_subtype, \
-           file_size \
+           file_size, \
+           duration_ms, \
+           codec, \
+           sample_rate, \
+           channels, \
+           blob \
     from audio_entries \
-    where entry_id = $1");
+    where entry = $1");
     let mut query_slice = query::QueryParams::with_capacity(1);
     query_slice.push(entry_id);
 
@@ -88,6 +113,11 @@ pub async fn find_from_entry(
             mime_type: row.get(4),
             mime_subtype: row.get(5),
             file_size: row.get(6),
+            duration_ms: row.get(7),
+            codec: row.get(8),
+            sample_rate: row.get(9),
+            channels: row.get(10),
+            blob: row.get(11),
         })
         .collect())
 }
@@ -0,0 +1,77 @@
+use tokio_postgres::GenericClient;
+use chrono::{DateTime, Utc};
+
+use crate::db::error;
+
+/// a blob that is no longer referenced by any media
+pub struct UnusedBlob {
+    pub hash: String,
+    pub extension: String,
+}
+
+/// records a new blob for a user with no references
+///
+/// returns false if the user already has the blob. the references are counted
+/// by triggers on the media tables so they are added when a row is given the
+/// hash of the blob
+pub async fn create(
+    conn: &impl GenericClient,
+    owner: &i32,
+    hash: &str,
+    size: &i64,
+    extension: &str,
+    created: &DateTime<Utc>,
+) -> error::Result<bool> {
+    let result = conn.query_opt(
+        "\
+        insert into blobs (owner, hash, size, extension, created) \
+        values ($1, $2, $3, $4, $5) \
+        on conflict (owner, hash) do nothing \
+        returning hash",
+        &[owner, &hash, size, &extension, created]
+    ).await?;
+
+    Ok(result.is_some())
+}
+
+/// retrieves the extension of a blob of a user
+pub async fn find_extension(
+    conn: &impl GenericClient,
+    owner: &i32,
+    hash: &str,
+) -> error::Result<Option<String>> {
+    Ok(conn.query_opt(
+        "select extension from blobs where owner = $1 and hash = $2",
+        &[owner, &hash]
+    )
+        .await?
+        .map(|row| row.get(0)))
+}
+
+/// removes the given blobs of a user if nothing references them anymore
+///
+/// returns the blobs that were removed so their objects can be removed
+pub async fn remove_unused(
+    conn: &impl GenericClient,
+    owner: &i32,
+    hashes: &[String],
+) -> error::Result<Vec<UnusedBlob>> {
+    if hashes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(conn.query(
+        "\
+        delete from blobs \
+        where owner = $1 and hash = any($2) and ref_count <= 0 \
+        returning hash, extension",
+        &[owner, &hashes]
+    )
+        .await?
+        .iter()
+        .map(|row| UnusedBlob {
+            hash: row.get(0),
+            extension: row.get(1),
+        })
+        .collect())
+}
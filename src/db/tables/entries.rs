@@ -14,6 +14,9 @@ pub struct Entry {
     pub updated: Option<DateTime<Utc>>,
     pub deleted: Option<DateTime<Utc>>,
     pub owner: i32,
+    /// incremented every time the entry is changed
+    #[serde(default)]
+    pub version: i32,
 }
 
 /// finds an entry based on the user id and entry id
@@ -29,7 +32,8 @@ pub async fn from_user_and_id(
                created, \
                updated, \
                deleted, \
-               owner \
+               owner, \
+               version \
         from entries \
         where owner = $1 and \
               id = $2",
@@ -41,7 +45,8 @@ pub async fn from_user_and_id(
             created: record.get(2),
             updated: record.get(3),
             deleted: record.get(4),
-            owner: record.get(5)
+            owner: record.get(5),
+            version: record.get(6),
         }))
     } else {
         Ok(None)
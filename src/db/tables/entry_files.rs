@@ -0,0 +1,117 @@
+use std::fmt::{Write};
+
+use tokio_postgres::{GenericClient};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::db::{error, query};
+
+#[derive(Serialize, Deserialize)]
+pub struct EntryFile {
+    pub id: i32,
+    pub private: bool,
+    pub comment: Option<String>,
+    pub name: String,
+    pub entry: i32,
+    pub mime_type: String,
+    pub mime_subtype: String,
+    pub file_size: i64,
+    pub created: DateTime<Utc>,
+    /// hash of the stored file. None for files that have not been moved to
+    /// blobs yet
+    #[serde(skip)]
+    pub blob: Option<String>,
+}
+
+pub async fn find_from_id(
+    conn: &impl GenericClient,
+    entry_id: &i32,
+    file_id: &i32,
+    is_private: &Option<bool>,
+) -> error::Result<Option<EntryFile>> {
+    let mut query_str = format!("\
+    select id, \
+           private, \
+           comment, \
+           name, \
+           entry, \
+           mime_type, \
+           mime_subtype, \
+           file_size, \
+           created, \
+           blob \
+    from entry_files \
+    where id = $1 and entry = $2");
+    let mut query_slice = query::QueryParams::with_capacity(3);
+    query_slice.push(file_id);
+    query_slice.push(entry_id);
+
+    if let Some(private) = is_private {
+        write!(&mut query_str, " and private = ${}", query_slice.push(private))?;
+    }
+
+    if let Some(row) = conn.query_opt(
+        query_str.as_str(),
+        query_slice.slice()
+    ).await? {
+        Ok(Some(EntryFile {
+            id: row.get(0),
+            private: row.get(1),
+            comment: row.get(2),
+            name: row.get(3),
+            entry: row.get(4),
+            mime_type: row.get(5),
+            mime_subtype: row.get(6),
+            file_size: row.get(7),
+            created: row.get(8),
+            blob: row.get(9),
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn find_from_entry(
+    conn: &impl GenericClient,
+    entry_id: &i32,
+    is_private: &Option<bool>
+) -> error::Result<Vec<EntryFile>> {
+    let mut query_str = format!("\
+    select id, \
+           private, \
+           comment, \
+           name, \
+           entry, \
+           mime_type, \
+           mime_subtype, \
+           file_size, \
+           created, \
+           blob \
+    from entry_files \
+    where entry = $1");
+    let mut query_slice = query::QueryParams::with_capacity(2);
+    query_slice.push(entry_id);
+
+    if let Some(private) = is_private {
+        write!(&mut query_str, " and private = ${}", query_slice.push(private))?;
+    }
+
+    query_str.push_str(" order by id");
+
+    Ok(conn.query(query_str.as_str(), query_slice.slice())
+        .await?
+        .iter()
+        .map(|row| EntryFile {
+            id: row.get(0),
+            private: row.get(1),
+            comment: row.get(2),
+            name: row.get(3),
+            entry: row.get(4),
+            mime_type: row.get(5),
+            mime_subtype: row.get(6),
+            file_size: row.get(7),
+            created: row.get(8),
+            blob: row.get(9),
+        })
+        .collect())
+}
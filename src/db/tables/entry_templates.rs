@@ -0,0 +1,108 @@
+use tokio_postgres::{GenericClient};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::db::error;
+use crate::db::tables::custom_field_entries::CustomFieldEntryType;
+
+/// default value for a custom field when creating an entry from a template
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TemplateCustomFieldEntry {
+    pub field: i32,
+    pub value: CustomFieldEntryType,
+    pub comment: Option<String>,
+}
+
+/// starter text entry when creating an entry from a template
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TemplateTextEntry {
+    pub thought: String,
+    pub private: bool,
+}
+
+/// the presets that will be applied to a new entry
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct EntryTemplateData {
+    #[serde(default)]
+    pub tags: Vec<i32>,
+    #[serde(default)]
+    pub custom_field_entries: Vec<TemplateCustomFieldEntry>,
+    #[serde(default)]
+    pub text_entries: Vec<TemplateTextEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EntryTemplate {
+    pub id: i32,
+    pub name: String,
+    pub owner: i32,
+    pub comment: Option<String>,
+    pub data: EntryTemplateData,
+    pub created: DateTime<Utc>,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+pub async fn find_from_id(
+    conn: &impl GenericClient,
+    id: &i32
+) -> error::Result<Option<EntryTemplate>> {
+    if let Some(row) = conn.query_opt(
+        "\
+        select id, \
+               name, \
+               owner, \
+               comment, \
+               data, \
+               created, \
+               updated \
+        from entry_templates \
+        where id = $1",
+        &[id]
+    ).await? {
+        Ok(Some(EntryTemplate {
+            id: row.get(0),
+            name: row.get(1),
+            owner: row.get(2),
+            comment: row.get(3),
+            data: serde_json::from_value(row.get(4)).unwrap(),
+            created: row.get(5),
+            updated: row.get(6),
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn find_from_owner(
+    conn: &impl GenericClient,
+    owner: &i32
+) -> error::Result<Vec<EntryTemplate>> {
+    Ok(
+        conn.query(
+            "\
+            select id, \
+                   name, \
+                   owner, \
+                   comment, \
+                   data, \
+                   created, \
+                   updated \
+            from entry_templates \
+            where owner = $1 \
+            order by name",
+            &[owner]
+        )
+        .await?
+        .iter()
+        .map(|row| EntryTemplate {
+            id: row.get(0),
+            name: row.get(1),
+            owner: row.get(2),
+            comment: row.get(3),
+            data: serde_json::from_value(row.get(4)).unwrap(),
+            created: row.get(5),
+            updated: row.get(6),
+        })
+        .collect()
+    )
+}
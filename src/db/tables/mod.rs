@@ -14,6 +14,13 @@ pub mod global_custom_fields;
 pub mod entries;
 pub mod entries2tags;
 pub mod text_entries;
+pub mod text_entry_revisions;
 pub mod audio_entries;
+pub mod blobs;
+pub mod entry_files;
+pub mod image_entries;
+pub mod video_entries;
+pub mod processing_jobs;
 pub mod entry_markers;
-pub mod entry_comments;
\ No newline at end of file
+pub mod entry_comments;
+pub mod entry_templates;
\ No newline at end of file
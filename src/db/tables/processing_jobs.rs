@@ -0,0 +1,97 @@
+use tokio_postgres::{GenericClient, Row};
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+
+use crate::db::error;
+
+/// the states of a processing job
+pub mod status {
+    /// waiting to be run. also used for jobs waiting for a retry
+    pub const PENDING: &str = "pending";
+    pub const RUNNING: &str = "running";
+    pub const DONE: &str = "done";
+    /// failed on every attempt
+    pub const FAILED: &str = "failed";
+}
+
+/// a local command run on an uploaded audio file
+#[derive(Serialize)]
+pub struct ProcessingJob {
+    pub id: i64,
+    pub audio: i32,
+    pub command: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt: DateTime<Utc>,
+    /// stdout of the command when it succeeded
+    pub output: Option<String>,
+    /// the reason the last attempt failed
+    pub error: Option<String>,
+    /// the text entry created from the output
+    pub text_entry: Option<i32>,
+    pub created: DateTime<Utc>,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+const SELECT_COLUMNS: &str = "\
+select id, \
+       audio, \
+       command, \
+       status, \
+       attempts, \
+       next_attempt, \
+       output, \
+       error, \
+       text_entry, \
+       created, \
+       updated \
+from processing_jobs";
+
+fn from_row(row: &Row) -> ProcessingJob {
+    ProcessingJob {
+        id: row.get(0),
+        audio: row.get(1),
+        command: row.get(2),
+        status: row.get(3),
+        attempts: row.get(4),
+        next_attempt: row.get(5),
+        output: row.get(6),
+        error: row.get(7),
+        text_entry: row.get(8),
+        created: row.get(9),
+        updated: row.get(10),
+    }
+}
+
+pub async fn find_from_audio(
+    conn: &impl GenericClient,
+    audio_id: &i32,
+) -> error::Result<Vec<ProcessingJob>> {
+    let query_str = format!("{} where audio = $1 order by command", SELECT_COLUMNS);
+
+    Ok(conn.query(query_str.as_str(), &[audio_id])
+        .await?
+        .iter()
+        .map(from_row)
+        .collect())
+}
+
+/// creates pending jobs for an audio entry that will run as soon as the worker
+/// picks them up
+pub async fn create_for_audio(
+    conn: &impl GenericClient,
+    audio_id: &i32,
+    commands: &[&str],
+    created: &DateTime<Utc>,
+) -> error::Result<()> {
+    for command in commands {
+        conn.execute(
+            "\
+            insert into processing_jobs (audio, command, status, next_attempt, created) \
+            values ($1, $2, $3, $4, $4)",
+            &[audio_id, command, &status::PENDING, created]
+        ).await?;
+    }
+
+    Ok(())
+}
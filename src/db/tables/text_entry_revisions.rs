@@ -0,0 +1,156 @@
+use std::fmt::{Write};
+
+use tokio_postgres::{GenericClient};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::db::error;
+
+/// a single version of a text entry
+///
+/// revisions are only ever added. a new one is created every time the
+/// thought or private flag of a text entry changes and a last one marked as
+/// removed is created when the text entry is removed from its entry
+#[derive(Serialize, Deserialize)]
+pub struct TextEntryRevision {
+    pub id: i32,
+    pub text_entry: i32,
+    pub thought: String,
+    pub private: bool,
+    pub removed: bool,
+    pub created: DateTime<Utc>,
+    pub edited_by: i32,
+}
+
+/// appends a new revision for the given text entry
+pub async fn create(
+    conn: &impl GenericClient,
+    entry: &i32,
+    text_entry: &i32,
+    thought: &String,
+    private: &bool,
+    edited_by: &i32,
+) -> error::Result<TextEntryRevision> {
+    insert(conn, entry, text_entry, thought, private, &false, edited_by).await
+}
+
+/// appends the last revision for a text entry that has been removed
+///
+/// the revision keeps the thought and private flag the text entry had when
+/// it was removed
+pub async fn create_removed(
+    conn: &impl GenericClient,
+    entry: &i32,
+    text_entry: &i32,
+    thought: &String,
+    private: &bool,
+    edited_by: &i32,
+) -> error::Result<TextEntryRevision> {
+    insert(conn, entry, text_entry, thought, private, &true, edited_by).await
+}
+
+async fn insert(
+    conn: &impl GenericClient,
+    entry: &i32,
+    text_entry: &i32,
+    thought: &String,
+    private: &bool,
+    removed: &bool,
+    edited_by: &i32,
+) -> error::Result<TextEntryRevision> {
+    let created = Utc::now();
+    let result = conn.query_one(
+        "\
+        insert into text_entry_revisions (entry, text_entry, thought, private, removed, created, edited_by) \
+        values ($1, $2, $3, $4, $5, $6, $7) \
+        returning id",
+        &[entry, text_entry, thought, private, removed, &created, edited_by]
+    ).await?;
+
+    Ok(TextEntryRevision {
+        id: result.get(0),
+        text_entry: *text_entry,
+        thought: thought.clone(),
+        private: *private,
+        removed: *removed,
+        created,
+        edited_by: *edited_by,
+    })
+}
+
+/// finds all revisions for a text entry, oldest first
+pub async fn find_from_text_entry(
+    conn: &impl GenericClient,
+    text_entry: &i32,
+    is_private: &Option<bool>,
+) -> error::Result<Vec<TextEntryRevision>> {
+    let mut query = "\
+    select id, \
+           text_entry, \
+           thought, \
+           private, \
+           removed, \
+           created, \
+           edited_by \
+    from text_entry_revisions \
+    where text_entry = $1".to_owned();
+
+    if let Some(value) = is_private {
+        write!(&mut query, " and private = {}", if *value { "true" } else { "false" })?;
+    }
+
+    write!(&mut query, " order by created, id")?;
+
+    Ok(
+        conn.query(
+            query.as_str(),
+            &[text_entry]
+        )
+        .await?
+        .iter()
+        .map(|row| TextEntryRevision {
+            id: row.get(0),
+            text_entry: row.get(1),
+            thought: row.get(2),
+            private: row.get(3),
+            removed: row.get(4),
+            created: row.get(5),
+            edited_by: row.get(6),
+        })
+        .collect()
+    )
+}
+
+/// finds a single revision for a text entry
+pub async fn find_from_id(
+    conn: &impl GenericClient,
+    text_entry: &i32,
+    id: &i32,
+) -> error::Result<Option<TextEntryRevision>> {
+    if let Some(row) = conn.query_opt(
+        "\
+        select id, \
+               text_entry, \
+               thought, \
+               private, \
+               removed, \
+               created, \
+               edited_by \
+        from text_entry_revisions \
+        where text_entry = $1 and \
+              id = $2",
+        &[text_entry, id]
+    ).await? {
+        Ok(Some(TextEntryRevision {
+            id: row.get(0),
+            text_entry: row.get(1),
+            thought: row.get(2),
+            private: row.get(3),
+            removed: row.get(4),
+            created: row.get(5),
+            edited_by: row.get(6),
+        }))
+    } else {
+        Ok(None)
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::{Write};
+
+use tokio_postgres::{GenericClient, Row};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::db::{error, query};
+
+#[derive(Serialize, Deserialize)]
+pub struct VideoEntry {
+    pub id: i32,
+    pub private: bool,
+    pub comment: Option<String>,
+    pub entry: i32,
+    pub mime_type: String,
+    pub mime_subtype: String,
+    pub file_size: i64,
+    pub created: DateTime<Utc>,
+    /// hash of the stored file. None for files that have not been moved to
+    /// blobs yet
+    #[serde(skip)]
+    pub blob: Option<String>,
+}
+
+const SELECT_COLUMNS: &str = "\
+select id, \
+       private, \
+       comment, \
+       entry, \
+       mime_type, \
+       mime_subtype, \
+       file_size, \
+       created, \
+       blob \
+from video_entries";
+
+fn from_row(row: &Row) -> VideoEntry {
+    VideoEntry {
+        id: row.get(0),
+        private: row.get(1),
+        comment: row.get(2),
+        entry: row.get(3),
+        mime_type: row.get(4),
+        mime_subtype: row.get(5),
+        file_size: row.get(6),
+        created: row.get(7),
+        blob: row.get(8),
+    }
+}
+
+pub async fn find_from_id(
+    conn: &impl GenericClient,
+    entry_id: &i32,
+    video_id: &i32,
+    is_private: &Option<bool>,
+) -> error::Result<Option<VideoEntry>> {
+    let mut query_str = format!("{} where id = $1 and entry = $2", SELECT_COLUMNS);
+    let mut query_slice = query::QueryParams::with_capacity(3);
+    query_slice.push(video_id);
+    query_slice.push(entry_id);
+
+    if let Some(private) = is_private {
+        write!(&mut query_str, " and private = ${}", query_slice.push(private))?;
+    }
+
+    Ok(conn.query_opt(query_str.as_str(), query_slice.slice())
+        .await?
+        .map(|row| from_row(&row)))
+}
+
+pub async fn find_from_entry(
+    conn: &impl GenericClient,
+    entry_id: &i32,
+    is_private: &Option<bool>
+) -> error::Result<Vec<VideoEntry>> {
+    let mut query_str = format!("{} where entry = $1", SELECT_COLUMNS);
+    let mut query_slice = query::QueryParams::with_capacity(2);
+    query_slice.push(entry_id);
+
+    if let Some(private) = is_private {
+        write!(&mut query_str, " and private = ${}", query_slice.push(private))?;
+    }
+
+    query_str.push_str(" order by id");
+
+    Ok(conn.query(query_str.as_str(), query_slice.slice())
+        .await?
+        .iter()
+        .map(from_row)
+        .collect())
+}
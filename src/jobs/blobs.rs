@@ -0,0 +1,182 @@
+//! moving audio files stored before blobs were added
+//!
+//! run with `--migrate-blobs` while the server is stopped. each file is hashed
+//! and either moved to the key of its blob or removed if the owner already
+//! has a blob with the same data. files that fail are left where they are so the
+//! migration can be run again.
+
+use std::io;
+
+use futures::stream::StreamExt;
+use tokio_postgres::Client;
+
+use crate::db::tables::blobs;
+use crate::net::http::error;
+use crate::state::{DBState, StorageState};
+use crate::storage::{self, Backend};
+use crate::util::sniff::AudioKind;
+
+/// the number of audio files that ended up in each state
+#[derive(Default)]
+pub struct MigrateReport {
+    /// moved to the key of a new blob
+    pub moved: usize,
+    /// matched an existing blob and were removed
+    pub deduplicated: usize,
+    /// the audio entry does not have a stored file
+    pub missing: usize,
+    pub failed: usize,
+}
+
+enum Outcome {
+    Moved,
+    Deduplicated,
+    Missing,
+    /// the audio entry was removed or given a blob while it was being hashed
+    Skipped,
+}
+
+/// the size and blake3 hash of a stored object
+async fn hash_object(backend: &dyn Backend, key: &str) -> io::Result<(i64, blake3::Hash)> {
+    let mut hasher = blake3::Hasher::new();
+    let mut size: i64 = 0;
+    let mut stream = backend.read(key, None).await?;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+
+        hasher.update(&chunk);
+        size += chunk.len() as i64;
+    }
+
+    Ok((size, hasher.finalize()))
+}
+
+async fn migrate_audio(
+    conn: &mut Client,
+    storage: &StorageState,
+    owner: &i32,
+    entry_id: &i32,
+    audio_id: &i32,
+    mime_subtype: &str,
+) -> error::Result<Outcome> {
+    let backend = storage.backend();
+    let extension = AudioKind::extension_for(mime_subtype);
+    let audio_key = storage.get_audio_key(owner, entry_id, audio_id, extension);
+    let peaks_key = storage.get_audio_peaks_key(owner, entry_id, audio_id);
+
+    if backend.size(&audio_key).await?.is_none() {
+        return Ok(Outcome::Missing);
+    }
+
+    let (size, hash) = hash_object(backend, &audio_key).await?;
+    let hash = hash.to_hex().to_string();
+    let blob_key = storage.get_blob_key(owner, &hash, extension);
+
+    let transaction = conn.transaction().await?;
+
+    let Some(record) = transaction.query_opt(
+        "select blob from audio_entries where id = $1 for update",
+        &[audio_id]
+    ).await? else {
+        return Ok(Outcome::Skipped);
+    };
+
+    if record.get::<_, Option<&str>>(0).is_some() {
+        return Ok(Outcome::Skipped);
+    }
+
+    let created = blobs::create(&transaction, owner, &hash, &size, extension, &chrono::Utc::now()).await?;
+    let moved = created || backend.size(&blob_key).await?.is_none();
+
+    if moved {
+        backend.rename(&audio_key, &blob_key).await?;
+    }
+
+    let result = async {
+        transaction.execute(
+            "update audio_entries set blob = $2 where id = $1",
+            &[audio_id, &hash]
+        ).await?;
+
+        transaction.commit().await?;
+
+        Ok::<(), error::Error>(())
+    }.await;
+
+    if let Err(err) = result {
+        if moved {
+            if let Err(err) = backend.rename(&blob_key, &audio_key).await {
+                log::error!("failed to restore audio file: {} -> {} {}", blob_key, audio_key, err);
+            }
+        }
+
+        return Err(err);
+    }
+
+    if moved {
+        // the peaks are computed again when requested so they are only kept
+        // if they can be moved
+        if let Ok(Some(_)) = backend.size(&peaks_key).await {
+            if let Err(err) = backend.rename(&peaks_key, &storage.get_blob_peaks_key(owner, &hash)).await {
+                log::warn!("failed to move audio peaks: {} {}", peaks_key, err);
+            }
+        }
+    } else {
+        storage::discard(backend, &audio_key).await;
+    }
+
+    storage::discard(backend, &peaks_key).await;
+
+    Ok(if moved {
+        Outcome::Moved
+    } else {
+        Outcome::Deduplicated
+    })
+}
+
+/// moves every audio file that does not have a blob yet
+///
+/// each file is handled in its own transaction so a single failure will not
+/// stop the rest from being moved
+pub async fn migrate(db: &DBState, storage: &StorageState) -> error::Result<MigrateReport> {
+    let conn = &mut *db.get_conn().await?;
+    let records = conn.query(
+        "\
+        select audio_entries.id, \
+               audio_entries.entry, \
+               entries.owner, \
+               audio_entries.mime_subtype \
+        from audio_entries \
+        join entries on audio_entries.entry = entries.id \
+        where audio_entries.blob is null \
+        order by audio_entries.id",
+        &[]
+    ).await?;
+    let mut report = MigrateReport::default();
+
+    for record in records {
+        let audio_id: i32 = record.get(0);
+        let entry_id: i32 = record.get(1);
+        let owner: i32 = record.get(2);
+        let mime_subtype: String = record.get(3);
+
+        match migrate_audio(conn, storage, &owner, &entry_id, &audio_id, &mime_subtype).await {
+            Ok(Outcome::Moved) => report.moved += 1,
+            Ok(Outcome::Deduplicated) => report.deduplicated += 1,
+            Ok(Outcome::Missing) => {
+                log::warn!("audio entry is missing its file: {}", audio_id);
+
+                report.missing += 1;
+            },
+            Ok(Outcome::Skipped) => {},
+            Err(err) => {
+                log::error!("failed to migrate audio file: {} {:?}", audio_id, err);
+
+                report.failed += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,165 @@
+//! removal of files left behind by uploads that never finished
+//!
+//! tmp files and partial objects are normally removed as soon as the request
+//! that created them is done. anything left after a crash or restart is
+//! removed once it has gone unmodified for longer than the configured age.
+
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use actix_web::{rt, web};
+
+use crate::config::JanitorConfig;
+use crate::state::StorageState;
+
+/// removes the files in a directory that have not been modified since the
+/// given time
+///
+/// files in the skip check are left alone. returns the number of files
+/// removed
+fn sweep_dir<F>(dir: &Path, before: SystemTime, skip: F) -> io::Result<usize>
+where
+    F: Fn(&Path) -> bool
+{
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err)
+    };
+    let mut count = 0;
+
+    for entry in read_dir {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        if !metadata.is_file() || metadata.modified()? >= before || skip(&path) {
+            continue;
+        }
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => count += 1,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => log::error!("failed to remove abandoned file: {} {}", path.display(), err)
+        }
+    }
+
+    Ok(count)
+}
+
+/// removes abandoned files from the tmp directory and the local uploads
+/// directory
+///
+/// tmp files still in use by a request are skipped regardless of age.
+/// returns the number of files removed
+pub fn sweep(storage: &StorageState, max_age: u64) -> io::Result<usize> {
+    let before = SystemTime::now()
+        .checked_sub(Duration::from_secs(max_age))
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let tmp_dir = storage.tmp_dir();
+
+    let mut count = sweep_dir(tmp_dir.path(), before, |path| tmp_dir.is_active(path))?;
+
+    if let Some(uploads) = storage.uploads_dir() {
+        // an upload still being written is modified as each chunk arrives
+        count += sweep_dir(uploads, before, |_| false)?;
+    }
+
+    Ok(count)
+}
+
+/// spawns the janitor sweep on the current runtime
+///
+/// does nothing if the janitor is disabled in the config
+pub fn spawn(storage: web::Data<StorageState>, config: JanitorConfig) {
+    if config.max_age == 0 {
+        log::info!("tmp janitor is disabled");
+        return;
+    }
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(config.interval));
+
+        loop {
+            interval.tick().await;
+
+            let storage = storage.clone();
+            let result = web::block(move || sweep(&storage, config.max_age)).await;
+
+            match result {
+                Ok(Ok(count)) => if count > 0 {
+                    log::info!("removed {} abandoned upload files", count);
+                },
+                Ok(Err(err)) => {
+                    log::error!("failed to sweep abandoned upload files {}", err);
+                },
+                Err(err) => {
+                    log::error!("failed to run upload file sweep {}", err);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("janitor_{}_{}", name, std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn sweep_dir_removes_old_files() {
+        let dir = test_dir("removes_old_files");
+        let old = dir.join("old.tmp");
+        let skipped = dir.join("skipped.tmp");
+        let nested = dir.join("nested");
+
+        std::fs::write(&old, b"old").unwrap();
+        std::fs::write(&skipped, b"skipped").unwrap();
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let before = SystemTime::now() + Duration::from_secs(60);
+        let count = sweep_dir(&dir, before, |path| path == skipped.as_path()).unwrap();
+
+        assert_eq!(count, 1);
+        assert!(!old.exists());
+        assert!(skipped.exists());
+        assert!(nested.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sweep_dir_keeps_new_files() {
+        let dir = test_dir("keeps_new_files");
+        let new = dir.join("new.tmp");
+
+        std::fs::write(&new, b"new").unwrap();
+
+        let before = SystemTime::now() - Duration::from_secs(60);
+        let count = sweep_dir(&dir, before, |_| false).unwrap();
+
+        assert_eq!(count, 0);
+        assert!(new.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sweep_dir_missing() {
+        let dir = std::env::temp_dir()
+            .join(format!("janitor_missing_{}", std::process::id()));
+
+        assert_eq!(sweep_dir(&dir, SystemTime::now(), |_| false).unwrap(), 0);
+    }
+}
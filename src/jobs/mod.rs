@@ -0,0 +1,8 @@
+//! background tasks that run alongside the server along with one off tasks
+//! run from the command line
+
+pub mod trash;
+pub mod processing;
+pub mod blobs;
+pub mod storage_keys;
+pub mod janitor;
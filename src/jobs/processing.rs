@@ -0,0 +1,497 @@
+//! running local commands on uploaded media
+//!
+//! jobs are created along with the media they are for and picked up by a
+//! single worker that runs them one at a time. a job that fails is retried
+//! with a growing delay until it runs out of attempts. jobs left running by a
+//! previous server process are reset when the worker starts. jobs that stay
+//! running long past their timeout, such as when their result could not be
+//! saved, are reset before every run.
+
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use actix_web::{rt, web};
+use chrono::{DateTime, Utc};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio_postgres::GenericClient;
+
+use crate::config::ProcessCommand;
+use crate::db::tables::{processing_jobs::status, text_entry_revisions};
+use crate::net::http::error;
+use crate::state::{DBState, StorageState, ProcessingState};
+use crate::components::audio::AudioFile;
+
+/// the max amount of stdout kept from a command
+const MAX_OUTPUT: usize = 1024 * 1024;
+
+/// the max amount of stderr kept when a command fails
+const MAX_ERROR: usize = 4 * 1024;
+
+/// the longest a job will wait before its next attempt
+const MAX_RETRY_DELAY: u64 = 60 * 60 * 24;
+
+/// how long past the longest command timeout a job can be running before it
+/// is considered abandoned
+const STALE_GRACE: u64 = 60 * 10;
+
+/// a job that has been claimed by the worker
+struct ClaimedJob {
+    id: i64,
+    command: String,
+    attempts: i32,
+    audio: i32,
+    entry: i32,
+    owner: i32,
+    private: bool,
+    mime_type: String,
+    mime_subtype: String,
+    blob: Option<String>,
+}
+
+#[derive(Debug)]
+enum RunError {
+    Io(std::io::Error),
+    Timeout(u64),
+    Failed {
+        code: Option<i32>,
+        stderr: String,
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Io(err) => write!(f, "failed to run command: {}", err),
+            RunError::Timeout(secs) => write!(f, "command did not finish within {} seconds", secs),
+            RunError::Failed { code: Some(code), stderr } => write!(f, "command exited with {}: {}", code, stderr),
+            RunError::Failed { code: None, stderr } => write!(f, "command was terminated by a signal: {}", stderr),
+        }
+    }
+}
+
+impl From<std::io::Error> for RunError {
+    fn from(err: std::io::Error) -> RunError {
+        RunError::Io(err)
+    }
+}
+
+/// converts command output to a string cutting it to the given length
+fn lossy_truncated(bytes: &[u8], max: usize) -> String {
+    let mut rtn = String::from_utf8_lossy(&bytes[..bytes.len().min(max)]).into_owned();
+
+    // a multi-byte character may have been cut in half
+    while rtn.ends_with(char::REPLACEMENT_CHARACTER) && bytes.len() > max {
+        rtn.pop();
+    }
+
+    rtn
+}
+
+/// reads all of a command output while only keeping the start of it
+///
+/// one byte past the max is kept so that [lossy_truncated] can tell the
+/// output was cut. the rest is read and dropped so the command does not
+/// block on a full pipe
+async fn read_bounded<R>(mut reader: R, max: usize) -> std::io::Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin
+{
+    let mut rtn = Vec::new();
+    let mut buffer = [0u8; 8 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        let keep = (max + 1).saturating_sub(rtn.len()).min(read);
+        rtn.extend_from_slice(&buffer[..keep]);
+    }
+
+    Ok(rtn)
+}
+
+/// runs a command for the given file returning its stdout
+///
+/// the command is killed if it runs longer than its timeout
+async fn run_command(command: &ProcessCommand, file: &Path, mime: &str) -> Result<String, RunError> {
+    let file = file.to_string_lossy();
+    let args = command.args.iter()
+        .map(|arg| arg.replace("{file}", &file).replace("{mime}", mime));
+
+    let mut child = Command::new(&command.program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    // both are piped so they will be available
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    let (exit, stdout, stderr) = match rt::time::timeout(
+        Duration::from_secs(command.timeout),
+        async {
+            futures::try_join!(
+                child.wait(),
+                read_bounded(stdout, MAX_OUTPUT),
+                read_bounded(stderr, MAX_ERROR)
+            )
+        }
+    ).await {
+        Ok(result) => result?,
+        Err(_) => return Err(RunError::Timeout(command.timeout))
+    };
+
+    if exit.success() {
+        Ok(lossy_truncated(&stdout, MAX_OUTPUT))
+    } else {
+        Err(RunError::Failed {
+            code: exit.code(),
+            stderr: lossy_truncated(&stderr, MAX_ERROR).trim().to_owned(),
+        })
+    }
+}
+
+/// marks jobs that have been running since before the given time as pending
+///
+/// given the current time on start this resets the jobs that were running
+/// when the server stopped
+async fn reset_running(conn: &impl GenericClient, before: &DateTime<Utc>) -> error::Result<u64> {
+    Ok(conn.execute(
+        "update processing_jobs set status = $1 where status = $2 and updated < $3",
+        &[&status::PENDING, &status::RUNNING, before]
+    ).await?)
+}
+
+/// resets jobs that have been running long enough that whoever claimed them
+/// has given up on them
+async fn reset_stale(db: &DBState, state: &ProcessingState) -> error::Result<u64> {
+    let stale_after = Duration::from_secs(state.max_timeout().saturating_add(STALE_GRACE));
+    let Some(before) = chrono::Duration::from_std(stale_after).ok()
+        .and_then(|stale_after| Utc::now().checked_sub_signed(stale_after)) else {
+        return Ok(0);
+    };
+
+    let conn = &*db.get_conn().await?;
+
+    reset_running(conn, &before).await
+}
+
+/// claims the next pending job that is ready to run
+async fn claim(conn: &impl GenericClient) -> error::Result<Option<ClaimedJob>> {
+    let result = conn.query_opt(
+        "\
+        with claimed as ( \
+            update processing_jobs \
+            set status = $1, \
+                attempts = attempts + 1, \
+                updated = $2 \
+            where id = ( \
+                select id \
+                from processing_jobs \
+                where status = $3 and next_attempt <= $2 \
+                order by next_attempt \
+                limit 1 \
+                for update skip locked \
+            ) \
+            returning id, command, attempts, audio \
+        ) \
+        select claimed.id, \
+               claimed.command, \
+               claimed.attempts, \
+               audio_entries.id, \
+               audio_entries.entry, \
+               entries.owner, \
+               audio_entries.private, \
+               audio_entries.mime_type, \
+               audio_entries.mime_subtype, \
+               audio_entries.blob \
+        from claimed \
+        join audio_entries on claimed.audio = audio_entries.id \
+        join entries on audio_entries.entry = entries.id",
+        &[&status::RUNNING, &Utc::now(), &status::PENDING]
+    ).await?;
+
+    Ok(result.map(|row| ClaimedJob {
+        id: row.get(0),
+        command: row.get(1),
+        attempts: row.get(2),
+        audio: row.get(3),
+        entry: row.get(4),
+        owner: row.get(5),
+        private: row.get(6),
+        mime_type: row.get(7),
+        mime_subtype: row.get(8),
+        blob: row.get(9),
+    }))
+}
+
+/// stores the output of a successful job
+///
+/// if the command is set to create a text entry and there was any output then
+/// it is added to the entry the audio belongs to
+async fn complete(
+    db: &DBState,
+    job: &ClaimedJob,
+    command: &ProcessCommand,
+    output: String,
+) -> error::Result<()> {
+    let conn = &mut *db.get_conn().await?;
+    let transaction = conn.transaction().await?;
+
+    // the audio may have been deleted or replaced while the command was
+    // running which removes the job
+    if transaction.query_opt(
+        "select id from processing_jobs where id = $1 for update",
+        &[&job.id]
+    ).await?.is_none() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let thought = output.trim().to_owned();
+    let mut text_entry: Option<i32> = None;
+
+    if command.text_entry && !thought.is_empty() {
+        let result = transaction.query_one(
+            "insert into text_entries (thought, private, entry) values ($1, $2, $3) returning id",
+            &[&thought, &job.private, &job.entry]
+        ).await?;
+        let text_id: i32 = result.get(0);
+
+        text_entry_revisions::create(
+            &transaction,
+            &job.entry,
+            &text_id,
+            &thought,
+            &job.private,
+            &job.owner
+        ).await?;
+
+        transaction.execute(
+            "update entries set updated = $1, version = version + 1 where id = $2",
+            &[&now, &job.entry]
+        ).await?;
+
+        text_entry = Some(text_id);
+    }
+
+    transaction.execute(
+        "\
+        update processing_jobs \
+        set status = $1, \
+            output = $2, \
+            error = null, \
+            text_entry = $3, \
+            updated = $4 \
+        where id = $5",
+        &[&status::DONE, &output, &text_entry, &now, &job.id]
+    ).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// records a failed attempt and schedules the next one if there are attempts
+/// left
+async fn fail(
+    db: &DBState,
+    state: &ProcessingState,
+    job: &ClaimedJob,
+    reason: String,
+) -> error::Result<()> {
+    let conn = &*db.get_conn().await?;
+    let now = Utc::now();
+    let exhausted = job.attempts as i64 >= state.max_attempts() as i64;
+
+    // doubles the wait for every failed attempt
+    let delay = state.interval()
+        .saturating_mul(1u64 << (job.attempts.max(1) - 1).min(32))
+        .min(MAX_RETRY_DELAY);
+    let next_attempt = now + chrono::Duration::seconds(delay as i64);
+    let next_status = if exhausted { status::FAILED } else { status::PENDING };
+
+    conn.execute(
+        "\
+        update processing_jobs \
+        set status = $1, \
+            error = $2, \
+            next_attempt = $3, \
+            updated = $4 \
+        where id = $5",
+        &[
+            &next_status,
+            &reason,
+            &next_attempt,
+            &now,
+            &job.id
+        ]
+    ).await?;
+
+    Ok(())
+}
+
+async fn process(
+    db: &DBState,
+    storage: &StorageState,
+    state: &ProcessingState,
+    job: ClaimedJob,
+) -> error::Result<()> {
+    let Some(command) = state.get_command(&job.command) else {
+        return fail(db, state, &job, format!("unknown command: {}", job.command)).await;
+    };
+
+    let file = AudioFile::locate(
+        storage,
+        &job.owner,
+        &job.entry,
+        &job.audio,
+        &job.mime_subtype,
+        job.blob.as_deref()
+    );
+    let local = match storage.get_local_file(&file.key, file.hash.as_deref()).await {
+        Ok(local) => local,
+        Err(err) => {
+            log::warn!("processing job {} failed to retrieve audio: {}", job.id, err);
+
+            return fail(db, state, &job, format!("failed to retrieve audio: {}", err)).await;
+        }
+    };
+    let mime = format!("{}/{}", job.mime_type, job.mime_subtype);
+
+    match run_command(command, local.path(), &mime).await {
+        Ok(output) => complete(db, &job, command, output).await,
+        Err(err) => {
+            log::warn!("processing job {} failed: {}", job.id, err);
+
+            fail(db, state, &job, err.to_string()).await
+        }
+    }
+}
+
+/// runs every job that is ready. returns the number of jobs that were run
+pub async fn run_pending(
+    db: &DBState,
+    storage: &StorageState,
+    state: &ProcessingState,
+) -> error::Result<usize> {
+    let mut count = 0;
+
+    loop {
+        let job = {
+            let conn = &*db.get_conn().await?;
+
+            claim(conn).await?
+        };
+
+        let Some(job) = job else {
+            break;
+        };
+
+        let job_id = job.id;
+
+        // the job is left as running and will be reset once it is stale
+        if let Err(err) = process(db, storage, state, job).await {
+            log::error!("failed to update processing job {} {:?}", job_id, err);
+        }
+
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// spawns the processing worker on the current runtime
+///
+/// does nothing if there are no commands in the config
+pub fn spawn(db: web::Data<DBState>, storage: web::Data<StorageState>, state: web::Data<ProcessingState>) {
+    if !state.is_enabled() {
+        log::info!("media processing is disabled");
+        return;
+    }
+
+    rt::spawn(async move {
+        match db.get_conn().await {
+            Ok(conn) => if let Err(err) = reset_running(&*conn, &Utc::now()).await {
+                log::error!("failed to reset running processing jobs {:?}", err);
+            },
+            Err(err) => log::error!("failed to reset running processing jobs {:?}", err)
+        }
+
+        let mut interval = rt::time::interval(Duration::from_secs(state.interval()));
+
+        loop {
+            interval.tick().await;
+
+            match reset_stale(&db, &state).await {
+                Ok(count) => if count > 0 {
+                    log::warn!("reset {} stale processing jobs", count);
+                },
+                Err(err) => {
+                    log::error!("failed to reset stale processing jobs {:?}", err);
+                }
+            }
+
+            match run_pending(&db, &storage, &state).await {
+                Ok(count) => if count > 0 {
+                    log::info!("ran {} processing jobs", count);
+                },
+                Err(err) => {
+                    log::error!("failed to run processing jobs {:?}", err);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn lossy_truncated_cuts_output() {
+        assert_eq!(lossy_truncated(b"hello", 10), "hello");
+        assert_eq!(lossy_truncated(b"hello world", 5), "hello");
+        assert_eq!(lossy_truncated(b"", 5), "");
+    }
+
+    #[test]
+    fn lossy_truncated_drops_partial_characters() {
+        // the 2 byte character is cut after its first byte
+        assert_eq!(lossy_truncated("abcé".as_bytes(), 4), "abc");
+        assert_eq!(lossy_truncated("abcé".as_bytes(), 5), "abcé");
+    }
+
+    #[test]
+    fn read_bounded_keeps_the_start() {
+        let output = vec![b'a'; 100 * 1024];
+
+        let read = block_on(read_bounded(output.as_slice(), 10)).unwrap();
+        assert_eq!(read, vec![b'a'; 11]);
+
+        let read = block_on(read_bounded(&b"short"[..], 10)).unwrap();
+        assert_eq!(read, b"short");
+
+        let read = block_on(read_bounded(&b""[..], 10)).unwrap();
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn read_bounded_output_is_truncated() {
+        let output = "abcé".repeat(1024);
+        let read = block_on(read_bounded(output.as_bytes(), 5)).unwrap();
+
+        assert_eq!(lossy_truncated(&read, 5), "abcé");
+    }
+}
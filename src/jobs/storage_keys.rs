@@ -0,0 +1,73 @@
+//! rotating the keys used to encrypt stored media
+//!
+//! run with `--rotate-keys` after a new master key has been added to the
+//! config and made current. every user key is wrapped again with the current
+//! master key and the active user keys are retired so new objects get new
+//! keys. objects already stored keep the user key they were written with so
+//! nothing has to be uploaded again. once no keys fail the old master key can
+//! be removed from the config.
+
+use crate::config::EncryptionConfig;
+use crate::net::http::error;
+use crate::state::DBState;
+use crate::storage::keys;
+
+/// the number of user keys that ended up in each state
+#[derive(Default)]
+pub struct RotateReport {
+    /// wrapped again with the current master key
+    pub rewrapped: usize,
+    /// will no longer be used for new objects
+    pub retired: u64,
+    /// could not be unwrapped with the master keys in the config
+    pub failed: usize,
+}
+
+pub async fn rotate(db: &DBState, config: &EncryptionConfig) -> error::Result<RotateReport> {
+    let mut report = RotateReport::default();
+    let mut conn = db.get_conn().await?;
+    let transaction = conn.transaction().await?;
+
+    let rows = transaction.query(
+        "\
+        select id, master_key, wrapped_key \
+        from user_storage_keys \
+        where master_key != $1 \
+        for update",
+        &[&config.current]
+    ).await?;
+
+    for row in rows {
+        let id: i32 = row.get(0);
+        let master_key: &str = row.get(1);
+
+        let user_key = match keys::unwrap_user_key(config, master_key, row.get::<_, &[u8]>(2)) {
+            Ok(key) => key,
+            Err(err) => {
+                log::error!("failed to unwrap storage key: {} {}", id, err);
+
+                report.failed += 1;
+
+                continue;
+            }
+        };
+
+        let wrapped = keys::wrap_user_key(config, &user_key)?;
+
+        transaction.execute(
+            "update user_storage_keys set master_key = $2, wrapped_key = $3 where id = $1",
+            &[&id, &config.current, &wrapped]
+        ).await?;
+
+        report.rewrapped += 1;
+    }
+
+    report.retired = transaction.execute(
+        "update user_storage_keys set retired = $1 where retired is null",
+        &[&chrono::Utc::now()]
+    ).await?;
+
+    transaction.commit().await?;
+
+    Ok(report)
+}
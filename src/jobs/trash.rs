@@ -0,0 +1,135 @@
+//! purging of entries that have been in the trash for too long along with
+//! compacting the sync feed
+
+use std::time::Duration;
+
+use actix_web::{rt, web};
+use chrono::Utc;
+
+use crate::config::{SyncConfig, TrashConfig};
+use crate::components;
+use crate::net::http::error;
+use crate::state::{DBState, StorageState};
+
+/// permanently removes entries that were trashed before the given number of
+/// days ago
+///
+/// each entry is removed in its own transaction so a single failure will not
+/// stop the rest from being purged. returns the number of entries purged
+pub async fn purge(db: &DBState, storage: &StorageState, purge_after: u32) -> error::Result<usize> {
+    let conn = &mut *db.get_conn().await?;
+    let before = Utc::now() - chrono::Duration::days(purge_after as i64);
+
+    let records = conn.query(
+        "\
+        select id, owner \
+        from entries \
+        where deleted is not null and \
+              deleted < $1",
+        &[&before]
+    ).await?;
+    let mut count = 0;
+
+    for record in records {
+        let entry_id: i32 = record.get(0);
+        let owner: i32 = record.get(1);
+
+        let transaction = conn.transaction().await?;
+
+        // the entry may have been restored or purged since it was listed
+        let still_trashed = transaction.query_opt(
+            "\
+            select id \
+            from entries \
+            where id = $1 and \
+                  deleted is not null and \
+                  deleted < $2 \
+            for update",
+            &[&entry_id, &before]
+        ).await?;
+
+        if still_trashed.is_none() {
+            transaction.rollback().await?;
+            continue;
+        }
+
+        match components::entries::purge(&transaction, storage, &owner, &entry_id).await {
+            Ok(files) => match components::files::commit_removing(transaction, storage, files).await {
+                Ok(()) => count += 1,
+                Err(err) => log::error!("failed to remove trashed entry files: {} {:?}", entry_id, err)
+            },
+            Err(err) => {
+                log::error!("failed to purge trashed entry: {} {}", entry_id, err);
+
+                transaction.rollback().await?;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// compacts changes in the sync feed older than the given number of days
+pub async fn compact_sync(db: &DBState, retention: u32) -> error::Result<components::sync::Compacted> {
+    let conn = &mut *db.get_conn().await?;
+    let before = Utc::now() - chrono::Duration::days(retention as i64);
+
+    let transaction = conn.transaction().await?;
+    let compacted = components::sync::compact(&transaction, &before).await?;
+
+    transaction.commit().await?;
+
+    Ok(compacted)
+}
+
+/// spawns the trash sweep on the current runtime
+///
+/// the sync feed is compacted on the same interval. does nothing if both
+/// purging and compacting are disabled in the config
+pub fn spawn(db: web::Data<DBState>, storage: web::Data<StorageState>, config: TrashConfig, sync: SyncConfig) {
+    if config.purge_after == 0 {
+        log::info!("trash purging is disabled");
+    }
+
+    if sync.retention == 0 {
+        log::info!("sync compaction is disabled");
+    }
+
+    if config.purge_after == 0 && sync.retention == 0 {
+        return;
+    }
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(config.interval));
+
+        loop {
+            interval.tick().await;
+
+            if config.purge_after > 0 {
+                match purge(&db, &storage, config.purge_after).await {
+                    Ok(count) => if count > 0 {
+                        log::info!("purged {} entries from the trash", count);
+                    },
+                    Err(err) => {
+                        log::error!("failed to purge trash {}", err);
+                    }
+                }
+            }
+
+            if sync.retention > 0 {
+                match compact_sync(&db, sync.retention).await {
+                    Ok(compacted) => if compacted.superseded > 0 || compacted.removed > 0 {
+                        log::info!(
+                            "compacted sync changes. superseded: {} removed: {}",
+                            compacted.superseded,
+                            compacted.removed
+                        );
+                    },
+                    Err(err) => {
+                        log::error!("failed to compact sync changes {}", err);
+                    }
+                }
+            }
+        }
+    });
+}
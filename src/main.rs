@@ -19,16 +19,20 @@ mod net;
 mod db;
 mod security;
 mod state;
+mod storage;
 mod util;
 mod email;
 mod template;
 mod components;
 mod routing;
+mod jobs;
 
 use error::Result;
 
 fn main() -> error::Result<()> {
     let mut conf_files: Vec<std::path::PathBuf> = Vec::new();
+    let mut migrate_blobs = false;
+    let mut rotate_keys = false;
     let mut args = std::env::args();
     args.next();
 
@@ -44,6 +48,10 @@ fn main() -> error::Result<()> {
                 std::env::set_var("RUST_LOG", "info");
             } else if arg_substring == "backtrace" {
                 std::env::set_var("RUST_BACKTRACE", "full");
+            } else if arg_substring == "migrate-blobs" {
+                migrate_blobs = true;
+            } else if arg_substring == "rotate-keys" {
+                rotate_keys = true;
             } else {
                 return Err(cli::error::Error::UnknownArg(arg_substring.to_owned()).into());
             }
@@ -58,31 +66,85 @@ fn main() -> error::Result<()> {
 
     log::debug!("conf: {:#?}", conf);
 
-    actix_web::rt::System::new()
-        .block_on(server_runner(conf))?;
+    if migrate_blobs {
+        actix_web::rt::System::new()
+            .block_on(migrate_blobs_runner(conf))?;
+    } else if rotate_keys {
+        actix_web::rt::System::new()
+            .block_on(rotate_keys_runner(conf))?;
+    } else {
+        actix_web::rt::System::new()
+            .block_on(server_runner(conf))?;
+    }
 
     Ok(())
 }
 
-async fn server_runner(config: config::ServerConfig) -> Result<()> {
+async fn create_db_state(config: config::DBConfig) -> Result<state::DBState> {
     let db_config = {
         let mut rtn = PGConfig::new();
-        rtn.user(&config.db.username);
-        rtn.password(config.db.password);
-        rtn.host(&config.db.hostname);
-        rtn.port(config.db.port);
-        rtn.dbname(&config.db.database);
+        rtn.user(&config.username);
+        rtn.password(config.password);
+        rtn.host(&config.hostname);
+        rtn.port(config.port);
+        rtn.dbname(&config.database);
         rtn
     };
 
-    let bind_config = config.bind;
-
-    let security_state_ref = web::Data::new(security::state::SecurityState::from(config.security));
-    let db_state_ref = web::Data::new(state::DBState::from(
+    Ok(state::DBState::from(
         bb8::Pool::builder().build(
             PostgresConnectionManager::new(db_config, NoTls)
         ).await?
-    ));
+    ))
+}
+
+/// moves audio files stored before blobs were added and exits
+async fn migrate_blobs_runner(config: config::ServerConfig) -> Result<()> {
+    let db_state = create_db_state(config.db).await?;
+    let storage_state = state::StorageState::new(config.storage, &db_state)?;
+
+    let report = jobs::blobs::migrate(&db_state, &storage_state).await
+        .map_err(|err| error::AppError::General(format!("failed to migrate blobs: {:?}", err)))?;
+
+    println!(
+        "moved: {} deduplicated: {} missing: {} failed: {}",
+        report.moved,
+        report.deduplicated,
+        report.missing,
+        report.failed
+    );
+
+    Ok(())
+}
+
+/// wraps the storage keys of users with the current master key and exits
+async fn rotate_keys_runner(config: config::ServerConfig) -> Result<()> {
+    let Some(encryption) = config.storage.encryption else {
+        return Err(error::AppError::General(
+            String::from("storage encryption is not configured (conf.storage.encryption)")
+        ));
+    };
+
+    let db_state = create_db_state(config.db).await?;
+
+    let report = jobs::storage_keys::rotate(&db_state, &encryption).await
+        .map_err(|err| error::AppError::General(format!("failed to rotate storage keys: {:?}", err)))?;
+
+    println!(
+        "rewrapped: {} retired: {} failed: {}",
+        report.rewrapped,
+        report.retired,
+        report.failed
+    );
+
+    Ok(())
+}
+
+async fn server_runner(config: config::ServerConfig) -> Result<()> {
+    let bind_config = config.bind;
+
+    let security_state_ref = web::Data::new(security::state::SecurityState::from(config.security));
+    let db_state_ref = web::Data::new(create_db_state(config.db).await?);
     let template_state_ref = web::Data::new(template::state::TemplateState::new(
         template::get_built_registry(config.template)?
     ));
@@ -92,13 +154,23 @@ async fn server_runner(config: config::ServerConfig) -> Result<()> {
     let server_info_state_ref = web::Data::new(state::ServerInfoState::new(
         config.info
     ));
+    let janitor_config = config.storage.janitor.clone();
     let storage_state_ref = web::Data::new(state::StorageState::new(
-        config.storage
+        config.storage,
+        &db_state_ref
     )?);
     let file_serving_ref = web::Data::new(state::FileServingState::from(
         config.file_serving
     ));
 
+    let processing_state_ref = web::Data::new(state::ProcessingState::new(
+        config.processing
+    ));
+
+    jobs::trash::spawn(db_state_ref.clone(), storage_state_ref.clone(), config.trash, config.sync);
+    jobs::processing::spawn(db_state_ref.clone(), storage_state_ref.clone(), processing_state_ref.clone());
+    jobs::janitor::spawn(storage_state_ref.clone(), janitor_config);
+
     let mut server = HttpServer::new(move || {
         use routing::handler;
 
@@ -116,12 +188,14 @@ async fn server_runner(config: config::ServerConfig) -> Result<()> {
             .app_data(server_info_state_ref.clone())
             .app_data(storage_state_ref.clone())
             .app_data(file_serving_ref.clone())
+            .app_data(processing_state_ref.clone())
             .wrap(Logger::new("%a XF-%{X-Forwarded-For}i:%{X-Forwarded-Port}i %t \"%r\" %s %b \"%{Referer}i\" %T"))
 
             .route("/ping", web::get().to(handler::ping::handle_get))
             .route("/", web::get().to(handler::handle_get))
             .route("/account", web::get().to(handler::account::handle_get))
             .route("/account", web::put().to(handler::account::handle_put))
+            .route("/account/storage", web::get().to(handler::account::storage::handle_get))
             .service(web::scope("/auth")
                 .route("/login", web::get().to(handler::auth::session::handle_get))
                 .route("/login", web::post().to(handler::auth::session::handle_post))
@@ -158,10 +232,17 @@ async fn server_runner(config: config::ServerConfig) -> Result<()> {
             .service(web::scope("/entries")
                 .route("", web::get().to(handler::entries::handle_get))
                 .route("", web::post().to(handler::entries::handle_post))
+                .route("/trash", web::get().to(handler::entries::trash::handle_get))
+                .route("/batch", web::post().to(handler::entries::batch::handle_post))
                 .service(web::scope("/{entry_id}")
                     .route("", web::get().to(handler::entries::entry_id::handle_get))
                     .route("", web::put().to(handler::entries::entry_id::handle_put))
                     .route("", web::delete().to(handler::entries::entry_id::handle_delete))
+                    .route("/restore", web::post().to(handler::entries::entry_id::restore::handle_post))
+                    .service(web::scope("/text/{text_id}/revisions")
+                        .route("", web::get().to(handler::entries::entry_id::text::revisions::handle_get))
+                        .route("/diff", web::get().to(handler::entries::entry_id::text::revisions::diff::handle_get))
+                    )
                     .service(web::scope("/comments")
                         .route("", web::get().to(handler::entries::entry_id::comments::handle_get))
                         .route("", web::post().to(handler::entries::entry_id::comments::handle_post))
@@ -173,8 +254,47 @@ async fn server_runner(config: config::ServerConfig) -> Result<()> {
                         .service(web::scope("/{audio_id}")
                             .route("", web::get().to(handler::entries::entry_id::audio::audio_id::handle_get))
                             .route("", web::put().to(handler::entries::entry_id::audio::audio_id::handle_put))
+                            .route("", web::delete().to(handler::entries::entry_id::audio::audio_id::handle_delete))
+                            .route("/file", web::put().to(handler::entries::entry_id::audio::audio_id::file::handle_put))
+                            .route("/peaks", web::get().to(handler::entries::entry_id::audio::audio_id::peaks::handle_get))
+                            .route("/processing", web::get().to(handler::entries::entry_id::audio::audio_id::processing::handle_get))
+                        )
+                    )
+                    .service(web::scope("/images")
+                        .route("", web::get().to(handler::entries::entry_id::images::handle_get))
+                        .route("", web::post().to(handler::entries::entry_id::images::handle_post))
+                        .service(web::scope("/{image_id}")
+                            .route("", web::get().to(handler::entries::entry_id::images::image_id::handle_get))
+                            .route("", web::delete().to(handler::entries::entry_id::images::image_id::handle_delete))
+                            .route("/thumbnail", web::get().to(handler::entries::entry_id::images::image_id::thumbnail::handle_get))
+                        )
+                    )
+                    .service(web::scope("/video")
+                        .route("", web::get().to(handler::entries::entry_id::video::handle_get))
+                        .route("", web::post().to(handler::entries::entry_id::video::handle_post))
+                        .service(web::scope("/{video_id}")
+                            .route("", web::get().to(handler::entries::entry_id::video::video_id::handle_get))
+                            .route("", web::put().to(handler::entries::entry_id::video::video_id::handle_put))
+                            .route("", web::delete().to(handler::entries::entry_id::video::video_id::handle_delete))
                         )
                     )
+                    .service(web::scope("/files")
+                        .route("", web::get().to(handler::entries::entry_id::files::handle_get))
+                        .route("", web::post().to(handler::entries::entry_id::files::handle_post))
+                        .service(web::scope("/{file_id}")
+                            .route("", web::get().to(handler::entries::entry_id::files::file_id::handle_get))
+                            .route("", web::delete().to(handler::entries::entry_id::files::file_id::handle_delete))
+                        )
+                    )
+                )
+            )
+            .service(web::scope("/entry_templates")
+                .route("", web::get().to(handler::entry_templates::handle_get))
+                .route("", web::post().to(handler::entry_templates::handle_post))
+                .service(web::scope("/{template_id}")
+                    .route("", web::get().to(handler::entry_templates::template_id::handle_get))
+                    .route("", web::put().to(handler::entry_templates::template_id::handle_put))
+                    .route("", web::delete().to(handler::entry_templates::template_id::handle_delete))
                 )
             )
             .service(web::scope("/global")
@@ -188,6 +308,8 @@ async fn server_runner(config: config::ServerConfig) -> Result<()> {
                     )
                 )
             )
+            .route("/sync", web::get().to(handler::sync::handle_get))
+            .route("/storage", web::get().to(handler::storage::handle_get))
             .route("/settings", web::get().to(routing::okay))
             .route("/settings", web::put().to(routing::okay))
             .service(web::scope("/tags")
@@ -226,6 +348,21 @@ async fn server_runner(config: config::ServerConfig) -> Result<()> {
                             ).service(web::scope("/audio")
                                 .route("", web::get().to(handler::entries::entry_id::audio::handle_get))
                                 .route("/{audio_id}", web::get().to(handler::entries::entry_id::audio::audio_id::handle_get))
+                                .route("/{audio_id}/peaks", web::get().to(handler::entries::entry_id::audio::audio_id::peaks::handle_get))
+                                .route("/{audio_id}/processing", web::get().to(handler::entries::entry_id::audio::audio_id::processing::handle_get))
+                            ).service(web::scope("/images")
+                                .route("", web::get().to(handler::entries::entry_id::images::handle_get))
+                                .route("/{image_id}", web::get().to(handler::entries::entry_id::images::image_id::handle_get))
+                                .route("/{image_id}/thumbnail", web::get().to(handler::entries::entry_id::images::image_id::thumbnail::handle_get))
+                            ).service(web::scope("/video")
+                                .route("", web::get().to(handler::entries::entry_id::video::handle_get))
+                                .route("/{video_id}", web::get().to(handler::entries::entry_id::video::video_id::handle_get))
+                            ).service(web::scope("/files")
+                                .route("", web::get().to(handler::entries::entry_id::files::handle_get))
+                                .route("/{file_id}", web::get().to(handler::entries::entry_id::files::file_id::handle_get))
+                            ).service(web::scope("/text/{text_id}/revisions")
+                                .route("", web::get().to(handler::entries::entry_id::text::revisions::handle_get))
+                                .route("/diff", web::get().to(handler::entries::entry_id::text::revisions::diff::handle_get))
                             )
                         )
                     )
@@ -55,6 +55,15 @@ pub fn text_entry_not_found(id: &i32) -> Error
         .set_message(format!("failed to find the requested text entry id: {}", id))
 }
 
+#[inline]
+pub fn text_entry_revision_not_found(id: &i32) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::NOT_FOUND)
+        .set_name("TextEntryRevisionNotFound")
+        .set_message(format!("failed to find the requested text entry revision id: {}", id))
+}
+
 #[inline]
 pub fn global_custom_field_not_found(id: &i32) -> Error
 {
@@ -109,6 +118,89 @@ pub fn audio_entry_not_found(id: &i32) -> Error
         .set_message(format!("failed to find the requested audio entry id: {}", id))
 }
 
+#[inline]
+pub fn entry_file_not_found(id: &i32) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::NOT_FOUND)
+        .set_name("EntryFileNotFound")
+        .set_message(format!("failed to find the requested entry file id: {}", id))
+}
+
+#[inline]
+pub fn image_entry_not_found(id: &i32) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::NOT_FOUND)
+        .set_name("ImageEntryNotFound")
+        .set_message(format!("failed to find the requested image entry id: {}", id))
+}
+
+#[inline]
+pub fn video_entry_not_found(id: &i32) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::NOT_FOUND)
+        .set_name("VideoEntryNotFound")
+        .set_message(format!("failed to find the requested video entry id: {}", id))
+}
+
+#[inline]
+pub fn audio_peaks_not_available(id: &i32) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::NOT_FOUND)
+        .set_name("AudioPeaksNotAvailable")
+        .set_message(format!("waveform peaks are not available for the audio format of audio entry id: {}", id))
+}
+
+#[inline]
+pub fn payload_too_large<M>(message: M) -> Error
+where
+    M: Into<String>
+{
+    Error::new()
+        .set_status(StatusCode::PAYLOAD_TOO_LARGE)
+        .set_name("PayloadTooLarge")
+        .set_message(message)
+}
+
+#[inline]
+pub fn storage_quota_exceeded(remaining: u64) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::PAYLOAD_TOO_LARGE)
+        .set_name("StorageQuotaExceeded")
+        .set_message(format!("upload exceeds the storage quota. remaining: {} bytes", remaining))
+}
+
+#[inline]
+pub fn sync_resync_required() -> Error
+{
+    Error::new()
+        .set_status(StatusCode::GONE)
+        .set_name("FullResyncRequired")
+        .set_message("the sync token is older than the kept changes. sync again without a token")
+}
+
+#[inline]
+pub fn entry_in_trash(id: &i32) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::CONFLICT)
+        .set_name("EntryInTrash")
+        .set_message(format!("the requested entry is in the trash and must be restored before it can be changed. id: {}", id))
+}
+
+#[inline]
+pub fn entry_template_not_found(id: &i32) -> Error
+{
+    Error::new()
+        .set_status(StatusCode::NOT_FOUND)
+        .set_name("EntryTemplateNotFound")
+        .set_message(format!("failed to find the requested entry template id: {}", id))
+}
+
 #[inline]
 pub fn group_not_found(id: &i32) -> Error
 {
@@ -141,36 +233,36 @@ where
 }
 
 #[inline]
-pub fn entry_exists<C>(created: C) -> Error
+pub fn custom_field_exists<N>(name: N) -> Error
 where
-    C: Into<String>
+    N: Into<String>
 {
     Error::new()
         .set_status(StatusCode::BAD_REQUEST)
-        .set_name("EntryExists")
-        .set_message(format!("given entry date already exists. date: {}", created.into()))
+        .set_name("CustomFieldExists")
+        .set_message(format!("given custom field already exists. name: {}", name.into()))
 }
 
 #[inline]
-pub fn custom_field_exists<N>(name: N) -> Error
+pub fn global_custom_field_exists<N>(name: N) -> Error
 where
     N: Into<String>
 {
     Error::new()
         .set_status(StatusCode::BAD_REQUEST)
-        .set_name("CustomFieldExists")
-        .set_message(format!("given custom field already exists. name: {}", name.into()))
+        .set_name("GlobalCustomFieldExists")
+        .set_message(format!("given global custom field already exists. name: {}", name.into()))
 }
 
 #[inline]
-pub fn global_custom_field_exists<N>(name: N) -> Error
+pub fn entry_template_exists<N>(name: N) -> Error
 where
     N: Into<String>
 {
     Error::new()
         .set_status(StatusCode::BAD_REQUEST)
-        .set_name("GlobalCustomFieldExists")
-        .set_message(format!("given global custom field already exists. name: {}", name.into()))
+        .set_name("EntryTemplateExists")
+        .set_message(format!("given entry template name already exists. name: {}", name.into()))
 }
 
 #[inline]
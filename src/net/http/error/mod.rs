@@ -65,6 +65,21 @@ impl Error
         self
     }
 
+    pub fn status(&self) -> &StatusCode
+    {
+        &self.status
+    }
+
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    pub fn message(&self) -> &str
+    {
+        &self.message
+    }
+
     pub fn _set_data<D>(mut self, data: D) -> Self
     where
         D: Serialize
@@ -1,3 +1,4 @@
 pub mod error;
 pub mod cookie;
-pub mod response;
\ No newline at end of file
+pub mod response;
+pub mod upload;
\ No newline at end of file
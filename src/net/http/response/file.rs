@@ -0,0 +1,209 @@
+//! responding with stored files that support range requests
+//!
+//! only single byte ranges are supported. requests with multiple ranges or a
+//! range that cannot be parsed are given the full file which is allowed by
+//! RFC 9110.
+
+use actix_web::{http, HttpRequest, HttpResponse, HttpResponseBuilder};
+use actix_web::body::SizedStream;
+use actix_web::http::header::{self, EntityTag};
+
+use crate::net::http::error;
+use crate::storage::{self, Backend};
+
+/// an inclusive range of bytes in a file
+#[derive(Debug, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RangeRequest {
+    /// send the full file
+    Full,
+    /// send part of the file
+    Partial(ByteRange),
+    /// the range does not overlap the file
+    Unsatisfiable,
+}
+
+/// parses the value of a Range header for a file of the given size
+pub fn parse_range(given: &str, size: u64) -> RangeRequest {
+    let Some(spec) = given.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let start = start.trim();
+    let end = end.trim();
+
+    if start.is_empty() {
+        // suffix range for the last n bytes of the file
+        let Ok(suffix) = end.parse::<u64>() else {
+            return RangeRequest::Full;
+        };
+
+        if suffix == 0 || size == 0 {
+            return RangeRequest::Unsatisfiable;
+        }
+
+        return RangeRequest::Partial(ByteRange {
+            start: size.saturating_sub(suffix),
+            end: size - 1,
+        });
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return RangeRequest::Full;
+    };
+
+    let end = if end.is_empty() {
+        size.saturating_sub(1)
+    } else {
+        let Ok(end) = end.parse::<u64>() else {
+            return RangeRequest::Full;
+        };
+
+        if end < start {
+            return RangeRequest::Full;
+        }
+
+        end.min(size.saturating_sub(1))
+    };
+
+    if start >= size {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(ByteRange { start, end })
+    }
+}
+
+/// checks if any of the etags in an If-Match style header value match
+fn header_matches(value: &header::HeaderValue, etag: &EntityTag, weak: bool) -> bool {
+    let Ok(value) = value.to_str() else {
+        return false;
+    };
+
+    value.split(',').any(|tag| {
+        let tag = tag.trim();
+
+        if tag == "*" {
+            return true;
+        }
+
+        match tag.parse::<EntityTag>() {
+            Ok(parsed) => if weak {
+                parsed.weak_eq(etag)
+            } else {
+                parsed.strong_eq(etag)
+            },
+            Err(_) => false
+        }
+    })
+}
+
+/// responds with 304 if the client already has the given version
+fn check_not_modified(req: &HttpRequest, etag: &EntityTag) -> Option<HttpResponse> {
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH)?;
+
+    if header_matches(if_none_match, etag, true) {
+        Some(HttpResponse::build(http::StatusCode::NOT_MODIFIED)
+            .insert_header(header::ETag(etag.clone()))
+            .finish())
+    } else {
+        None
+    }
+}
+
+/// determines the range to send for a file of the given size
+fn requested_range(req: &HttpRequest, etag: &EntityTag, size: u64) -> RangeRequest {
+    let range = match req.headers().get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) => parse_range(value, size),
+        None => RangeRequest::Full
+    };
+
+    // the range only applies if the client still has the same version
+    if let Some(if_range) = req.headers().get(header::IF_RANGE) {
+        if !header_matches(if_range, etag, false) {
+            return RangeRequest::Full;
+        }
+    }
+
+    range
+}
+
+fn range_builder(etag: EntityTag) -> HttpResponseBuilder {
+    let mut builder = HttpResponse::build(http::StatusCode::OK);
+    builder.insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header(header::ETag(etag));
+
+    builder
+}
+
+/// responds with a stored object honoring Range, If-Range, and
+/// If-None-Match
+///
+/// the etag should be derived from the stored record so that it changes when
+/// the object is replaced. if the hash of the object is given then full reads
+/// are checked against it and the response is cut short if they do not match
+pub async fn respond_object(
+    req: &HttpRequest,
+    backend: &dyn Backend,
+    key: &str,
+    hash: Option<&str>,
+    content_type: mime::Mime,
+    etag: EntityTag,
+) -> error::Result<HttpResponse> {
+    if let Some(not_modified) = check_not_modified(req, &etag) {
+        return Ok(not_modified);
+    }
+
+    let Some(size) = backend.size(key).await? else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("stored object is missing: {}", key)
+        ).into());
+    };
+
+    let range = requested_range(req, &etag, size);
+    let mut builder = range_builder(etag);
+
+    match range {
+        RangeRequest::Full => {
+            builder.insert_header((header::CONTENT_TYPE, content_type.to_string()));
+
+            let mut stream = backend.read(key, None).await?;
+
+            if let Some(hash) = hash {
+                stream = storage::verify_stream(stream, key.to_owned(), hash.to_owned());
+            }
+
+            Ok(builder.body(SizedStream::new(size, stream)))
+        },
+        RangeRequest::Partial(ByteRange { start, end }) => {
+            let length = end - start + 1;
+
+            builder.status(http::StatusCode::PARTIAL_CONTENT)
+                .insert_header((header::CONTENT_TYPE, content_type.to_string()))
+                .insert_header((
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end, size)
+                ));
+
+            Ok(builder.body(SizedStream::new(length, backend.read(key, Some(start..end + 1)).await?)))
+        },
+        RangeRequest::Unsatisfiable => {
+            builder.status(http::StatusCode::RANGE_NOT_SATISFIABLE)
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{}", size)));
+
+            Ok(builder.finish())
+        }
+    }
+}
@@ -10,7 +10,8 @@ pub struct JsonBuilder {
     message: String,
     error: Option<String>,
     reason: Option<String>,
-    time: Option<chrono::DateTime<chrono::Utc>>
+    time: Option<chrono::DateTime<chrono::Utc>>,
+    cursor: Option<(Option<String>, Option<String>)>,
 }
 
 impl JsonBuilder {
@@ -21,6 +22,7 @@ impl JsonBuilder {
             error: None,
             reason: None,
             time: None,
+            cursor: None,
         }
     }
 
@@ -58,6 +60,12 @@ impl JsonBuilder {
     //     self
     // }
 
+    /// sets the next and prev cursors for a paged response
+    pub fn set_cursor(mut self, next: Option<String>, prev: Option<String>) -> JsonBuilder {
+        self.cursor = Some((next, prev));
+        self
+    }
+
     pub fn insert_header(mut self, header: impl TryIntoHeaderPair) -> JsonBuilder {
         self.builder.insert_header(header);
         self
@@ -88,6 +96,10 @@ impl JsonBuilder {
             map.insert("timestamp".into(), serde_json::Value::Number(serde_json::Number::from(time.timestamp())));
         }
 
+        if let Some((next, prev)) = self.cursor {
+            map.insert("cursor".into(), json!({"next": next, "prev": prev}));
+        }
+
         let mut builder = self.builder;
         builder.insert_header((http::header::CONTENT_TYPE, "application/json"));
 
@@ -3,7 +3,8 @@ use serde_json::json;
 
 use crate::db::tables::users;
 
-pub mod json;
+pub mod json;
+pub mod file;
 
 use crate::template::TemplateState;
 use super::error;
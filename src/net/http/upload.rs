@@ -0,0 +1,168 @@
+//! writing uploaded data to storage objects
+//!
+//! uploads are streamed straight to a storage backend. the leading bytes can
+//! be checked first so that unsupported files are rejected before the rest of
+//! the upload is read. uploads can be given a limit on how much can be written
+//! so a storage quota is enforced as the data arrives.
+
+use actix_web::web::Bytes;
+use futures_util::stream::{Stream, StreamExt};
+
+use crate::net::http::error;
+use crate::storage::ObjectWriter;
+use crate::util::sniff;
+
+/// checks the amount written so far against the remaining storage quota
+pub fn check_quota(written: u64, limit: Option<u64>) -> error::Result<()> {
+    match limit {
+        Some(remaining) if written > remaining => Err(error::build::storage_quota_exceeded(remaining)),
+        _ => Ok(())
+    }
+}
+
+/// an upload that has had its leading bytes read but not been stored
+pub struct SniffedUpload<T, S> {
+    pub kind: T,
+    /// the data read from the stream before it was stored
+    head: Vec<u8>,
+    stream: S,
+}
+
+/// reads the leading bytes of an upload without checking its format
+///
+/// used for uploads that accept any data. the rest of the upload is left in
+/// the stream to be written with [SniffedUpload::write_object]
+pub async fn read_head<S, E>(mut stream: S) -> error::Result<SniffedUpload<(), S>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let mut head: Vec<u8> = Vec::with_capacity(sniff::SNIFF_LEN);
+
+    while head.len() < sniff::SNIFF_LEN {
+        let Some(item) = stream.next().await else {
+            break;
+        };
+        let chunk = item.map_err(|e| error::Error::new()
+            .set_message("problem with reading file from request")
+            .set_source(e))?;
+
+        head.extend_from_slice(&chunk);
+    }
+
+    Ok(SniffedUpload {
+        kind: (),
+        head,
+        stream,
+    })
+}
+
+/// reads the leading bytes of an upload to detect its format
+///
+/// the rest of the upload is left in the stream to be written with
+/// [SniffedUpload::write_object]
+pub async fn sniff<S, E, T, D>(
+    stream: S,
+    detect: D,
+    invalid: &dyn Fn() -> error::Error,
+) -> error::Result<SniffedUpload<T, S>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::error::Error + Send + Sync + 'static,
+    D: Fn(&[u8]) -> Option<T>,
+{
+    let uploaded = read_head(stream).await?;
+    let kind = detect(uploaded.head()).ok_or_else(invalid)?;
+
+    Ok(SniffedUpload {
+        kind,
+        head: uploaded.head,
+        stream: uploaded.stream,
+    })
+}
+
+impl<T, S> SniffedUpload<T, S> {
+    /// the leading bytes of the upload used to detect its format
+    pub fn head(&self) -> &[u8] {
+        &self.head[..self.head.len().min(sniff::SNIFF_LEN)]
+    }
+}
+
+impl<T, S, E> SniffedUpload<T, S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    async fn write_all(
+        &mut self,
+        writer: &mut Box<dyn ObjectWriter>,
+        hasher: &mut blake3::Hasher,
+        limit: Option<u64>,
+    ) -> error::Result<()> {
+        let mut written = self.head.len() as u64;
+        check_quota(written, limit)?;
+
+        hasher.update(&self.head);
+        writer.write(Bytes::from(std::mem::take(&mut self.head))).await?;
+
+        while let Some(item) = self.stream.next().await {
+            let chunk = item.map_err(|e| error::Error::new()
+                .set_message("problem with reading file from request")
+                .set_source(e))?;
+
+            written += chunk.len() as u64;
+            check_quota(written, limit)?;
+
+            hasher.update(&chunk);
+            writer.write(chunk).await?;
+        }
+
+        Ok(())
+    }
+
+    /// writes the rest of the upload to a storage object
+    ///
+    /// limit is the most that can be written if given. the object is
+    /// discarded on any error. returns the size and blake3 hash of the upload
+    pub async fn write_object(
+        mut self,
+        mut writer: Box<dyn ObjectWriter>,
+        limit: Option<u64>,
+    ) -> error::Result<(i64, blake3::Hash)> {
+        let mut hasher = blake3::Hasher::new();
+
+        if let Err(err) = self.write_all(&mut writer, &mut hasher, limit).await {
+            writer.abort().await;
+
+            return Err(err);
+        }
+
+        Ok((writer.finish().await? as i64, hasher.finalize()))
+    }
+}
+
+/// reads a small text value from a stream such as a multipart form field
+pub async fn read_text<S, E>(mut stream: S, limit: usize) -> error::Result<String>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let mut bytes = Vec::new();
+
+    while let Some(item) = stream.next().await {
+        let chunk = item.map_err(|e| error::Error::new()
+            .set_message("problem with reading value from request")
+            .set_source(e))?;
+
+        if bytes.len() + chunk.len() > limit {
+            return Err(error::build::bad_request(
+                format!("form value is too large. max size: {} bytes", limit)
+            ));
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(bytes)
+        .map_err(|_| error::build::bad_request("form value is not valid utf-8"))
+}
@@ -15,6 +15,8 @@ use crate::security::{self, InitiatorLookup, Initiator};
 use crate::email;
 use crate::template;
 
+pub mod storage;
+
 pub async fn handle_get(
     req: HttpRequest,
     security: security::state::WebSecurityState,
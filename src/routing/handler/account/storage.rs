@@ -0,0 +1,41 @@
+//! storage usage of the current user
+
+use actix_web::{http, Responder};
+use serde::Serialize;
+
+use crate::components::quota::{self, QuotaLimit, StorageUsage};
+use crate::net::http::error;
+use crate::net::http::response::json::JsonBuilder;
+use crate::security::Initiator;
+use crate::state;
+
+#[derive(Serialize)]
+pub struct AccountStorageJson {
+    usage: StorageUsage,
+    /// the amount that can still be stored. None if there is no limit
+    remaining: Option<u64>,
+    limits: Vec<QuotaLimit>,
+}
+
+/// retrieves the storage usage and quotas of the current user
+///
+/// GET /account/storage
+///
+/// usage is broken down by media type in bytes. limits has the quota of the
+/// user and the quotas of any groups they belong to along with the usage
+/// counted against each
+pub async fn handle_get(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+) -> error::Result<impl Responder> {
+    let conn = db.get_conn().await?;
+    let found = quota::find(&*conn, storage.quota(), &initiator.user.id).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(AccountStorageJson {
+            remaining: found.remaining(),
+            usage: found.usage,
+            limits: found.limits,
+        }))
+}
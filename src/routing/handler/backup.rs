@@ -133,11 +133,18 @@ pub async fn handle_post(
     tags_mapping.shrink_to_fit();
 
     for entry in json_data.data.entries {
+        // multiple entries can exist on the same day so an entry is only
+        // skipped if one already exists at the exact same time
         let result = transaction.query(
             r#"
-            insert into entries (day, owner) values
-            ($1, $2)
-            on conflict on constraint unique_day_owner_key do nothing
+            insert into entries (day, owner)
+            select $1, $2
+            where not exists (
+                select 1
+                from entries
+                where day = $1 and
+                      owner = $2
+            )
             returning id
             "#,
             &[&entry.entry.day, &initiator.user.id]
@@ -0,0 +1,237 @@
+//! handling multiple entry changes in a single request
+
+use actix_web::{web, http, Responder};
+use serde::{Serialize, Deserialize};
+use tokio_postgres::GenericClient;
+
+use crate::db::tables::permissions;
+use crate::net::http::{error, response::json::JsonBuilder};
+use crate::state;
+use crate::security::{self, Initiator};
+use crate::components::{self, entries::schema, files::Removal};
+
+use super::{PostEntryJson, entry_id::{self, PutComposedEntry, DeletedEntry}};
+
+/// the max number of operations that can be given in a single batch
+const MAX_OPERATIONS: usize = 500;
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOperation {
+    Create {
+        data: PostEntryJson,
+    },
+    Update {
+        id: i32,
+        version: Option<i32>,
+        data: PutComposedEntry,
+    },
+    Delete {
+        id: i32,
+        version: Option<i32>,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct PostBatchJson {
+    operations: Vec<BatchOperation>,
+}
+
+#[derive(Serialize)]
+pub struct BatchError {
+    status: u16,
+    error: String,
+    message: String,
+}
+
+impl From<error::Error> for BatchError {
+    fn from(err: error::Error) -> BatchError {
+        if err.status().is_server_error() {
+            log::error!("batch operation failed {:?}", err);
+        }
+
+        BatchError {
+            status: err.status().as_u16(),
+            error: err.name().to_owned(),
+            message: err.message().to_owned(),
+        }
+    }
+}
+
+/// the outcome of a single operation. index is the position of the
+/// operation in the request
+#[derive(Serialize)]
+pub struct BatchResult {
+    index: usize,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry: Option<schema::Entry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<BatchError>,
+}
+
+/// the result of an operation that did not error
+enum Outcome {
+    /// the created or updated entry, or the id of a deleted entry
+    Done(Option<schema::Entry>, Option<i32>),
+    /// the version given did not match the current version of the entry
+    Modified(schema::Entry),
+}
+
+/// checks the optional version given with an operation
+async fn check_version(
+    conn: &impl GenericClient,
+    owner: &i32,
+    id: &i32,
+    version: &Option<i32>,
+) -> error::Result<Option<schema::Entry>> {
+    if let Some(version) = version {
+        entry_id::check_version(conn, owner, id, version).await
+    } else {
+        Ok(None)
+    }
+}
+
+async fn run_operation(
+    conn: &impl GenericClient,
+    storage: &state::StorageState,
+    owner: &i32,
+    operation: BatchOperation,
+    files: &mut Removal,
+) -> error::Result<Outcome> {
+    match operation {
+        BatchOperation::Create { data } => {
+            let entry = super::create_entry(conn, owner, data).await?;
+
+            Ok(Outcome::Done(Some(entry), None))
+        },
+        BatchOperation::Update { id, version, data } => {
+            if let Some(current) = check_version(conn, owner, &id, &version).await? {
+                return Ok(Outcome::Modified(current));
+            }
+
+            let entry = entry_id::update_entry(conn, owner, &id, data).await?;
+
+            Ok(Outcome::Done(Some(entry), None))
+        },
+        BatchOperation::Delete { id, version } => {
+            if let Some(current) = check_version(conn, owner, &id, &version).await? {
+                return Ok(Outcome::Modified(current));
+            }
+
+            if let DeletedEntry::Purged(purged) = entry_id::delete_entry(conn, storage, owner, &id).await? {
+                files.extend(purged);
+            }
+
+            Ok(Outcome::Done(None, Some(id)))
+        }
+    }
+}
+
+/// applies multiple create, update, and delete operations to entries
+///
+/// POST /entries/batch
+///
+/// all operations are run in a single transaction in the order given. each
+/// operation runs in its own savepoint so a failed operation is rolled back
+/// without affecting the others. the response contains a result for every
+/// operation with either the entry, the deleted id, or the error.
+///
+/// update and delete operations can include the version of the entry that
+/// was last retrieved. if the entry has changed since then the operation
+/// fails with a 412 and the current copy of the entry.
+///
+/// example body
+/// ```json
+/// {
+///     "operations": [
+///         {"op": "create", "data": {"entry": {"day": 1672531200}}},
+///         {"op": "update", "id": 12, "version": 3, "data": {"entry": {"day": 1672617600}}},
+///         {"op": "delete", "id": 13, "version": 1}
+///     ]
+/// }
+/// ```
+pub async fn handle_post(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    posted: web::Json<PostBatchJson>,
+) -> error::Result<impl Responder> {
+    let posted = posted.into_inner();
+    let conn = &mut *db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[permissions::abilities::READ_WRITE],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to modify entries"
+        ));
+    }
+
+    if posted.operations.len() > MAX_OPERATIONS {
+        return Err(error::build::bad_request(
+            format!("too many operations given. max: {}", MAX_OPERATIONS)
+        ));
+    }
+
+    let mut transaction = conn.transaction().await?;
+    let mut results = Vec::with_capacity(posted.operations.len());
+    let mut files = Removal::default();
+
+    for (index, operation) in posted.operations.into_iter().enumerate() {
+        let savepoint = transaction.savepoint("batch_operation").await?;
+        let mut op_files = Removal::default();
+
+        match run_operation(&savepoint, &storage, &initiator.user.id, operation, &mut op_files).await {
+            Ok(Outcome::Modified(current)) => {
+                savepoint.rollback().await?;
+
+                results.push(BatchResult {
+                    index,
+                    ok: false,
+                    entry: Some(current),
+                    id: None,
+                    error: Some(BatchError {
+                        status: http::StatusCode::PRECONDITION_FAILED.as_u16(),
+                        error: "PreconditionFailed".into(),
+                        message: "the entry has been modified since it was last retrieved".into(),
+                    }),
+                });
+            },
+            Ok(Outcome::Done(entry, id)) => {
+                savepoint.commit().await?;
+                files.extend(op_files);
+
+                results.push(BatchResult {
+                    index,
+                    ok: true,
+                    entry,
+                    id,
+                    error: None,
+                });
+            },
+            Err(err) => {
+                savepoint.rollback().await?;
+
+                results.push(BatchResult {
+                    index,
+                    ok: false,
+                    entry: None,
+                    id: None,
+                    error: Some(err.into()),
+                });
+            }
+        }
+    }
+
+    components::files::commit_removing(transaction, &storage, files).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(results))
+}
@@ -0,0 +1,213 @@
+//! replacing the file of an existing audio entry
+
+use actix_web::{web, http, HttpRequest, Responder};
+
+use crate::db::tables::{permissions, audio_entries, processing_jobs};
+use crate::net::http::error;
+use crate::net::http::response::json::JsonBuilder;
+use crate::state;
+use crate::security::{self, Initiator};
+use crate::storage;
+use crate::components::{self, audio::AudioFile};
+use crate::util::{self, peaks};
+use crate::routing;
+
+use super::super::AudioUpload;
+use super::find_owned_audio;
+
+/// replaces the audio file of an audio entry
+///
+/// PUT /entries/{entry_id}/audio/{audio_id}/file
+///
+/// accepts the same bodies as creating an audio entry. the metadata is read
+/// from the new file and the processing jobs are created again for it. the
+/// old file and its cached peaks are removed unless another audio entry was
+/// uploaded with the same data. the private flag and comment are only
+/// changed if they are given in the form
+pub async fn handle_put(
+    req: HttpRequest,
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    processing: state::WebProcessingState,
+    path: web::Path<routing::path::params::EntryAudioPath>,
+    body: web::Payload,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[permissions::abilities::READ_WRITE],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to update audio entries"
+        ));
+    }
+
+    let original = find_owned_audio(&*conn, &initiator.user.id, &path).await?;
+
+    components::entries::lock_for_changes(&*conn, &path.entry_id).await?;
+
+    // the space used by the file being replaced is freed once it is done
+    let limit = components::quota::remaining(&*conn, storage.quota(), &initiator.user.id).await?
+        .map(|remaining| remaining.saturating_add(original.file_size.max(0) as u64));
+
+    let uploaded = AudioUpload::from_request(&req, body).await?;
+    let kind = uploaded.kind();
+
+    let upload_key = storage.get_upload_key();
+    let stored = uploaded.store(&storage, &initiator.user.id, &upload_key, limit).await?;
+    let private = stored.private.unwrap_or(original.private);
+    let comment = match stored.comment {
+        Some(given) => util::string::trimmed_optional_string(Some(given)),
+        None => original.comment
+    };
+    let metadata = components::audio::read_metadata(&storage, &upload_key, None, kind).await;
+
+    let commands: Vec<&str> = processing.commands_for("audio", kind.mime_subtype())
+        .map(|command| command.name.as_str())
+        .collect();
+    let now = chrono::Utc::now();
+
+    let locked = async {
+        let transaction = conn.transaction().await?;
+
+        components::entries::lock_for_changes(&transaction, &path.entry_id).await?;
+
+        // the file may have been replaced while the upload was being read
+        let Some(current) = transaction.query_opt(
+            "select mime_subtype, blob, file_size from audio_entries where id = $1 for update",
+            &[&path.audio_id]
+        ).await? else {
+            return Err(error::build::audio_entry_not_found(&path.audio_id));
+        };
+
+        // the space used by the file being replaced is freed once it is done
+        components::quota::reserve(
+            &transaction,
+            storage.quota(),
+            &initiator.user.id,
+            stored.file_size,
+            current.get(2)
+        ).await?;
+
+        let old_file = AudioFile::locate(
+            &storage,
+            &initiator.user.id,
+            &path.entry_id,
+            &path.audio_id,
+            current.get(0),
+            current.get::<_, Option<&str>>(1)
+        );
+
+        Ok::<_, error::Error>((transaction, old_file))
+    }.await;
+
+    let (transaction, old_file) = match locked {
+        Ok(found) => found,
+        Err(err) => {
+            storage::discard(storage.backend(), &upload_key).await;
+
+            return Err(err);
+        }
+    };
+
+    let blob = components::blobs::place(
+        &transaction,
+        &storage,
+        &initiator.user.id,
+        &upload_key,
+        &stored.hash,
+        &stored.file_size,
+        kind.extension()
+    ).await?;
+
+    let updated = async {
+        transaction.execute(
+            "\
+            update audio_entries \
+            set private = $2, \
+                comment = $3, \
+                mime_subtype = $4, \
+                file_size = $5, \
+                duration_ms = $6, \
+                codec = $7, \
+                sample_rate = $8, \
+                channels = $9, \
+                blob = $10 \
+            where id = $1",
+            &[
+                &path.audio_id,
+                &private,
+                &comment,
+                &kind.mime_subtype(),
+                &stored.file_size,
+                &metadata.duration_ms,
+                &metadata.codec,
+                &metadata.sample_rate,
+                &metadata.channels,
+                &blob.hash,
+            ]
+        ).await?;
+
+        transaction.execute(
+            "delete from processing_jobs where audio = $1",
+            &[&path.audio_id]
+        ).await?;
+
+        processing_jobs::create_for_audio(&transaction, &path.audio_id, &commands, &now).await?;
+        components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+        components::audio::release_files(&transaction, &storage, &initiator.user.id, vec![old_file]).await
+    }.await;
+
+    let removal = match updated {
+        Ok(removal) => removal,
+        Err(err) => {
+            blob.undo(&storage).await;
+
+            return Err(err);
+        }
+    };
+
+    if let Err(err) = components::files::commit_removing(transaction, &storage, removal).await {
+        blob.undo(&storage).await;
+
+        return Err(err);
+    }
+
+    if peaks::is_supported(kind) {
+        components::audio::cache_peaks(
+            storage.clone(),
+            AudioFile::locate(
+                &storage,
+                &initiator.user.id,
+                &path.entry_id,
+                &path.audio_id,
+                kind.mime_subtype(),
+                Some(&blob.hash)
+            ),
+            kind
+        );
+    }
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(audio_entries::AudioEntry {
+            id: path.audio_id,
+            private,
+            comment,
+            entry: path.entry_id,
+            mime_type: original.mime_type,
+            mime_subtype: kind.mime_subtype().to_owned(),
+            file_size: stored.file_size,
+            duration_ms: metadata.duration_ms,
+            codec: metadata.codec,
+            sample_rate: metadata.sample_rate,
+            channels: metadata.channels,
+            blob: Some(blob.hash),
+        }))
+}
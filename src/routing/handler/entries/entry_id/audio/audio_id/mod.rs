@@ -0,0 +1,270 @@
+//! handles working with audio entries on a singular basis
+
+use std::str::FromStr;
+
+use actix_web::{web, http, HttpRequest, Responder};
+use actix_web::http::header::EntityTag;
+use serde::Deserialize;
+use tokio_postgres::GenericClient;
+
+pub mod file;
+pub mod peaks;
+pub mod processing;
+
+use crate::db::tables::{entries, audio_entries, permissions};
+use crate::components;
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::util;
+use crate::routing;
+
+/// checks that the initiator can read the audio entry and retrieves it
+///
+/// used by [handle_get] and [peaks::handle_get] so both check access the
+/// same way. returns the owner of the entry along with the audio entry
+pub(crate) async fn find_readable_audio(
+    conn: &impl GenericClient,
+    initiator: &Initiator,
+    path: &routing::path::params::EntryAudioPath,
+) -> error::Result<(i32, audio_entries::AudioEntry)> {
+    let owner: i32;
+    let mut is_private = None::<bool>;
+
+    if let Some(user_id) = path.user_id {
+        if !security::permissions::has_permission(
+            conn,
+            &initiator.user.id,
+            permissions::rolls::USERS_ENTRIES,
+            &[permissions::abilities::READ],
+            Some(&user_id)
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read this users audio entries"
+            ));
+        }
+
+        owner = user_id;
+        is_private = Some(false);
+    } else {
+        if !security::permissions::has_permission(
+            conn,
+            &initiator.user.id,
+            permissions::rolls::ENTRIES,
+            &[
+                permissions::abilities::READ,
+                permissions::abilities::READ_WRITE,
+            ],
+            None
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to write audio entries"
+            ));
+        }
+
+        owner = initiator.user.id;
+    }
+
+    let Some(_entry) = entries::from_user_and_id(conn, &owner, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    match audio_entries::find_from_id(conn, &path.audio_id, &is_private).await? {
+        Some(audio_entry) if audio_entry.entry == path.entry_id => Ok((owner, audio_entry)),
+        _ => Err(error::build::audio_entry_not_found(&path.audio_id))
+    }
+}
+
+/// retrieves a single audio entry with the given entry and audio id
+///
+/// GET /entries/{entry_id}/audio/{audio_id}
+/// GET /users/{user_id}/entries/{entry_id}/audio/{audio_id}
+///
+/// supports Range and If-Range requests so clients are able to seek without
+/// downloading the whole file. the ETag is the hash of the file or the id and
+/// stored size for files that have not been moved to blobs. full downloads
+/// are checked against the hash as they are sent
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryAudioPath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let (owner, audio_entry) = find_readable_audio(&*conn, &initiator, &path).await?;
+
+    let mime = {
+        let known = format!("{}/{}", audio_entry.mime_type, audio_entry.mime_subtype);
+
+        mime::Mime::from_str(&known)?
+    };
+    let etag = match &audio_entry.blob {
+        Some(hash) => EntityTag::new_strong(hash.clone()),
+        None => EntityTag::new_strong(format!("{}-{}", audio_entry.id, audio_entry.file_size))
+    };
+    let file = components::audio::AudioFile::from_entry(&storage, &owner, &audio_entry);
+
+    response::file::respond_object(
+        &req,
+        storage.backend(),
+        &file.key,
+        file.hash.as_deref(),
+        mime,
+        etag
+    ).await
+}
+
+/// retrieves an audio entry of an entry owned by the given user
+///
+/// used by the handlers that modify an audio entry
+pub(crate) async fn find_owned_audio(
+    conn: &impl GenericClient,
+    owner: &i32,
+    path: &routing::path::params::EntryAudioPath,
+) -> error::Result<audio_entries::AudioEntry> {
+    let Some(_entry) = entries::from_user_and_id(conn, owner, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    match audio_entries::find_from_id(conn, &path.audio_id, &None).await? {
+        Some(audio_entry) if audio_entry.entry == path.entry_id => Ok(audio_entry),
+        _ => Err(error::build::audio_entry_not_found(&path.audio_id))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PutAudioEntry {
+    private: bool,
+    comment: Option<String>,
+}
+
+/// updates an single audio id
+///
+/// PUT /entries/{entry_id}/audio/{audio_id}
+///
+/// only the private flag and comment can be changed. the file is replaced
+/// with [file::handle_put]
+pub async fn handle_put(
+    initiator: Initiator,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryAudioPath>,
+    posted: web::Json<PutAudioEntry>
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let posted = posted.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn, 
+        &initiator.user.id, 
+        permissions::rolls::ENTRIES, 
+        &[
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to update audio entries"
+        ));
+    }
+
+    let mut audio_entry = find_owned_audio(&*conn, &initiator.user.id, &path).await?;
+    let comment = util::string::trimmed_optional_string(posted.comment);
+
+    let transaction = conn.transaction().await?;
+    transaction.execute(
+        "\
+        update audio_entries \
+        set private = $2, \
+            comment = $3 \
+        where id = $1",
+        &[&path.audio_id, &posted.private, &comment]
+    ).await?;
+    components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+    transaction.commit().await?;
+
+    audio_entry.private = posted.private;
+    audio_entry.comment = comment;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(audio_entry))
+}
+
+/// deletes a single audio entry along with its file and cached peaks
+///
+/// DELETE /entries/{entry_id}/audio/{audio_id}
+///
+/// any processing jobs for the audio are removed with it. the file is only
+/// removed if no other audio entry was uploaded with the same data
+pub async fn handle_delete(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryAudioPath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to delete audio entries"
+        ));
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &initiator.user.id, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let transaction = conn.transaction().await?;
+    let Some(record) = transaction.query_opt(
+        "delete from audio_entries where id = $1 and entry = $2 returning mime_subtype, blob",
+        &[&path.audio_id, &path.entry_id]
+    ).await? else {
+        return Err(error::build::audio_entry_not_found(&path.audio_id));
+    };
+    components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+    let mime_subtype: String = record.get(0);
+    let blob: Option<String> = record.get(1);
+    let removal = components::audio::release_files(&transaction, &storage, &initiator.user.id, vec![
+        components::audio::AudioFile::locate(
+            &storage,
+            &initiator.user.id,
+            &path.entry_id,
+            &path.audio_id,
+            &mime_subtype,
+            blob.as_deref()
+        )
+    ]).await?;
+
+    components::files::commit_removing(transaction, &storage, removal).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .set_message("audio entry deleted")
+        .build_empty()
+}
@@ -0,0 +1,104 @@
+//! handles retrieving waveform peaks for audio entries
+
+use actix_web::{web, http, HttpRequest, Responder};
+use serde::{Serialize, Deserialize};
+
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::components;
+use crate::security::{self, InitiatorLookup};
+use crate::util::peaks::{self, PeaksError};
+use crate::util::sniff::AudioKind;
+use crate::routing;
+
+/// the number of peaks returned if no resolution is given
+const DEFAULT_RESOLUTION: usize = 200;
+
+/// the max number of peaks that can be requested
+const MAX_RESOLUTION: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct PeaksQuery {
+    resolution: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct PeaksResult {
+    duration_ms: i64,
+    /// the number of peaks returned. may be less than requested for short
+    /// recordings
+    resolution: usize,
+    /// max amplitudes from 0.0 - 1.0 evenly spread across the audio
+    peaks: Vec<f32>,
+}
+
+/// retrieves downsampled waveform peaks for the given entry and audio id
+///
+/// GET /entries/{entry_id}/audio/{audio_id}/peaks
+/// GET /users/{user_id}/entries/{entry_id}/audio/{audio_id}/peaks
+///
+/// peaks are cached in storage beside the audio file after upload and are
+/// shared by audio entries with the same file. audio entries created before
+/// peaks were available are computed and cached on the first request
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryAudioPath>,
+    info: web::Query<PeaksQuery>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let resolution = info.resolution.unwrap_or(DEFAULT_RESOLUTION);
+
+    if resolution == 0 || resolution > MAX_RESOLUTION {
+        return Err(error::build::bad_request(
+            format!("invalid resolution given. must be between 1 and {}", MAX_RESOLUTION)
+        ));
+    }
+
+    let initiator = lookup.try_into()?;
+    let (owner, audio_entry) = super::find_readable_audio(&*conn, &initiator, &path).await?;
+
+    let Some(kind) = AudioKind::from_mime_subtype(&audio_entry.mime_subtype) else {
+        return Err(error::build::audio_peaks_not_available(&path.audio_id));
+    };
+
+    if !peaks::is_supported(kind) {
+        return Err(error::build::audio_peaks_not_available(&path.audio_id));
+    }
+
+    let file = components::audio::AudioFile::from_entry(&storage, &owner, &audio_entry);
+
+    let found = match components::audio::load_peaks(&storage, &file, kind).await {
+        Ok(found) => found,
+        Err(PeaksError::Unsupported) => return Err(
+            error::build::audio_peaks_not_available(&path.audio_id)
+        ),
+        Err(err) => return Err(error::Error::new()
+            .set_message("failed to read waveform peaks")
+            .set_source(err))
+    };
+    let peaks = found.downsample(resolution);
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(PeaksResult {
+            duration_ms: found.duration_ms(),
+            resolution: peaks.len(),
+            peaks,
+        }))
+}
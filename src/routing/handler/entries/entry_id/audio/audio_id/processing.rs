@@ -0,0 +1,47 @@
+//! handles retrieving the processing jobs of audio entries
+
+use actix_web::{web, http, HttpRequest, Responder};
+
+use crate::db::tables::processing_jobs;
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::security::{self, InitiatorLookup};
+use crate::routing;
+
+/// retrieves the processing jobs for the given entry and audio id
+///
+/// GET /entries/{entry_id}/audio/{audio_id}/processing
+/// GET /users/{user_id}/entries/{entry_id}/audio/{audio_id}/processing
+///
+/// jobs are listed with their status, the number of attempts, and the error
+/// of the last failed attempt. a job that created a text entry will have its
+/// id in `text_entry`
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryAudioPath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let (_owner, audio_entry) = super::find_readable_audio(&*conn, &initiator, &path).await?;
+
+    let jobs = processing_jobs::find_from_audio(&*conn, &audio_entry.id).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(jobs))
+}
@@ -1,22 +1,22 @@
 //! handling audio data for a given entry
 
-use std::io::Write;
-use std::fs::File;
-use std::path::PathBuf;
-use std::convert::TryInto;
-
-use futures_util::stream::StreamExt;
+use futures_util::stream::{LocalBoxStream, Stream, StreamExt};
 use actix_web::{web, http, HttpRequest, Responder};
+use actix_web::web::Bytes;
+use actix_multipart::{Field, Multipart};
+use serde::Deserialize;
 
 pub mod audio_id;
 
-use crate::db::tables::{permissions, audio_entries};
-use crate::net::http::error;
+use crate::db::tables::{permissions, audio_entries, processing_jobs};
+use crate::net::http::{error, upload};
 use crate::net::http::response;
 use crate::net::http::response::json::JsonBuilder;
 use crate::state;
 use crate::security::{self, InitiatorLookup, Initiator};
-use crate::util;
+use crate::storage;
+use crate::components;
+use crate::util::{self, peaks, sniff::{self, AudioKind}};
 use crate::routing;
 
 /// retrieves audio entry data for a given entry id
@@ -27,7 +27,7 @@ pub async fn handle_get(
     req: HttpRequest,
     security: security::state::WebSecurityState,
     db: state::WebDbState,
-    path: web::Path<routing::path::params::EntryAudioPath>
+    path: web::Path<routing::path::params::EntryPath>
 ) -> error::Result<impl Responder> {
     let path = path.into_inner();
     let conn = db.get_conn().await?;
@@ -97,54 +97,220 @@ pub async fn handle_get(
         .build(Some(entry))
 }
 
-struct AudioWebmResult {
-    size: i64,
-    file: File,
-    path: PathBuf
+/// the max size of a text field in a multipart form
+const MAX_FORM_VALUE: usize = 4 * 1024;
+
+/// the data of an audio upload as it is read from the request
+type AudioStream = LocalBoxStream<'static, std::io::Result<Bytes>>;
+
+fn unsupported_audio() -> error::Error {
+    error::build::bad_request(
+        "unsupported audio format. expect: webm | ogg (opus) | mp3 | wav | m4a"
+    )
+}
+
+/// parses a boolean form value as sent by html checkboxes or clients
+fn parse_form_bool(value: &str) -> error::Result<bool> {
+    match value.trim() {
+        "true" | "1" | "on" => Ok(true),
+        "false" | "0" | "off" | "" => Ok(false),
+        _ => Err(error::build::bad_request(
+            format!("invalid boolean given for form field. given: {}", value)
+        ))
+    }
+}
+
+fn to_audio_stream<S, E>(stream: S) -> AudioStream
+where
+    S: Stream<Item = Result<Bytes, E>> + 'static,
+    E: std::fmt::Display,
+{
+    stream.map(|item| item.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))
+        .boxed_local()
 }
 
-/// handles creating new audio entries when given a audio webm file
-async fn handle_audio_webm(
-    storage: &state::WebStorageState,
-    mut body: web::Payload
-) -> error::Result<AudioWebmResult> {
-    let path = util::file::get_tmp_path(storage.get_tmp_dir_ref(), "webm")?;
-    let mut file = File::create(&path)?;
-    let mut size = 0;
+/// reads a form field that is not the audio file
+async fn read_form_field(
+    field: Field,
+    private: &mut Option<bool>,
+    comment: &mut Option<String>,
+) -> error::Result<()> {
+    let name = field.content_disposition()
+        .get_name()
+        .unwrap_or("")
+        .to_owned();
+
+    match name.as_str() {
+        "file" => {
+            return Err(error::build::bad_request(
+                "only one audio file can be uploaded at a time"
+            ));
+        },
+        "private" => {
+            let value = upload::read_text(field, MAX_FORM_VALUE).await?;
+
+            *private = Some(parse_form_bool(&value)?);
+        },
+        "comment" => {
+            *comment = Some(upload::read_text(field, MAX_FORM_VALUE).await?);
+        },
+        _ => {
+            upload::read_text(field, MAX_FORM_VALUE).await?;
+        }
+    }
 
-    while let Some(item) = body.next().await {
-        let chunk = item.map_err(|e| error::Error::new()
-            .set_message("problem with reading file from request")
-            .set_source(e))?;
+    Ok(())
+}
 
-        file.write(&chunk)?;
+fn next_field_error(err: actix_multipart::MultipartError) -> error::Error {
+    error::build::bad_request("invalid multipart form given")
+        .set_source(std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))
+}
 
-        if let Ok(cast) = TryInto::<i64>::try_into(chunk.len()) {
-            size += cast;
+/// an audio upload that has been detected but not stored yet
+pub(crate) struct AudioUpload {
+    data: upload::SniffedUpload<AudioKind, AudioStream>,
+    /// the rest of a multipart form after the audio file
+    form: Option<Multipart>,
+    private: Option<bool>,
+    comment: Option<String>,
+}
+
+/// an audio upload that has been stored
+pub(crate) struct StoredAudio {
+    pub file_size: i64,
+    /// hex encoded blake3 hash of the file
+    pub hash: String,
+    pub private: Option<bool>,
+    pub comment: Option<String>,
+}
+
+impl AudioUpload {
+    /// starts reading the audio from the request
+    ///
+    /// multipart forms are read up to the `file` field along with the
+    /// optional `private` and `comment` fields before it, otherwise the body
+    /// is the audio file. the format is always detected from the data itself
+    pub async fn from_request(req: &HttpRequest, body: web::Payload) -> error::Result<AudioUpload> {
+        let is_multipart = req.headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_start().starts_with("multipart/form-data"))
+            .unwrap_or(false);
+
+        if !is_multipart {
+            return Ok(AudioUpload {
+                data: upload::sniff(to_audio_stream(body), sniff::audio, &unsupported_audio).await?,
+                form: None,
+                private: None,
+                comment: None,
+            });
         }
+
+        let mut form = Multipart::new(req.headers(), body);
+        let mut private = None;
+        let mut comment = None;
+
+        while let Some(item) = form.next().await {
+            let field = item.map_err(next_field_error)?;
+
+            if field.content_disposition().get_name() == Some("file") {
+                let data = upload::sniff(to_audio_stream(field), sniff::audio, &unsupported_audio).await?;
+
+                return Ok(AudioUpload {
+                    data,
+                    form: Some(form),
+                    private,
+                    comment,
+                });
+            }
+
+            read_form_field(field, &mut private, &mut comment).await?;
+        }
+
+        Err(error::build::bad_request(
+            "no audio file given. expect the file in the \"file\" form field"
+        ))
+    }
+
+    pub fn kind(&self) -> AudioKind {
+        self.data.kind
     }
 
-    Ok(AudioWebmResult {
-        size,
-        file,
-        path,
-    })
+    async fn read_rest(
+        form: Option<Multipart>,
+        private: &mut Option<bool>,
+        comment: &mut Option<String>,
+    ) -> error::Result<()> {
+        if let Some(mut form) = form {
+            while let Some(item) = form.next().await {
+                read_form_field(item.map_err(next_field_error)?, private, comment).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// streams the audio to the given key for the owner and reads the rest of
+    /// the form
+    ///
+    /// the upload fails once it is larger than the limit if one is given. the
+    /// object is removed if anything fails
+    pub async fn store(
+        self,
+        storage: &state::StorageState,
+        owner: &i32,
+        key: &str,
+        limit: Option<u64>,
+    ) -> error::Result<StoredAudio> {
+        let AudioUpload { data, form, mut private, mut comment } = self;
+        let (file_size, hash) = data.write_object(storage.writer(owner, key).await?, limit).await?;
+
+        if let Err(err) = AudioUpload::read_rest(form, &mut private, &mut comment).await {
+            storage::discard(storage.backend(), key).await;
+
+            return Err(err);
+        }
+
+        Ok(StoredAudio {
+            file_size,
+            hash: hash.to_hex().to_string(),
+            private,
+            comment,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PostAudioQuery {
+    private: Option<bool>,
+    comment: Option<String>,
 }
 
 /// handles creating new audio entries for a given entry id
 ///
 /// POST /entries/{entry_id}/audio
 ///
-/// can handle either multipar forms or webm audio files directly
+/// can handle either multipart forms or audio files directly. a form expects
+/// the audio in the `file` field with optional `private` and `comment`
+/// fields, otherwise they can be given as query parameters. webm, ogg (opus),
+/// mp3, wav, and m4a files are accepted and are detected from the data itself.
+/// the audio is streamed to the storage backend as it arrives and is stored
+/// by its hash so uploading the same file again does not keep another copy. a
+/// processing job is created for every configured command that matches. the
+/// upload is rejected as soon as it is larger than the remaining storage quota
 pub async fn handle_post(
     req: HttpRequest,
     initiator: Initiator,
     db: state::WebDbState,
     storage: state::WebStorageState,
-    path: web::Path<routing::path::params::EntryAudioPath>,
+    processing: state::WebProcessingState,
+    path: web::Path<routing::path::params::EntryPath>,
+    info: web::Query<PostAudioQuery>,
     body: web::Payload,
 ) -> error::Result<impl Responder> {
     let path = path.into_inner();
+    let info = info.into_inner();
     let mut conn = db.get_conn().await?;
 
     if !security::permissions::has_permission(
@@ -160,74 +326,121 @@ pub async fn handle_post(
     }
 
     security::assert::is_owner_for_entry(&*conn, &path.entry_id, &initiator.user.id).await?;
+    components::entries::lock_for_changes(&*conn, &path.entry_id).await?;
 
-    let private = false;
-    let mime_type: &str;
-    let mime_subtype: &str;
-    let file_ext: &str;
-    let audio_file;
-    let audio_file_size;
-    let audio_file_path;
-
-    if let Some(content_type_value) = req.headers().get("content-type") {
-        if content_type_value == "audio/webm" {
-            let results = handle_audio_webm(&storage, body).await?;
-
-            mime_type = "audio";
-            mime_subtype = "webm";
-            file_ext = "webm";
-            audio_file = results.file;
-            audio_file_size = results.size;
-            audio_file_path = results.path;
-        } else {
-            if let Ok(header_value) = content_type_value.to_str() {
-                return Err(error::build::bad_request(
-                    format!("invalid content-type given. expect: audio/webm | given: {}", header_value)
-                ));
-            } else {
-                return Err(error::build::bad_request(
-                    "header value contains invalid characters. cannot display value"
-                ))
-            }
-        }
-    } else {
-        return Err(error::build::bad_request(
-            "no content-type specified for request body"
-        ));
-    }
+    let limit = components::quota::remaining(&*conn, storage.quota(), &initiator.user.id).await?;
 
-    let transaction = conn.transaction().await?;
-
-    let result = transaction.query_one(
-        "\
-        insert into audio_entries (entry, private, mime_type, mime_subtype, file_size) \
-        values ($1, $2, $3) \
-        returning id",
-        &[
-            &path.entry_id, 
-            &private, 
-            &mime_type,
-            &mime_subtype,
-            &audio_file_size,
-        ]
-    ).await?;
+    let uploaded = AudioUpload::from_request(&req, body).await?;
+    let kind = uploaded.kind();
 
-    let id: i32 = result.get(0);
-    let new_path = storage.get_audio_file_path(&initiator.user.id, &path.entry_id, &id, file_ext);
+    let upload_key = storage.get_upload_key();
+    let stored = uploaded.store(&storage, &initiator.user.id, &upload_key, limit).await?;
+    let private = stored.private.or(info.private).unwrap_or(false);
+    let comment = util::string::trimmed_optional_string(stored.comment.or(info.comment));
+    let metadata = components::audio::read_metadata(&storage, &upload_key, None, kind).await;
 
-    {
-        let parent = new_path.parent().unwrap();
+    let commands: Vec<&str> = processing.commands_for("audio", kind.mime_subtype())
+        .map(|command| command.name.as_str())
+        .collect();
 
-        if !parent.try_exists()? {
-            std::fs::create_dir_all(parent)?;
+    let transaction = match conn.transaction().await {
+        Ok(t) => t,
+        Err(err) => {
+            storage::discard(storage.backend(), &upload_key).await;
+
+            return Err(err.into());
         }
-    }
+    };
+
+    let blob = components::blobs::place(
+        &transaction,
+        &storage,
+        &initiator.user.id,
+        &upload_key,
+        &stored.hash,
+        &stored.file_size,
+        kind.extension()
+    ).await?;
 
-    util::file::copy_file(&audio_file, new_path)?;
-    std::fs::remove_file(audio_file_path)?;
+    let created = async {
+        // the entry may have been trashed or other uploads may have used up
+        // the quota while the upload was being read
+        components::entries::lock_for_changes(&transaction, &path.entry_id).await?;
+        components::quota::reserve(&transaction, storage.quota(), &initiator.user.id, stored.file_size, 0).await?;
+
+        let result = transaction.query_one(
+            "\
+            insert into audio_entries ( \
+                entry, mime_type, mime_subtype, private, comment, file_size, \
+                duration_ms, codec, sample_rate, channels, blob \
+            ) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) \
+            returning id",
+            &[
+                &path.entry_id,
+                &"audio",
+                &kind.mime_subtype(),
+                &private,
+                &comment,
+                &stored.file_size,
+                &metadata.duration_ms,
+                &metadata.codec,
+                &metadata.sample_rate,
+                &metadata.channels,
+                &blob.hash,
+            ]
+        ).await?;
+        let id: i32 = result.get(0);
+
+        processing_jobs::create_for_audio(&transaction, &id, &commands, &chrono::Utc::now()).await?;
+        components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+        Ok::<i32, error::Error>(id)
+    }.await;
+
+    let id = match created {
+        Ok(id) => id,
+        Err(err) => {
+            blob.undo(&storage).await;
+
+            return Err(err);
+        }
+    };
+
+    if let Err(err) = transaction.commit().await {
+        blob.undo(&storage).await;
 
-    transaction.commit().await?;
+        return Err(err.into());
+    }
+
+    if peaks::is_supported(kind) {
+        components::audio::cache_peaks(
+            storage.clone(),
+            components::audio::AudioFile::locate(
+                &storage,
+                &initiator.user.id,
+                &path.entry_id,
+                &id,
+                kind.mime_subtype(),
+                Some(&blob.hash)
+            ),
+            kind
+        );
+    }
 
     JsonBuilder::new(http::StatusCode::OK)
-        .build(None::<()>)
+        .build(Some(audio_entries::AudioEntry {
+            id,
+            private,
+            comment,
+            entry: path.entry_id,
+            mime_type: "audio".to_owned(),
+            mime_subtype: kind.mime_subtype().to_owned(),
+            file_size: stored.file_size,
+            duration_ms: metadata.duration_ms,
+            codec: metadata.codec,
+            sample_rate: metadata.sample_rate,
+            channels: metadata.channels,
+            blob: Some(blob.hash),
+        }))
 }
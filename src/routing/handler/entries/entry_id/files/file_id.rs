@@ -0,0 +1,185 @@
+//! handles working with entry files on a singular basis
+
+use std::str::FromStr;
+
+use actix_web::{web, http, HttpRequest, Responder};
+use actix_web::http::header::{
+    self,
+    ContentDisposition,
+    DispositionType,
+    DispositionParam,
+    EntityTag,
+    TryIntoHeaderValue,
+};
+
+use crate::db::tables::{entries, entry_files, permissions};
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::components::{self, media::Released};
+use crate::routing;
+
+/// downloads a single file with the given entry and file id
+///
+/// GET /entries/{entry_id}/files/{file_id}
+/// GET /users/{user_id}/entries/{entry_id}/files/{file_id}
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryFilePath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let owner: i32;
+    let mut is_private = None::<bool>;
+
+    if let Some(user_id) = path.user_id {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::USERS_ENTRIES,
+            &[permissions::abilities::READ],
+            Some(&user_id)
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read this users entry files"
+            ));
+        }
+
+        owner = user_id;
+        is_private = Some(false);
+    } else {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::ENTRIES,
+            &[
+                permissions::abilities::READ,
+                permissions::abilities::READ_WRITE,
+            ],
+            None
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read entry files"
+            ));
+        }
+
+        owner = initiator.user.id;
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &owner, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let Some(entry_file) = entry_files::find_from_id(
+        &*conn,
+        &path.entry_id,
+        &path.file_id,
+        &is_private
+    ).await? else {
+        return Err(error::build::entry_file_not_found(&path.file_id));
+    };
+
+    let mime = {
+        let known = format!("{}/{}", entry_file.mime_type, entry_file.mime_subtype);
+
+        mime::Mime::from_str(&known)?
+    };
+    let key = components::media::entry_file_key(&*conn, &storage, &owner, &entry_file).await?;
+    let etag = match &entry_file.blob {
+        Some(hash) => EntityTag::new_strong(hash.clone()),
+        None => EntityTag::new_strong(format!("{}-{}", entry_file.id, entry_file.file_size))
+    };
+    let disposition = ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(entry_file.name)],
+    }.try_into_value()
+        .map_err(|e| error::Error::new()
+            .set_message("failed to create content disposition for file")
+            .set_source(e))?;
+
+    let mut res = response::file::respond_object(
+        &req,
+        storage.backend(),
+        &key,
+        entry_file.blob.as_deref(),
+        mime,
+        etag
+    ).await?;
+    res.headers_mut().insert(header::CONTENT_DISPOSITION, disposition);
+
+    Ok(res)
+}
+
+/// deletes a single file from an entry
+///
+/// DELETE /entries/{entry_id}/files/{file_id}
+///
+/// the file is removed from storage once the record has been deleted
+pub async fn handle_delete(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryFilePath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to delete entry files"
+        ));
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &initiator.user.id, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let transaction = conn.transaction().await?;
+    let Some(record) = transaction.query_opt(
+        "delete from entry_files where id = $1 and entry = $2 returning blob",
+        &[&path.file_id, &path.entry_id]
+    ).await? else {
+        return Err(error::build::entry_file_not_found(&path.file_id));
+    };
+
+    components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+    let removal = components::media::release(&transaction, &storage, &initiator.user.id, vec![Released::entry_file(
+        &storage,
+        &initiator.user.id,
+        &path.entry_id,
+        &path.file_id,
+        record.get(0)
+    )]).await?;
+
+    components::files::commit_removing(transaction, &storage, removal).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .set_message("entry file deleted")
+        .build_empty()
+}
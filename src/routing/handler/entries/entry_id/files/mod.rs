@@ -0,0 +1,255 @@
+//! handling file attachments for a given entry
+
+use std::str::FromStr;
+
+use actix_web::{web, http, HttpRequest, Responder};
+use serde::Deserialize;
+
+pub mod file_id;
+
+use crate::db::tables::{permissions, entry_files};
+use crate::net::http::{error, upload};
+use crate::net::http::response;
+use crate::net::http::response::json::JsonBuilder;
+use crate::state;
+use crate::storage;
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::components;
+use crate::util::{self, sniff};
+use crate::routing;
+
+/// retrieves file attachment data for a given entry id
+///
+/// GET /entries/{entry_id}/files
+/// GET /users/{user_id}/entries/{entry_id}/files
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryPath>
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let is_private: Option<bool>;
+    let owner: i32;
+
+    if let Some(user_id) = path.user_id {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::USERS_ENTRIES,
+            &[
+                permissions::abilities::READ
+            ],
+            Some(&user_id)
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read this users entry files"
+            ));
+        }
+
+        owner = user_id;
+        is_private = Some(false);
+    } else {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::ENTRIES,
+            &[
+                permissions::abilities::READ,
+                permissions::abilities::READ_WRITE
+            ],
+            None
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read entry files"
+            ));
+        }
+
+        owner = initiator.user.id;
+        is_private = None;
+    }
+
+    security::assert::is_owner_of_entry(&*conn, &owner, &path.entry_id).await?;
+
+    let files = entry_files::find_from_entry(
+        &*conn,
+        &path.entry_id,
+        &is_private
+    ).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(files))
+}
+
+#[derive(Deserialize)]
+pub struct PostFileQuery {
+    name: String,
+    private: Option<bool>,
+    comment: Option<String>,
+}
+
+/// uploads a new file for a given entry id
+///
+/// POST /entries/{entry_id}/files?name=<file name>
+///
+/// the request body is the contents of the file. the content-type header is
+/// stored as the mime type of the file and defaults to
+/// application/octet-stream if not given. `private` and `comment` can also be
+/// given as query parameters. the upload is rejected once it is larger than
+/// the remaining storage quota
+pub async fn handle_post(
+    req: HttpRequest,
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryPath>,
+    info: web::Query<PostFileQuery>,
+    body: web::Payload,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let info = info.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[permissions::abilities::READ_WRITE],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to create entry files"
+        ));
+    }
+
+    security::assert::is_owner_for_entry(&*conn, &path.entry_id, &initiator.user.id).await?;
+    components::entries::lock_for_changes(&*conn, &path.entry_id).await?;
+
+    let name = util::string::trimmed_string(info.name);
+
+    if name.is_empty() {
+        return Err(error::build::bad_request("file name cannot be empty"));
+    }
+
+    let mime = if let Some(content_type_value) = req.headers().get("content-type") {
+        let Ok(header_value) = content_type_value.to_str() else {
+            return Err(error::build::bad_request(
+                "header value contains invalid characters. cannot display value"
+            ));
+        };
+
+        match mime::Mime::from_str(header_value) {
+            Ok(mime) => mime,
+            Err(_) => return Err(error::build::bad_request(
+                format!("invalid content-type given: {}", header_value)
+            ))
+        }
+    } else {
+        mime::APPLICATION_OCTET_STREAM
+    };
+
+    let private = info.private.unwrap_or(false);
+    let comment = util::string::trimmed_optional_string(info.comment);
+    let limit = components::quota::remaining(&*conn, storage.quota(), &initiator.user.id).await?;
+    let uploaded = upload::read_head(body).await?;
+    let extension = sniff::extension(uploaded.head());
+    let upload_key = storage.get_upload_key();
+    let (file_size, hash) = uploaded.write_object(
+        storage.writer(&initiator.user.id, &upload_key).await?,
+        limit
+    ).await?;
+    let created = chrono::Utc::now();
+
+    let transaction = match conn.transaction().await {
+        Ok(transaction) => transaction,
+        Err(err) => {
+            storage::discard(storage.backend(), &upload_key).await;
+
+            return Err(err.into());
+        }
+    };
+
+    let blob = components::blobs::place(
+        &transaction,
+        &storage,
+        &initiator.user.id,
+        &upload_key,
+        hash.to_hex().as_str(),
+        &file_size,
+        extension
+    ).await?;
+
+    let inserted = async {
+        // the entry may have been trashed or other uploads may have used up
+        // the quota while the upload was being read
+        components::entries::lock_for_changes(&transaction, &path.entry_id).await?;
+        components::quota::reserve(&transaction, storage.quota(), &initiator.user.id, file_size, 0).await?;
+
+        let result = transaction.query_one(
+            "\
+            insert into entry_files (entry, private, comment, name, mime_type, mime_subtype, file_size, created, blob) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+            returning id",
+            &[
+                &path.entry_id,
+                &private,
+                &comment,
+                &name,
+                &mime.type_().as_str(),
+                &mime.subtype().as_str(),
+                &file_size,
+                &created,
+                &blob.hash,
+            ]
+        ).await?;
+        let id: i32 = result.get(0);
+
+        components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+        Ok::<i32, error::Error>(id)
+    }.await;
+
+    let id = match inserted {
+        Ok(id) => id,
+        Err(err) => {
+            blob.undo(&storage).await;
+
+            return Err(err);
+        }
+    };
+
+    if let Err(err) = transaction.commit().await {
+        blob.undo(&storage).await;
+
+        return Err(err.into());
+    }
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(entry_files::EntryFile {
+            id,
+            private,
+            comment,
+            name,
+            entry: path.entry_id,
+            mime_type: mime.type_().to_string(),
+            mime_subtype: mime.subtype().to_string(),
+            file_size,
+            created,
+            blob: Some(blob.hash),
+        }))
+}
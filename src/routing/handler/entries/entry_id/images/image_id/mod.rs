@@ -0,0 +1,201 @@
+//! handles working with image entries on a singular basis
+
+use std::str::FromStr;
+
+use actix_web::{web, http, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::EntityTag;
+
+pub mod thumbnail;
+
+use crate::db::tables::{entries, image_entries, permissions};
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::components::{self, media::Released};
+use crate::routing;
+
+/// responds with either the original image or its thumbnail
+///
+/// used by [handle_get] and [thumbnail::handle_get] so both check access the
+/// same way
+pub(crate) async fn respond_image(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: routing::path::params::EntryImagePath,
+    thumbnail: bool,
+) -> error::Result<HttpResponse> {
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let owner: i32;
+    let mut is_private = None::<bool>;
+
+    if let Some(user_id) = path.user_id {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::USERS_ENTRIES,
+            &[permissions::abilities::READ],
+            Some(&user_id)
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read this users image entries"
+            ));
+        }
+
+        owner = user_id;
+        is_private = Some(false);
+    } else {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::ENTRIES,
+            &[
+                permissions::abilities::READ,
+                permissions::abilities::READ_WRITE,
+            ],
+            None
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read image entries"
+            ));
+        }
+
+        owner = initiator.user.id;
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &owner, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let Some(image_entry) = image_entries::find_from_id(
+        &*conn,
+        &path.entry_id,
+        &path.image_id,
+        &is_private
+    ).await? else {
+        return Err(error::build::image_entry_not_found(&path.image_id));
+    };
+
+    let version = match &image_entry.blob {
+        Some(hash) => hash.clone(),
+        None => format!("{}-{}", image_entry.id, image_entry.file_size)
+    };
+    // the thumbnail is created from the image so it is not checked against
+    // the hash
+    let (key, hash, subtype, etag) = if thumbnail {
+        (
+            components::media::image_thumbnail_key(&storage, &owner, &image_entry),
+            None,
+            &image_entry.thumbnail_mime_subtype,
+            format!("{}-thumb", version)
+        )
+    } else {
+        (
+            components::media::image_key(&storage, &owner, &image_entry),
+            image_entry.blob.as_deref(),
+            &image_entry.mime_subtype,
+            version
+        )
+    };
+    let mime = {
+        let known = format!("{}/{}", image_entry.mime_type, subtype);
+
+        mime::Mime::from_str(&known)?
+    };
+
+    response::file::respond_object(
+        &req,
+        storage.backend(),
+        &key,
+        hash,
+        mime,
+        EntityTag::new_strong(etag)
+    ).await
+}
+
+/// retrieves the original image for the given entry and image id
+///
+/// GET /entries/{entry_id}/images/{image_id}
+/// GET /users/{user_id}/entries/{entry_id}/images/{image_id}
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryImagePath>,
+) -> error::Result<impl Responder> {
+    respond_image(req, security, db, storage, path.into_inner(), false).await
+}
+
+/// deletes a single image from an entry
+///
+/// DELETE /entries/{entry_id}/images/{image_id}
+///
+/// the image and its thumbnail are removed from storage once the record has
+/// been deleted and nothing else references the same image
+pub async fn handle_delete(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryImagePath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to delete image entries"
+        ));
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &initiator.user.id, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let transaction = conn.transaction().await?;
+    let Some(record) = transaction.query_opt(
+        "delete from image_entries where id = $1 and entry = $2 returning blob",
+        &[&path.image_id, &path.entry_id]
+    ).await? else {
+        return Err(error::build::image_entry_not_found(&path.image_id));
+    };
+
+    components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+    let removal = components::media::release(&transaction, &storage, &initiator.user.id, vec![Released::image(
+        &storage,
+        &initiator.user.id,
+        &path.entry_id,
+        &path.image_id,
+        record.get(0)
+    )]).await?;
+
+    components::files::commit_removing(transaction, &storage, removal).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .set_message("image entry deleted")
+        .build_empty()
+}
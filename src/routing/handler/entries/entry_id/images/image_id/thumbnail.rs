@@ -0,0 +1,22 @@
+//! handles retrieving image thumbnails
+
+use actix_web::{web, HttpRequest, Responder};
+
+use crate::net::http::error;
+use crate::state;
+use crate::security;
+use crate::routing;
+
+/// retrieves the thumbnail for the given entry and image id
+///
+/// GET /entries/{entry_id}/images/{image_id}/thumbnail
+/// GET /users/{user_id}/entries/{entry_id}/images/{image_id}/thumbnail
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryImagePath>,
+) -> error::Result<impl Responder> {
+    super::respond_image(req, security, db, storage, path.into_inner(), true).await
+}
@@ -0,0 +1,315 @@
+//! handling image entries for a given entry
+
+use futures_util::stream::StreamExt;
+use actix_web::{web, http, HttpRequest, Responder};
+use serde::Deserialize;
+
+pub mod image_id;
+
+use crate::db::tables::{permissions, image_entries};
+use crate::net::http::{error, upload};
+use crate::net::http::response;
+use crate::net::http::response::json::JsonBuilder;
+use crate::state;
+use crate::storage;
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::components;
+use crate::util::{self, images::{self, ImageError}};
+use crate::routing;
+
+/// the max size of an uploaded image in bytes
+const MAX_IMAGE_SIZE: usize = 1024 * 1024 * 32;
+
+/// retrieves image entry data for a given entry id
+///
+/// GET /entries/{entry_id}/images
+/// GET /users/{user_id}/entries/{entry_id}/images
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryPath>
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let is_private: Option<bool>;
+    let owner: i32;
+
+    if let Some(user_id) = path.user_id {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::USERS_ENTRIES,
+            &[
+                permissions::abilities::READ
+            ],
+            Some(&user_id)
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read this users image entries"
+            ));
+        }
+
+        owner = user_id;
+        is_private = Some(false);
+    } else {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::ENTRIES,
+            &[
+                permissions::abilities::READ,
+                permissions::abilities::READ_WRITE
+            ],
+            None
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read image entries"
+            ));
+        }
+
+        owner = initiator.user.id;
+        is_private = None;
+    }
+
+    security::assert::is_owner_of_entry(&*conn, &owner, &path.entry_id).await?;
+
+    let images = image_entries::find_from_entry(
+        &*conn,
+        &path.entry_id,
+        &is_private
+    ).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(images))
+}
+
+#[derive(Deserialize)]
+pub struct PostImageQuery {
+    private: Option<bool>,
+    comment: Option<String>,
+    /// keeps the exif and other metadata of the original image
+    keep_metadata: Option<bool>,
+}
+
+/// uploads a new image for a given entry id
+///
+/// POST /entries/{entry_id}/images
+///
+/// the request body is the image data. jpeg, png, and webp images are
+/// accepted and the format is checked from the data itself. a thumbnail is
+/// created when the image is stored. metadata like exif gps and orientation
+/// is removed unless `keep_metadata=true` is given with the orientation
+/// applied to the image beforehand. the upload is rejected if the stored
+/// image is larger than the remaining storage quota.
+pub async fn handle_post(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryPath>,
+    info: web::Query<PostImageQuery>,
+    mut body: web::Payload,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let info = info.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[permissions::abilities::READ_WRITE],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to create image entries"
+        ));
+    }
+
+    security::assert::is_owner_for_entry(&*conn, &path.entry_id, &initiator.user.id).await?;
+    components::entries::lock_for_changes(&*conn, &path.entry_id).await?;
+
+    let limit = components::quota::remaining(&*conn, storage.quota(), &initiator.user.id).await?;
+    let mut bytes = Vec::new();
+
+    while let Some(item) = body.next().await {
+        let chunk = item.map_err(|e| error::Error::new()
+            .set_message("problem with reading image from request")
+            .set_source(e))?;
+
+        if bytes.len() + chunk.len() > MAX_IMAGE_SIZE {
+            return Err(error::build::payload_too_large(
+                format!("image is too large. max size: {} bytes", MAX_IMAGE_SIZE)
+            ));
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let strip_metadata = !info.keep_metadata.unwrap_or(false);
+    let processed = match web::block(move || images::process(bytes, strip_metadata)).await {
+        Ok(Ok(processed)) => processed,
+        Ok(Err(ImageError::Unsupported)) => return Err(error::build::bad_request(
+            "unsupported image format. expect: jpeg | png | webp"
+        )),
+        Ok(Err(err)) => return Err(error::build::bad_request("failed to read the given image")
+            .set_source(err)),
+        Err(err) => return Err(error::Error::new()
+            .set_message("failed to process image")
+            .set_source(err))
+    };
+
+    let private = info.private.unwrap_or(false);
+    let comment = util::string::trimmed_optional_string(info.comment);
+    let created = chrono::Utc::now();
+    let images::ProcessedImage { kind, data, width, height, thumbnail_kind, thumbnail } = processed;
+    let file_size = data.len() as i64;
+    let width = width as i32;
+    let height = height as i32;
+
+    // the stored image is what counts towards the quota and it can be larger
+    // or smaller than what was uploaded once it has been processed
+    upload::check_quota(data.len() as u64, limit)?;
+
+    let hash = blake3::hash(&data).to_hex().to_string();
+    let upload_key = storage.get_upload_key();
+
+    storage::put_bytes(storage.writer(&initiator.user.id, &upload_key).await?, data).await?;
+
+    let transaction = match conn.transaction().await {
+        Ok(t) => t,
+        Err(err) => {
+            storage::discard(storage.backend(), &upload_key).await;
+
+            return Err(err.into());
+        }
+    };
+
+    let blob = components::blobs::place(
+        &transaction,
+        &storage,
+        &initiator.user.id,
+        &upload_key,
+        &hash,
+        &file_size,
+        kind.extension()
+    ).await?;
+
+    // the same image may already be stored as a file without a thumbnail
+    let thumbnail_key = storage.get_blob_thumbnail_key(&initiator.user.id, &hash);
+    let thumbnail_written = async {
+        if storage.backend().size(&thumbnail_key).await?.is_some() {
+            return Ok(false);
+        }
+
+        storage::put_bytes(
+            storage.writer(&initiator.user.id, &thumbnail_key).await?,
+            thumbnail
+        ).await?;
+
+        Ok::<bool, std::io::Error>(true)
+    }.await;
+
+    let thumbnail_written = match thumbnail_written {
+        Ok(written) => written,
+        Err(err) => {
+            blob.undo(&storage).await;
+
+            return Err(err.into());
+        }
+    };
+
+    let inserted = async {
+        // the entry may have been trashed or other uploads may have used up
+        // the quota while the upload was being read
+        components::entries::lock_for_changes(&transaction, &path.entry_id).await?;
+        components::quota::reserve(&transaction, storage.quota(), &initiator.user.id, file_size, 0).await?;
+
+        let result = transaction.query_one(
+            "\
+            insert into image_entries (\
+                entry, \
+                private, \
+                comment, \
+                mime_type, \
+                mime_subtype, \
+                file_size, \
+                width, \
+                height, \
+                thumbnail_mime_subtype, \
+                created, \
+                blob\
+            ) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) \
+            returning id",
+            &[
+                &path.entry_id,
+                &private,
+                &comment,
+                &"image",
+                &kind.mime_subtype(),
+                &file_size,
+                &width,
+                &height,
+                &thumbnail_kind.mime_subtype(),
+                &created,
+                &blob.hash,
+            ]
+        ).await?;
+        let id: i32 = result.get(0);
+
+        components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+        Ok::<i32, error::Error>(id)
+    }.await;
+
+    let result = match inserted {
+        Ok(id) => transaction.commit().await
+            .map(|_| id)
+            .map_err(error::Error::from),
+        Err(err) => Err(err)
+    };
+
+    let id = match result {
+        Ok(id) => id,
+        Err(err) => {
+            if thumbnail_written {
+                storage::discard(storage.backend(), &thumbnail_key).await;
+            }
+
+            blob.undo(&storage).await;
+
+            return Err(err);
+        }
+    };
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(image_entries::ImageEntry {
+            id,
+            private,
+            comment,
+            entry: path.entry_id,
+            mime_type: "image".to_owned(),
+            mime_subtype: kind.mime_subtype().to_owned(),
+            file_size,
+            width,
+            height,
+            thumbnail_mime_subtype: thumbnail_kind.mime_subtype().to_owned(),
+            created,
+            blob: Some(blob.hash),
+        }))
+}
@@ -2,12 +2,19 @@
 
 use std::iter::Extend;
 
-use actix_web::{web, http, HttpRequest, Responder};
+use actix_web::{web, http, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::{self, Header};
 use serde::Deserialize;
 use chrono::{Utc, serde::ts_seconds};
+use tokio_postgres::GenericClient;
 
 pub mod comments;
 pub mod audio;
+pub mod images;
+pub mod video;
+pub mod files;
+pub mod restore;
+pub mod text;
 
 use crate::db::{
     self, 
@@ -15,9 +22,13 @@ use crate::db::{
         permissions,
         custom_field_entries, 
         text_entries,
+        text_entry_revisions,
         entries2tags,
         entry_markers,
         audio_entries,
+        image_entries,
+        video_entries,
+        entry_files,
     }
 };
 use crate::net::http::error;
@@ -25,7 +36,7 @@ use crate::net::http::response;
 use crate::net::http::response::json::JsonBuilder;
 use crate::state;
 use crate::security::{self, InitiatorLookup, Initiator};
-use crate::components::{self, entries::schema};
+use crate::components::{self, entries::schema, files::Removal};
 use crate::template;
 use crate::routing;
 
@@ -36,7 +47,8 @@ use crate::routing;
 ///
 /// returns the requested entry with additional information for the current 
 /// user based on the session. auth checks will be performed if reqesting an
-/// entry for a nother user
+/// entry for a nother user. the ETag header can be given back in If-Match
+/// when updating or deleting the entry
 pub async fn handle_get(
     req: HttpRequest,
     security: security::state::WebSecurityState,
@@ -95,42 +107,104 @@ pub async fn handle_get(
         owner = initiator.user.id;
     }
 
-    if let Some(record) = db::tables::entries::from_user_and_id(conn, &owner, &path.entry_id).await? {
-        let mut rtn = schema::Entry {
-            id: record.id,
-            day: record.day,
-            created: record.created,
-            updated: record.updated,
-            deleted: record.deleted,
-            owner: record.owner,
-            tags: Vec::new(),
-            markers: Vec::new(),
-            fields: Vec::new(),
-            text: Vec::new(),
-            audio: Vec::new()
-        };
-
-        rtn.tags.extend(entries2tags::find_id_from_entry(conn, &path.entry_id).await?);
-        rtn.markers.extend(entry_markers::find_from_entry(conn, &path.entry_id).await?
-            .into_iter()
-            .map(|m| m.into()));
-        rtn.fields.extend(custom_field_entries::find_from_entry(conn, &path.entry_id).await?
-            .into_iter()
-            .map(|f| f.into()));
-        rtn.text.extend(text_entries::find_from_entry(conn, &path.entry_id, &is_private).await?
-            .into_iter()
-            .map(|t| t.into()));
-        rtn.audio.extend(audio_entries::find_from_entry(conn, &path.entry_id, &is_private).await?
-            .into_iter()
-            .map(|a| a.into()));
-
+    if let Some(rtn) = components::entries::find_entry(conn, &owner, &path.entry_id, &is_private).await? {
         JsonBuilder::new(http::StatusCode::OK)
+            .insert_header(header::ETag(components::entries::etag(&rtn.id, &rtn.version)))
             .build(Some(rtn))
     } else {
         Err(error::build::entry_not_found(&path.entry_id))
     }
 }
 
+/// parses the If-Match header if one was given
+fn get_if_match(req: &HttpRequest) -> error::Result<Option<header::IfMatch>> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Ok(None);
+    }
+
+    match header::IfMatch::parse(req) {
+        Ok(if_match) => Ok(Some(if_match)),
+        Err(_) => Err(error::build::bad_request("invalid If-Match header given"))
+    }
+}
+
+/// retrieves the current version of an entry and locks it for the rest of
+/// the transaction so it cannot change after a check
+async fn lock_version(
+    conn: &impl GenericClient,
+    owner: &i32,
+    entry_id: &i32,
+) -> error::Result<i32> {
+    if let Some(record) = conn.query_opt(
+        "select version from entries where id = $1 and owner = $2 for update",
+        &[entry_id, owner]
+    ).await? {
+        Ok(record.get(0))
+    } else {
+        Err(error::build::entry_not_found(entry_id))
+    }
+}
+
+/// checks the current version of an entry against an If-Match header
+///
+/// the entry is locked for the rest of the transaction so it cannot change
+/// after the check. if the precondition fails then the current copy of the
+/// entry is returned
+async fn check_if_match(
+    conn: &impl GenericClient,
+    owner: &i32,
+    entry_id: &i32,
+    if_match: &header::IfMatch,
+) -> error::Result<Option<schema::Entry>> {
+    let version = lock_version(conn, owner, entry_id).await?;
+
+    let matched = match if_match {
+        header::IfMatch::Any => true,
+        header::IfMatch::Items(tags) => {
+            let current = components::entries::etag(entry_id, &version);
+
+            tags.iter().any(|tag| tag.strong_eq(&current))
+        }
+    };
+
+    if matched {
+        Ok(None)
+    } else {
+        let is_private = None;
+
+        Ok(components::entries::find_entry(conn, owner, entry_id, &is_private).await?)
+    }
+}
+
+/// checks the current version of an entry against an expected version
+///
+/// same as [check_if_match] but for versions given in a request body
+pub(crate) async fn check_version(
+    conn: &impl GenericClient,
+    owner: &i32,
+    entry_id: &i32,
+    expected: &i32,
+) -> error::Result<Option<schema::Entry>> {
+    let version = lock_version(conn, owner, entry_id).await?;
+
+    if version == *expected {
+        Ok(None)
+    } else {
+        let is_private = None;
+
+        Ok(components::entries::find_entry(conn, owner, entry_id, &is_private).await?)
+    }
+}
+
+/// responds with the current copy of an entry that failed an If-Match check
+fn precondition_failed(current: schema::Entry) -> error::Result<HttpResponse> {
+    JsonBuilder::new(http::StatusCode::PRECONDITION_FAILED)
+        .insert_header(header::ETag(components::entries::etag(&current.id, &current.version)))
+        .set_error("PreconditionFailed")
+        .set_message("the entry has been modified since it was last retrieved")
+        .build(Some(current))
+}
+
 #[derive(Deserialize)]
 pub struct PutTextEntry {
     id: Option<i32>,
@@ -172,14 +246,18 @@ pub struct PutComposedEntry {
 /// PUT /entries/{id}
 /// 
 /// updates the requested entry with new information. it will assume that the
-/// new information is the final form and will add/remove/update accordingly
+/// new information is the final form and will add/remove/update accordingly.
+/// if an If-Match header is given and does not match the current ETag of the
+/// entry then a 412 is returned with the current copy of the entry
 pub async fn handle_put(
+    req: HttpRequest,
     initiator: Initiator,
     db: state::WebDbState,
     path: web::Path<routing::path::params::EntryPath>,
     posted: web::Json<PutComposedEntry>
 ) -> error::Result<impl Responder> {
     let posted = posted.into_inner();
+    let if_match = get_if_match(&req)?;
     let conn = &mut *db.get_conn().await?;
 
     if !security::permissions::has_permission(
@@ -196,34 +274,71 @@ pub async fn handle_put(
         ));
     }
 
+    let transaction = conn.transaction().await?;
+
+    if let Some(if_match) = &if_match {
+        if let Some(current) = check_if_match(&transaction, &initiator.user.id, &path.entry_id, if_match).await? {
+            return precondition_failed(current);
+        }
+    }
+
+    let rtn = update_entry(&transaction, &initiator.user.id, &path.entry_id, posted).await?;
+
+    transaction.commit().await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .insert_header(header::ETag(components::entries::etag(&rtn.id, &rtn.version)))
+        .build(Some(rtn))
+}
+
+/// updates an entry with everything attached to it
+///
+/// used by [handle_put] and [super::batch::handle_post] so both update
+/// entries the same way
+pub(crate) async fn update_entry(
+    conn: &impl GenericClient,
+    owner: &i32,
+    entry_id: &i32,
+    posted: PutComposedEntry,
+) -> error::Result<schema::Entry> {
     let Some(original) = db::tables::entries::from_user_and_id(
         conn, 
-        &path.entry_id, 
-        &initiator.user.id
+        owner, 
+        entry_id
     ).await? else {
-        return Err(error::build::entry_not_found(&path.entry_id));
+        return Err(error::build::entry_not_found(entry_id));
     };
 
-    let transaction = conn.transaction().await?;
+    components::entries::lock_for_changes(conn, entry_id).await?;
 
     let updated = Utc::now();
-    let _result = transaction.execute(
-        "update entries set day = $1 where id = $2 returning day",
-        &[&posted.entry.day, &path.entry_id]
+    let result = conn.query_one(
+        "\
+        update entries \
+        set day = $1, \
+            updated = $2, \
+            version = version + 1 \
+        where id = $3 \
+        returning version",
+        &[&posted.entry.day, &updated, entry_id]
     ).await?;
 
     let mut rtn = schema::Entry {
-        id: path.entry_id,
+        id: *entry_id,
         day: posted.entry.day.clone(),
         created: original.created,
         updated: Some(updated),
         deleted: original.deleted,
-        owner: initiator.user.id,
+        owner: *owner,
+        version: result.get(0),
         tags: Vec::new(),
         markers: Vec::new(),
         fields: Vec::new(),
         text: Vec::new(),
         audio: Vec::new(),
+        images: Vec::new(),
+        videos: Vec::new(),
+        files: Vec::new(),
     };
 
     if let Some(m) = posted.custom_field_entries {
@@ -231,22 +346,22 @@ pub async fn handle_put(
 
         for custom_field_entry in m {
             let field = components::custom_fields::get_via_id(
-                &transaction, 
+                conn, 
                 &custom_field_entry.field, 
-                Some(&initiator.user.id)
+                Some(owner)
             ).await?;
 
             db::validation::verifiy_custom_field_entry(&field.config, &custom_field_entry.value)?;
 
             let value_json = serde_json::to_value(custom_field_entry.value.clone())?;
-            let _result = transaction.execute(
+            let _result = conn.execute(
                 "\
                 insert into custom_field_entries (field, value, comment, entry) \
                 values ($1, $2, $3, $4) \
                 on conflict on constraint entry_field_key do update \
                 set value = excluded.value, \
                     comment = excluded.comment",
-                &[&field.id, &value_json, &custom_field_entry.comment, &path.entry_id]
+                &[&field.id, &value_json, &custom_field_entry.comment, entry_id]
             ).await?;
 
             ids.push(field.id);
@@ -257,12 +372,12 @@ pub async fn handle_put(
             });
         }
 
-        let _dropped = transaction.query(
+        let _dropped = conn.query(
             "delete from custom_field_entries where entry = $1 and field <> all($2)",
-            &[&path.entry_id, &ids]
+            &[entry_id, &ids]
         ).await?;
     } else {
-        rtn.fields.extend(custom_field_entries::find_from_entry(&transaction, &path.entry_id).await?
+        rtn.fields.extend(custom_field_entries::find_from_entry(conn, entry_id).await?
             .into_iter()
             .map(|f| f.into()));
     }
@@ -272,13 +387,30 @@ pub async fn handle_put(
 
         for text_entry in t {
             if let Some(id) = text_entry.id {
-                let result = transaction.execute(
-                    "update text_entries set thought = $1, private = $2 where id = $3 returning id",
-                    &[&text_entry.thought, &text_entry.private, &id]
-                ).await?;
-
-                if result == 0 {
+                let Some(current) = conn.query_opt(
+                    "select thought, private from text_entries where id = $1 and entry = $2 for update",
+                    &[&id, entry_id]
+                ).await? else {
                     return Err(error::build::text_entry_not_found(&id));
+                };
+
+                let current_thought: String = current.get(0);
+                let current_private: bool = current.get(1);
+
+                if current_thought != text_entry.thought || current_private != text_entry.private {
+                    let _result = conn.execute(
+                        "update text_entries set thought = $1, private = $2 where id = $3",
+                        &[&text_entry.thought, &text_entry.private, &id]
+                    ).await?;
+
+                    text_entry_revisions::create(
+                        conn,
+                        entry_id,
+                        &id,
+                        &text_entry.thought,
+                        &text_entry.private,
+                        owner
+                    ).await?;
                 }
 
                 ids.push(id);
@@ -288,9 +420,18 @@ pub async fn handle_put(
                     private: text_entry.private,
                 });
             } else {
-                let result = transaction.query_one(
+                let result = conn.query_one(
                     "insert into text_entries (thought, private, entry) values ($1, $2, $3) returning id",
-                    &[&text_entry.thought, &text_entry.private, &path.entry_id]
+                    &[&text_entry.thought, &text_entry.private, entry_id]
+                ).await?;
+
+                text_entry_revisions::create(
+                    conn,
+                    entry_id,
+                    &result.get(0),
+                    &text_entry.thought,
+                    &text_entry.private,
+                    owner
                 ).await?;
 
                 ids.push(result.get(0));
@@ -302,37 +443,50 @@ pub async fn handle_put(
             }
         }
 
-        let _dropped = transaction.query(
-            "delete from text_entries where entry = $1 and id <> all($2)",
-            &[&path.entry_id, &ids]
+        // the revisions of dropped text entries are kept with a last one to
+        // show that they were removed
+        let dropped = conn.query(
+            "delete from text_entries where entry = $1 and id <> all($2) returning id, thought, private",
+            &[entry_id, &ids]
         ).await?;
+
+        for row in dropped {
+            text_entry_revisions::create_removed(
+                conn,
+                entry_id,
+                &row.get(0),
+                &row.get(1),
+                &row.get(2),
+                owner
+            ).await?;
+        }
     } else {
         let is_private = None;
-        rtn.text.extend(text_entries::find_from_entry(&transaction, &path.entry_id, &is_private).await?
+        rtn.text.extend(text_entries::find_from_entry(conn, entry_id, &is_private).await?
             .into_iter()
             .map(|t| t.into()));
     }
 
     if let Some(tags) = posted.tags {
         for tag_id in &tags {
-            let _result = transaction.execute(
+            let _result = conn.execute(
                 "\
                 insert into entries2tags (tag, entry) \
                 values ($1, $2) \
                 on conflict on constraint unique_entry_tag do update \
                 set tag = excluded.tag",
-                &[&tag_id, &path.entry_id]
+                &[&tag_id, entry_id]
             ).await?;
         }
 
-        let _dropped = transaction.execute(
+        let _dropped = conn.execute(
             "delete from entries2tags where entry = $1 and tag <> all($2)",
-            &[&path.entry_id, &tags]
+            &[entry_id, &tags]
         ).await?;
 
         rtn.tags = tags;
     } else {
-        rtn.tags = entries2tags::find_id_from_entry(&transaction, &path.entry_id).await?;
+        rtn.tags = entries2tags::find_id_from_entry(conn, entry_id).await?;
     }
 
     if let Some(markers) = posted.markers {
@@ -340,9 +494,9 @@ pub async fn handle_put(
 
         for marker in markers {
             if let Some(id) = marker.id {
-                let result = transaction.execute(
+                let result = conn.execute(
                     "update entry_markers set title = $1, comment = $2 where id = $3 and entry = $4",
-                    &[&marker.title, &marker.comment, &id, &path.entry_id]
+                    &[&marker.title, &marker.comment, &id, entry_id]
                 ).await?;
 
                 if result == 0 {
@@ -356,12 +510,12 @@ pub async fn handle_put(
                     comment: marker.comment,
                 });
             } else {
-                let result = transaction.query_one(
+                let result = conn.query_one(
                     "\
                     insert into entry_markers (title, comment, entry) \
                     values ($1, $2, $3) \
                     returning id",
-                    &[&marker.title, &marker.comment, &path.entry_id]
+                    &[&marker.title, &marker.comment, entry_id]
                 ).await?;
 
                 ids.push(result.get(0));
@@ -373,12 +527,12 @@ pub async fn handle_put(
             }
         }
 
-        let _dropped = transaction.execute(
+        let _dropped = conn.execute(
             "delete from entry_markers where entry = $1 and id <> all($2)",
-            &[&path.entry_id, &ids]
+            &[entry_id, &ids]
         ).await?;
     } else {
-        rtn.markers.extend(entry_markers::find_from_entry(&transaction, &path.entry_id).await?
+        rtn.markers.extend(entry_markers::find_from_entry(conn, entry_id).await?
             .into_iter()
             .map(|m| schema::Marker {
                 id: m.id,
@@ -388,15 +542,20 @@ pub async fn handle_put(
     }
 
     let is_private = None;
-    rtn.audio.extend(audio_entries::find_from_entry(&transaction, &path.entry_id, &is_private).await?
+    rtn.audio.extend(audio_entries::find_from_entry(conn, entry_id, &is_private).await?
         .into_iter()
         .map(|a| a.into()));
+    rtn.images.extend(image_entries::find_from_entry(conn, entry_id, &is_private).await?
+        .into_iter()
+        .map(|i| i.into()));
+    rtn.videos.extend(video_entries::find_from_entry(conn, entry_id, &is_private).await?
+        .into_iter()
+        .map(|v| v.into()));
+    rtn.files.extend(entry_files::find_from_entry(conn, entry_id, &is_private).await?
+        .into_iter()
+        .map(|f| f.into()));
 
-
-    transaction.commit().await?;
-
-    JsonBuilder::new(http::StatusCode::OK)
-        .build(Some(rtn))
+    Ok(rtn)
 }
 
 /// deletes the given entry id
@@ -404,14 +563,18 @@ pub async fn handle_put(
 /// DELETE /entries/{id}
 ///
 /// checks to make sure that the entry is owned by the current user before
-/// deleting. all data associated with the entry will be removed first and
-/// then the actual entry will be deleted
+/// deleting. the entry will be moved to the trash and can be restored with
+/// `POST /entries/{id}/restore`. if the entry is already in the trash then it
+/// will be permanently deleted along with any files attached to it. accepts
+/// an If-Match header the same as [handle_put]
 pub async fn handle_delete(
+    req: HttpRequest,
     initiator: Initiator,
     db: state::WebDbState,
     storage: state::WebStorageState,
     path: web::Path<routing::path::params::EntryPath>
 ) -> error::Result<impl Responder> {
+    let if_match = get_if_match(&req)?;
     let conn = &mut *db.get_conn().await?;
 
     if !security::permissions::has_permission(
@@ -430,59 +593,67 @@ pub async fn handle_delete(
 
     let transaction = conn.transaction().await?;
 
-    let Some(_record) = transaction.query_opt(
-        "select id, owner from entries where id = $1 and owner = $2",
-        &[&path.entry_id, &initiator.user.id]
-    ).await? else {
-        return Err(error::build::entry_not_found(&path.entry_id));
-    };
-
-    let is_private = None;
-    let audio = audio_entries::find_from_entry(&transaction, &path.entry_id, &is_private).await?;
-
-    for a in audio {
-        let file_path = storage.get_audio_file_path(
-            &initiator.user.id,
-            &path.entry_id,
-            &a.id,
-            ".webm"
-        );
-
-        std::fs::remove_file(&file_path)?;
+    if let Some(if_match) = &if_match {
+        if let Some(current) = check_if_match(&transaction, &initiator.user.id, &path.entry_id, if_match).await? {
+            return precondition_failed(current);
+        }
     }
+    let deleted = delete_entry(&transaction, &storage, &initiator.user.id, &path.entry_id).await?;
+
+    match deleted {
+        DeletedEntry::Trashed => {
+            transaction.commit().await?;
+
+            JsonBuilder::new(http::StatusCode::OK)
+                .set_message("entry moved to trash")
+                .build_empty()
+        },
+        DeletedEntry::Purged(files) => {
+            components::files::commit_removing(transaction, &storage, files).await?;
+
+            JsonBuilder::new(http::StatusCode::OK)
+                .set_message("entry deleted")
+                .build_empty()
+        }
+    }
+}
 
-    let _audio_result = transaction.execute(
-        "delete from audio_entries where entry = $1",
-        &[&path.entry_id]
-    ).await?;
-
-    let _text_result = transaction.execute(
-        "delete from text_entries where entry = $1",
-        &[&path.entry_id]
-    ).await?;
-
-    let _custom_field_entries_result = transaction.execute(
-        "delete from custom_field_entries where entry = $1",
-        &[&path.entry_id]
-    ).await?;
+/// what happened to an entry when it was deleted
+pub(crate) enum DeletedEntry {
+    Trashed,
+    /// the entry was permanently removed. the files should be removed once
+    /// the transaction has been committed
+    Purged(Removal),
+}
 
-    let _tag_result = transaction.execute(
-        "delete from entries2tags where entry = $1",
-        &[&path.entry_id]
-    ).await?;
+/// moves an entry to the trash or purges it if it is already in the trash
+///
+/// used by [handle_delete] and [super::batch::handle_post]
+pub(crate) async fn delete_entry(
+    conn: &impl GenericClient,
+    storage: &state::StorageState,
+    owner: &i32,
+    entry_id: &i32,
+) -> error::Result<DeletedEntry> {
+    let Some(record) = conn.query_opt(
+        "select id, deleted from entries where id = $1 and owner = $2",
+        &[entry_id, owner]
+    ).await? else {
+        return Err(error::build::entry_not_found(entry_id));
+    };
 
-    let _marker_result = transaction.execute(
-        "delete from entry_markers where entry = $1",
-        &[&path.entry_id]
-    ).await?;
+    let deleted: Option<chrono::DateTime<Utc>> = record.get(1);
 
-    let _entry_result = transaction.execute(
-        "delete from entries where id = $1",
-        &[&path.entry_id]
-    ).await?;
+    if deleted.is_some() {
+        let files = components::entries::purge(conn, storage, owner, entry_id).await?;
 
-    transaction.commit().await?;
+        Ok(DeletedEntry::Purged(files))
+    } else {
+        let _entry_result = conn.execute(
+            "update entries set deleted = $1, version = version + 1 where id = $2",
+            &[&Utc::now(), entry_id]
+        ).await?;
 
-    JsonBuilder::new(http::StatusCode::OK)
-        .build(None::<()>)
+        Ok(DeletedEntry::Trashed)
+    }
 }
@@ -0,0 +1,55 @@
+//! handling restoring entries from the trash
+
+use actix_web::{web, http, Responder};
+
+use crate::db::tables::permissions;
+use crate::net::http::{error, response::json::JsonBuilder};
+use crate::state;
+use crate::security::{self, Initiator};
+use crate::routing;
+
+/// restores an entry from the trash
+///
+/// POST /entries/{id}/restore
+///
+/// the entry must be owned by the current user and currently be in the trash
+pub async fn handle_post(
+    initiator: Initiator,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryPath>
+) -> error::Result<impl Responder> {
+    let conn = &*db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to restore entries"
+        ));
+    }
+
+    let result = conn.execute(
+        "\
+        update entries \
+        set deleted = null, \
+            version = version + 1 \
+        where id = $1 and \
+              owner = $2 and \
+              deleted is not null",
+        &[&path.entry_id, &initiator.user.id]
+    ).await?;
+
+    if result == 0 {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    }
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .set_message("entry restored")
+        .build_empty()
+}
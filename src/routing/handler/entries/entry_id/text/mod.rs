@@ -0,0 +1,86 @@
+//! handling text entries for an entry
+
+use tokio_postgres::GenericClient;
+
+use crate::db::tables::permissions;
+use crate::net::http::error;
+use crate::security::{self, Initiator};
+use crate::routing;
+
+pub mod revisions;
+
+/// checks that the initiator is allowed to view the requested text entry
+///
+/// returns the private flag to filter revisions with. the text entry must
+/// belong to the entry and the entry must belong to the owner. private text
+/// entries are not available when viewing another users entries. removed
+/// text entries are still available through their revisions
+pub async fn check_text_access(
+    conn: &impl GenericClient,
+    initiator: &Initiator,
+    path: &routing::path::params::EntryTextPath,
+) -> error::Result<Option<bool>> {
+    let is_private: Option<bool>;
+    let owner: i32;
+
+    if let Some(user_id) = path.user_id {
+        if !security::permissions::has_permission(
+            conn,
+            &initiator.user.id,
+            permissions::rolls::USERS_ENTRIES,
+            &[permissions::abilities::READ],
+            Some(&user_id)
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read this users entries"
+            ));
+        }
+
+        is_private = Some(false);
+        owner = user_id;
+    } else {
+        if !security::permissions::has_permission(
+            conn,
+            &initiator.user.id,
+            permissions::rolls::ENTRIES,
+            &[
+                permissions::abilities::READ,
+                permissions::abilities::READ_WRITE
+            ],
+            None
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read entries"
+            ));
+        }
+
+        is_private = None;
+        owner = initiator.user.id;
+    }
+
+    // a text entry that has been removed from the entry is only left in its
+    // revisions so the last one is used
+    let Some(record) = conn.query_opt(
+        "\
+        select text_entry_revisions.private \
+        from text_entry_revisions \
+        join entries on text_entry_revisions.entry = entries.id \
+        where text_entry_revisions.text_entry = $1 and \
+              entries.id = $2 and \
+              entries.owner = $3 \
+        order by text_entry_revisions.created desc, \
+                 text_entry_revisions.id desc \
+        limit 1",
+        &[&path.text_id, &path.entry_id, &owner]
+    ).await? else {
+        return Err(error::build::text_entry_not_found(&path.text_id));
+    };
+
+    let private: bool = record.get(0);
+
+    if private && is_private == Some(false) {
+        return Err(error::build::text_entry_not_found(&path.text_id));
+    }
+
+    Ok(is_private)
+}
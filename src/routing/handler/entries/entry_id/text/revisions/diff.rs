@@ -0,0 +1,77 @@
+//! handling diffs between revisions of text entries
+
+use actix_web::{web, http, HttpRequest, Responder};
+use serde::Deserialize;
+
+use crate::db::tables::text_entry_revisions;
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::security::{self, InitiatorLookup};
+use crate::routing;
+use crate::components::entries::schema;
+use crate::util;
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    from: i32,
+    to: i32,
+}
+
+/// creates a word level diff between two revisions of a text entry
+///
+/// GET /entries/{entry_id}/text/{text_id}/revisions/diff?from={id}&to={id}
+/// GET /users/{user_id}/entries/{entry_id}/text/{text_id}/revisions/diff?from={id}&to={id}
+///
+/// the changes are a list of runs of text that were either kept, added, or
+/// removed going from one revision to the other
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryTextPath>,
+    info: web::Query<DiffQuery>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let info = info.into_inner();
+    let conn = &*db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let is_private = super::super::check_text_access(conn, &initiator, &path).await?;
+
+    let mut revisions = Vec::with_capacity(2);
+
+    for id in [info.from, info.to] {
+        let Some(revision) = text_entry_revisions::find_from_id(conn, &path.text_id, &id).await? else {
+            return Err(error::build::text_entry_revision_not_found(&id));
+        };
+
+        if revision.private && is_private == Some(false) {
+            return Err(error::build::text_entry_revision_not_found(&id));
+        }
+
+        revisions.push(revision);
+    }
+
+    let to = revisions.pop().unwrap();
+    let from = revisions.pop().unwrap();
+    let changes = util::diff::words(&from.thought, &to.thought);
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(schema::TextRevisionDiff {
+            from,
+            to,
+            changes,
+        }))
+}
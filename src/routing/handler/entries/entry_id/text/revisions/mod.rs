@@ -0,0 +1,48 @@
+//! handling revisions of text entries
+
+use actix_web::{web, http, HttpRequest, Responder};
+
+use crate::db::tables::text_entry_revisions;
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::security::{self, InitiatorLookup};
+use crate::routing;
+
+pub mod diff;
+
+/// retrieves the revisions of a text entry
+///
+/// GET /entries/{entry_id}/text/{text_id}/revisions
+/// GET /users/{user_id}/entries/{entry_id}/text/{text_id}/revisions
+///
+/// returns every version of the text entry oldest first with when it was
+/// created and who made the edit. private revisions are not included when
+/// viewing another users entries
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryTextPath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = &*db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let is_private = super::check_text_access(conn, &initiator, &path).await?;
+    let revisions = text_entry_revisions::find_from_text_entry(conn, &path.text_id, &is_private).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(revisions))
+}
@@ -0,0 +1,232 @@
+//! handles working with video entries on a singular basis
+
+use std::str::FromStr;
+
+use actix_web::{web, http, HttpRequest, Responder};
+use actix_web::http::header::EntityTag;
+use serde::Deserialize;
+
+use crate::db::tables::{entries, video_entries, permissions};
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::state;
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::components::{self, media::Released};
+use crate::util;
+use crate::routing;
+
+/// streams a single video with the given entry and video id
+///
+/// GET /entries/{entry_id}/video/{video_id}
+/// GET /users/{user_id}/entries/{entry_id}/video/{video_id}
+///
+/// range requests are supported so clients can seek without downloading the
+/// whole file
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryVideoPath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let conn = db.get_conn().await?;
+    let accept_html = response::try_check_if_html_req(&req);
+    let lookup = InitiatorLookup::from_request(&security, &*conn, &req).await?;
+
+    if accept_html {
+        let redirect_to = format!("/entries/{}", path.entry_id);
+
+        return if lookup.is_some() {
+            Ok(response::redirect_to_path(redirect_to.as_str()))
+        } else {
+            Ok(response::redirect_to_login_with(redirect_to.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+    let owner: i32;
+    let mut is_private = None::<bool>;
+
+    if let Some(user_id) = path.user_id {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::USERS_ENTRIES,
+            &[permissions::abilities::READ],
+            Some(&user_id)
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read this users video entries"
+            ));
+        }
+
+        owner = user_id;
+        is_private = Some(false);
+    } else {
+        if !security::permissions::has_permission(
+            &*conn,
+            &initiator.user.id,
+            permissions::rolls::ENTRIES,
+            &[
+                permissions::abilities::READ,
+                permissions::abilities::READ_WRITE,
+            ],
+            None
+        ).await? {
+            return Err(error::build::permission_denied(
+                "you do not have permission to read video entries"
+            ));
+        }
+
+        owner = initiator.user.id;
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &owner, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let Some(video_entry) = video_entries::find_from_id(
+        &*conn,
+        &path.entry_id,
+        &path.video_id,
+        &is_private
+    ).await? else {
+        return Err(error::build::video_entry_not_found(&path.video_id));
+    };
+
+    let mime = {
+        let known = format!("{}/{}", video_entry.mime_type, video_entry.mime_subtype);
+
+        mime::Mime::from_str(&known)?
+    };
+
+    let etag = match &video_entry.blob {
+        Some(hash) => EntityTag::new_strong(hash.clone()),
+        None => EntityTag::new_strong(format!("{}-{}", video_entry.id, video_entry.file_size))
+    };
+
+    response::file::respond_object(
+        &req,
+        storage.backend(),
+        &components::media::video_key(&storage, &owner, &video_entry),
+        video_entry.blob.as_deref(),
+        mime,
+        etag
+    ).await
+}
+
+#[derive(Deserialize)]
+pub struct PutVideoEntry {
+    private: bool,
+    comment: Option<String>,
+}
+
+/// updates the details of a single video
+///
+/// PUT /entries/{entry_id}/video/{video_id}
+pub async fn handle_put(
+    initiator: Initiator,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryVideoPath>,
+    posted: web::Json<PutVideoEntry>
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let posted = posted.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to update video entries"
+        ));
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &initiator.user.id, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let comment = util::string::trimmed_optional_string(posted.comment);
+    let transaction = conn.transaction().await?;
+    let result = transaction.execute(
+        "\
+        update video_entries \
+        set private = $3, \
+            comment = $4 \
+        where id = $1 and entry = $2",
+        &[&path.video_id, &path.entry_id, &posted.private, &comment]
+    ).await?;
+
+    if result == 0 {
+        return Err(error::build::video_entry_not_found(&path.video_id));
+    }
+
+    components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+    transaction.commit().await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(None::<()>)
+}
+
+/// deletes a single video from an entry
+///
+/// DELETE /entries/{entry_id}/video/{video_id}
+pub async fn handle_delete(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+    path: web::Path<routing::path::params::EntryVideoPath>,
+) -> error::Result<impl Responder> {
+    let path = path.into_inner();
+    let mut conn = db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to delete video entries"
+        ));
+    }
+
+    let Some(_entry) = entries::from_user_and_id(&*conn, &initiator.user.id, &path.entry_id).await? else {
+        return Err(error::build::entry_not_found(&path.entry_id));
+    };
+
+    let transaction = conn.transaction().await?;
+    let Some(record) = transaction.query_opt(
+        "delete from video_entries where id = $1 and entry = $2 returning mime_subtype, blob",
+        &[&path.video_id, &path.entry_id]
+    ).await? else {
+        return Err(error::build::video_entry_not_found(&path.video_id));
+    };
+    components::entries::bump_version(&transaction, &path.entry_id).await?;
+
+    let mime_subtype: String = record.get(0);
+    let removal = components::media::release(&transaction, &storage, &initiator.user.id, vec![Released::video(
+        &storage,
+        &initiator.user.id,
+        &path.entry_id,
+        &path.video_id,
+        &mime_subtype,
+        record.get(1)
+    )]).await?;
+
+    components::files::commit_removing(transaction, &storage, removal).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .set_message("video entry deleted")
+        .build_empty()
+}
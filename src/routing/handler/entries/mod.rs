@@ -4,7 +4,7 @@ use std::fmt::Write;
 //use std::pin::{Pin};
 //use std::task::{Context, Poll};
 
-use actix_web::{web, http, HttpRequest, Responder};
+use actix_web::{web, http, HttpRequest, HttpResponse, Responder};
 use serde::Deserialize;
 use chrono::{DateTime, Utc};
 use chrono::serde::ts_seconds;
@@ -13,12 +13,17 @@ use tokio_postgres::GenericClient;
 use futures::future;
 
 pub mod entry_id;
+pub mod trash;
+pub mod batch;
 
 use crate::db::{
     self,
+    filter::{EntriesCursor, CursorDirection},
     tables::{
         permissions,
         custom_field_entries,
+        text_entry_revisions,
+        entry_templates,
     },
 };
 use crate::net::http::{error, response::{self, json::JsonBuilder}};
@@ -27,6 +32,7 @@ use crate::state;
 use crate::security::{self, InitiatorLookup, Initiator};
 use crate::components::{self, entries::schema};
 use crate::template;
+use crate::util;
 
 #[derive(Deserialize)]
 pub struct EntriesQuery {
@@ -35,17 +41,30 @@ pub struct EntriesQuery {
     tags: Option<String>,
     from_marker: Option<i32>,
     to_marker: Option<i32>,
+    fields: Option<String>,
+    has_audio: Option<bool>,
+    has_text: Option<bool>,
+    q: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
 }
 
-/// retrieves entry date from marker id
-pub async fn get_marker_date(
+/// the max number of entries that can be requested in a single page
+const MAX_LIMIT: i64 = 1000;
+
+/// retrieves the day and id of the entry a marker is attached to
+///
+/// multiple entries can share the same day so the entry id is needed to
+/// know exactly where the marker is
+pub async fn get_marker_entry(
     conn: &impl GenericClient,
     owner: &i32,
     marker: &i32,
-) -> error::Result<Option<DateTime<Utc>>> {
+) -> error::Result<Option<(DateTime<Utc>, i32)>> {
     let marker_check = conn.query(
         "\
-        select entries.day \
+        select entries.day, \
+               entries.id \
         from entries \
         join entry_markers on entries.id = entry_markers.entry \
         where entry_markers.id = $1 and entries.owner = $2",
@@ -55,7 +74,7 @@ pub async fn get_marker_date(
     if marker_check.is_empty() {
         Ok(None)
     } else {
-        Ok(Some(marker_check[0].get::<usize, DateTime<Utc>>(0)))
+        Ok(Some((marker_check[0].get(0), marker_check[0].get(1))))
     }
 }
 
@@ -68,6 +87,26 @@ pub async fn get_marker_date(
 /// available and allowed entries for the current user from the session. if
 /// attempting to access another users entries auth checks will be performed
 /// to see if they are allowed to view this information.
+///
+/// available filters are given as query params
+///  - `from` / `to` dates or `from_marker` / `to_marker` marker ids
+///  - `tags` expression of tag ids. example: `(1 and 2) or not 3`
+///  - `fields` comma separated custom field filters. example: `3>5,4.low>=2`
+///  - `has_audio` / `has_text` booleans
+///  - `q` full text search
+///
+/// if `q` is given then only entries with text entries matching the search
+/// will be returned, ordered by rank with highlighted snippets of the matched
+/// text entries. private text entries are not searched when viewing another
+/// users entries.
+///
+/// results can be paged with `limit` and `cursor`. entries are ordered by day
+/// and id newest first and the response will contain `cursor.next` and
+/// `cursor.prev` when there are more entries in either direction. if
+/// searching, entries are ordered by rank before day and id across all pages
+/// and a cursor is only valid for the search it was created with.
+///
+/// entries in the trash are not included. see [trash::handle_get]
 pub async fn handle_get(
     req: HttpRequest,
     security: security::state::WebSecurityState,
@@ -76,6 +115,21 @@ pub async fn handle_get(
     info: web::Query<EntriesQuery>,
     path: web::Path<routing::path::params::OptUserPath>,
 ) -> error::Result<impl Responder> {
+    list_entries(req, security, db, template, info, path, false).await
+}
+
+/// lists either the active or trashed entries for a user
+///
+/// shared between [handle_get] and [trash::handle_get]
+pub(crate) async fn list_entries(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    template: template::WebTemplateState<'_>,
+    info: web::Query<EntriesQuery>,
+    path: web::Path<routing::path::params::OptUserPath>,
+    trashed: bool,
+) -> error::Result<HttpResponse> {
     let info = info.into_inner();
     let pool_conn = db.pool.get().await?;
     let accept_html = response::try_check_if_html_req(&req);
@@ -128,158 +182,358 @@ pub async fn handle_get(
         owner = initiator.user.id;
     }
 
-    let mut results = {
-        let mut entries_where: String = String::new();
+    let mut filter = db::filter::EntriesFilter::new(owner);
+    filter.is_private = is_private;
+    filter.trashed = trashed;
+
+    if let Some(from_marker) = info.from_marker {
+        let Some(position) = get_marker_entry(&*pool_conn, &owner, &from_marker).await? else {
+            return Err(error::build::bad_request(
+                format!("from makrer id given does not exist: {}", from_marker)
+            ));
+        };
+
+        filter.from_entry = Some(position);
+    } else {
+        filter.from = routing::query::get_date(&info.from)?;
+    }
+
+    if let Some(to_marker) = info.to_marker {
+        let Some(position) = get_marker_entry(&*pool_conn, &owner, &to_marker).await? else {
+            return Err(error::build::bad_request(
+                format!("to marker id given does not exist: {}", to_marker)
+            ));
+        };
 
-        write!(&mut entries_where, "entries.owner = {}", owner)?;
+        filter.to_entry = Some(position);
+    } else {
+        filter.to = routing::query::get_date(&info.to)?;
+    }
 
-        if let Some(from_marker) = info.from_marker {
-            let Some(day) = get_marker_date(&*pool_conn, &owner, &from_marker).await? else {
-                return Err(error::build::bad_request(
-                    format!("from makrer id given does not exist: {}", from_marker)
-                ));
-            };
+    filter.tags = routing::query::get_tag_expr(&info.tags)?;
+    filter.fields = routing::query::get_field_filters(&info.fields)?;
+    filter.has_audio = info.has_audio;
+    filter.has_text = info.has_text;
+    filter.search = util::string::trimmed_optional_string(info.q);
 
-            write!(&mut entries_where, " and entries.day >= '{}'", day.to_rfc3339())?;
-        } else if let Some(from) = routing::query::get_date(&info.from)? {
-            write!(&mut entries_where, " and entries.day >= '{}'", from.to_rfc3339())?;
+    let limit = if let Some(limit) = info.limit {
+        if limit < 1 || limit > MAX_LIMIT {
+            return Err(error::build::bad_request(
+                format!("limit must be between 1 and {}", MAX_LIMIT)
+            ));
         }
 
-        if let Some(to_marker) = info.to_marker {
-            let Some(day) = get_marker_date(&*pool_conn, &owner, &to_marker).await? else {
-                return Err(error::build::bad_request(
-                    format!("to marker id given does not exist: {}", to_marker)
-                ));
-            };
+        Some(limit)
+    } else {
+        None
+    };
+
+    if let Some(cursor) = info.cursor.as_ref() {
+        filter.cursor = Some(cursor.parse()?);
+    }
+
+    let is_prev = filter.cursor.as_ref()
+        .map(|c| c.direction == CursorDirection::Prev)
+        .unwrap_or(false);
+
+    let mut rows = {
+        let mut query_params = db::query::QueryParams::with_capacity(4);
+        let compiled = filter.compile(&mut query_params)?;
+        // fetch one extra to know if there are more entries past this page
+        let fetch_limit = limit.map(|l| l + 1);
+
+        let order = if is_prev { "asc" } else { "desc" };
+        // when searching the rank is the last column and the entries are
+        // ordered by it before the day so the best matches are on the first
+        // page
+        let mut rows_statement = if let Some(rank) = compiled.rank.as_ref() {
+            format!(
+                "\
+                select id, \
+                       day, \
+                       created, \
+                       updated, \
+                       deleted, \
+                       owner, \
+                       {1} as rank \
+                from entries \
+                where {0} \
+                order by rank {2}, day {2}, id {2}",
+                compiled.clause,
+                rank,
+                order
+            )
+        } else {
+            format!(
+                "\
+                select id, \
+                       day, \
+                       created, \
+                       updated, \
+                       deleted, \
+                       owner \
+                from entries \
+                where {} \
+                order by day {1}, id {1}",
+                compiled.clause,
+                order
+            )
+        };
 
-            write!(&mut entries_where, "and entries.day <= '{}'", day.to_rfc3339())?;
-        } else if let Some(to) = routing::query::get_date(&info.to)? {
-            write!(&mut entries_where, " and entries.day <= '{}'", to.to_rfc3339())?;
+        if let Some(fetch_limit) = fetch_limit.as_ref() {
+            write!(&mut rows_statement, " limit ${}", query_params.push(fetch_limit))?;
         }
 
-        if let Some(tags) = routing::query::get_tags(&info.tags) {
-            write!(
-                &mut entries_where,
-                " and entries.id in (select entry from entries2tags where tag in ({}))", 
-                tags.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
-            )?;
+        (*pool_conn).query(rows_statement.as_str(), query_params.slice()).await?
+    };
+
+    let has_more = if let Some(limit) = limit {
+        if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            true
+        } else {
+            false
         }
+    } else {
+        false
+    };
 
-        let rows_statement = format!(
-            "\
-            select id, \
-                   day, \
-                   created, \
-                   updated, \
-                   deleted, \
-                   owner \
-            from entries \
-            where {} \
-            order by day desc",
-            entries_where
-        );
+    if is_prev {
+        rows.reverse();
+    }
 
-        let custom_field_entries_statement = format!(
-            "\
+    let (next_cursor, prev_cursor) = if rows.is_empty() {
+        (None, None)
+    } else {
+        let first = rows.first().unwrap();
+        let last = rows.last().unwrap();
+        let has_next = if is_prev { true } else { has_more };
+        let has_prev = if is_prev { has_more } else { filter.cursor.is_some() };
+        let cursor_for = |direction: CursorDirection, row: &tokio_postgres::Row| {
+            if filter.search.is_some() {
+                EntriesCursor::with_rank(direction, row.get(6), row.get(1), row.get(0))
+            } else {
+                EntriesCursor::new(direction, row.get(1), row.get(0))
+            }
+        };
+
+        (
+            if has_next {
+                Some(cursor_for(CursorDirection::Next, last).encode())
+            } else {
+                None
+            },
+            if has_prev {
+                Some(cursor_for(CursorDirection::Prev, first).encode())
+            } else {
+                None
+            }
+        )
+    };
+
+    let page_ids: Vec<i32> = rows.iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    // the companion queries only look at entries in the current page and
+    // must be in the same order as the rows for the merge below to work. the
+    // rows are not always in day order when searching so the position in the
+    // page ids is used
+    let mut results = {
+        let private_text = if let Some(is_private) = is_private {
+            format!(" and text_entries.private = {}", if is_private { "true" } else { "false" })
+        } else {
+            String::new()
+        };
+        let private_audio = if let Some(is_private) = is_private {
+            format!(" and audio_entries.private = {}", if is_private { "true" } else { "false" })
+        } else {
+            String::new()
+        };
+        let private_images = if let Some(is_private) = is_private {
+            format!(" and image_entries.private = {}", if is_private { "true" } else { "false" })
+        } else {
+            String::new()
+        };
+        let private_video = if let Some(is_private) = is_private {
+            format!(" and video_entries.private = {}", if is_private { "true" } else { "false" })
+        } else {
+            String::new()
+        };
+        let private_files = if let Some(is_private) = is_private {
+            format!(" and entry_files.private = {}", if is_private { "true" } else { "false" })
+        } else {
+            String::new()
+        };
+
+        let custom_field_entries_statement = "\
             select custom_field_entries.field, \
                    custom_field_entries.value, \
                    custom_field_entries.entry \
             from custom_field_entries \
             join entries on custom_field_entries.entry = entries.id \
             join custom_fields on custom_field_entries.field = custom_fields.id \
-            where {} \
-            order by entries.day desc, custom_fields.\"order\"",
-            entries_where
-        );
+            where entries.id = any($1) \
+            order by array_position($1, entries.id), custom_fields.\"order\"";
 
-        let entry_markers_statement = format!(
-            "\
+        let entry_markers_statement = "\
             select entry_markers.id, \
                    entry_markers.title, \
                    entry_markers.entry \
             from entry_markers \
             join entries on entry_markers.entry = entries.id \
-            where {} \
-            order by entries.day desc, entry_markers.id",
-            entries_where
-        );
+            where entries.id = any($1) \
+            order by array_position($1, entries.id), entry_markers.id";
 
-        let tags_statement = format!(
-            "\
+        let tags_statement = "\
             select entries2tags.tag, \
                    entries2tags.entry \
             from entries2tags \
             join entries on entries2tags.entry = entries.id \
-            where {} \
-            order by entries.day desc",
-            entries_where
-        );
+            where entries.id = any($1) \
+            order by array_position($1, entries.id)";
 
+        let text_entries_statement = format!(
+            "\
+            select text_entries.entry, \
+                   count(text_entries.id) \
+            from text_entries \
+            join entries on text_entries.entry = entries.id \
+            where entries.id = any($1){} \
+            group by text_entries.entry, entries.day, entries.id \
+            order by array_position($1, entries.id)",
+            private_text
+        );
 
-        let text_entries_statement = {
-            let mut query_str = format!(
-                "\
-                select text_entries.entry, \
-                       count(text_entries.id) \
-                from text_entries \
-                join entries on text_entries.entry = entries.id \
-                where {}",
-                entries_where
-            );
-
-            if let Some(is_private) = is_private {
-                write!(&mut query_str, " and text_entries.private = {}", if is_private { "true" } else { "false" })?;
-            }
-
-            write!(
-                &mut query_str,
-                " \
-                group by text_entries.entry \
-                order by entries.day desc, text_entries.id"
-            )?;
-
-            query_str
-        };
+        let audio_entries_statement = format!(
+            "\
+            select audio_entries.entry, \
+                   count(audio_entries.id) \
+            from audio_entries \
+            join entries on audio_entries.entry = entries.id \
+            where entries.id = any($1){} \
+            group by audio_entries.entry, entries.day, entries.id \
+            order by array_position($1, entries.id)",
+            private_audio
+        );
 
-        let audio_entries_statement = {
-            let mut query_str = format!(
-                "\
-                select audio_entries.entry, \
-                       count(audio_entries.id) \
-                from audio_entries \
-                join entries on audio_entries.entry = entries.id \
-                where {}",
-                entries_where
-            );
-
-            if let Some(is_private) = is_private {
-                write!(&mut query_str, " and audio_entries.private = {}", if is_private { "true" } else { "false" })?;
-            }
+        let image_entries_statement = format!(
+            "\
+            select image_entries.entry, \
+                   count(image_entries.id) \
+            from image_entries \
+            join entries on image_entries.entry = entries.id \
+            where entries.id = any($1){} \
+            group by image_entries.entry, entries.day, entries.id \
+            order by array_position($1, entries.id)",
+            private_images
+        );
 
-            write!(
-                &mut query_str,
-                " \
-                group by audio_entries.entry \
-                order by entries.day desc"
-            )?;
+        let video_entries_statement = format!(
+            "\
+            select video_entries.entry, \
+                   count(video_entries.id) \
+            from video_entries \
+            join entries on video_entries.entry = entries.id \
+            where entries.id = any($1){} \
+            group by video_entries.entry, entries.day, entries.id \
+            order by array_position($1, entries.id)",
+            private_video
+        );
 
-            query_str
-        };
+        let entry_files_statement = format!(
+            "\
+            select entry_files.entry, \
+                   count(entry_files.id) \
+            from entry_files \
+            join entries on entry_files.entry = entries.id \
+            where entries.id = any($1){} \
+            group by entry_files.entry, entries.day, entries.id \
+            order by array_position($1, entries.id)",
+            private_files
+        );
 
-        // video entries
+        // the thought is escaped before the headline is created so the only
+        // markup in a snippet is the <mark> around each match
+        let search_statement = format!(
+            "\
+            select text_entries.entry, \
+                   text_entries.id, \
+                   ts_rank(text_entries.thought_search, websearch_to_tsquery('english', $2)), \
+                   ts_headline(\
+                       'english', \
+                       replace(replace(replace(replace(replace(\
+                           text_entries.thought, \
+                           '&', '&amp;'), \
+                           '<', '&lt;'), \
+                           '>', '&gt;'), \
+                           '\"', '&quot;'), \
+                           '''', '&#39;'), \
+                       websearch_to_tsquery('english', $2), \
+                       'StartSel=<mark>, StopSel=</mark>, MaxFragments=3'\
+                   ) \
+            from text_entries \
+            join entries on text_entries.entry = entries.id \
+            where entries.id = any($1) and \
+                  text_entries.thought_search @@ websearch_to_tsquery('english', $2){} \
+            order by array_position($1, entries.id), text_entries.id",
+            private_text
+        );
 
-        // files
+        let mut queries = vec![
+            (*pool_conn).query(custom_field_entries_statement, &[&page_ids]),
+            (*pool_conn).query(entry_markers_statement, &[&page_ids]),
+            (*pool_conn).query(tags_statement, &[&page_ids]),
+            (*pool_conn).query(text_entries_statement.as_str(), &[&page_ids]),
+            (*pool_conn).query(audio_entries_statement.as_str(), &[&page_ids]),
+            (*pool_conn).query(image_entries_statement.as_str(), &[&page_ids]),
+            (*pool_conn).query(video_entries_statement.as_str(), &[&page_ids]),
+            (*pool_conn).query(entry_files_statement.as_str(), &[&page_ids]),
+        ];
+
+        if let Some(q) = filter.search.as_ref() {
+            queries.push((*pool_conn).query(search_statement.as_str(), &[&page_ids, q]));
+        }
 
-        future::try_join_all(vec![
-            (*pool_conn).query(rows_statement.as_str(), &[]),
-            (*pool_conn).query(custom_field_entries_statement.as_str(), &[]),
-            (*pool_conn).query(entry_markers_statement.as_str(), &[]),
-            (*pool_conn).query(tags_statement.as_str(), &[]),
-            (*pool_conn).query(text_entries_statement.as_str(), &[]),
-            (*pool_conn).query(audio_entries_statement.as_str(), &[]),
-        ]).await?
+        future::try_join_all(queries).await?
     };
 
+    let search_results = if filter.search.is_some() {
+        results.pop()
+            .unwrap()
+    } else {
+        Vec::new()
+    };
+    let mut search_iter = search_results.iter()
+        .map(|row| (
+            row.get::<usize, i32>(0),
+            schema::ListSearchMatch {
+                id: row.get(1),
+                rank: row.get(2),
+                snippet: row.get(3),
+            }
+        ));
+    let files = results.pop()
+        .unwrap();
+    let mut files_iter = files.iter()
+        .map(|row| (
+            row.get::<usize, i32>(0),
+            row.get::<usize, i64>(1)
+        ));
+    let video = results.pop()
+        .unwrap();
+    let mut video_iter = video.iter()
+        .map(|row| (
+            row.get::<usize, i32>(0),
+            row.get::<usize, i64>(1)
+        ));
+    let images = results.pop()
+        .unwrap();
+    let mut images_iter = images.iter()
+        .map(|row| (
+            row.get::<usize, i32>(0),
+            row.get::<usize, i64>(1)
+        ));
     let audio = results.pop()
         .unwrap();
     let mut audio_iter = audio.iter()
@@ -322,8 +576,6 @@ pub async fn handle_get(
                 value: serde_json::from_value(row.get(1)).unwrap(),
             }
         ));
-    let rows = results.pop()
-        .unwrap();
     let rows_iter = rows.iter()
         .map(|row| schema::ListEntry {
             id: row.get(0),
@@ -337,22 +589,32 @@ pub async fn handle_get(
             fields: Vec::new(),
             text: 0,
             audio: 0,
+            images: 0,
             video: 0,
             files: 0,
+            search: None,
         });
 
     let mut rtn = Vec::with_capacity(rows.len());
 
     let mut audio_done = false;
+    let mut images_done = false;
+    let mut video_done = false;
+    let mut files_done = false;
     let mut fields_done = false;
     let mut markers_done = false;
     let mut text_done = false;
     let mut tags_done = false;
+    let mut search_done = false;
     let mut next_audio_count: Option<(i32, i64)> = None;
+    let mut next_images_count: Option<(i32, i64)> = None;
+    let mut next_video_count: Option<(i32, i64)> = None;
+    let mut next_files_count: Option<(i32, i64)> = None;
     let mut next_text_count: Option<(i32, i64)> = None;
     let mut next_custom_field_entry: Option<(i32, schema::ListCustomField)> = None;
     let mut next_entry_marker: Option<(i32, schema::ListMarker)> = None;
     let mut next_tag: Option<(i32, i32)> = None;
+    let mut next_search_match: Option<(i32, schema::ListSearchMatch)> = None;
 
     for mut row in rows_iter {
         if let Some(refer) = next_audio_count.as_ref() {
@@ -374,6 +636,63 @@ pub async fn handle_get(
             }
         }
 
+        if let Some(refer) = next_images_count.as_ref() {
+            if refer.0 == row.id {
+                let taken = next_images_count.take().unwrap();
+                row.images = taken.1;
+            }
+        }
+
+        if !images_done && next_images_count.is_none() {
+            if let Some(count) = images_iter.next() {
+                if count.0 == row.id {
+                    row.images = count.1;
+                } else {
+                    next_images_count = Some(count);
+                }
+            } else {
+                images_done = true;
+            }
+        }
+
+        if let Some(refer) = next_video_count.as_ref() {
+            if refer.0 == row.id {
+                let taken = next_video_count.take().unwrap();
+                row.video = taken.1;
+            }
+        }
+
+        if !video_done && next_video_count.is_none() {
+            if let Some(count) = video_iter.next() {
+                if count.0 == row.id {
+                    row.video = count.1;
+                } else {
+                    next_video_count = Some(count);
+                }
+            } else {
+                video_done = true;
+            }
+        }
+
+        if let Some(refer) = next_files_count.as_ref() {
+            if refer.0 == row.id {
+                let taken = next_files_count.take().unwrap();
+                row.files = taken.1;
+            }
+        }
+
+        if !files_done && next_files_count.is_none() {
+            if let Some(count) = files_iter.next() {
+                if count.0 == row.id {
+                    row.files = count.1;
+                } else {
+                    next_files_count = Some(count);
+                }
+            } else {
+                files_done = true;
+            }
+        }
+
         if let Some(refer) = next_text_count.as_ref() {
             if refer.0 == row.id {
                 let taken = next_text_count.take().unwrap();
@@ -461,10 +780,42 @@ pub async fn handle_get(
             }
         }
 
+        let mut matches = Vec::new();
+
+        if let Some(refer) = next_search_match.as_ref() {
+            if refer.0 == row.id {
+                matches.push(next_search_match.take().unwrap().1);
+            }
+        }
+
+        if !search_done && next_search_match.is_none() {
+            loop {
+                if let Some(tup) = search_iter.next() {
+                    if tup.0 == row.id {
+                        matches.push(tup.1);
+                    } else {
+                        next_search_match = Some(tup);
+                        break;
+                    }
+                } else {
+                    search_done = true;
+                    break;
+                }
+            }
+        }
+
+        if !matches.is_empty() {
+            let rank = matches.iter()
+                .fold(0f32, |max, m| if m.rank > max { m.rank } else { max });
+
+            row.search = Some(schema::ListSearch { rank, matches });
+        }
+
         rtn.push(row);
     }
 
     JsonBuilder::new(http::StatusCode::OK)
+        .set_cursor(next_cursor, prev_cursor)
         .build(Some(rtn))
 }
 
@@ -499,7 +850,46 @@ pub struct PostEntryJson {
     tags: Option<Vec<i32>>,
     custom_field_entries: Option<Vec<PostCustomFieldEntryJson>>,
     text_entries: Option<Vec<PostTextEntryJson>>,
-    markers: Option<Vec<PostEntryMarker>>
+    markers: Option<Vec<PostEntryMarker>>,
+    template_id: Option<i32>,
+}
+
+impl PostEntryJson {
+    /// fills in the entry with the presets from a template
+    ///
+    /// tags are combined with the given tags. custom field defaults are only
+    /// used for fields that were not given and the starter text entries are
+    /// only used if no text entries were given.
+    fn apply_template(&mut self, data: entry_templates::EntryTemplateData) {
+        let tags = self.tags.get_or_insert_with(Vec::new);
+
+        for tag in data.tags {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        let fields = self.custom_field_entries.get_or_insert_with(Vec::new);
+
+        for default in data.custom_field_entries {
+            if !fields.iter().any(|f| f.field == default.field) {
+                fields.push(PostCustomFieldEntryJson {
+                    field: default.field,
+                    value: default.value,
+                    comment: default.comment,
+                });
+            }
+        }
+
+        if self.text_entries.is_none() {
+            self.text_entries = Some(data.text_entries.into_iter()
+                .map(|t| PostTextEntryJson {
+                    thought: t.thought,
+                    private: t.private,
+                })
+                .collect());
+        }
+    }
 }
 
 /// posts entries for a given user
@@ -507,7 +897,13 @@ pub struct PostEntryJson {
 /// POST /entries
 ///
 /// creates a new entry when given a date for the current user from the 
-/// session. 
+/// session. multiple entries can be created for the same day with the time
+/// of the given date used to order them.
+///
+/// if a `template_id` is given then the presets from the template are added
+/// to the entry before it is created. custom field values from the template
+/// are validated the same as any given values. tags and custom fields that
+/// were deleted since the template was saved are skipped.
 pub async fn handle_post(
     initiator: Initiator,
     db: state::WebDbState,
@@ -528,26 +924,45 @@ pub async fn handle_post(
         ));
     }
 
-    let entry_check = conn.query(
-        "select id from entries where day = $1 and owner = $2",
-        &[&posted.entry.day, &initiator.user.id]
-    ).await?;
+    let transaction = conn.transaction().await?;
+    let rtn = create_entry(&transaction, &initiator.user.id, posted).await?;
 
-    if entry_check.len() != 0 {
-        return Err(error::build::entry_exists(
-            posted.entry.day.to_string()
-        ));
+    transaction.commit().await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(rtn))
+}
+
+/// creates an entry with everything attached to it
+///
+/// expands the template if one is given. used by [handle_post] and
+/// [batch::handle_post] so both create entries the same way
+pub(crate) async fn create_entry(
+    conn: &impl GenericClient,
+    owner: &i32,
+    mut posted: PostEntryJson,
+) -> error::Result<schema::Entry> {
+    if let Some(template_id) = posted.template_id {
+        let mut entry_template = components::entry_templates::get_via_id(
+            conn,
+            &template_id,
+            owner
+        ).await?;
+
+        components::entry_templates::drop_missing(conn, owner, &mut entry_template.data).await?;
+
+        posted.apply_template(entry_template.data);
     }
 
-    let transaction = conn.transaction().await?;
     let created = Utc::now();
 
-    let result = transaction.query_one(
-        "insert into entries (day, owner, created) values ($1, $2) returning id",
-        &[&posted.entry.day, &initiator.user.id, &created]
+    let result = conn.query_one(
+        "insert into entries (day, owner, created) values ($1, $2, $3) returning id, version",
+        &[&posted.entry.day, owner, &created]
     ).await?;
 
     let entry_id: i32 = result.get(0);
+    let version: i32 = result.get(1);
 
     let mut custom_field_entries = Vec::new();
 
@@ -556,15 +971,15 @@ pub async fn handle_post(
 
         for custom_field_entry in m {
             let field = components::custom_fields::get_via_id(
-                &transaction, 
+                conn, 
                 &custom_field_entry.field, 
-                Some(&initiator.user.id)
+                Some(owner)
             ).await?;
 
             db::validation::verifiy_custom_field_entry(&field.config, &custom_field_entry.value)?;
 
             let value_json = serde_json::to_value(custom_field_entry.value.clone())?;
-            let _result = transaction.execute(
+            let _result = conn.execute(
                 "\
                 insert into custom_field_entries (field, value, comment, entry) values \
                 ($1, $2, $3, $4)",
@@ -585,11 +1000,20 @@ pub async fn handle_post(
         text_entries.reserve(t.len());
 
         for text_entry in t {
-            let result = transaction.query_one(
+            let result = conn.query_one(
                 "insert into text_entries (thought, private, entry) values ($1, $2, $3) returning id",
                 &[&text_entry.thought, &text_entry.private, &entry_id]
             ).await?;
 
+            text_entry_revisions::create(
+                conn,
+                &entry_id,
+                &result.get(0),
+                &text_entry.thought,
+                &text_entry.private,
+                owner
+            ).await?;
+
             text_entries.push(schema::Text {
                 id: result.get(0),
                 thought: text_entry.thought,
@@ -604,7 +1028,7 @@ pub async fn handle_post(
         entry_tags.reserve(tags.len());
 
         for tag_id in tags {
-            let _result = transaction.execute(
+            let _result = conn.execute(
                 "insert into entries2tags (tag, entry) values ($1, $2)",
                 &[&tag_id, &entry_id]
             ).await?;
@@ -619,7 +1043,7 @@ pub async fn handle_post(
         entry_markers.reserve(markers.len());
 
         for marker in markers {
-            let result = transaction.query_one(
+            let result = conn.query_one(
                 "\
                 insert into entry_markers (title, comment, entry) values \
                 ($1, $2, $3) \
@@ -635,20 +1059,21 @@ pub async fn handle_post(
         }
     }
 
-    transaction.commit().await?;
-
-    JsonBuilder::new(http::StatusCode::OK)
-        .build(Some(schema::Entry {
-            id: result.get(0),
-            day: posted.entry.day,
-            created,
-            updated: None,
-            deleted: None,
-            owner: initiator.user.id.clone(),
-            tags: entry_tags,
-            markers: entry_markers,
-            fields: custom_field_entries,
-            text: text_entries,
-            audio: Vec::new(),
-        }))
+    Ok(schema::Entry {
+        id: entry_id,
+        day: posted.entry.day,
+        created,
+        updated: None,
+        deleted: None,
+        owner: *owner,
+        version,
+        tags: entry_tags,
+        markers: entry_markers,
+        fields: custom_field_entries,
+        text: text_entries,
+        audio: Vec::new(),
+        images: Vec::new(),
+        videos: Vec::new(),
+        files: Vec::new(),
+    })
 }
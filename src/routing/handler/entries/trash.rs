@@ -0,0 +1,28 @@
+//! handling entries that are in the trash
+
+use actix_web::{web, HttpRequest, Responder};
+
+use crate::net::http::error;
+use crate::state;
+use crate::security;
+use crate::template;
+use crate::routing;
+
+use super::EntriesQuery;
+
+/// lists entries that are in the trash
+///
+/// GET /entries/trash
+///
+/// accepts the same filters as [super::handle_get]. trashed entries will be
+/// purged after the amount of days specified in the server config
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    template: template::WebTemplateState<'_>,
+    info: web::Query<EntriesQuery>,
+    path: web::Path<routing::path::params::OptUserPath>,
+) -> error::Result<impl Responder> {
+    super::list_entries(req, security, db, template, info, path, true).await
+}
@@ -0,0 +1,130 @@
+//! handles entry templates
+
+use actix_web::{web, http, HttpRequest, Responder};
+use serde::Deserialize;
+use chrono::Utc;
+
+pub mod template_id;
+
+use crate::db::tables::{entry_templates, permissions};
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::components;
+use crate::state;
+use crate::template;
+
+/// retrieves entry templates for the current user
+///
+/// GET /entry_templates
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    template: template::WebTemplateState<'_>,
+) -> error::Result<impl Responder> {
+    let accept_html = response::try_check_if_html_req(&req);
+    let conn = &*db.get_conn().await?;
+    let lookup = InitiatorLookup::from_request(&security, conn, &req).await?;
+
+    if accept_html {
+        return if lookup.is_some() {
+            Ok(response::respond_index_html(&template.into_inner(), Some(lookup.unwrap().user))?)
+        } else {
+            Ok(response::redirect_to_path("/auth/login?jump_to=/entry_templates"))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+
+    if !security::permissions::has_permission(
+        conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ,
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to read entry templates"
+        ));
+    }
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(entry_templates::find_from_owner(conn, &initiator.user.id).await?))
+}
+
+#[derive(Deserialize)]
+pub struct PostEntryTemplateJson {
+    name: String,
+    comment: Option<String>,
+    data: entry_templates::EntryTemplateData,
+}
+
+/// creates a new entry template
+///
+/// POST /entry_templates
+///
+/// tags and custom fields in the template must be owned by the current user
+/// and custom field defaults must be valid for their field
+pub async fn handle_post(
+    initiator: Initiator,
+    db: state::WebDbState,
+    posted: web::Json<PostEntryTemplateJson>,
+) -> error::Result<impl Responder> {
+    let conn = &*db.get_conn().await?;
+    let posted = posted.into_inner();
+
+    if !security::permissions::has_permission(
+        conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE,
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to write entry templates"
+        ));
+    }
+
+    let check = conn.query(
+        "select id from entry_templates where name = $1 and owner = $2",
+        &[&posted.name, &initiator.user.id]
+    ).await?;
+
+    if check.len() != 0 {
+        return Err(error::build::entry_template_exists(posted.name));
+    }
+
+    components::entry_templates::verify_data(conn, &initiator.user.id, &posted.data).await?;
+
+    let created = Utc::now();
+    let data_json = serde_json::to_value(posted.data.clone())?;
+    let result = conn.query_one(
+        "\
+        insert into entry_templates (name, owner, comment, data, created) values \
+        ($1, $2, $3, $4, $5) \
+        returning id",
+        &[
+            &posted.name,
+            &initiator.user.id,
+            &posted.comment,
+            &data_json,
+            &created
+        ]
+    ).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(entry_templates::EntryTemplate {
+            id: result.get(0),
+            name: posted.name,
+            owner: initiator.user.id,
+            comment: posted.comment,
+            data: posted.data,
+            created,
+            updated: None,
+        }))
+}
@@ -0,0 +1,181 @@
+//! handles working on single entry templates
+
+use actix_web::{web, http, HttpRequest, Responder};
+use serde::Deserialize;
+use chrono::Utc;
+
+use crate::db::tables::{entry_templates, permissions};
+use crate::security::{self, InitiatorLookup, Initiator};
+use crate::net::http::{error, response::{self, json::JsonBuilder}};
+use crate::components;
+use crate::state;
+use crate::template;
+use crate::routing;
+
+/// retrieves a single entry template
+///
+/// GET /entry_templates/{template_id}
+pub async fn handle_get(
+    req: HttpRequest,
+    security: security::state::WebSecurityState,
+    db: state::WebDbState,
+    template: template::WebTemplateState<'_>,
+    path: web::Path<routing::path::params::EntryTemplatePath>,
+) -> error::Result<impl Responder> {
+    let accept_html = response::try_check_if_html_req(&req);
+    let conn = &*db.get_conn().await?;
+    let lookup = InitiatorLookup::from_request(&security, conn, &req).await?;
+
+    if accept_html {
+        return if lookup.is_some() {
+            Ok(response::respond_index_html(&template.into_inner(), Some(lookup.unwrap().user))?)
+        } else {
+            let redirect = format!("/auth/login?jump_to=/entry_templates/{}", path.template_id);
+            Ok(response::redirect_to_path(redirect.as_str()))
+        }
+    }
+
+    let initiator = lookup.try_into()?;
+
+    if !security::permissions::has_permission(
+        conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ,
+            permissions::abilities::READ_WRITE,
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to read entry templates"
+        ));
+    }
+
+    let entry_template = components::entry_templates::get_via_id(
+        conn,
+        &path.template_id,
+        &initiator.user.id
+    ).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(entry_template))
+}
+
+#[derive(Deserialize)]
+pub struct PutEntryTemplateJson {
+    name: String,
+    comment: Option<String>,
+    data: entry_templates::EntryTemplateData,
+}
+
+/// updates a single entry template
+///
+/// PUT /entry_templates/{template_id}
+pub async fn handle_put(
+    initiator: Initiator,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryTemplatePath>,
+    posted: web::Json<PutEntryTemplateJson>,
+) -> error::Result<impl Responder> {
+    let conn = &*db.get_conn().await?;
+    let posted = posted.into_inner();
+
+    if !security::permissions::has_permission(
+        conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ_WRITE,
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to write entry templates"
+        ));
+    }
+
+    let original = components::entry_templates::get_via_id(
+        conn,
+        &path.template_id,
+        &initiator.user.id
+    ).await?;
+
+    let check = conn.query(
+        "select id from entry_templates where name = $1 and owner = $2 and id <> $3",
+        &[&posted.name, &initiator.user.id, &path.template_id]
+    ).await?;
+
+    if check.len() != 0 {
+        return Err(error::build::entry_template_exists(posted.name));
+    }
+
+    components::entry_templates::verify_data(conn, &initiator.user.id, &posted.data).await?;
+
+    let updated = Utc::now();
+    let data_json = serde_json::to_value(posted.data.clone())?;
+    let _result = conn.execute(
+        "\
+        update entry_templates \
+        set name = $1, \
+            comment = $2, \
+            data = $3, \
+            updated = $4 \
+        where id = $5",
+        &[
+            &posted.name,
+            &posted.comment,
+            &data_json,
+            &updated,
+            &path.template_id
+        ]
+    ).await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(entry_templates::EntryTemplate {
+            id: path.template_id,
+            name: posted.name,
+            owner: initiator.user.id,
+            comment: posted.comment,
+            data: posted.data,
+            created: original.created,
+            updated: Some(updated),
+        }))
+}
+
+/// deletes a single entry template
+///
+/// DELETE /entry_templates/{template_id}
+///
+/// entries created from the template are not affected
+pub async fn handle_delete(
+    initiator: Initiator,
+    db: state::WebDbState,
+    path: web::Path<routing::path::params::EntryTemplatePath>,
+) -> error::Result<impl Responder> {
+    let conn = &*db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[permissions::abilities::READ_WRITE],
+        None,
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to write entry templates"
+        ));
+    }
+
+    let result = conn.execute(
+        "delete from entry_templates where id = $1 and owner = $2",
+        &[&path.template_id, &initiator.user.id]
+    ).await?;
+
+    if result == 0 {
+        return Err(error::build::entry_template_not_found(&path.template_id));
+    }
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(None::<()>)
+}
@@ -13,6 +13,7 @@ use crate::state;
 pub mod ping;
 pub mod auth;
 pub mod entries;
+pub mod entry_templates;
 pub mod custom_fields;
 pub mod users;
 pub mod account;
@@ -21,6 +22,8 @@ pub mod tags;
 pub mod email;
 pub mod global;
 pub mod groups;
+pub mod sync;
+pub mod storage;
 
 /// handles root requests
 ///
@@ -0,0 +1,71 @@
+//! storage usage across all users
+
+use actix_web::{http, Responder};
+use serde::Serialize;
+
+use crate::components::quota::{self, QuotaLimit, StorageUsage};
+use crate::net::http::error;
+use crate::net::http::response::json::JsonBuilder;
+use crate::security::{self, Initiator};
+use crate::state;
+
+#[derive(Serialize)]
+pub struct UserStorageJson {
+    id: i32,
+    username: String,
+    usage: StorageUsage,
+    /// the quota of the user without their groups. None if there is no limit
+    limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct StorageOverviewJson {
+    total: StorageUsage,
+    users: Vec<UserStorageJson>,
+    groups: Vec<QuotaLimit>,
+}
+
+/// retrieves the storage usage of every user
+///
+/// GET /storage
+///
+/// only available to users that can view all users. groups has every group
+/// with a quota along with the combined usage of its members
+pub async fn handle_get(
+    initiator: Initiator,
+    db: state::WebDbState,
+    storage: state::WebStorageState,
+) -> error::Result<impl Responder> {
+    let conn = db.get_conn().await?;
+
+    if !security::permissions::can_view_all_users(&*conn, &initiator.user.id).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to view the storage usage of all users"
+        ));
+    }
+
+    let mut total = StorageUsage::default();
+    let mut users = Vec::new();
+
+    for found in quota::usage_by_user(&*conn).await? {
+        total.audio += found.usage.audio;
+        total.images += found.usage.images;
+        total.video += found.usage.video;
+        total.files += found.usage.files;
+        total.total += found.usage.total;
+
+        users.push(UserStorageJson {
+            limit: quota::user_limit(storage.quota(), &found.id),
+            id: found.id,
+            username: found.username,
+            usage: found.usage,
+        });
+    }
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(StorageOverviewJson {
+            total,
+            users,
+            groups: quota::group_usage(&*conn, storage.quota()).await?,
+        }))
+}
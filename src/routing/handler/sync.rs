@@ -0,0 +1,68 @@
+//! handles syncing changes with offline clients
+
+use std::str::FromStr;
+
+use actix_web::{web, http, Responder};
+use serde::Deserialize;
+use tokio_postgres::IsolationLevel;
+
+use crate::db::tables::permissions;
+use crate::net::http::{error, response::json::JsonBuilder};
+use crate::state;
+use crate::security::{self, Initiator};
+use crate::components::sync::{self, SyncToken};
+
+#[derive(Deserialize)]
+pub struct SyncQuery {
+    since: Option<String>,
+}
+
+/// retrieves changes made since the last sync
+///
+/// GET /sync?since=<token>
+///
+/// returns the entries, text entries, markers, comments, tags, and custom
+/// fields of the current user that changed since the token along with the ids
+/// of records that were deleted. if no token is given then every record is
+/// returned. the returned token should be given to the next request.
+pub async fn handle_get(
+    initiator: Initiator,
+    db: state::WebDbState,
+    info: web::Query<SyncQuery>,
+) -> error::Result<impl Responder> {
+    let info = info.into_inner();
+    let conn = &mut *db.get_conn().await?;
+
+    if !security::permissions::has_permission(
+        &*conn,
+        &initiator.user.id,
+        permissions::rolls::ENTRIES,
+        &[
+            permissions::abilities::READ,
+            permissions::abilities::READ_WRITE
+        ],
+        None
+    ).await? {
+        return Err(error::build::permission_denied(
+            "you do not have permission to read entries"
+        ));
+    }
+
+    let since = match info.since {
+        Some(given) => Some(SyncToken::from_str(&given)?),
+        None => None
+    };
+
+    let transaction = conn.build_transaction()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .read_only(true)
+        .start()
+        .await?;
+
+    let result = sync::collect(&transaction, &initiator.user.id, since).await?;
+
+    transaction.commit().await?;
+
+    JsonBuilder::new(http::StatusCode::OK)
+        .build(Some(result))
+}
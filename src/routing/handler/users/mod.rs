@@ -80,31 +80,13 @@ pub async fn handle_get(
 
     // first we will check to see if they just have a sweeping ability to
     // view all users. eg root admin
-    let view_all = conn.execute(
-        "\
-        with user_groups as (\
-            select group_id \
-            from group_users \
-            where users_id = $1\
-        ) \
-        select id \
-        from permissions \
-        where roll = 'users' and \
-                (ability = 'r' or ability = 'rw') and \
-                resource_table is null and \
-                resource_id is null and \
-                (\
-                    (subject_table = 'groups' and subject_id in (select group_id from user_groups)) or \
-                    (subject_table = 'users' and subject_id = $1)\
-                )",
-        &[&initiator.user.id]
-    ).await?;
+    let view_all = security::permissions::can_view_all_users(conn, &initiator.user.id).await?;
 
     // this process is probably not the most efficient especially since
     // it will send back all results and not do any paging to send back
     // smaller portions
 
-    if view_all > 0 {
+    if view_all {
         let users_rows = conn.query(
             "\
             select id, \
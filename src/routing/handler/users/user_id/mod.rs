@@ -19,6 +19,7 @@ use crate::email;
 use crate::util;
 use crate::routing::path;
 use crate::template;
+use crate::components;
 
 pub async fn handle_get(
     req: HttpRequest,
@@ -230,9 +231,16 @@ pub async fn handle_put(
         }))
 }
 
+/// deletes a user along with everything they own
+///
+/// DELETE /users/{user_id}
+///
+/// the files of the users entries are removed with the rows and are put back
+/// if the transaction fails to commit
 pub async fn handle_delete(
     initiator: Initiator,
     db: state::WebDbState,
+    storage: state::WebStorageState,
     path: web::Path<path::params::UserPath>,
 ) -> error::Result<impl Responder> {
     let conn = &mut *db.get_conn().await?;
@@ -266,8 +274,36 @@ pub async fn handle_delete(
         &[&path.user_id]
     ).await?;
 
-    let _custom_field_entries = transaction.execute(
-        "delete from custom_field_entries where entry in (select id from entries where owner = $1)",
+    let _entry_templates = transaction.execute(
+        "delete from entry_templates where owner = $1",
+        &[&path.user_id]
+    ).await?;
+
+    let mut files = components::files::Removal::default();
+
+    for row in transaction.query(
+        "select id from entries where owner = $1",
+        &[&path.user_id]
+    ).await? {
+        let entry_id: i32 = row.get(0);
+
+        files.extend(components::entries::purge(&transaction, &storage, &path.user_id, &entry_id).await?);
+    }
+
+    // blobs belong to the user so any that are left once the entries are
+    // gone are removed as well
+    let leftover: Vec<String> = transaction.query(
+        "select hash from blobs where owner = $1",
+        &[&path.user_id]
+    ).await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    files.extend(components::blobs::release(&transaction, &storage, &path.user_id, &leftover).await?);
+
+    let _entry_comments = transaction.execute(
+        "delete from entry_comments where owner = $1",
         &[&path.user_id]
     ).await?;
 
@@ -276,33 +312,45 @@ pub async fn handle_delete(
         &[&path.user_id]
     ).await?;
 
-    let _text_entries = transaction.execute(
-        "delete from text_entries where entry in (select id from entries where owner = $1)",
+    let _tags = transaction.execute(
+        "delete from tags where owner = $1",
         &[&path.user_id]
     ).await?;
 
-    let _entries2tags = transaction.execute(
-        "delete from entries2tags where entry in (select id from entries where owner = $1)",
+    let _user_sessions = transaction.execute(
+        "delete from user_sessions where owner = $1",
         &[&path.user_id]
     ).await?;
 
-    let _entry_markers = transaction.execute(
-        "delete from entry_markers where entry in (select id from entries where owner = $1)",
+    let _sync_changes = transaction.execute(
+        "delete from sync_changes where owner = $1",
         &[&path.user_id]
     ).await?;
 
-    let _entries = transaction.execute(
-        "delete from entries where owner = $1",
+    // these reference the user without cascading so the user cannot be
+    // removed while any of them are left
+    let _user_data = transaction.execute(
+        "delete from user_data where owner = $1",
         &[&path.user_id]
     ).await?;
 
-    let _tags = transaction.execute(
-        "delete from tags where owner = $1",
+    let _email_verifications = transaction.execute(
+        "delete from email_verifications where owner = $1",
         &[&path.user_id]
     ).await?;
 
-    let _user_sessions = transaction.execute(
-        "delete from user_sessions where owner = $1",
+    let _auth_otp_codes = transaction.execute(
+        "delete from auth_otp_codes where auth_otp_id in (select id from auth_otp where users_id = $1)",
+        &[&path.user_id]
+    ).await?;
+
+    let _auth_otp = transaction.execute(
+        "delete from auth_otp where users_id = $1",
+        &[&path.user_id]
+    ).await?;
+
+    let _group_users = transaction.execute(
+        "delete from group_users where users_id = $1",
         &[&path.user_id]
     ).await?;
 
@@ -311,7 +359,7 @@ pub async fn handle_delete(
         &[&path.user_id]
     ).await?;
 
-    transaction.commit().await?;
+    components::files::commit_removing(transaction, &storage, files).await?;
 
     JsonBuilder::new(http::StatusCode::OK)
         .build(None::<()>)
@@ -53,6 +53,46 @@ pub mod params {
         pub audio_id: i32,
     }
 
+    /// common params for dealing with entry files
+    ///
+    /// optionally handles user_id if possible
+    #[derive(Deserialize)]
+    pub struct EntryFilePath {
+        pub user_id: Option<i32>,
+        pub entry_id: i32,
+        pub file_id: i32,
+    }
+
+    /// common params for dealing with video entries
+    ///
+    /// optionally handles user_id if possible
+    #[derive(Deserialize)]
+    pub struct EntryVideoPath {
+        pub user_id: Option<i32>,
+        pub entry_id: i32,
+        pub video_id: i32,
+    }
+
+    /// common params for dealing with image entries
+    ///
+    /// optionally handles user_id if possible
+    #[derive(Deserialize)]
+    pub struct EntryImagePath {
+        pub user_id: Option<i32>,
+        pub entry_id: i32,
+        pub image_id: i32,
+    }
+
+    /// common params for dealing with text entries
+    ///
+    /// optionally handles user_id if possible
+    #[derive(Deserialize)]
+    pub struct EntryTextPath {
+        pub user_id: Option<i32>,
+        pub entry_id: i32,
+        pub text_id: i32,
+    }
+
     /// common params for dealing with comments for entries
     ///
     /// optionally handles user_id if possible
@@ -63,6 +103,12 @@ pub mod params {
         pub comment_id: i32,
     }
 
+    /// path params for entry templates
+    #[derive(Deserialize)]
+    pub struct EntryTemplatePath {
+        pub template_id: i32,
+    }
+
     /// path params for custom fields
     ///
     /// optionally handles user_id if possible
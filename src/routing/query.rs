@@ -1,10 +1,8 @@
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc, ParseResult, NaiveDateTime};
 
-lazy_static! {
-    static ref TAGS_REG: Regex = Regex::new(r"(\d+),?").unwrap();
-}
+use crate::db::{self, filter::{TagExpr, FieldFilter}};
 
 pub fn get_date(from: &Option<String>) -> ParseResult<Option<DateTime<Utc>>> {
     if let Some(ref_from) = from.as_ref() {
@@ -20,18 +18,22 @@ pub fn get_date(from: &Option<String>) -> ParseResult<Option<DateTime<Utc>>> {
     }
 }
 
-pub fn get_tags(tags: &Option<String>) -> Option<Vec<i32>> {
+pub fn get_tag_expr(tags: &Option<String>) -> db::error::Result<Option<TagExpr>> {
     if let Some(ref_tags) = tags.as_ref() {
-        let mut rtn: Vec<i32> = Vec::new();
-
-        for split in ref_tags.split(",") {
-            if let Ok(tag) = i32::from_str_radix(split, 10) {
-                rtn.push(tag);
-            }
+        if ref_tags.trim().is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(TagExpr::from_str(ref_tags)?))
         }
+    } else {
+        Ok(None)
+    }
+}
 
-        Some(rtn)
+pub fn get_field_filters(fields: &Option<String>) -> db::error::Result<Vec<FieldFilter>> {
+    if let Some(ref_fields) = fields.as_ref() {
+        FieldFilter::from_list(ref_fields)
     } else {
-        None
+        Ok(Vec::new())
     }
-}
\ No newline at end of file
+}
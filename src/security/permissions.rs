@@ -118,6 +118,37 @@ pub async fn has_permission(
     Ok(count != 0)
 }
 
+/// checks to see if the given user has the sweeping ability to view all
+/// users. eg root admin
+///
+/// unlike [has_permission] the permission must not be limited to a resource
+pub async fn can_view_all_users(
+    conn: &impl GenericClient,
+    users_id: &i32,
+) -> Result<bool> {
+    let count = conn.execute(
+        "\
+        with user_groups as (\
+            select group_id \
+            from group_users \
+            where users_id = $1\
+        ) \
+        select id \
+        from permissions \
+        where roll = 'users' and \
+                (ability = 'r' or ability = 'rw') and \
+                resource_table is null and \
+                resource_id is null and \
+                (\
+                    (subject_table = 'groups' and subject_id in (select group_id from user_groups)) or \
+                    (subject_table = 'users' and subject_id = $1)\
+                )",
+        &[users_id]
+    ).await?;
+
+    Ok(count != 0)
+}
+
 /// common struct for specifying a subjects permission
 /// 
 /// to be given to update_subject_permissions so the subject table and id are
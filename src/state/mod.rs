@@ -7,4 +7,6 @@ pub use server_info::*;
 mod storage;
 pub use storage::*;
 mod file_serving;
-pub use file_serving::*;
\ No newline at end of file
+pub use file_serving::*;
+mod processing;
+pub use processing::*;
\ No newline at end of file
@@ -0,0 +1,51 @@
+use actix_web::web;
+
+use crate::config::{ProcessingConfig, ProcessCommand};
+
+pub struct ProcessingState {
+    config: ProcessingConfig,
+}
+
+pub type WebProcessingState = web::Data<ProcessingState>;
+
+impl ProcessingState {
+    pub fn new(config: ProcessingConfig) -> ProcessingState {
+        ProcessingState { config }
+    }
+
+    /// true if there are any commands to run
+    pub fn is_enabled(&self) -> bool {
+        !self.config.commands.is_empty()
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.config.max_attempts
+    }
+
+    /// the longest timeout of any command
+    pub fn max_timeout(&self) -> u64 {
+        self.config.commands.iter()
+            .map(|command| command.timeout)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// the commands that should run for the given mime type
+    pub fn commands_for<'a>(
+        &'a self,
+        mime_type: &'a str,
+        mime_subtype: &'a str
+    ) -> impl Iterator<Item = &'a ProcessCommand> {
+        self.config.commands.iter()
+            .filter(move |command| command.matches(mime_type, mime_subtype))
+    }
+
+    pub fn get_command(&self, name: &str) -> Option<&ProcessCommand> {
+        self.config.commands.iter()
+            .find(|command| command.name == name)
+    }
+}
@@ -1,16 +1,23 @@
-use std::path::PathBuf;
-use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::sync::Arc;
 
 use actix_web::web;
 
-use crate::config::StorageConfig;
+use crate::config::{QuotaConfig, StorageBackendConfig, StorageConfig};
+use crate::state::DBState;
+use crate::storage::{self, Backend, ObjectWriter};
+use crate::storage::crypt::UserKeys;
+use crate::storage::keys::DbUserKeys;
+use crate::storage::tmp::TmpDir;
 
 use crate::error;
 
 pub struct StorageState {
-    dir: PathBuf,
-    tmp: PathBuf
+    tmp: TmpDir,
+    uploads: Option<PathBuf>,
+    backend: Box<dyn Backend>,
+    quota: QuotaConfig,
 }
 
 pub type WebStorageState = web::Data<StorageState>;
@@ -31,33 +38,123 @@ impl StorageState {
         Ok(())
     }
 
-    pub fn new(conf: StorageConfig) -> error::Result<StorageState> {
+    /// the database is used to look up the storage keys of users when
+    /// encryption is enabled
+    pub fn new(conf: StorageConfig, db: &DBState) -> error::Result<StorageState> {
         StorageState::check_create_dir("data", &conf.directory)?;
         StorageState::check_create_dir("tmp", &conf.temp)?;
 
+        let keys = conf.encryption.clone().map(|encryption| -> Arc<dyn UserKeys> {
+            Arc::new(DbUserKeys::new(db.pool.clone(), encryption))
+        });
+        let backend = storage::from_config(&conf, keys)?;
+        let uploads = match conf.backend {
+            StorageBackendConfig::Fs => Some(conf.directory.join("uploads")),
+            StorageBackendConfig::S3(_) => None
+        };
+
         Ok(StorageState {
-            dir: conf.directory,
-            tmp: conf.temp
+            tmp: TmpDir::new(conf.temp),
+            uploads,
+            backend,
+            quota: conf.quota,
         })
     }
 
-    pub fn get_tmp_dir_ref(&self) -> &PathBuf {
+    /// the tmp directory for creating tracked tmp files
+    pub fn tmp_dir(&self) -> &TmpDir {
         &self.tmp
     }
 
-    pub fn get_audio_file_path<E>(&self, user_id: &i32, entry_id: &i32, audio_id: &i32, extension: E) -> PathBuf
-    where
-        E: AsRef<OsStr>
-     {
-        let mut audio_file = self.dir.clone();
-        audio_file.push("users");
-        audio_file.push(user_id.to_string());
-        audio_file.push("entries");
-        audio_file.push(entry_id.to_string());
-        audio_file.push("audio");
-        audio_file.set_file_name(audio_id.to_string());
-        audio_file.set_extension(extension);
-
-        audio_file
+    /// the local directory that uploads are written to before they are hashed
+    ///
+    /// None if uploads are not kept on the local file system
+    pub fn uploads_dir(&self) -> Option<&Path> {
+        self.uploads.as_deref()
+    }
+
+    /// the storage limits for users and groups
+    pub fn quota(&self) -> &QuotaConfig {
+        &self.quota
+    }
+
+    /// the backend that audio, images, video and files are kept in
+    pub fn backend(&self) -> &dyn Backend {
+        &*self.backend
+    }
+
+    /// starts writing an object for a user
+    ///
+    /// the object is encrypted with the key of the user if encryption is
+    /// enabled
+    pub async fn writer(&self, owner: &i32, key: &str) -> std::io::Result<Box<dyn ObjectWriter>> {
+        self.backend.writer_for(*owner, key).await
+    }
+
+    /// the storage key of a blob of a user
+    ///
+    /// blobs are kept under the user they belong to and spread across
+    /// directories by the first two characters of their hash. the extension
+    /// is kept so local commands given the file can still tell what it is
+    pub fn get_blob_key(&self, user_id: &i32, hash: &str, extension: &str) -> String {
+        format!("users/{}/blobs/{}/{}.{}", user_id, hash.get(..2).unwrap_or(hash), hash, extension)
+    }
+
+    /// the storage key of the cached waveform peaks of a blob
+    pub fn get_blob_peaks_key(&self, user_id: &i32, hash: &str) -> String {
+        self.get_blob_key(user_id, hash, "peaks")
+    }
+
+    /// the storage key of the thumbnail of an image blob
+    pub fn get_blob_thumbnail_key(&self, user_id: &i32, hash: &str) -> String {
+        self.get_blob_key(user_id, hash, "thumb")
+    }
+
+    /// a new storage key to write an upload to before it has been hashed
+    pub fn get_upload_key(&self) -> String {
+        format!("uploads/{}_{:016x}", chrono::Utc::now().timestamp(), rand::random::<u64>())
+    }
+
+    /// the storage key of an audio file that has not been moved to a blob
+    ///
+    /// audio files sit directly in the entry directory which is where they
+    /// were kept before the backends were added
+    pub fn get_audio_key(&self, user_id: &i32, entry_id: &i32, audio_id: &i32, extension: &str) -> String {
+        format!("users/{}/entries/{}/{}.{}", user_id, entry_id, audio_id, extension)
+    }
+
+    /// the storage key of the cached waveform peaks of an audio file that has
+    /// not been moved to a blob
+    pub fn get_audio_peaks_key(&self, user_id: &i32, entry_id: &i32, audio_id: &i32) -> String {
+        self.get_audio_key(user_id, entry_id, audio_id, "peaks")
+    }
+
+    /// retrieves a local path for a stored audio file
+    ///
+    /// audio kept in a remote backend is downloaded to the tmp directory. the
+    /// file is checked against the hash if one is given
+    pub async fn get_local_file(&self, key: &str, hash: Option<&str>) -> std::io::Result<storage::LocalFile> {
+        storage::local_file(&*self.backend, key, hash, &self.tmp).await
+    }
+
+    /// the storage key of an image that has not been moved to a blob
+    pub fn get_image_key(&self, user_id: &i32, entry_id: &i32, image_id: &i32) -> String {
+        format!("users/{}/entries/{}/images/{}", user_id, entry_id, image_id)
+    }
+
+    /// the storage key of the thumbnail for an image that has not been moved
+    /// to a blob
+    pub fn get_image_thumbnail_key(&self, user_id: &i32, entry_id: &i32, image_id: &i32) -> String {
+        format!("{}.thumb", self.get_image_key(user_id, entry_id, image_id))
+    }
+
+    /// the storage key of a video that has not been moved to a blob
+    pub fn get_video_key(&self, user_id: &i32, entry_id: &i32, video_id: &i32, extension: &str) -> String {
+        format!("users/{}/entries/{}/video/{}.{}", user_id, entry_id, video_id, extension)
+    }
+
+    /// the storage key of an entry file that has not been moved to a blob
+    pub fn get_entry_file_key(&self, user_id: &i32, entry_id: &i32, file_id: &i32) -> String {
+        format!("users/{}/entries/{}/files/{}", user_id, entry_id, file_id)
     }
 }
\ No newline at end of file
@@ -0,0 +1,693 @@
+//! encrypting stored objects
+//!
+//! each object is encrypted with its own random data key. the data key is
+//! wrapped with the storage key of the user that wrote the object and kept in
+//! the header of the object along with the id of the user key. user keys are
+//! wrapped by a master key from the config. rotating the master key only
+//! wraps the user keys again and retiring a user key only changes the key
+//! used for new objects so nothing has to be uploaded again. blobs are never
+//! shared between users so every object is only readable with the key of the
+//! user it belongs to.
+//!
+//! the data is split into chunks that are each sealed with chacha20poly1305
+//! so objects can be decrypted as they are streamed and ranges can be read
+//! without decrypting the whole object. the nonce of each chunk includes its
+//! position and whether it is the last chunk so chunks cannot be reordered,
+//! dropped, or cut off.
+
+use std::convert::{TryFrom, TryInto};
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::web::Bytes;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, StreamExt};
+
+use super::{Backend, ByteStream, ObjectWriter};
+
+/// the length in bytes of data and user keys
+pub const KEY_LEN: usize = 32;
+
+/// marks the start of an encrypted object
+const MAGIC: &[u8; 4] = b"TSE1";
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// the random part of the chunk nonces
+const PREFIX_LEN: usize = 7;
+
+/// a key sealed with another key. the nonce followed by the sealed key
+pub const WRAPPED_KEY_LEN: usize = NONCE_LEN + KEY_LEN + TAG_LEN;
+
+/// magic, user key id, wrapped data key, and nonce prefix
+const HEADER_LEN: u64 = (MAGIC.len() + 4 + WRAPPED_KEY_LEN + PREFIX_LEN) as u64;
+
+/// the amount of data sealed in each chunk
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+const SEALED_CHUNK_SIZE: u64 = CHUNK_SIZE + TAG_LEN as u64;
+
+fn crypt_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// seals a key with another key
+pub fn wrap_key(wrapping: &[u8; KEY_LEN], key: &[u8; KEY_LEN], aad: &[u8]) -> io::Result<Vec<u8>> {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(wrapping));
+    let sealed = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: key, aad })
+        .map_err(|_| crypt_error("failed to wrap key"))?;
+
+    let mut rtn = Vec::with_capacity(WRAPPED_KEY_LEN);
+    rtn.extend_from_slice(&nonce);
+    rtn.extend_from_slice(&sealed);
+
+    Ok(rtn)
+}
+
+/// opens a key sealed with [wrap_key]
+pub fn unwrap_key(wrapping: &[u8; KEY_LEN], wrapped: &[u8], aad: &[u8]) -> io::Result<[u8; KEY_LEN]> {
+    if wrapped.len() != WRAPPED_KEY_LEN {
+        return Err(crypt_error("wrapped key has an invalid length"));
+    }
+
+    let (nonce, sealed) = wrapped.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(wrapping));
+    let opened = cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad })
+        .map_err(|_| crypt_error("failed to unwrap key"))?;
+
+    <[u8; KEY_LEN]>::try_from(opened.as_slice())
+        .map_err(|_| crypt_error("unwrapped key has an invalid length"))
+}
+
+fn chunk_nonce(prefix: &[u8; PREFIX_LEN], index: u32, last: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..PREFIX_LEN + 4].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+
+    nonce
+}
+
+/// the number of chunks in an encrypted object of the given stored size
+fn chunk_count(stored: u64) -> u64 {
+    let body = stored.saturating_sub(HEADER_LEN);
+
+    ((body + SEALED_CHUNK_SIZE - 1) / SEALED_CHUNK_SIZE).max(1)
+}
+
+/// the size of the data in an encrypted object of the given stored size
+fn plain_size(stored: u64) -> u64 {
+    stored.saturating_sub(HEADER_LEN)
+        .saturating_sub(chunk_count(stored) * TAG_LEN as u64)
+}
+
+/// the data and user keys of an object
+struct Header {
+    key_id: i32,
+    wrapped_key: Vec<u8>,
+    prefix: [u8; PREFIX_LEN],
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut rtn = Vec::with_capacity(HEADER_LEN as usize);
+        rtn.extend_from_slice(MAGIC);
+        rtn.extend_from_slice(&self.key_id.to_be_bytes());
+        rtn.extend_from_slice(&self.wrapped_key);
+        rtn.extend_from_slice(&self.prefix);
+
+        rtn
+    }
+
+    /// None if the bytes are not the start of an encrypted object
+    fn from_bytes(bytes: &[u8]) -> Option<Header> {
+        if bytes.len() < HEADER_LEN as usize || !bytes.starts_with(MAGIC) {
+            return None;
+        }
+
+        let mut offset = MAGIC.len();
+        let key_id = i32::from_be_bytes(bytes[offset..offset + 4].try_into().ok()?);
+        offset += 4;
+        let wrapped_key = bytes[offset..offset + WRAPPED_KEY_LEN].to_vec();
+        offset += WRAPPED_KEY_LEN;
+        let prefix = bytes[offset..offset + PREFIX_LEN].try_into().ok()?;
+
+        Some(Header {
+            key_id,
+            wrapped_key,
+            prefix,
+        })
+    }
+
+    /// the additional data that binds the data key to the user key
+    fn aad(&self) -> Vec<u8> {
+        let mut rtn = Vec::with_capacity(MAGIC.len() + 4);
+        rtn.extend_from_slice(MAGIC);
+        rtn.extend_from_slice(&self.key_id.to_be_bytes());
+
+        rtn
+    }
+}
+
+/// looks up the storage keys of users
+pub trait UserKeys: Send + Sync {
+    /// the user key with the given id
+    fn get(&self, id: i32) -> BoxFuture<'_, io::Result<[u8; KEY_LEN]>>;
+
+    /// the id and key that new objects for a user are written with
+    fn active(&self, owner: i32) -> BoxFuture<'_, io::Result<(i32, [u8; KEY_LEN])>>;
+}
+
+/// seals data as it is written
+struct EncryptWriter {
+    inner: Box<dyn ObjectWriter>,
+    cipher: ChaCha20Poly1305,
+    prefix: [u8; PREFIX_LEN],
+    index: u32,
+    /// data waiting for a full chunk. the last chunk is only sealed once the
+    /// writer finishes
+    buffer: Vec<u8>,
+    size: u64,
+}
+
+impl EncryptWriter {
+    fn seal(&mut self, data: &[u8], last: bool) -> io::Result<Vec<u8>> {
+        let nonce = chunk_nonce(&self.prefix, self.index, last);
+        let sealed = self.cipher.encrypt(Nonce::from_slice(&nonce), data)
+            .map_err(|_| crypt_error("failed to encrypt chunk"))?;
+
+        self.index = self.index.checked_add(1)
+            .ok_or_else(|| crypt_error("object is too large to encrypt"))?;
+
+        Ok(sealed)
+    }
+}
+
+impl ObjectWriter for EncryptWriter {
+    fn write(&mut self, chunk: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        async move {
+            self.size += chunk.len() as u64;
+            self.buffer.extend_from_slice(&chunk);
+
+            while self.buffer.len() as u64 > CHUNK_SIZE {
+                let data: Vec<u8> = self.buffer.drain(..CHUNK_SIZE as usize).collect();
+                let sealed = self.seal(&data, false)?;
+
+                self.inner.write(Bytes::from(sealed)).await?;
+            }
+
+            Ok(())
+        }.boxed()
+    }
+
+    fn finish(self: Box<Self>) -> BoxFuture<'static, io::Result<u64>> {
+        async move {
+            let mut writer = *self;
+            let data = std::mem::take(&mut writer.buffer);
+
+            let sealed = match writer.seal(&data, true) {
+                Ok(sealed) => sealed,
+                Err(err) => {
+                    writer.inner.abort().await;
+
+                    return Err(err);
+                }
+            };
+
+            if let Err(err) = writer.inner.write(Bytes::from(sealed)).await {
+                writer.inner.abort().await;
+
+                return Err(err);
+            }
+
+            writer.inner.finish().await?;
+
+            Ok(writer.size)
+        }.boxed()
+    }
+
+    fn abort(self: Box<Self>) -> BoxFuture<'static, ()> {
+        let writer = *self;
+
+        writer.inner.abort()
+    }
+}
+
+struct DecryptState {
+    stream: ByteStream,
+    cipher: ChaCha20Poly1305,
+    prefix: [u8; PREFIX_LEN],
+    index: u32,
+    /// the index of the last chunk of the object
+    last_index: u32,
+    buffer: Vec<u8>,
+    ended: bool,
+    /// data to drop from the start of the first chunk
+    skip: usize,
+    /// data left to return
+    remaining: u64,
+}
+
+/// opens sealed chunks as they are read
+fn decrypt_stream(state: DecryptState) -> ByteStream {
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if state.remaining == 0 {
+                return None;
+            }
+
+            let last = state.index == state.last_index;
+            let ready = if last {
+                state.ended
+            } else {
+                state.buffer.len() as u64 >= SEALED_CHUNK_SIZE
+            };
+
+            if ready {
+                let sealed: Vec<u8> = if last {
+                    std::mem::take(&mut state.buffer)
+                } else {
+                    state.buffer.drain(..SEALED_CHUNK_SIZE as usize).collect()
+                };
+                let nonce = chunk_nonce(&state.prefix, state.index, last);
+
+                let mut data = match state.cipher.decrypt(Nonce::from_slice(&nonce), sealed.as_slice()) {
+                    Ok(data) => data,
+                    Err(_) => {
+                        state.remaining = 0;
+
+                        return Some((Err(crypt_error("failed to decrypt stored object")), state));
+                    }
+                };
+
+                state.index += 1;
+
+                if state.skip > 0 {
+                    data.drain(..state.skip.min(data.len()));
+                    state.skip = 0;
+                }
+
+                data.truncate(state.remaining.min(data.len() as u64) as usize);
+                state.remaining -= data.len() as u64;
+
+                if data.is_empty() {
+                    continue;
+                }
+
+                return Some((Ok(Bytes::from(data)), state));
+            }
+
+            if state.ended {
+                state.remaining = 0;
+
+                return Some((Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "encrypted object ended before the requested range"
+                )), state));
+            }
+
+            match state.stream.next().await {
+                Some(Ok(bytes)) => state.buffer.extend_from_slice(&bytes),
+                Some(Err(err)) => {
+                    state.remaining = 0;
+
+                    return Some((Err(err), state));
+                },
+                None => state.ended = true
+            }
+        }
+    }).boxed()
+}
+
+/// encrypts the objects of another backend
+///
+/// objects that were stored before encryption was enabled do not start with
+/// a header and are read as they are
+pub struct EncryptedBackend {
+    inner: Box<dyn Backend>,
+    keys: Arc<dyn UserKeys>,
+}
+
+impl EncryptedBackend {
+    pub fn new(inner: Box<dyn Backend>, keys: Arc<dyn UserKeys>) -> EncryptedBackend {
+        EncryptedBackend { inner, keys }
+    }
+
+    /// the header and stored size of an object. the header is None if the
+    /// object is not encrypted
+    async fn header(&self, key: &str) -> io::Result<Option<(Option<Header>, u64)>> {
+        let Some(stored) = self.inner.size(key).await? else {
+            return Ok(None);
+        };
+
+        if stored < HEADER_LEN {
+            return Ok(Some((None, stored)));
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN as usize);
+        let mut stream = self.inner.read(key, Some(0..HEADER_LEN)).await?;
+
+        while let Some(chunk) = stream.next().await {
+            bytes.extend_from_slice(&chunk?);
+        }
+
+        Ok(Some((Header::from_bytes(&bytes), stored)))
+    }
+
+    async fn data_key(&self, header: &Header) -> io::Result<ChaCha20Poly1305> {
+        let user_key = self.keys.get(header.key_id).await?;
+        let data_key = unwrap_key(&user_key, &header.wrapped_key, &header.aad())?;
+
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&data_key)))
+    }
+
+    async fn read_decrypted(&self, key: &str, range: Option<Range<u64>>) -> io::Result<ByteStream> {
+        let Some((header, stored)) = self.header(key).await? else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("object not found: {}", key)));
+        };
+
+        let Some(header) = header else {
+            return self.inner.read(key, range).await;
+        };
+
+        let range = range.unwrap_or(0..plain_size(stored));
+
+        if range.start >= range.end {
+            return Ok(stream::empty().boxed());
+        }
+
+        let first = range.start / CHUNK_SIZE;
+        let last_needed = (range.end - 1) / CHUNK_SIZE;
+        let sealed_range = (HEADER_LEN + first * SEALED_CHUNK_SIZE)
+            ..(HEADER_LEN + (last_needed + 1) * SEALED_CHUNK_SIZE).min(stored);
+
+        Ok(decrypt_stream(DecryptState {
+            stream: self.inner.read(key, Some(sealed_range)).await?,
+            cipher: self.data_key(&header).await?,
+            prefix: header.prefix,
+            index: first as u32,
+            last_index: (chunk_count(stored) - 1) as u32,
+            buffer: Vec::new(),
+            ended: false,
+            skip: (range.start - first * CHUNK_SIZE) as usize,
+            remaining: range.end - range.start,
+        }))
+    }
+
+    async fn encrypted_writer(&self, owner: i32, key: &str) -> io::Result<Box<dyn ObjectWriter>> {
+        let (key_id, user_key) = self.keys.active(owner).await?;
+        let data_key: [u8; KEY_LEN] = rand::random();
+
+        let mut header = Header {
+            key_id,
+            wrapped_key: Vec::new(),
+            prefix: rand::random(),
+        };
+        header.wrapped_key = wrap_key(&user_key, &data_key, &header.aad())?;
+
+        let mut inner = self.inner.writer(key).await?;
+
+        if let Err(err) = inner.write(Bytes::from(header.to_bytes())).await {
+            inner.abort().await;
+
+            return Err(err);
+        }
+
+        Ok(Box::new(EncryptWriter {
+            inner,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&data_key)),
+            prefix: header.prefix,
+            index: 0,
+            buffer: Vec::with_capacity(CHUNK_SIZE as usize),
+            size: 0,
+        }))
+    }
+}
+
+impl Backend for EncryptedBackend {
+    /// objects have to be decrypted before they can be read so they are
+    /// never given as local files
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
+
+    fn size<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Option<u64>>> {
+        async move {
+            Ok(self.header(key).await?.map(|(header, stored)| match header {
+                Some(_) => plain_size(stored),
+                None => stored
+            }))
+        }.boxed()
+    }
+
+    fn read<'a>(&'a self, key: &'a str, range: Option<Range<u64>>) -> BoxFuture<'a, io::Result<ByteStream>> {
+        self.read_decrypted(key, range).boxed()
+    }
+
+    fn writer<'a>(&'a self, _key: &'a str) -> BoxFuture<'a, io::Result<Box<dyn ObjectWriter>>> {
+        futures::future::ready(Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "encrypted objects must be written for a user"
+        ))).boxed()
+    }
+
+    fn writer_for<'a>(&'a self, owner: i32, key: &'a str) -> BoxFuture<'a, io::Result<Box<dyn ObjectWriter>>> {
+        self.encrypted_writer(owner, key).boxed()
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        self.inner.rename(from, to)
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        self.inner.delete(key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    use crate::storage::fs::FsBackend;
+
+    const USER_KEY: [u8; KEY_LEN] = [7; KEY_LEN];
+
+    struct TestKeys;
+
+    impl UserKeys for TestKeys {
+        fn get(&self, id: i32) -> BoxFuture<'_, io::Result<[u8; KEY_LEN]>> {
+            futures::future::ready(if id == 1 {
+                Ok(USER_KEY)
+            } else {
+                Err(io::Error::new(io::ErrorKind::NotFound, "unknown user key"))
+            }).boxed()
+        }
+
+        fn active(&self, _owner: i32) -> BoxFuture<'_, io::Result<(i32, [u8; KEY_LEN])>> {
+            futures::future::ready(Ok((1, USER_KEY))).boxed()
+        }
+    }
+
+    fn test_backend(name: &str) -> (PathBuf, EncryptedBackend) {
+        let dir = std::env::temp_dir()
+            .join(format!("crypt_backend_{}_{}", name, std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let backend = EncryptedBackend::new(
+            Box::new(FsBackend::new(dir.clone())),
+            Arc::new(TestKeys)
+        );
+
+        (dir, backend)
+    }
+
+    /// data that differs in each chunk so misplaced chunks are noticed
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    async fn write_object(backend: &EncryptedBackend, key: &str, data: &[u8]) {
+        let mut writer = backend.writer_for(1, key).await.unwrap();
+        writer.write(Bytes::copy_from_slice(data)).await.unwrap();
+
+        assert_eq!(writer.finish().await.unwrap(), data.len() as u64);
+    }
+
+    async fn read_object(backend: &EncryptedBackend, key: &str, range: Option<Range<u64>>) -> io::Result<Vec<u8>> {
+        let mut rtn = Vec::new();
+        let mut stream = backend.read(key, range).await?;
+
+        while let Some(chunk) = stream.next().await {
+            rtn.extend_from_slice(&chunk?);
+        }
+
+        Ok(rtn)
+    }
+
+    #[test]
+    fn wrapped_key_round_trip() {
+        let key: [u8; KEY_LEN] = rand::random();
+        let wrapped = wrap_key(&USER_KEY, &key, b"aad").unwrap();
+
+        assert_eq!(wrapped.len(), WRAPPED_KEY_LEN);
+        assert_eq!(unwrap_key(&USER_KEY, &wrapped, b"aad").unwrap(), key);
+        assert!(unwrap_key(&USER_KEY, &wrapped, b"other").is_err());
+        assert!(unwrap_key(&[8; KEY_LEN], &wrapped, b"aad").is_err());
+        assert!(unwrap_key(&USER_KEY, &wrapped[1..], b"aad").is_err());
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = Header {
+            key_id: 42,
+            wrapped_key: vec![3; WRAPPED_KEY_LEN],
+            prefix: [5; PREFIX_LEN],
+        };
+        let bytes = header.to_bytes();
+
+        assert_eq!(bytes.len() as u64, HEADER_LEN);
+
+        let parsed = Header::from_bytes(&bytes).unwrap();
+
+        assert_eq!(parsed.key_id, 42);
+        assert_eq!(parsed.wrapped_key, header.wrapped_key);
+        assert_eq!(parsed.prefix, header.prefix);
+
+        assert!(Header::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        assert!(Header::from_bytes(&[0; HEADER_LEN as usize]).is_none());
+    }
+
+    #[test]
+    fn stored_sizes() {
+        assert_eq!(plain_size(HEADER_LEN + TAG_LEN as u64), 0);
+        assert_eq!(plain_size(HEADER_LEN + 10 + TAG_LEN as u64), 10);
+        assert_eq!(plain_size(HEADER_LEN + SEALED_CHUNK_SIZE), CHUNK_SIZE);
+        assert_eq!(plain_size(HEADER_LEN + SEALED_CHUNK_SIZE + 1 + TAG_LEN as u64), CHUNK_SIZE + 1);
+    }
+
+    #[test]
+    fn encrypted_object_round_trip() {
+        let (dir, backend) = test_backend("round_trip");
+        let data = test_data(CHUNK_SIZE as usize * 2 + 100);
+
+        block_on(async {
+            write_object(&backend, "objects/a", &data).await;
+
+            assert_eq!(backend.size("objects/a").await.unwrap(), Some(data.len() as u64));
+            assert_eq!(read_object(&backend, "objects/a", None).await.unwrap(), data);
+        });
+
+        let stored = std::fs::read(dir.join("objects/a")).unwrap();
+
+        assert!(stored.starts_with(MAGIC));
+        assert!(!stored.windows(64).any(|window| window == &data[..64]));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn empty_object_round_trip() {
+        let (dir, backend) = test_backend("empty");
+
+        block_on(async {
+            write_object(&backend, "objects/a", &[]).await;
+
+            assert_eq!(backend.size("objects/a").await.unwrap(), Some(0));
+            assert!(read_object(&backend, "objects/a", None).await.unwrap().is_empty());
+        });
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ranges_across_chunks() {
+        let (dir, backend) = test_backend("ranges");
+        let data = test_data(CHUNK_SIZE as usize * 3 + 10);
+        let chunk = CHUNK_SIZE as usize;
+
+        block_on(async {
+            write_object(&backend, "objects/a", &data).await;
+
+            for range in [
+                0..10,
+                chunk - 5..chunk + 5,
+                chunk..chunk * 2,
+                10..chunk * 3 + 10,
+                chunk * 3..chunk * 3 + 10,
+                5..5,
+            ] {
+                let read = read_object(&backend, "objects/a", Some(range.start as u64..range.end as u64))
+                    .await
+                    .unwrap();
+
+                assert_eq!(read, &data[range.clone()], "range {:?}", range);
+            }
+        });
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn plain_objects_are_read_as_is() {
+        let (dir, backend) = test_backend("plain");
+
+        std::fs::create_dir_all(dir.join("objects")).unwrap();
+        std::fs::write(dir.join("objects/a"), b"stored before encryption").unwrap();
+
+        block_on(async {
+            assert_eq!(backend.size("objects/a").await.unwrap(), Some(24));
+            assert_eq!(
+                read_object(&backend, "objects/a", None).await.unwrap(),
+                b"stored before encryption"
+            );
+            assert_eq!(
+                read_object(&backend, "objects/a", Some(7..13)).await.unwrap(),
+                b"before"
+            );
+        });
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn modified_objects_fail_to_read() {
+        let (dir, backend) = test_backend("modified");
+        let data = test_data(CHUNK_SIZE as usize + 100);
+
+        block_on(write_object(&backend, "objects/a", &data));
+
+        let path = dir.join("objects/a");
+        let stored = std::fs::read(&path).unwrap();
+
+        // a flipped bit in the last chunk
+        let mut flipped = stored.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &flipped).unwrap();
+
+        assert!(block_on(read_object(&backend, "objects/a", None)).is_err());
+
+        // cut off after the first chunk
+        std::fs::write(&path, &stored[..(HEADER_LEN + SEALED_CHUNK_SIZE) as usize]).unwrap();
+
+        assert!(block_on(read_object(&backend, "objects/a", None)).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn plain_writer_is_refused() {
+        let (dir, backend) = test_backend("plain_writer");
+
+        assert!(block_on(backend.writer("objects/a")).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
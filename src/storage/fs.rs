@@ -0,0 +1,289 @@
+//! keeping objects in a local directory
+
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use actix_web::web::{self, Bytes};
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, Stream, StreamExt};
+
+use super::{Backend, ByteStream, ObjectWriter};
+
+/// the max amount of data read from a file at a time
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// streams a section of a file in chunks without blocking the worker
+pub fn file_stream(file: File, remaining: u64) -> impl Stream<Item = io::Result<Bytes>> {
+    stream::try_unfold((file, remaining), |(mut file, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+
+        let (file, bytes) = web::block(move || -> io::Result<(File, Vec<u8>)> {
+            let mut buffer = vec![0; remaining.min(CHUNK_SIZE) as usize];
+            let read = io::Read::read(&mut file, &mut buffer)?;
+            buffer.truncate(read);
+
+            Ok((file, buffer))
+        })
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))??;
+
+        if bytes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file ended before the requested range"
+            ));
+        }
+
+        let remaining = remaining - bytes.len() as u64;
+
+        Ok(Some((Bytes::from(bytes), (file, remaining))))
+    })
+}
+
+/// objects kept as files under a root directory
+pub struct FsBackend {
+    root: PathBuf,
+}
+
+impl FsBackend {
+    pub fn new(root: PathBuf) -> FsBackend {
+        FsBackend { root }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        super::check_key(key)?;
+
+        let mut rtn = self.root.clone();
+        rtn.extend(key.split('/'));
+
+        Ok(rtn)
+    }
+}
+
+/// writes to a sibling file that is renamed once finished
+///
+/// the partial file is removed if the writer is dropped before it finishes
+struct FsWriter {
+    /// None once the writer has finished
+    file: Option<File>,
+    partial: PathBuf,
+    path: PathBuf,
+    size: u64,
+}
+
+impl Drop for FsWriter {
+    fn drop(&mut self) {
+        let Some(file) = self.file.take() else {
+            return;
+        };
+
+        drop(file);
+
+        if let Err(err) = std::fs::remove_file(&self.partial) {
+            if err.kind() != io::ErrorKind::NotFound {
+                log::error!("failed to remove partial file: {} {}", self.partial.display(), err);
+            }
+        }
+    }
+}
+
+impl ObjectWriter for FsWriter {
+    fn write(&mut self, chunk: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        let result = match self.file.as_mut() {
+            Some(file) => file.write_all(&chunk),
+            None => Err(io::Error::new(io::ErrorKind::Other, "writer has already finished"))
+        };
+        self.size += chunk.len() as u64;
+
+        futures::future::ready(result).boxed()
+    }
+
+    fn finish(self: Box<Self>) -> BoxFuture<'static, io::Result<u64>> {
+        let result = (|| {
+            let mut writer = *self;
+            let Some(mut file) = writer.file.take() else {
+                return Err(io::Error::new(io::ErrorKind::Other, "writer has already finished"));
+            };
+
+            if let Err(err) = file.flush() {
+                writer.file = Some(file);
+
+                return Err(err);
+            }
+
+            drop(file);
+
+            if let Err(err) = std::fs::rename(&writer.partial, &writer.path) {
+                let _ = std::fs::remove_file(&writer.partial);
+
+                return Err(err);
+            }
+
+            Ok(writer.size)
+        })();
+
+        futures::future::ready(result).boxed()
+    }
+
+    fn abort(self: Box<Self>) -> BoxFuture<'static, ()> {
+        // dropping removes the partial file
+        drop(self);
+
+        futures::future::ready(()).boxed()
+    }
+}
+
+impl Backend for FsBackend {
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        self.path(key).ok()
+    }
+
+    fn size<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Option<u64>>> {
+        let result = self.path(key).and_then(|path| match std::fs::metadata(path) {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err)
+        });
+
+        futures::future::ready(result).boxed()
+    }
+
+    fn read<'a>(&'a self, key: &'a str, range: Option<Range<u64>>) -> BoxFuture<'a, io::Result<ByteStream>> {
+        let result = self.path(key).and_then(|path| {
+            let mut file = File::open(path)?;
+
+            let length = match range {
+                Some(range) => {
+                    file.seek(SeekFrom::Start(range.start))?;
+
+                    range.end - range.start
+                },
+                None => file.metadata()?.len()
+            };
+
+            Ok(file_stream(file, length).boxed())
+        });
+
+        futures::future::ready(result).boxed()
+    }
+
+    fn writer<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Box<dyn ObjectWriter>>> {
+        let result = self.path(key).and_then(|path| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut name = path.file_name()
+                .map(|n| n.to_os_string())
+                .unwrap_or_default();
+            name.push(".partial");
+
+            let partial = path.with_file_name(name);
+            let file = File::create(&partial)?;
+
+            Ok(Box::new(FsWriter {
+                file: Some(file),
+                partial,
+                path,
+                size: 0,
+            }) as Box<dyn ObjectWriter>)
+        });
+
+        futures::future::ready(result).boxed()
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        let result = self.path(from).and_then(|from| {
+            let to = self.path(to)?;
+
+            if let Some(parent) = to.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::rename(from, to)
+        });
+
+        futures::future::ready(result).boxed()
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        let result = self.path(key).and_then(|path| match std::fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err)
+        });
+
+        futures::future::ready(result).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    fn test_backend(name: &str) -> (PathBuf, FsBackend) {
+        let dir = std::env::temp_dir()
+            .join(format!("fs_backend_{}_{}", name, std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        (dir.clone(), FsBackend::new(dir))
+    }
+
+    #[test]
+    fn finished_writer_renames_partial() {
+        let (dir, backend) = test_backend("finished");
+
+        block_on(async {
+            let mut writer = backend.writer("objects/a").await.unwrap();
+            writer.write(Bytes::from_static(b"data")).await.unwrap();
+
+            assert!(dir.join("objects/a.partial").exists());
+            assert_eq!(writer.finish().await.unwrap(), 4);
+        });
+
+        assert!(!dir.join("objects/a.partial").exists());
+        assert_eq!(std::fs::read(dir.join("objects/a")).unwrap(), b"data");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn dropped_writer_removes_partial() {
+        let (dir, backend) = test_backend("dropped");
+
+        block_on(async {
+            let mut writer = backend.writer("objects/a").await.unwrap();
+            writer.write(Bytes::from_static(b"data")).await.unwrap();
+
+            drop(writer);
+        });
+
+        assert!(!dir.join("objects/a.partial").exists());
+        assert!(!dir.join("objects/a").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn aborted_writer_removes_partial() {
+        let (dir, backend) = test_backend("aborted");
+
+        block_on(async {
+            let mut writer = backend.writer("objects/a").await.unwrap();
+            writer.write(Bytes::from_static(b"data")).await.unwrap();
+            writer.abort().await;
+        });
+
+        assert!(!dir.join("objects/a.partial").exists());
+        assert!(!dir.join("objects/a").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
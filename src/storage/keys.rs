@@ -0,0 +1,158 @@
+//! the storage keys of users kept in the database
+//!
+//! keys are created the first time something is stored for a user and are
+//! wrapped with the current master key from the config.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+
+use bb8_postgres::{PostgresConnectionManager, bb8::Pool};
+use futures::future::{BoxFuture, FutureExt};
+use tokio_postgres::NoTls;
+
+use crate::config::EncryptionConfig;
+
+use super::crypt::{self, UserKeys, KEY_LEN};
+
+/// additional data used when wrapping user keys with a master key
+pub const USER_KEY_AAD: &[u8] = b"user storage key";
+
+fn db_error<E>(err: E) -> io::Error
+where
+    E: std::fmt::Display
+{
+    io::Error::new(io::ErrorKind::Other, format!("failed to retrieve storage key: {}", err))
+}
+
+/// opens a user key with the master key it was wrapped with
+pub fn unwrap_user_key(config: &EncryptionConfig, master_key: &str, wrapped: &[u8]) -> io::Result<[u8; KEY_LEN]> {
+    let Some(master) = config.keys.get(master_key) else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("storage encryption key \"{}\" is not in the config", master_key)
+        ));
+    };
+
+    crypt::unwrap_key(&master.0, wrapped, USER_KEY_AAD)
+}
+
+/// seals a user key with the current master key
+pub fn wrap_user_key(config: &EncryptionConfig, key: &[u8; KEY_LEN]) -> io::Result<Vec<u8>> {
+    // the config checks that the current key is present
+    let master = &config.keys[&config.current];
+
+    crypt::wrap_key(&master.0, key, USER_KEY_AAD)
+}
+
+pub struct DbUserKeys {
+    pool: Pool<PostgresConnectionManager<NoTls>>,
+    config: EncryptionConfig,
+    /// unwrapped keys by id. the key itself never changes once created
+    cache: Mutex<HashMap<i32, [u8; KEY_LEN]>>,
+}
+
+impl DbUserKeys {
+    pub fn new(pool: Pool<PostgresConnectionManager<NoTls>>, config: EncryptionConfig) -> DbUserKeys {
+        DbUserKeys {
+            pool,
+            config,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached(&self, id: i32) -> Option<[u8; KEY_LEN]> {
+        self.cache.lock().ok()?.get(&id).copied()
+    }
+
+    fn remember(&self, id: i32, key: [u8; KEY_LEN]) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(id, key);
+        }
+    }
+
+    async fn lookup(&self, id: i32) -> io::Result<[u8; KEY_LEN]> {
+        if let Some(key) = self.cached(id) {
+            return Ok(key);
+        }
+
+        let conn = self.pool.get().await.map_err(db_error)?;
+        let Some(row) = conn.query_opt(
+            "select master_key, wrapped_key from user_storage_keys where id = $1",
+            &[&id]
+        ).await.map_err(db_error)? else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("storage key not found: {}", id)
+            ));
+        };
+
+        let key = unwrap_user_key(&self.config, row.get(0), row.get::<_, &[u8]>(1))?;
+
+        self.remember(id, key);
+
+        Ok(key)
+    }
+
+    async fn lookup_active(&self, owner: i32) -> io::Result<(i32, [u8; KEY_LEN])> {
+        let conn = self.pool.get().await.map_err(db_error)?;
+        let query = "\
+            select id, master_key, wrapped_key \
+            from user_storage_keys \
+            where owner = $1 and retired is null";
+
+        if let Some(row) = conn.query_opt(query, &[&owner]).await.map_err(db_error)? {
+            let id: i32 = row.get(0);
+            let key = unwrap_user_key(&self.config, row.get(1), row.get::<_, &[u8]>(2))?;
+
+            self.remember(id, key);
+
+            return Ok((id, key));
+        }
+
+        let key: [u8; KEY_LEN] = rand::random();
+        let wrapped = wrap_user_key(&self.config, &key)?;
+
+        let created = conn.query_opt(
+            "\
+            insert into user_storage_keys (owner, master_key, wrapped_key, created) \
+            values ($1, $2, $3, $4) \
+            on conflict (owner) where retired is null do nothing \
+            returning id",
+            &[&owner, &self.config.current, &wrapped, &chrono::Utc::now()]
+        ).await.map_err(db_error)?;
+
+        if let Some(row) = created {
+            let id: i32 = row.get(0);
+
+            self.remember(id, key);
+
+            return Ok((id, key));
+        }
+
+        // another request created the key first
+        let Some(row) = conn.query_opt(query, &[&owner]).await.map_err(db_error)? else {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("failed to create storage key for user: {}", owner)
+            ));
+        };
+
+        let id: i32 = row.get(0);
+        let key = unwrap_user_key(&self.config, row.get(1), row.get::<_, &[u8]>(2))?;
+
+        self.remember(id, key);
+
+        Ok((id, key))
+    }
+}
+
+impl UserKeys for DbUserKeys {
+    fn get(&self, id: i32) -> BoxFuture<'_, io::Result<[u8; KEY_LEN]>> {
+        self.lookup(id).boxed()
+    }
+
+    fn active(&self, owner: i32) -> BoxFuture<'_, io::Result<(i32, [u8; KEY_LEN])>> {
+        self.lookup_active(owner).boxed()
+    }
+}
@@ -0,0 +1,258 @@
+//! backends for keeping stored media
+//!
+//! objects are addressed by keys made of `/` separated parts such as
+//! `users/1/entries/2/3.webm`. the file system backend maps keys to paths in
+//! the storage directory so existing files keep working. the s3 backend sends
+//! them to an s3 compatible object store.
+//!
+//! uploads are written through an [ObjectWriter] as they arrive so they never
+//! need to be fully buffered or written to the tmp directory. an object is
+//! only visible once the writer has finished.
+//!
+//! objects stored by the blake3 hash of their data can be checked as they are
+//! read with [verify_stream] and [local_file].
+
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use actix_web::web::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, StreamExt};
+
+use crate::config::{StorageBackendConfig, StorageConfig};
+
+pub mod fs;
+pub mod s3;
+pub mod crypt;
+pub mod keys;
+pub mod tmp;
+
+/// the data of an object as it is read
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// writes the data of a single object
+///
+/// if the writer is dropped without calling [finish](ObjectWriter::finish)
+/// then what was written is discarded the same as
+/// [abort](ObjectWriter::abort). this covers requests that are cancelled
+/// while an upload is being streamed
+pub trait ObjectWriter: Send {
+    /// adds the given data to the end of the object
+    fn write(&mut self, chunk: Bytes) -> BoxFuture<'_, io::Result<()>>;
+
+    /// completes the object returning its total size
+    fn finish(self: Box<Self>) -> BoxFuture<'static, io::Result<u64>>;
+
+    /// discards anything that was written
+    fn abort(self: Box<Self>) -> BoxFuture<'static, ()>;
+}
+
+/// a place to keep stored objects
+pub trait Backend: Send + Sync {
+    /// the path of an object if it is kept on the local file system
+    fn local_path(&self, key: &str) -> Option<PathBuf>;
+
+    /// the size of an object or None if it does not exist
+    fn size<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Option<u64>>>;
+
+    /// reads an object or part of one
+    ///
+    /// the range is expected to be within the size of the object
+    fn read<'a>(&'a self, key: &'a str, range: Option<Range<u64>>) -> BoxFuture<'a, io::Result<ByteStream>>;
+
+    /// starts writing an object replacing any object with the same key once
+    /// it has finished
+    fn writer<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Box<dyn ObjectWriter>>>;
+
+    /// starts writing an object on behalf of a user
+    ///
+    /// backends that encrypt objects use the key of the user. others write
+    /// the object the same as [writer](Backend::writer)
+    fn writer_for<'a>(&'a self, owner: i32, key: &'a str) -> BoxFuture<'a, io::Result<Box<dyn ObjectWriter>>> {
+        let _ = owner;
+
+        self.writer(key)
+    }
+
+    /// moves an object to a new key
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, io::Result<()>>;
+
+    /// removes an object. an object that does not exist is not an error
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>>;
+}
+
+/// creates the backend chosen in the config
+///
+/// the user keys are only used if encryption is enabled
+pub fn from_config(conf: &StorageConfig, keys: Option<Arc<dyn crypt::UserKeys>>) -> io::Result<Box<dyn Backend>> {
+    let backend: Box<dyn Backend> = match &conf.backend {
+        StorageBackendConfig::Fs => Box::new(fs::FsBackend::new(conf.directory.clone())),
+        StorageBackendConfig::S3(s3_conf) => Box::new(s3::S3Backend::new(s3_conf.clone())?),
+    };
+
+    match keys {
+        Some(keys) => Ok(Box::new(crypt::EncryptedBackend::new(backend, keys))),
+        None => Ok(backend)
+    }
+}
+
+/// checks that a key only contains normal path parts
+pub fn check_key(key: &str) -> io::Result<()> {
+    let valid = !key.is_empty() && key.split('/')
+        .all(|part| !part.is_empty() && part != "." && part != "..");
+
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid storage key: {}", key)))
+    }
+}
+
+/// writes the given data as a whole object
+pub async fn put_bytes(mut writer: Box<dyn ObjectWriter>, data: Vec<u8>) -> io::Result<()> {
+    if let Err(err) = writer.write(Bytes::from(data)).await {
+        writer.abort().await;
+
+        return Err(err);
+    }
+
+    writer.finish().await?;
+
+    Ok(())
+}
+
+/// removes an object that should not have been stored
+///
+/// used when cleaning up after another error so failures are only logged
+pub async fn discard(backend: &dyn Backend, key: &str) {
+    if let Err(err) = backend.delete(key).await {
+        log::error!("failed to remove object: {} {}", key, err);
+    }
+}
+
+/// checks the hash of the data read from an object against the expected hex
+/// encoded blake3 hash
+///
+/// a mismatch is logged since it means the stored data has been changed or
+/// damaged
+pub fn check_hash(key: &str, actual: &blake3::Hash, expected: &str) -> io::Result<()> {
+    if actual.to_hex().as_str() == expected {
+        Ok(())
+    } else {
+        log::error!("object failed integrity check: {} expected: {} actual: {}", key, expected, actual.to_hex());
+
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("stored object failed integrity check: {}", key)
+        ))
+    }
+}
+
+/// hashes the data of a stream as it is read
+///
+/// the stream ends with an error instead of finishing if the data does not
+/// match the expected hash. this can only be done for full reads of an object
+pub fn verify_stream(stream: ByteStream, key: String, expected: String) -> ByteStream {
+    let state = Some((stream, blake3::Hasher::new(), key, expected));
+
+    stream::unfold(state, |state| async move {
+        let (mut stream, mut hasher, key, expected) = state?;
+
+        match stream.next().await {
+            Some(Ok(chunk)) => {
+                hasher.update(&chunk);
+
+                Some((Ok(chunk), Some((stream, hasher, key, expected))))
+            },
+            Some(Err(err)) => Some((Err(err), None)),
+            None => match check_hash(&key, &hasher.finalize(), &expected) {
+                Ok(()) => None,
+                Err(err) => Some((Err(err), None))
+            }
+        }
+    }).boxed()
+}
+
+/// reads a whole object into memory or None if it does not exist
+///
+/// only meant for small objects
+pub async fn read_bytes(backend: &dyn Backend, key: &str) -> io::Result<Option<Vec<u8>>> {
+    let Some(size) = backend.size(key).await? else {
+        return Ok(None);
+    };
+
+    let mut rtn = Vec::with_capacity(size as usize);
+    let mut stream = backend.read(key, None).await?;
+
+    while let Some(chunk) = stream.next().await {
+        rtn.extend_from_slice(&chunk?);
+    }
+
+    Ok(Some(rtn))
+}
+
+/// an object that can be read from the local file system
+///
+/// objects that are not kept locally are downloaded to the tmp directory and
+/// removed when this is dropped
+pub enum LocalFile {
+    Stored(PathBuf),
+    Downloaded(tmp::TmpFile),
+}
+
+impl LocalFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            LocalFile::Stored(path) => path,
+            LocalFile::Downloaded(file) => file.path(),
+        }
+    }
+}
+
+/// retrieves a local path for an object downloading it if necessary
+///
+/// if the hash of the object is given then the file is checked against it
+/// before it is returned
+pub async fn local_file(
+    backend: &dyn Backend,
+    key: &str,
+    hash: Option<&str>,
+    tmp_dir: &tmp::TmpDir,
+) -> io::Result<LocalFile> {
+    if let Some(path) = backend.local_path(key) {
+        if let Some(expected) = hash {
+            let to_hash = path.clone();
+            let actual = actix_web::web::block(move || -> io::Result<blake3::Hash> {
+                let mut hasher = blake3::Hasher::new();
+                io::copy(&mut std::fs::File::open(to_hash)?, &mut hasher)?;
+
+                Ok(hasher.finalize())
+            })
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))??;
+
+            check_hash(key, &actual, expected)?;
+        }
+
+        return Ok(LocalFile::Stored(path));
+    }
+
+    let ext = Path::new(key).extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("download");
+    let local = LocalFile::Downloaded(tmp_dir.create(ext)?);
+    let mut file = std::fs::File::create(local.path())?;
+    let mut stream = backend.read(key, None).await?;
+
+    if let Some(expected) = hash {
+        stream = verify_stream(stream, key.to_owned(), expected.to_owned());
+    }
+
+    while let Some(chunk) = stream.next().await {
+        io::Write::write_all(&mut file, &chunk?)?;
+    }
+
+    Ok(local)
+}
@@ -0,0 +1,150 @@
+//! files written to the tmp directory
+//!
+//! every tmp file is created through a [TmpDir] so it is tracked while in use
+//! and removed when dropped. a request that is cancelled or whose body stream
+//! fails part way through does not leave its file behind. files left by a
+//! crash are removed by the janitor which skips any that are still tracked.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::util;
+
+type Active = Arc<Mutex<HashSet<PathBuf>>>;
+
+/// the tmp directory along with the files currently in use
+#[derive(Clone)]
+pub struct TmpDir {
+    dir: PathBuf,
+    active: Active,
+}
+
+impl TmpDir {
+    pub fn new(dir: PathBuf) -> TmpDir {
+        TmpDir {
+            dir,
+            active: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// reserves a new tmp file path with the given extension
+    ///
+    /// the file itself is not created
+    pub fn create(&self, ext: &str) -> io::Result<TmpFile> {
+        let path = util::file::get_tmp_path(&self.dir, ext)?;
+
+        if let Ok(mut active) = self.active.lock() {
+            active.insert(path.clone());
+        }
+
+        Ok(TmpFile {
+            path,
+            active: self.active.clone(),
+        })
+    }
+
+    /// checks if a tmp file is still in use
+    pub fn is_active(&self, path: &Path) -> bool {
+        self.active.lock()
+            .map(|active| active.contains(path))
+            .unwrap_or(false)
+    }
+}
+
+/// a file in the tmp directory that is removed when dropped
+///
+/// files moved somewhere else before this is dropped are left alone
+pub struct TmpFile {
+    path: PathBuf,
+    active: Active,
+}
+
+impl TmpFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TmpFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            if err.kind() != io::ErrorKind::NotFound {
+                log::error!("failed to remove tmp file: {} {}", self.path.display(), err);
+            }
+        }
+
+        if let Ok(mut active) = self.active.lock() {
+            active.remove(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("tmp_dir_{}_{}", name, std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn drop_removes_file() {
+        let dir = test_dir("drop_removes_file");
+        let tmp_dir = TmpDir::new(dir.clone());
+        let tmp_file = tmp_dir.create("txt").unwrap();
+        let path = tmp_file.path().to_owned();
+
+        std::fs::write(&path, b"data").unwrap();
+
+        assert!(tmp_dir.is_active(&path));
+
+        drop(tmp_file);
+
+        assert!(!path.exists());
+        assert!(!tmp_dir.is_active(&path));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drop_without_file() {
+        let dir = test_dir("drop_without_file");
+        let tmp_dir = TmpDir::new(dir.clone());
+        let tmp_file = tmp_dir.create("txt").unwrap();
+        let path = tmp_file.path().to_owned();
+
+        drop(tmp_file);
+
+        assert!(!tmp_dir.is_active(&path));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drop_leaves_moved_file() {
+        let dir = test_dir("drop_leaves_moved_file");
+        let tmp_dir = TmpDir::new(dir.clone());
+        let tmp_file = tmp_dir.create("txt").unwrap();
+        let moved = dir.join("moved.txt");
+
+        std::fs::write(tmp_file.path(), b"data").unwrap();
+        std::fs::rename(tmp_file.path(), &moved).unwrap();
+
+        drop(tmp_file);
+
+        assert_eq!(std::fs::read(&moved).unwrap(), b"data");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,866 @@
+//! reading metadata from the headers of uploaded audio
+//!
+//! nothing is decoded, only the container headers are read so this is cheap
+//! even for long recordings. values that cannot be found are left empty. webm
+//! files from browser recordings usually do not store a duration so the block
+//! timestamps are scanned to find it instead. m4a files are not parsed.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::util::sniff::AudioKind;
+
+pub type Reader = BufReader<File>;
+
+/// the amount of data read from the end of an ogg file to find the last page
+const OGG_TAIL_LEN: u64 = 64 * 1024;
+
+/// opus is always decoded at 48kHz regardless of the input sample rate
+const OPUS_SAMPLE_RATE: u32 = 48_000;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AudioMetadata {
+    /// length of the audio in milliseconds
+    pub duration_ms: Option<i64>,
+    pub codec: Option<String>,
+    pub sample_rate: Option<i32>,
+    pub channels: Option<i32>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+/// fills the buffer. returns false if the file ended before any data was read
+fn read_or_eof(reader: &mut Reader, buf: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err)
+    }
+}
+
+fn samples_to_ms(samples: u64, sample_rate: u32) -> Option<i64> {
+    if sample_rate == 0 {
+        None
+    } else {
+        Some((samples * 1000 / sample_rate as u64) as i64)
+    }
+}
+
+/// reads the metadata of an audio file that has already been sniffed
+///
+/// this does blocking io and should not be run on an async worker
+pub fn read_metadata<P>(path: P, kind: AudioKind) -> io::Result<AudioMetadata>
+where
+    P: AsRef<Path>
+{
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    match kind {
+        AudioKind::WebM => webm(&mut reader),
+        AudioKind::Ogg => ogg(&mut reader, size),
+        AudioKind::Wav => wav(&mut reader, size),
+        AudioKind::Mp3 => mp3(&mut reader, size),
+        AudioKind::M4a => Ok(AudioMetadata::default()),
+    }
+}
+
+/// the format and location of the samples in a wav file
+pub struct WavInfo {
+    pub format: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub byte_rate: u32,
+    /// the size of one sample for every channel
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    /// the size of the data chunk
+    pub data_len: u64,
+}
+
+impl WavInfo {
+    /// a short name for the codec of the samples
+    pub fn codec(&self) -> String {
+        match self.format {
+            0x0001 => "pcm".to_owned(),
+            0x0003 => "pcm_float".to_owned(),
+            0x0006 => "alaw".to_owned(),
+            0x0007 => "mulaw".to_owned(),
+            0x0055 => "mp3".to_owned(),
+            other => format!("wav_{:04x}", other),
+        }
+    }
+}
+
+/// reads the fmt chunk and finds the data chunk
+///
+/// the reader is left at the start of the sample data
+pub fn read_wav_info(reader: &mut Reader, size: u64) -> io::Result<WavInfo> {
+    let mut info = None;
+    let mut header = [0u8; 12];
+
+    reader.read_exact(&mut header)?;
+
+    loop {
+        let mut chunk = [0u8; 8];
+
+        if !read_or_eof(reader, &mut chunk)? {
+            return Err(invalid("wav file is missing a data chunk"));
+        }
+
+        let len = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        // chunks are padded to an even length
+        let padded = len as i64 + (len % 2) as i64;
+
+        match &chunk[0..4] {
+            b"fmt " => {
+                if len < 16 {
+                    return Err(invalid("wav fmt chunk is too small"));
+                }
+
+                let mut fmt = vec![0u8; len.min(40) as usize];
+                reader.read_exact(&mut fmt)?;
+                reader.seek_relative(padded - fmt.len() as i64)?;
+
+                let mut format = u16::from_le_bytes([fmt[0], fmt[1]]);
+
+                // extensible formats store the actual format in the sub format guid
+                if format == 0xfffe && fmt.len() >= 26 {
+                    format = u16::from_le_bytes([fmt[24], fmt[25]]);
+                }
+
+                info = Some(WavInfo {
+                    format,
+                    channels: u16::from_le_bytes([fmt[2], fmt[3]]),
+                    sample_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+                    byte_rate: u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]),
+                    block_align: u16::from_le_bytes([fmt[12], fmt[13]]),
+                    bits_per_sample: u16::from_le_bytes([fmt[14], fmt[15]]),
+                    data_len: 0,
+                });
+            },
+            b"data" => {
+                let Some(mut found) = info else {
+                    return Err(invalid("wav data chunk found before the fmt chunk"));
+                };
+
+                let remaining = size.saturating_sub(reader.stream_position()?);
+                // streamed recordings may not have updated the size
+                found.data_len = if len == 0 || len == u32::MAX {
+                    remaining
+                } else {
+                    (len as u64).min(remaining)
+                };
+
+                return Ok(found);
+            },
+            _ => {
+                reader.seek_relative(padded)?;
+            }
+        }
+    }
+}
+
+fn wav(reader: &mut Reader, size: u64) -> io::Result<AudioMetadata> {
+    let info = read_wav_info(reader, size)?;
+
+    Ok(AudioMetadata {
+        duration_ms: if info.byte_rate > 0 {
+            Some((info.data_len * 1000 / info.byte_rate as u64) as i64)
+        } else {
+            None
+        },
+        codec: Some(info.codec()),
+        sample_rate: Some(info.sample_rate as i32),
+        channels: Some(info.channels as i32),
+    })
+}
+
+/// reads the identification header from the first page and the granule
+/// position of the last page
+fn ogg(reader: &mut Reader, size: u64) -> io::Result<AudioMetadata> {
+    let mut rtn = AudioMetadata::default();
+    let mut header = [0u8; 27];
+
+    reader.read_exact(&mut header)?;
+
+    if &header[0..4] != b"OggS" {
+        return Err(invalid("missing ogg page header"));
+    }
+
+    let serial = &header[14..18];
+    let mut segments = vec![0u8; header[26] as usize];
+    reader.read_exact(&mut segments)?;
+
+    let body_len: usize = segments.iter().map(|v| *v as usize).sum();
+    let mut body = vec![0u8; body_len];
+    reader.read_exact(&mut body)?;
+
+    let pre_skip: u64;
+    let sample_rate: u32;
+
+    if body.len() >= 19 && body.starts_with(b"OpusHead") {
+        pre_skip = u16::from_le_bytes([body[10], body[11]]) as u64;
+        sample_rate = OPUS_SAMPLE_RATE;
+
+        rtn.codec = Some("opus".to_owned());
+        rtn.channels = Some(body[9] as i32);
+    } else if body.len() >= 16 && body.starts_with(b"\x01vorbis") {
+        pre_skip = 0;
+        sample_rate = u32::from_le_bytes([body[12], body[13], body[14], body[15]]);
+
+        rtn.codec = Some("vorbis".to_owned());
+        rtn.channels = Some(body[11] as i32);
+    } else {
+        return Ok(rtn);
+    }
+
+    rtn.sample_rate = Some(sample_rate as i32);
+
+    let tail_start = size.saturating_sub(OGG_TAIL_LEN);
+    let mut tail = Vec::with_capacity((size - tail_start) as usize);
+
+    reader.seek(SeekFrom::Start(tail_start))?;
+    reader.read_to_end(&mut tail)?;
+
+    // search backwards for the last page of the same stream that has a
+    // granule position. pages that only continue a packet use -1
+    let mut index = tail.len().saturating_sub(27);
+
+    loop {
+        let page = &tail[index..];
+
+        if page.len() >= 27 && &page[0..4] == b"OggS" && page[4] == 0 && &page[14..18] == serial {
+            let granule = u64::from_le_bytes([
+                page[6], page[7], page[8], page[9],
+                page[10], page[11], page[12], page[13],
+            ]);
+
+            if granule != u64::MAX {
+                rtn.duration_ms = samples_to_ms(granule.saturating_sub(pre_skip), sample_rate);
+                break;
+            }
+        }
+
+        if index == 0 {
+            break;
+        }
+
+        index -= 1;
+    }
+
+    Ok(rtn)
+}
+
+/// the parts of an mpeg audio layer 3 frame header that are needed
+struct Mp3Frame {
+    is_mpeg1: bool,
+    bitrate_kbps: u32,
+    sample_rate: u32,
+    channels: u32,
+}
+
+impl Mp3Frame {
+    fn parse(bytes: &[u8]) -> Option<Mp3Frame> {
+        const MPEG1_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+        const MPEG2_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+        if bytes.len() < 4 || bytes[0] != 0xff || bytes[1] & 0xe0 != 0xe0 {
+            return None;
+        }
+
+        let version = (bytes[1] >> 3) & 0x03;
+        let layer = (bytes[1] >> 1) & 0x03;
+        let bitrate = (bytes[2] >> 4) as usize;
+        let sample_rate = ((bytes[2] >> 2) & 0x03) as usize;
+
+        if version == 0x01 || layer != 0x01 || bitrate == 0x0f || sample_rate == 0x03 {
+            return None;
+        }
+
+        let (is_mpeg1, sample_rates) = match version {
+            0x03 => (true, [44_100, 48_000, 32_000]),
+            0x02 => (false, [22_050, 24_000, 16_000]),
+            _ => (false, [11_025, 12_000, 8_000]),
+        };
+
+        Some(Mp3Frame {
+            is_mpeg1,
+            bitrate_kbps: if is_mpeg1 {
+                MPEG1_BITRATES[bitrate]
+            } else {
+                MPEG2_BITRATES[bitrate]
+            },
+            sample_rate: sample_rates[sample_rate],
+            channels: if bytes[3] >> 6 == 0x03 { 1 } else { 2 },
+        })
+    }
+
+    fn samples_per_frame(&self) -> u64 {
+        if self.is_mpeg1 { 1152 } else { 576 }
+    }
+
+    /// the offset of a xing header from the start of the frame
+    fn xing_offset(&self) -> usize {
+        4 + match (self.is_mpeg1, self.channels) {
+            (true, 1) => 17,
+            (true, _) => 32,
+            (false, 1) => 9,
+            (false, _) => 17,
+        }
+    }
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4)
+        .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+}
+
+/// reads the first frame header after any id3 tag and uses the xing or vbri
+/// header for the duration if present. otherwise the duration is estimated
+/// from the bitrate of the first frame
+fn mp3(reader: &mut Reader, size: u64) -> io::Result<AudioMetadata> {
+    let mut rtn = AudioMetadata::default();
+    let mut start = 0u64;
+    let mut id3 = [0u8; 10];
+
+    if read_or_eof(reader, &mut id3)? && id3.starts_with(b"ID3") {
+        // the tag size is a syncsafe integer that excludes the header and footer
+        let tag_size = id3[6..10].iter()
+            .fold(0u64, |acc, v| (acc << 7) | (*v & 0x7f) as u64);
+
+        start = 10 + tag_size + if id3[5] & 0x10 != 0 { 10 } else { 0 };
+    }
+
+    reader.seek(SeekFrom::Start(start))?;
+
+    let mut buffer = Vec::with_capacity(8 * 1024);
+    reader.by_ref().take(8 * 1024).read_to_end(&mut buffer)?;
+
+    // padding may come before the first frame
+    let Some((offset, frame)) = (0..buffer.len())
+        .find_map(|index| Mp3Frame::parse(&buffer[index..]).map(|f| (index, f))) else {
+        return Ok(rtn);
+    };
+
+    rtn.codec = Some("mp3".to_owned());
+    rtn.sample_rate = Some(frame.sample_rate as i32);
+    rtn.channels = Some(frame.channels as i32);
+
+    let frame_bytes = &buffer[offset..];
+    let xing = frame.xing_offset();
+    let mut frames = None;
+
+    if let Some(tag) = frame_bytes.get(xing..xing + 4) {
+        if tag == b"Xing" || tag == b"Info" {
+            let flags = read_u32_be(frame_bytes, xing + 4).unwrap_or(0);
+
+            if flags & 0x01 != 0 {
+                frames = read_u32_be(frame_bytes, xing + 8);
+            }
+        }
+    }
+
+    if frames.is_none() && frame_bytes.get(36..40) == Some(b"VBRI".as_slice()) {
+        frames = read_u32_be(frame_bytes, 36 + 14);
+    }
+
+    rtn.duration_ms = if let Some(count) = frames {
+        samples_to_ms(count as u64 * frame.samples_per_frame(), frame.sample_rate)
+    } else if frame.bitrate_kbps > 0 {
+        // kilobits per second is the same as bits per millisecond
+        let audio_len = size.saturating_sub(start + offset as u64);
+
+        Some((audio_len * 8 / frame.bitrate_kbps as u64) as i64)
+    } else {
+        None
+    };
+
+    Ok(rtn)
+}
+
+mod ebml {
+    pub const SEGMENT: u64 = 0x18538067;
+    pub const INFO: u64 = 0x1549a966;
+    pub const TIMECODE_SCALE: u64 = 0x2ad7b1;
+    pub const DURATION: u64 = 0x4489;
+    pub const TRACKS: u64 = 0x1654ae6b;
+    pub const TRACK_ENTRY: u64 = 0xae;
+    pub const CODEC_ID: u64 = 0x86;
+    pub const AUDIO: u64 = 0xe1;
+    pub const SAMPLING_FREQUENCY: u64 = 0xb5;
+    pub const CHANNELS: u64 = 0x9f;
+    pub const CLUSTER: u64 = 0x1f43b675;
+    pub const CLUSTER_TIMECODE: u64 = 0xe7;
+    pub const SIMPLE_BLOCK: u64 = 0xa3;
+    pub const BLOCK_GROUP: u64 = 0xa0;
+    pub const BLOCK: u64 = 0xa1;
+}
+
+/// an ebml variable length integer
+struct VInt {
+    value: u64,
+    len: u8,
+    /// all value bits are set which marks an unknown element size
+    unknown: bool,
+}
+
+/// reads an ebml variable length integer. ids keep their length marker while
+/// sizes do not. returns None at the end of the file
+fn read_vint(reader: &mut Reader, keep_marker: bool) -> io::Result<Option<VInt>> {
+    let mut first = [0u8; 1];
+
+    if !read_or_eof(reader, &mut first)? {
+        return Ok(None);
+    }
+
+    if first[0] == 0 {
+        return Err(invalid("invalid ebml variable length integer"));
+    }
+
+    let len = first[0].leading_zeros() as u8 + 1;
+    let mask = if len == 8 { 0 } else { 0xffu8 >> len };
+    let mut value = (if keep_marker { first[0] } else { first[0] & mask }) as u64;
+    let mut unknown = first[0] & mask == mask;
+
+    for _ in 1..len {
+        let mut next = [0u8; 1];
+        reader.read_exact(&mut next)?;
+
+        value = (value << 8) | next[0] as u64;
+        unknown = unknown && next[0] == 0xff;
+    }
+
+    Ok(Some(VInt { value, len, unknown }))
+}
+
+fn read_uint(reader: &mut Reader, size: u64) -> io::Result<u64> {
+    if size > 8 {
+        return Err(invalid("ebml unsigned integer is too large"));
+    }
+
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf[8 - size as usize..])?;
+
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn read_float(reader: &mut Reader, size: u64) -> io::Result<f64> {
+    match size {
+        4 => {
+            let mut buf = [0u8; 4];
+            reader.read_exact(&mut buf)?;
+
+            Ok(f32::from_be_bytes(buf) as f64)
+        },
+        8 => {
+            let mut buf = [0u8; 8];
+            reader.read_exact(&mut buf)?;
+
+            Ok(f64::from_be_bytes(buf))
+        },
+        _ => Err(invalid("invalid ebml float size"))
+    }
+}
+
+/// converts a matroska codec id to a short name e.g. A_OPUS to opus
+fn codec_name(codec_id: &str) -> String {
+    match codec_id {
+        "A_MPEG/L3" => "mp3".to_owned(),
+        _ => codec_id.trim_start_matches("A_")
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    }
+}
+
+/// walks the elements of a webm file
+///
+/// the master elements that hold the needed values are entered in place
+/// instead of being read as a whole which also handles the unknown sizes used
+/// by live recordings. everything else is skipped over
+fn webm(reader: &mut Reader) -> io::Result<AudioMetadata> {
+    let mut rtn = AudioMetadata::default();
+    let mut timecode_scale: u64 = 1_000_000;
+    let mut duration: Option<f64> = None;
+    let mut cluster_time: u64 = 0;
+    let mut last_block: Option<i64> = None;
+
+    loop {
+        let Some(id) = read_vint(reader, true)? else {
+            break;
+        };
+        let Some(size) = read_vint(reader, false)? else {
+            break;
+        };
+
+        match id.value {
+            ebml::SEGMENT |
+            ebml::INFO |
+            ebml::TRACKS |
+            ebml::TRACK_ENTRY |
+            ebml::AUDIO |
+            ebml::BLOCK_GROUP => {},
+            ebml::CLUSTER => {
+                if duration.is_some() && rtn.codec.is_some() {
+                    break;
+                }
+            },
+            ebml::TIMECODE_SCALE => {
+                timecode_scale = read_uint(reader, size.value)?;
+            },
+            ebml::DURATION => {
+                duration = Some(read_float(reader, size.value)?);
+            },
+            ebml::CODEC_ID => {
+                if size.value > 64 {
+                    reader.seek_relative(size.value as i64)?;
+                    continue;
+                }
+
+                let mut buf = vec![0u8; size.value as usize];
+                reader.read_exact(&mut buf)?;
+
+                let codec_id = String::from_utf8_lossy(&buf);
+                let codec_id = codec_id.trim_end_matches('\0');
+
+                if rtn.codec.is_none() && codec_id.starts_with("A_") {
+                    rtn.codec = Some(codec_name(codec_id));
+                }
+            },
+            ebml::SAMPLING_FREQUENCY => {
+                let value = read_float(reader, size.value)?;
+
+                if rtn.sample_rate.is_none() {
+                    rtn.sample_rate = Some(value as i32);
+                }
+            },
+            ebml::CHANNELS => {
+                let value = read_uint(reader, size.value)?;
+
+                if rtn.channels.is_none() {
+                    rtn.channels = Some(value as i32);
+                }
+            },
+            ebml::CLUSTER_TIMECODE => {
+                cluster_time = read_uint(reader, size.value)?;
+            },
+            ebml::SIMPLE_BLOCK | ebml::BLOCK => {
+                let Some(track) = read_vint(reader, false)? else {
+                    break;
+                };
+                let mut relative = [0u8; 2];
+                reader.read_exact(&mut relative)?;
+
+                let time = cluster_time as i64 + i16::from_be_bytes(relative) as i64;
+                let read = track.len as i64 + 2;
+
+                last_block = Some(last_block.map_or(time, |v| v.max(time)));
+                reader.seek_relative(size.value as i64 - read)?;
+            },
+            _ => {
+                if size.unknown {
+                    break;
+                }
+
+                reader.seek_relative(size.value as i64)?;
+            }
+        }
+    }
+
+    let scale = timecode_scale as f64 / 1_000_000.0;
+
+    rtn.duration_ms = duration.map(|v| (v * scale) as i64)
+        .or_else(|| last_block.map(|v| (v as f64 * scale) as i64));
+
+    Ok(rtn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// writes test data to a file since the readers only work on files
+    fn test_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("audio_{}_{}", name, std::process::id()));
+
+        std::fs::write(&path, data).unwrap();
+
+        path
+    }
+
+    fn metadata(name: &str, data: &[u8], kind: AudioKind) -> AudioMetadata {
+        let path = test_file(name, data);
+        let rtn = read_metadata(&path, kind).unwrap();
+
+        std::fs::remove_file(path).unwrap();
+
+        rtn
+    }
+
+    fn riff_chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut rtn = id.to_vec();
+        rtn.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        rtn.extend_from_slice(body);
+
+        if body.len() % 2 == 1 {
+            rtn.push(0);
+        }
+
+        rtn
+    }
+
+    /// a 16 bit pcm wav file with an odd sized chunk before the samples
+    fn wav_file(channels: u16, sample_rate: u32, data: &[u8]) -> Vec<u8> {
+        let block_align = channels * 2;
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+
+        let mut rtn = b"RIFF\x00\x00\x00\x00WAVE".to_vec();
+        rtn.extend(riff_chunk(b"fmt ", &fmt));
+        rtn.extend(riff_chunk(b"LIST", b"odd"));
+        rtn.extend(riff_chunk(b"data", data));
+
+        rtn
+    }
+
+    fn ogg_page(granule: u64, serial: u32, body: &[u8]) -> Vec<u8> {
+        let mut rtn = b"OggS\x00\x00".to_vec();
+        rtn.extend_from_slice(&granule.to_le_bytes());
+        rtn.extend_from_slice(&serial.to_le_bytes());
+        // sequence number and checksum are not checked
+        rtn.extend_from_slice(&[0; 8]);
+        rtn.push(1);
+        rtn.push(body.len() as u8);
+        rtn.extend_from_slice(body);
+
+        rtn
+    }
+
+    /// an ebml element with a two byte size
+    fn element(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut rtn = id.to_vec();
+        rtn.push(0x40 | (body.len() >> 8) as u8);
+        rtn.push(body.len() as u8);
+        rtn.extend_from_slice(body);
+
+        rtn
+    }
+
+    fn webm_tracks() -> Vec<u8> {
+        let audio = [
+            element(&[0xb5], &48_000f64.to_be_bytes()),
+            element(&[0x9f], &[2]),
+        ].concat();
+        let entry = [
+            element(&[0x86], b"A_OPUS"),
+            element(&[0xe1], &audio),
+        ].concat();
+
+        element(&[0x16, 0x54, 0xae, 0x6b], &element(&[0xae], &entry))
+    }
+
+    #[test]
+    fn wav_metadata() {
+        // one second of 8khz mono audio
+        let data = wav_file(1, 8_000, &[0; 16_000]);
+
+        assert_eq!(metadata("wav", &data, AudioKind::Wav), AudioMetadata {
+            duration_ms: Some(1_000),
+            codec: Some("pcm".to_owned()),
+            sample_rate: Some(8_000),
+            channels: Some(1),
+        });
+    }
+
+    #[test]
+    fn wav_info_finds_data() {
+        let data = wav_file(2, 44_100, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let path = test_file("wav_info", &data);
+        let file = File::open(&path).unwrap();
+        let mut reader = BufReader::new(file);
+
+        let info = read_wav_info(&mut reader, data.len() as u64).unwrap();
+
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.block_align, 4);
+        assert_eq!(info.data_len, 8);
+
+        let mut samples = Vec::new();
+        reader.read_to_end(&mut samples).unwrap();
+
+        assert_eq!(samples, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn wav_missing_data() {
+        let mut data = wav_file(1, 8_000, &[]);
+        data.truncate(data.len() - 8);
+
+        let path = test_file("wav_missing_data", &data);
+
+        assert!(read_metadata(&path, AudioKind::Wav).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn wav_codecs() {
+        let info = |format| WavInfo {
+            format,
+            channels: 1,
+            sample_rate: 8_000,
+            byte_rate: 8_000,
+            block_align: 1,
+            bits_per_sample: 8,
+            data_len: 0,
+        };
+
+        assert_eq!(info(0x0001).codec(), "pcm");
+        assert_eq!(info(0x0003).codec(), "pcm_float");
+        assert_eq!(info(0x0007).codec(), "mulaw");
+        assert_eq!(info(0x1234).codec(), "wav_1234");
+    }
+
+    #[test]
+    fn ogg_opus_metadata() {
+        let mut head = b"OpusHead\x01\x02".to_vec();
+        // pre skip, input sample rate, gain, and mapping family
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&44_100u32.to_le_bytes());
+        head.extend_from_slice(&[0, 0, 0]);
+
+        let mut data = ogg_page(0, 1, &head);
+        data.extend(ogg_page(24_312, 1, &[0; 10]));
+        // a page from another stream and one that only continues a packet
+        data.extend(ogg_page(96_000, 2, &[0; 10]));
+        data.extend(ogg_page(u64::MAX, 1, &[0; 10]));
+
+        assert_eq!(metadata("ogg_opus", &data, AudioKind::Ogg), AudioMetadata {
+            duration_ms: Some(500),
+            codec: Some("opus".to_owned()),
+            sample_rate: Some(48_000),
+            channels: Some(2),
+        });
+    }
+
+    #[test]
+    fn ogg_missing_header() {
+        let path = test_file("ogg_missing_header", b"not an ogg file at all, just text");
+
+        assert!(read_metadata(&path, AudioKind::Ogg).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn mp3_estimated_duration() {
+        // an empty id3 tag then mpeg 1 layer 3, 128kbps, 44.1khz, stereo
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        data.resize(10 + 16_000, 0);
+
+        assert_eq!(metadata("mp3_estimated", &data, AudioKind::Mp3), AudioMetadata {
+            duration_ms: Some(1_000),
+            codec: Some("mp3".to_owned()),
+            sample_rate: Some(44_100),
+            channels: Some(2),
+        });
+    }
+
+    #[test]
+    fn mp3_xing_duration() {
+        // padding before a mono frame with a xing header holding the frame count
+        let mut data = vec![0; 3];
+        data.extend_from_slice(&[0xff, 0xfb, 0x90, 0xc0]);
+        data.resize(3 + 21, 0);
+        data.extend_from_slice(b"Xing");
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(&100u32.to_be_bytes());
+        data.resize(1_000, 0);
+
+        let found = metadata("mp3_xing", &data, AudioKind::Mp3);
+
+        // 100 frames of 1152 samples
+        assert_eq!(found.duration_ms, Some(2_612));
+        assert_eq!(found.channels, Some(1));
+    }
+
+    #[test]
+    fn mp3_without_frames() {
+        assert_eq!(
+            metadata("mp3_without_frames", &[0; 100], AudioKind::Mp3),
+            AudioMetadata::default()
+        );
+    }
+
+    #[test]
+    fn webm_duration() {
+        let info = [
+            element(&[0x2a, 0xd7, 0xb1], &[0x0f, 0x42, 0x40]),
+            element(&[0x44, 0x89], &1_500f64.to_be_bytes()),
+        ].concat();
+        let segment = [
+            element(&[0x15, 0x49, 0xa9, 0x66], &info),
+            webm_tracks(),
+        ].concat();
+        let data = [
+            element(&[0x1a, 0x45, 0xdf, 0xa3], &[0x42, 0x82, 0x84, b'w', b'e', b'b', b'm']),
+            element(&[0x18, 0x53, 0x80, 0x67], &segment),
+        ].concat();
+
+        assert_eq!(metadata("webm_duration", &data, AudioKind::WebM), AudioMetadata {
+            duration_ms: Some(1_500),
+            codec: Some("opus".to_owned()),
+            sample_rate: Some(48_000),
+            channels: Some(2),
+        });
+    }
+
+    #[test]
+    fn webm_block_duration() {
+        // live recordings have an unknown segment size and no duration
+        let block = |relative: i16| {
+            let mut body = vec![0x81];
+            body.extend_from_slice(&relative.to_be_bytes());
+            body.extend_from_slice(&[0x80, 0, 0]);
+
+            element(&[0xa3], &body)
+        };
+        let first = [element(&[0xe7], &[0x00]), block(0), block(20)].concat();
+        let second = [element(&[0xe7], &[0x03, 0xe8]), block(250)].concat();
+
+        let mut data = vec![0x18, 0x53, 0x80, 0x67, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        data.extend(webm_tracks());
+        data.extend(element(&[0x1f, 0x43, 0xb6, 0x75], &first));
+        data.extend(element(&[0x1f, 0x43, 0xb6, 0x75], &second));
+
+        let found = metadata("webm_block_duration", &data, AudioKind::WebM);
+
+        assert_eq!(found.duration_ms, Some(1_250));
+        assert_eq!(found.codec.as_deref(), Some("opus"));
+    }
+
+    #[test]
+    fn codec_names() {
+        assert_eq!(codec_name("A_OPUS"), "opus");
+        assert_eq!(codec_name("A_VORBIS"), "vorbis");
+        assert_eq!(codec_name("A_AAC/MPEG4/LC"), "aac");
+        assert_eq!(codec_name("A_MPEG/L3"), "mp3");
+    }
+}
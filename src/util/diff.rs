@@ -0,0 +1,337 @@
+//! word level diffing of text
+//!
+//! text is split into runs of whitespace and non whitespace so that the
+//! original strings can be rebuilt from the changes. uses the myers diff
+//! algorithm to find the shortest edit script between the two.
+//!
+//! the cost of the algorithm grows with the size of the text and the number
+//! of changes. text with too many tokens or changes is shown as the whole of
+//! one being replaced by the other instead.
+
+use serde::Serialize;
+
+/// the most tokens left after trimming the common prefix and suffix that
+/// will be diffed
+pub const MAX_TOKENS: usize = 20_000;
+
+/// the most changes that will be searched for before giving up
+pub const MAX_EDIT_DISTANCE: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Same,
+    Added,
+    Removed,
+}
+
+/// a run of text that was either kept, added, or removed
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub value: String,
+}
+
+fn tokenize(given: &str) -> Vec<&str> {
+    let mut rtn = Vec::new();
+    let mut start = 0;
+    let mut in_space: Option<bool> = None;
+
+    for (index, ch) in given.char_indices() {
+        let is_space = ch.is_whitespace();
+
+        if let Some(prev) = in_space {
+            if prev != is_space {
+                rtn.push(&given[start..index]);
+                start = index;
+            }
+        }
+
+        in_space = Some(is_space);
+    }
+
+    if start < given.len() {
+        rtn.push(&given[start..]);
+    }
+
+    rtn
+}
+
+fn push_change(list: &mut Vec<Change>, kind: ChangeKind, value: &str) {
+    if let Some(last) = list.last_mut() {
+        if last.kind == kind {
+            last.value.push_str(value);
+            return;
+        }
+    }
+
+    list.push(Change {
+        kind,
+        value: value.to_owned(),
+    });
+}
+
+/// finds the edit script between two lists of tokens
+///
+/// the changes are returned in reverse order. None is returned if the script
+/// needs more than max_d additions and removals
+fn myers<'a>(a: &[&'a str], b: &[&'a str], max_d: usize) -> Option<Vec<(ChangeKind, &'a str)>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (n + m).min(max_d as isize);
+    let offset = max + 1;
+    let mut v = vec![0isize; (2 * max + 3) as usize];
+    // only the range of diagonals visited for each step is kept
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut rtn = Vec::new();
+    let mut found = false;
+
+    'outer: for d in 0..=max {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+
+        let mut k = -d;
+
+        while k <= d {
+            let mut x = if k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]) {
+                v[(offset + k + 1) as usize]
+            } else {
+                v[(offset + k - 1) as usize] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[(offset + k) as usize] = x;
+
+            if x >= n && y >= m {
+                found = true;
+                break 'outer;
+            }
+
+            k += 2;
+        }
+    }
+
+    if !found {
+        return None;
+    }
+
+    let mut x = n;
+    let mut y = m;
+
+    for d in (0..trace.len() as isize).rev() {
+        let v = &trace[d as usize];
+        let get = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            rtn.push((ChangeKind::Same, a[(x - 1) as usize]));
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            if x == prev_x {
+                rtn.push((ChangeKind::Added, b[(y - 1) as usize]));
+            } else {
+                rtn.push((ChangeKind::Removed, a[(x - 1) as usize]));
+            }
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    Some(rtn)
+}
+
+/// creates a word level diff between two strings
+///
+/// adjacent changes of the same kind are joined together. if the text
+/// between the common prefix and suffix is over [MAX_TOKENS] or needs more
+/// than [MAX_EDIT_DISTANCE] changes then it is given as removed and added
+/// whole
+pub fn words(from: &str, to: &str) -> Vec<Change> {
+    let a = tokenize(from);
+    let b = tokenize(to);
+    let mut rtn = Vec::new();
+
+    // common prefix and suffix are trimmed to keep the edit graph small
+    let mut prefix = 0;
+
+    while prefix < a.len() && prefix < b.len() && a[prefix] == b[prefix] {
+        prefix += 1;
+    }
+
+    let mut suffix = 0;
+
+    while suffix < a.len() - prefix &&
+          suffix < b.len() - prefix &&
+          a[a.len() - suffix - 1] == b[b.len() - suffix - 1] {
+        suffix += 1;
+    }
+
+    for token in &a[..prefix] {
+        push_change(&mut rtn, ChangeKind::Same, token);
+    }
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let changes = if a_mid.len() + b_mid.len() > MAX_TOKENS {
+        None
+    } else {
+        myers(a_mid, b_mid, MAX_EDIT_DISTANCE)
+    };
+
+    if let Some(changes) = changes {
+        for (kind, token) in changes.into_iter().rev() {
+            push_change(&mut rtn, kind, token);
+        }
+    } else {
+        for token in a_mid {
+            push_change(&mut rtn, ChangeKind::Removed, token);
+        }
+
+        for token in b_mid {
+            push_change(&mut rtn, ChangeKind::Added, token);
+        }
+    }
+
+    for token in &a[a.len() - suffix..] {
+        push_change(&mut rtn, ChangeKind::Same, token);
+    }
+
+    rtn
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn simple(changes: &[Change]) -> Vec<(ChangeKind, &str)> {
+        changes.iter()
+            .map(|change| (change.kind, change.value.as_str()))
+            .collect()
+    }
+
+    fn rebuild(changes: &[Change], skip: ChangeKind) -> String {
+        changes.iter()
+            .filter(|change| change.kind != skip)
+            .map(|change| change.value.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_keeps_whitespace() {
+        assert_eq!(tokenize("  one two\n"), vec!["  ", "one", " ", "two", "\n"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn empty_inputs() {
+        assert!(words("", "").is_empty());
+        assert_eq!(myers(&[], &[], MAX_EDIT_DISTANCE), Some(Vec::new()));
+    }
+
+    #[test]
+    fn full_insert() {
+        let changes = words("", "hello world");
+
+        assert_eq!(simple(&changes), vec![(ChangeKind::Added, "hello world")]);
+    }
+
+    #[test]
+    fn full_delete() {
+        let changes = words("hello world", "");
+
+        assert_eq!(simple(&changes), vec![(ChangeKind::Removed, "hello world")]);
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(simple(&words("same", "same")), vec![(ChangeKind::Same, "same")]);
+    }
+
+    #[test]
+    fn appended() {
+        assert_eq!(simple(&words("one two", "one two three")), vec![
+            (ChangeKind::Same, "one two"),
+            (ChangeKind::Added, " three"),
+        ]);
+    }
+
+    #[test]
+    fn interleaved_changes() {
+        let from = "the quick brown fox";
+        let to = "the slow brown dog";
+        let changes = words(from, to);
+
+        assert_eq!(simple(&changes), vec![
+            (ChangeKind::Same, "the "),
+            (ChangeKind::Removed, "quick"),
+            (ChangeKind::Added, "slow"),
+            (ChangeKind::Same, " brown "),
+            (ChangeKind::Removed, "fox"),
+            (ChangeKind::Added, "dog"),
+        ]);
+        assert_eq!(rebuild(&changes, ChangeKind::Added), from);
+        assert_eq!(rebuild(&changes, ChangeKind::Removed), to);
+    }
+
+    #[test]
+    fn backtrack_without_trimming() {
+        // the prefix and suffix are not trimmed when calling myers directly
+        // so this walks the trace all the way back to the first step
+        let a = ["a", " ", "b", " ", "c"];
+        let b = ["x", " ", "b", " ", "y"];
+        let mut changes = myers(&a, &b, MAX_EDIT_DISTANCE).unwrap();
+        changes.reverse();
+
+        let from: String = changes.iter()
+            .filter(|(kind, _)| *kind != ChangeKind::Added)
+            .map(|(_, token)| *token)
+            .collect();
+        let to: String = changes.iter()
+            .filter(|(kind, _)| *kind != ChangeKind::Removed)
+            .map(|(_, token)| *token)
+            .collect();
+        let same = changes.iter()
+            .filter(|(kind, _)| *kind == ChangeKind::Same)
+            .count();
+
+        assert_eq!(from, "a b c");
+        assert_eq!(to, "x b y");
+        assert_eq!(same, 3);
+        assert_eq!(changes.len(), 7);
+    }
+
+    #[test]
+    fn edit_distance_limit() {
+        assert_eq!(myers(&["a", "b"], &["c", "d"], 3), None);
+        assert_eq!(myers(&["a", "b"], &["c", "d"], 4).map(|list| list.len()), Some(4));
+    }
+
+    #[test]
+    fn too_many_changes_replaces_whole() {
+        let count = MAX_EDIT_DISTANCE / 2 + 100;
+        let from = (0..count).map(|i| format!("a{}", i)).collect::<Vec<_>>().join(" ");
+        let to = (0..count).map(|i| format!("b{}", i)).collect::<Vec<_>>().join(" ");
+        let changes = words(&from, &to);
+
+        assert_eq!(simple(&changes), vec![
+            (ChangeKind::Removed, from.as_str()),
+            (ChangeKind::Added, to.as_str()),
+        ]);
+    }
+}
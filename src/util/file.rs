@@ -1,5 +1,4 @@
-use std::fs::{File, OpenOptions};
-use std::io::{Result, Read, Write as _};
+use std::io::Result;
 use std::path::{Path, PathBuf};
 use std::fmt::Write as _;
 
@@ -34,26 +33,3 @@ where
         }
     }
 }
-
-/// copies a given file to the desired path
-pub fn copy_file<P>(mut file: &File, path: P) -> Result<File>
-where
-    P: AsRef<Path>
-{
-    let mut rtn_file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(path)?;
-
-    { // should something different be happening here
-        let mut buffer = [0; 2048];
-
-        while file.read(&mut buffer)? > 0 {
-            rtn_file.write(&mut buffer)?;
-            buffer.fill(0);
-        }
-    }
-
-    Ok(rtn_file)
-}
\ No newline at end of file
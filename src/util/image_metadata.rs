@@ -0,0 +1,354 @@
+//! removing metadata from images without re-encoding them
+//!
+//! only the parts of the file that hold metadata are dropped so the image
+//! data is kept exactly as it was uploaded. the parts that change how an
+//! image is decoded or displayed (jfif, icc profiles, and the adobe marker of
+//! a jpeg) are kept. the exif orientation is removed along with everything
+//! else so images that are not upright have to be re-encoded instead.
+
+use std::convert::TryFrom;
+
+use crate::util::images::ImageKind;
+
+/// removes the metadata from an image
+///
+/// returns None if the structure of the image could not be followed
+pub fn strip(bytes: &[u8], kind: ImageKind) -> Option<Vec<u8>> {
+    match kind {
+        ImageKind::Jpeg => strip_jpeg(bytes),
+        ImageKind::Png => strip_png(bytes),
+        ImageKind::WebP => strip_webp(bytes),
+    }
+}
+
+/// if a jpeg segment holds metadata
+///
+/// APP0 is jfif, APP2 can be an icc profile, and APP14 is the adobe marker
+/// which says how the color channels are stored. every other APPn segment
+/// and comments are metadata
+fn is_jpeg_metadata(marker: u8, data: &[u8]) -> bool {
+    match marker {
+        0xe0 => false,
+        0xe2 => !data.starts_with(b"ICC_PROFILE\0"),
+        0xee => !data.starts_with(b"Adobe"),
+        0xe1..=0xef | 0xfe => true,
+        _ => false,
+    }
+}
+
+/// drops the metadata segments of a jpeg
+///
+/// segments are followed up to the start of scan. everything after it is
+/// entropy coded data that is kept as is
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    if !bytes.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+
+    let mut rtn = Vec::with_capacity(bytes.len());
+    rtn.extend_from_slice(&bytes[..2]);
+
+    let mut index = 2;
+
+    loop {
+        if *bytes.get(index)? != 0xff {
+            return None;
+        }
+
+        // any number of 0xff fill bytes can come before a marker
+        while *bytes.get(index + 1)? == 0xff {
+            index += 1;
+        }
+
+        let marker = bytes[index + 1];
+
+        match marker {
+            // end of image
+            0xd9 => {
+                rtn.extend_from_slice(&bytes[index..index + 2]);
+
+                return Some(rtn);
+            },
+            // markers without a length
+            0x01 | 0xd0..=0xd7 => {
+                rtn.extend_from_slice(&bytes[index..index + 2]);
+                index += 2;
+
+                continue;
+            },
+            _ => {}
+        }
+
+        let length = u16::from_be_bytes([*bytes.get(index + 2)?, *bytes.get(index + 3)?]) as usize;
+
+        if length < 2 {
+            return None;
+        }
+
+        let end = index + 2 + length;
+        let data = bytes.get(index + 4..end)?;
+
+        // start of scan
+        if marker == 0xda {
+            rtn.extend_from_slice(&bytes[index..]);
+
+            return Some(rtn);
+        }
+
+        if !is_jpeg_metadata(marker, data) {
+            rtn.extend_from_slice(&bytes[index..end]);
+        }
+
+        index = end;
+    }
+}
+
+/// if a png chunk holds metadata
+fn is_png_metadata(chunk_type: &[u8]) -> bool {
+    matches!(chunk_type, b"tEXt" | b"zTXt" | b"iTXt" | b"eXIf" | b"tIME")
+}
+
+/// drops the text, exif, and time chunks of a png
+///
+/// the crc of each chunk only covers the chunk itself so the chunks that are
+/// kept are still valid
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE_LEN: usize = 8;
+
+    if bytes.len() < SIGNATURE_LEN {
+        return None;
+    }
+
+    let mut rtn = Vec::with_capacity(bytes.len());
+    rtn.extend_from_slice(&bytes[..SIGNATURE_LEN]);
+
+    let mut index = SIGNATURE_LEN;
+
+    while index < bytes.len() {
+        let header = bytes.get(index..index + 8)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_type = &header[4..8];
+        // length, type, data, and crc
+        let end = index.checked_add(12)?.checked_add(length)?;
+
+        if end > bytes.len() {
+            return None;
+        }
+
+        if !is_png_metadata(chunk_type) {
+            rtn.extend_from_slice(&bytes[index..end]);
+        }
+
+        index = end;
+
+        if chunk_type == b"IEND" {
+            break;
+        }
+    }
+
+    Some(rtn)
+}
+
+/// the flags in a VP8X chunk for the exif and xmp chunks
+const VP8X_EXIF: u8 = 0x08;
+const VP8X_XMP: u8 = 0x04;
+
+/// drops the exif and xmp chunks of a webp
+///
+/// the flags for them in the extended header are cleared and the size of
+/// the riff container is updated to match
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    const HEADER_LEN: usize = 12;
+
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut rtn = Vec::with_capacity(bytes.len());
+    rtn.extend_from_slice(&bytes[..HEADER_LEN]);
+
+    let mut index = HEADER_LEN;
+
+    while index < bytes.len() {
+        let header = bytes.get(index..index + 8)?;
+        let fourcc = &header[0..4];
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        // chunks are padded to an even size
+        let end = index.checked_add(8)?.checked_add(size)?.checked_add(size % 2)?;
+
+        if end > bytes.len() {
+            return None;
+        }
+
+        match fourcc {
+            b"EXIF" | b"XMP " => {},
+            b"VP8X" => {
+                let start = rtn.len();
+                rtn.extend_from_slice(&bytes[index..end]);
+
+                if let Some(flags) = rtn.get_mut(start + 8) {
+                    *flags &= !(VP8X_EXIF | VP8X_XMP);
+                }
+            },
+            _ => rtn.extend_from_slice(&bytes[index..end]),
+        }
+
+        index = end;
+    }
+
+    let riff_size = u32::try_from(rtn.len() - 8).ok()?;
+    rtn[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    Some(rtn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageOutputFormat};
+
+    fn encoded(format: ImageOutputFormat) -> Vec<u8> {
+        let mut rtn = Cursor::new(Vec::new());
+
+        DynamicImage::new_rgb8(8, 8).write_to(&mut rtn, format).unwrap();
+
+        rtn.into_inner()
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = 0xffff_ffffu32;
+
+        for byte in data {
+            crc ^= *byte as u32;
+
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            }
+        }
+
+        !crc
+    }
+
+    fn jpeg_segment(marker: u8, data: &[u8]) -> Vec<u8> {
+        let mut rtn = vec![0xff, marker];
+        rtn.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+        rtn.extend_from_slice(data);
+        rtn
+    }
+
+    fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut rtn = (data.len() as u32).to_be_bytes().to_vec();
+        rtn.extend_from_slice(chunk_type);
+        rtn.extend_from_slice(data);
+
+        let crc = crc32(&rtn[4..]);
+        rtn.extend_from_slice(&crc.to_be_bytes());
+        rtn
+    }
+
+    fn webp_chunk(fourcc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut rtn = fourcc.to_vec();
+        rtn.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        rtn.extend_from_slice(data);
+
+        if data.len() % 2 == 1 {
+            rtn.push(0);
+        }
+
+        rtn
+    }
+
+    fn webp(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut rtn = b"RIFF".to_vec();
+        rtn.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        rtn.extend_from_slice(b"WEBP");
+        rtn.extend_from_slice(&body);
+        rtn
+    }
+
+    #[test]
+    fn jpeg_segments() {
+        let original = encoded(ImageOutputFormat::Jpeg(90));
+        let icc = jpeg_segment(0xe2, b"ICC_PROFILE\0\x01\x01profile");
+        let adobe = jpeg_segment(0xee, b"Adobe\0\x64\0\0\0\0\x01");
+
+        let mut tagged = original[..2].to_vec();
+        tagged.extend(jpeg_segment(0xe1, b"Exif\0\0gps location"));
+        tagged.extend(jpeg_segment(0xe1, b"http://ns.adobe.com/xap/1.0/\0xmp"));
+        tagged.extend_from_slice(&icc);
+        tagged.extend(jpeg_segment(0xe2, b"FPXR\0flashpix"));
+        tagged.extend(jpeg_segment(0xed, b"Photoshop 3.0\0iptc"));
+        tagged.extend_from_slice(&adobe);
+        tagged.extend(jpeg_segment(0xfe, b"a comment"));
+        tagged.extend_from_slice(&original[2..]);
+
+        let stripped = strip(&tagged, ImageKind::Jpeg).unwrap();
+
+        let mut expected = original[..2].to_vec();
+        expected.extend_from_slice(&icc);
+        expected.extend_from_slice(&adobe);
+        expected.extend_from_slice(&original[2..]);
+
+        assert_eq!(stripped, expected, "only the metadata segments should be removed");
+        assert!(image::load_from_memory(&stripped).is_ok());
+    }
+
+    #[test]
+    fn jpeg_without_metadata_is_unchanged() {
+        let original = encoded(ImageOutputFormat::Jpeg(90));
+
+        assert_eq!(strip(&original, ImageKind::Jpeg).unwrap(), original);
+    }
+
+    #[test]
+    fn png_chunks() {
+        let original = encoded(ImageOutputFormat::Png);
+        // the signature and IHDR come first
+        let split = 8 + 12 + 13;
+
+        let mut tagged = original[..split].to_vec();
+        tagged.extend(png_chunk(b"tEXt", b"Comment\0secret"));
+        tagged.extend(png_chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0xmp"));
+        tagged.extend(png_chunk(b"eXIf", b"MM\0\x2a"));
+        tagged.extend(png_chunk(b"tIME", &[0x07, 0xe8, 1, 1, 0, 0, 0]));
+        tagged.extend_from_slice(&original[split..]);
+
+        let stripped = strip(&tagged, ImageKind::Png).unwrap();
+
+        assert_eq!(stripped, original, "only the metadata chunks should be removed");
+    }
+
+    #[test]
+    fn webp_chunks() {
+        let mut vp8x = vec![VP8X_EXIF | VP8X_XMP | 0x10, 0, 0, 0];
+        vp8x.extend_from_slice(&[7, 0, 0, 7, 0, 0]);
+        let frame = webp_chunk(b"VP8L", &[0x2f, 0x07, 0xc0, 0x01, 0x00]);
+
+        let tagged = webp(&[
+            webp_chunk(b"VP8X", &vp8x),
+            frame.clone(),
+            webp_chunk(b"EXIF", b"MM\0\x2a gps"),
+            webp_chunk(b"XMP ", b"<x:xmpmeta/>"),
+        ]);
+
+        let stripped = strip(&tagged, ImageKind::WebP).unwrap();
+
+        let mut cleared = vp8x.clone();
+        cleared[0] = 0x10;
+        let expected = webp(&[webp_chunk(b"VP8X", &cleared), frame]);
+
+        assert_eq!(stripped, expected, "the metadata chunks and their flags should be removed");
+    }
+
+    #[test]
+    fn unreadable_structures() {
+        assert_eq!(strip(b"not an image", ImageKind::Jpeg), None);
+        assert_eq!(strip(&[0xff, 0xd8, 0xff, 0xe1, 0xff, 0xff], ImageKind::Jpeg), None);
+        assert_eq!(strip(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 1], ImageKind::Png), None);
+        assert_eq!(strip(b"RIFF\x10\x00\x00\x00WEBPVP8 \xff\xff\x00\x00", ImageKind::WebP), None);
+    }
+}
@@ -0,0 +1,319 @@
+//! processing of uploaded images
+//!
+//! images are decoded to check that they are valid and to create a thumbnail.
+//! metadata is stripped without touching the image data unless the image has
+//! an exif orientation. those are re-encoded after the orientation has been
+//! applied so that they still display the right way up without the tag.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageOutputFormat};
+use image::io::{Limits, Reader};
+
+use crate::util::image_metadata;
+
+/// the max width or height of a thumbnail
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// the max width or height of an uploaded image
+pub const MAX_DIMENSION: u32 = 16_384;
+
+/// the max amount of memory the decoder can allocate for a single image
+pub const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+
+/// quality used when encoding jpeg images
+const JPEG_QUALITY: u8 = 90;
+
+/// the image formats that can be uploaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageKind {
+    Jpeg,
+    Png,
+    WebP,
+}
+
+impl ImageKind {
+    /// checks the magic bytes of the given data for a known format
+    pub fn sniff(bytes: &[u8]) -> Option<ImageKind> {
+        if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageKind::Jpeg)
+        } else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]) {
+            Some(ImageKind::Png)
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageKind::WebP)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_mime_subtype(subtype: &str) -> Option<ImageKind> {
+        match subtype {
+            "jpeg" => Some(ImageKind::Jpeg),
+            "png" => Some(ImageKind::Png),
+            "webp" => Some(ImageKind::WebP),
+            _ => None
+        }
+    }
+
+    pub fn mime_subtype(&self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "jpeg",
+            ImageKind::Png => "png",
+            ImageKind::WebP => "webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "jpg",
+            ImageKind::Png => "png",
+            ImageKind::WebP => "webp",
+        }
+    }
+
+    fn image_format(&self) -> image::ImageFormat {
+        match self {
+            ImageKind::Jpeg => image::ImageFormat::Jpeg,
+            ImageKind::Png => image::ImageFormat::Png,
+            ImageKind::WebP => image::ImageFormat::WebP,
+        }
+    }
+
+    fn output_format(&self) -> ImageOutputFormat {
+        match self {
+            ImageKind::Jpeg => ImageOutputFormat::Jpeg(JPEG_QUALITY),
+            ImageKind::Png => ImageOutputFormat::Png,
+            ImageKind::WebP => ImageOutputFormat::WebP,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    /// the data is not a jpeg, png, or webp image
+    Unsupported,
+    Image(image::ImageError),
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::Unsupported => write!(f, "unsupported image format"),
+            ImageError::Image(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Unsupported => None,
+            ImageError::Image(err) => Some(err),
+        }
+    }
+}
+
+impl From<image::ImageError> for ImageError {
+    fn from(err: image::ImageError) -> ImageError {
+        ImageError::Image(err)
+    }
+}
+
+/// an image that is ready to be stored
+pub struct ProcessedImage {
+    pub kind: ImageKind,
+    pub data: Vec<u8>,
+    /// width after the orientation has been applied
+    pub width: u32,
+    /// height after the orientation has been applied
+    pub height: u32,
+    pub thumbnail_kind: ImageKind,
+    pub thumbnail: Vec<u8>,
+}
+
+/// reads the exif orientation of an image. defaults to 1 if there is no
+/// orientation or the exif data is invalid
+fn get_orientation(bytes: &[u8]) -> u32 {
+    let Ok(data) = exif::Reader::new().read_from_container(&mut Cursor::new(bytes)) else {
+        return 1;
+    };
+
+    data.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(1)
+}
+
+/// decodes an image without going past the dimension and allocation limits
+///
+/// the header is checked before any pixel data is allocated so a small
+/// upload cannot claim a huge size and exhaust memory
+fn decode(bytes: &[u8], kind: ImageKind) -> Result<DynamicImage, ImageError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = Reader::with_format(Cursor::new(bytes), kind.image_format());
+    reader.limits(limits);
+
+    Ok(reader.decode()?)
+}
+
+/// rotates and flips an image to match its exif orientation
+fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+fn encode(img: &DynamicImage, kind: ImageKind) -> Result<Vec<u8>, ImageError> {
+    let mut rtn = Cursor::new(Vec::new());
+
+    match kind {
+        // jpeg does not support an alpha channel
+        ImageKind::Jpeg => DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_to(&mut rtn, kind.output_format())?,
+        _ => img.write_to(&mut rtn, kind.output_format())?,
+    }
+
+    Ok(rtn.into_inner())
+}
+
+/// validates an uploaded image and creates its thumbnail
+///
+/// if strip_metadata is true then the metadata is removed from the image and
+/// it is only re-encoded if an orientation has to be applied, otherwise the
+/// original data is kept as is. this is cpu heavy and should not be run on an
+/// async worker
+pub fn process(bytes: Vec<u8>, strip_metadata: bool) -> Result<ProcessedImage, ImageError> {
+    let Some(kind) = ImageKind::sniff(&bytes) else {
+        return Err(ImageError::Unsupported);
+    };
+
+    let orientation = get_orientation(&bytes);
+    let decoded = decode(&bytes, kind)?;
+    let oriented = apply_orientation(decoded, orientation);
+
+    let thumbnail_kind = match kind {
+        ImageKind::Jpeg => ImageKind::Jpeg,
+        _ => ImageKind::Png,
+    };
+    let thumbnail = encode(
+        &oriented.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE),
+        thumbnail_kind
+    )?;
+
+    let data = if !strip_metadata {
+        bytes
+    } else if orientation != 1 {
+        encode(&oriented, kind)?
+    } else {
+        match image_metadata::strip(&bytes, kind) {
+            Some(stripped) => stripped,
+            None => encode(&oriented, kind)?
+        }
+    };
+
+    Ok(ProcessedImage {
+        kind,
+        data,
+        width: oriented.width(),
+        height: oriented.height(),
+        thumbnail_kind,
+        thumbnail,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = DynamicImage::new_rgb8(width, height);
+        let mut rtn = Cursor::new(Vec::new());
+
+        img.write_to(&mut rtn, ImageOutputFormat::Png).unwrap();
+
+        rtn.into_inner()
+    }
+
+    #[test]
+    fn sniff_formats() {
+        assert_eq!(ImageKind::sniff(&[0xff, 0xd8, 0xff, 0xe0]), Some(ImageKind::Jpeg));
+        assert_eq!(ImageKind::sniff(&png(1, 1)), Some(ImageKind::Png));
+        assert_eq!(ImageKind::sniff(b"RIFF\x00\x00\x00\x00WEBPVP8 "), Some(ImageKind::WebP));
+        assert_eq!(ImageKind::sniff(b"RIFF\x00\x00\x00\x00WAVEfmt "), None);
+        assert_eq!(ImageKind::sniff(b"GIF89a"), None);
+        assert_eq!(ImageKind::sniff(&[]), None);
+    }
+
+    #[test]
+    fn process_creates_thumbnail() {
+        let processed = process(png(600, 300), true).unwrap();
+
+        assert_eq!(processed.kind, ImageKind::Png);
+        assert_eq!(processed.width, 600);
+        assert_eq!(processed.height, 300);
+        assert_eq!(processed.thumbnail_kind, ImageKind::Png);
+
+        let thumbnail = image::load_from_memory(&processed.thumbnail).unwrap();
+
+        assert_eq!(thumbnail.width(), THUMBNAIL_SIZE);
+        assert_eq!(thumbnail.height(), THUMBNAIL_SIZE / 2);
+    }
+
+    #[test]
+    fn process_keeps_original() {
+        let original = png(4, 4);
+        let processed = process(original.clone(), false).unwrap();
+
+        assert_eq!(processed.data, original);
+    }
+
+    #[test]
+    fn process_strips_without_re_encoding() {
+        let original = png(4, 4);
+        let processed = process(original.clone(), true).unwrap();
+
+        assert_eq!(processed.data, original, "an upright image without metadata should not change");
+    }
+
+    #[test]
+    fn process_rejects_unsupported() {
+        assert!(matches!(process(b"GIF89a".to_vec(), true), Err(ImageError::Unsupported)));
+    }
+
+    #[test]
+    fn process_rejects_large_dimensions() {
+        let result = process(png(MAX_DIMENSION + 1, 1), true);
+
+        assert!(matches!(
+            result,
+            Err(ImageError::Image(image::ImageError::Limits(_)))
+        ));
+    }
+
+    #[test]
+    fn orientation_defaults() {
+        assert_eq!(get_orientation(&png(1, 1)), 1);
+        assert_eq!(get_orientation(&[]), 1);
+    }
+
+    #[test]
+    fn apply_orientation_swaps_dimensions() {
+        let img = DynamicImage::new_rgb8(4, 2);
+
+        let rotated = apply_orientation(img.clone(), 6);
+        assert_eq!((rotated.width(), rotated.height()), (2, 4));
+
+        let flipped = apply_orientation(img, 2);
+        assert_eq!((flipped.width(), flipped.height()), (4, 2));
+    }
+}
@@ -3,6 +3,12 @@ use std::fmt::{Write};
 pub mod time;
 pub mod string;
 pub mod file;
+pub mod diff;
+pub mod images;
+pub mod image_metadata;
+pub mod audio;
+pub mod peaks;
+pub mod sniff;
 
 /// clones the internal value of an option and returns a new option
 #[allow(dead_code)]
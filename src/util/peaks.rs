@@ -0,0 +1,388 @@
+//! waveform peaks for audio entries
+//!
+//! peaks are computed once at a fixed rate and cached in storage beside the
+//! audio file.
+//! requests for a specific resolution are downsampled from the cached peaks.
+//! only wav files can be decoded currently. opus needs a decoder that is not
+//! available in pure rust so webm and ogg entries do not have peaks.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use crate::util::audio;
+use crate::util::sniff::AudioKind;
+
+/// the number of peaks computed for every second of audio
+pub const PEAKS_PER_SECOND: u32 = 100;
+
+const MAGIC: &[u8; 4] = b"PEAK";
+const VERSION: u8 = 1;
+
+/// the number of frames read from a file at a time
+const READ_FRAMES: usize = 4096;
+
+#[derive(Debug)]
+pub enum PeaksError {
+    /// the audio format cannot be decoded
+    Unsupported,
+    Io(io::Error),
+}
+
+impl std::fmt::Display for PeaksError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeaksError::Unsupported => write!(f, "unsupported audio format for peaks"),
+            PeaksError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PeaksError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PeaksError::Unsupported => None,
+            PeaksError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for PeaksError {
+    fn from(err: io::Error) -> PeaksError {
+        PeaksError::Io(err)
+    }
+}
+
+/// checks if peaks can be computed for the given format
+pub fn is_supported(kind: AudioKind) -> bool {
+    matches!(kind, AudioKind::Wav)
+}
+
+/// the max amplitude of every block of frames in a file
+pub struct Peaks {
+    pub sample_rate: u32,
+    /// the number of frames each peak covers
+    pub frames_per_peak: u32,
+    /// amplitudes scaled from 0.0 - 1.0 to 0 - u16::MAX
+    pub values: Vec<u16>,
+}
+
+impl Peaks {
+    pub fn duration_ms(&self) -> i64 {
+        if self.sample_rate == 0 {
+            return 0;
+        }
+
+        (self.values.len() as u64 * self.frames_per_peak as u64 * 1000 / self.sample_rate as u64) as i64
+    }
+
+    /// reduces the peaks to the given amount by taking the max of each group
+    ///
+    /// if there are fewer peaks than requested then all of them are returned
+    pub fn downsample(&self, resolution: usize) -> Vec<f32> {
+        let len = self.values.len();
+        let count = resolution.min(len);
+        let mut rtn = Vec::with_capacity(count);
+
+        for index in 0..count {
+            let start = index * len / count;
+            let end = ((index + 1) * len / count).max(start + 1);
+            let max = self.values[start..end].iter().max().copied().unwrap_or(0);
+
+            rtn.push(max as f32 / u16::MAX as f32);
+        }
+
+        rtn
+    }
+
+    /// encodes the peaks for caching
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut rtn = Vec::with_capacity(17 + self.values.len() * 2);
+        rtn.extend_from_slice(MAGIC);
+        rtn.push(VERSION);
+        rtn.extend_from_slice(&self.sample_rate.to_le_bytes());
+        rtn.extend_from_slice(&self.frames_per_peak.to_le_bytes());
+        rtn.extend_from_slice(&(self.values.len() as u32).to_le_bytes());
+
+        for value in &self.values {
+            rtn.extend_from_slice(&value.to_le_bytes());
+        }
+
+        rtn
+    }
+
+    /// decodes peaks that were created by [Peaks::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Peaks> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid peaks data");
+
+        if bytes.len() < 17 || &bytes[0..4] != MAGIC || bytes[4] != VERSION {
+            return Err(invalid());
+        }
+
+        let sample_rate = u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+        let frames_per_peak = u32::from_le_bytes([bytes[9], bytes[10], bytes[11], bytes[12]]);
+        let count = u32::from_le_bytes([bytes[13], bytes[14], bytes[15], bytes[16]]) as usize;
+        let values = &bytes[17..];
+
+        if values.len() != count * 2 {
+            return Err(invalid());
+        }
+
+        Ok(Peaks {
+            sample_rate,
+            frames_per_peak,
+            values: values.chunks_exact(2)
+                .map(|v| u16::from_le_bytes([v[0], v[1]]))
+                .collect(),
+        })
+    }
+}
+
+/// computes the peaks for an audio file
+///
+/// this decodes the whole file and should not be run on an async worker
+pub fn compute<P>(path: P, kind: AudioKind) -> Result<Peaks, PeaksError>
+where
+    P: AsRef<Path>
+{
+    match kind {
+        AudioKind::Wav => wav(path.as_ref()),
+        _ => Err(PeaksError::Unsupported),
+    }
+}
+
+/// the sample formats that can be read from a wav file
+#[derive(Clone, Copy)]
+enum Sample {
+    U8,
+    I16,
+    I24,
+    I32,
+    F32,
+    F64,
+}
+
+impl Sample {
+    fn size(&self) -> usize {
+        match self {
+            Sample::U8 => 1,
+            Sample::I16 => 2,
+            Sample::I24 => 3,
+            Sample::I32 | Sample::F32 => 4,
+            Sample::F64 => 8,
+        }
+    }
+
+    /// the absolute amplitude of a sample from 0.0 - 1.0
+    fn amplitude(&self, b: &[u8]) -> f32 {
+        let value = match self {
+            Sample::U8 => (b[0] as f32 - 128.0) / 128.0,
+            Sample::I16 => i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.0,
+            // shift into the top of an i32 to keep the sign
+            Sample::I24 => (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0,
+            Sample::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0,
+            Sample::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            Sample::F64 => f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32,
+        };
+
+        value.abs().min(1.0)
+    }
+}
+
+fn to_value(amplitude: f32) -> u16 {
+    (amplitude * u16::MAX as f32).round() as u16
+}
+
+fn wav(path: &Path) -> Result<Peaks, PeaksError> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let info = audio::read_wav_info(&mut reader, size)?;
+
+    let sample = match (info.format, info.bits_per_sample) {
+        (0x0001, 8) => Sample::U8,
+        (0x0001, 16) => Sample::I16,
+        (0x0001, 24) => Sample::I24,
+        (0x0001, 32) => Sample::I32,
+        (0x0003, 32) => Sample::F32,
+        (0x0003, 64) => Sample::F64,
+        _ => return Err(PeaksError::Unsupported),
+    };
+    let channels = info.channels as usize;
+    let frame_len = info.block_align as usize;
+
+    if channels == 0 || info.sample_rate == 0 || frame_len < sample.size() * channels {
+        return Err(PeaksError::Unsupported);
+    }
+
+    let frames_per_peak = (info.sample_rate / PEAKS_PER_SECOND).max(1);
+    let mut values = Vec::new();
+    let mut current = 0f32;
+    let mut in_peak = 0u32;
+    let mut remaining = info.data_len - info.data_len % frame_len as u64;
+    let mut buffer = vec![0u8; frame_len * READ_FRAMES];
+
+    while remaining > 0 {
+        let amount = remaining.min(buffer.len() as u64) as usize;
+        reader.read_exact(&mut buffer[..amount])?;
+
+        for frame in buffer[..amount].chunks_exact(frame_len) {
+            for channel in 0..channels {
+                let start = channel * sample.size();
+
+                current = current.max(sample.amplitude(&frame[start..start + sample.size()]));
+            }
+
+            in_peak += 1;
+
+            if in_peak == frames_per_peak {
+                values.push(to_value(current));
+                current = 0.0;
+                in_peak = 0;
+            }
+        }
+
+        remaining -= amount as u64;
+    }
+
+    if in_peak > 0 {
+        values.push(to_value(current));
+    }
+
+    Ok(Peaks {
+        sample_rate: info.sample_rate,
+        frames_per_peak,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// writes a wav file with the given sample format and data
+    fn wav_file(name: &str, format: u16, channels: u16, bits: u16, data: &[u8]) -> PathBuf {
+        let sample_rate = 8_000u32;
+        let block_align = channels * bits / 8;
+        let mut rtn = b"RIFF\x00\x00\x00\x00WAVEfmt \x10\x00\x00\x00".to_vec();
+        rtn.extend_from_slice(&format.to_le_bytes());
+        rtn.extend_from_slice(&channels.to_le_bytes());
+        rtn.extend_from_slice(&sample_rate.to_le_bytes());
+        rtn.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        rtn.extend_from_slice(&block_align.to_le_bytes());
+        rtn.extend_from_slice(&bits.to_le_bytes());
+        rtn.extend_from_slice(b"data");
+        rtn.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        rtn.extend_from_slice(data);
+
+        let path = std::env::temp_dir()
+            .join(format!("peaks_{}_{}", name, std::process::id()));
+
+        std::fs::write(&path, rtn).unwrap();
+
+        path
+    }
+
+    fn test_peaks() -> Peaks {
+        Peaks {
+            sample_rate: 8_000,
+            frames_per_peak: 80,
+            values: vec![0, 100, u16::MAX, 10, 20],
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let peaks = test_peaks();
+        let decoded = Peaks::from_bytes(&peaks.to_bytes()).unwrap();
+
+        assert_eq!(decoded.sample_rate, peaks.sample_rate);
+        assert_eq!(decoded.frames_per_peak, peaks.frames_per_peak);
+        assert_eq!(decoded.values, peaks.values);
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let bytes = test_peaks().to_bytes();
+
+        assert!(Peaks::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Peaks::from_bytes(&bytes[..16]).is_err());
+
+        let mut version = bytes.clone();
+        version[4] = VERSION + 1;
+        assert!(Peaks::from_bytes(&version).is_err());
+
+        let mut magic = bytes;
+        magic[0] = b'X';
+        assert!(Peaks::from_bytes(&magic).is_err());
+    }
+
+    #[test]
+    fn downsample_takes_max() {
+        let peaks = test_peaks();
+
+        assert_eq!(peaks.downsample(2), vec![100.0 / u16::MAX as f32, 1.0]);
+        assert_eq!(peaks.downsample(100).len(), 5);
+        assert!(peaks.downsample(0).is_empty());
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(test_peaks().duration_ms(), 50);
+        assert_eq!(Peaks { sample_rate: 0, frames_per_peak: 1, values: vec![1] }.duration_ms(), 0);
+    }
+
+    #[test]
+    fn sample_amplitudes() {
+        assert_eq!(Sample::U8.amplitude(&[128]), 0.0);
+        assert_eq!(Sample::U8.amplitude(&[0]), 1.0);
+        assert_eq!(Sample::I16.amplitude(&i16::MIN.to_le_bytes()), 1.0);
+        assert_eq!(Sample::I16.amplitude(&16_384i16.to_le_bytes()), 0.5);
+        assert_eq!(Sample::I24.amplitude(&[0x00, 0x00, 0x80]), 1.0);
+        assert_eq!(Sample::I24.amplitude(&[0x00, 0x00, 0xc0]), 0.5);
+        assert_eq!(Sample::F32.amplitude(&(-2.0f32).to_le_bytes()), 1.0);
+        assert_eq!(Sample::F64.amplitude(&0.25f64.to_le_bytes()), 0.25);
+    }
+
+    #[test]
+    fn wav_peaks() {
+        // 8khz gives 80 frames per peak. a half and a full peak for the left
+        // channel followed by a partial peak of silence
+        let mut data = Vec::new();
+
+        for frame in 0..170 {
+            let left: i16 = match frame {
+                0..=79 => 16_384,
+                80..=159 => i16::MIN,
+                _ => 0,
+            };
+
+            data.extend_from_slice(&left.to_le_bytes());
+            data.extend_from_slice(&0i16.to_le_bytes());
+        }
+
+        let path = wav_file("wav", 0x0001, 2, 16, &data);
+        let peaks = compute(&path, AudioKind::Wav).unwrap();
+
+        assert_eq!(peaks.sample_rate, 8_000);
+        assert_eq!(peaks.frames_per_peak, 80);
+        assert_eq!(peaks.values, vec![to_value(0.5), u16::MAX, 0]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unsupported_formats() {
+        assert!(is_supported(AudioKind::Wav));
+        assert!(!is_supported(AudioKind::WebM));
+
+        let path = wav_file("alaw", 0x0006, 1, 8, &[0; 10]);
+
+        assert!(matches!(compute(&path, AudioKind::Wav), Err(PeaksError::Unsupported)));
+        assert!(matches!(compute(&path, AudioKind::Ogg), Err(PeaksError::Unsupported)));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+//! detecting the format of uploaded media from its leading bytes
+//!
+//! the content-type given by a client is not trusted so uploads are checked
+//! against the magic bytes of the formats that are accepted
+
+use crate::util::images::ImageKind;
+
+/// the number of leading bytes needed to detect a format
+///
+/// most formats only need the first few bytes but an mp4 with its moov box
+/// at the start needs enough to reach the handler of its first track
+pub const SNIFF_LEN: usize = 4096;
+
+/// the video containers that can be uploaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoKind {
+    Mp4,
+    WebM,
+}
+
+impl VideoKind {
+    pub fn mime_subtype(&self) -> &'static str {
+        match self {
+            VideoKind::Mp4 => "mp4",
+            VideoKind::WebM => "webm",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VideoKind::Mp4 => "mp4",
+            VideoKind::WebM => "webm",
+        }
+    }
+}
+
+/// checks for an ebml header with a webm doctype
+fn is_webm(bytes: &[u8]) -> bool {
+    if !bytes.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+        return false;
+    }
+
+    // the doctype element (0x4282) is followed by a size and then the
+    // doctype string. matroska files use "matroska" instead
+    bytes.windows(2)
+        .position(|w| w == [0x42, 0x82])
+        .map(|index| bytes[index..].windows(4).any(|w| w == b"webm"))
+        .unwrap_or(false)
+}
+
+/// checks for an iso base media file with an mp4 compatible brand
+///
+/// files detected as [is_m4a] are only audio and are not given as video
+fn is_mp4(bytes: &[u8]) -> bool {
+    if bytes.len() < 12 || &bytes[4..8] != b"ftyp" {
+        return false;
+    }
+
+    let brand = &bytes[8..12];
+
+    let compatible = brand.starts_with(b"mp4") ||
+        brand == b"isom" ||
+        brand == b"iso2" ||
+        brand == b"avc1" ||
+        brand == b"M4V " ||
+        brand == b"qt  ";
+
+    compatible && !is_m4a(bytes)
+}
+
+/// the compatible brands listed in the ftyp box of an iso base media file
+///
+/// only the brands that are in the given bytes are returned
+fn compatible_brands(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let size = bytes.get(0..4)
+        .map(|size| u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize)
+        .unwrap_or(0);
+    let end = size.min(bytes.len());
+
+    // the major brand and minor version come before the compatible brands
+    bytes.get(16..end)
+        .unwrap_or(&[])
+        .chunks_exact(4)
+}
+
+/// the handler types of the tracks found in the given bytes
+///
+/// a handler box is the size, "hdlr", version and flags, a predefined value,
+/// and then the handler type. tracks are only found if the moov box comes
+/// before the media data
+fn handler_types(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    bytes.windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == b"hdlr")
+        .filter_map(move |(index, _)| bytes.get(index + 12..index + 16))
+}
+
+/// detects the container of a video from its leading bytes
+pub fn video(bytes: &[u8]) -> Option<VideoKind> {
+    if is_webm(bytes) {
+        Some(VideoKind::WebM)
+    } else if is_mp4(bytes) {
+        Some(VideoKind::Mp4)
+    } else {
+        None
+    }
+}
+
+/// the audio formats that can be uploaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioKind {
+    WebM,
+    Ogg,
+    Mp3,
+    Wav,
+    M4a,
+}
+
+impl AudioKind {
+    /// finds the kind for a mime subtype stored with an audio entry
+    pub fn from_mime_subtype(subtype: &str) -> Option<AudioKind> {
+        match subtype {
+            "webm" => Some(AudioKind::WebM),
+            "ogg" => Some(AudioKind::Ogg),
+            "mpeg" => Some(AudioKind::Mp3),
+            "wav" => Some(AudioKind::Wav),
+            "mp4" => Some(AudioKind::M4a),
+            _ => None
+        }
+    }
+
+    pub fn mime_subtype(&self) -> &'static str {
+        match self {
+            AudioKind::WebM => "webm",
+            AudioKind::Ogg => "ogg",
+            AudioKind::Mp3 => "mpeg",
+            AudioKind::Wav => "wav",
+            AudioKind::M4a => "mp4",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioKind::WebM => "webm",
+            AudioKind::Ogg => "ogg",
+            AudioKind::Mp3 => "mp3",
+            AudioKind::Wav => "wav",
+            AudioKind::M4a => "m4a",
+        }
+    }
+
+    /// the file extension for a stored mime subtype
+    ///
+    /// audio entries created before other formats were accepted are all webm
+    pub fn extension_for(subtype: &str) -> &'static str {
+        AudioKind::from_mime_subtype(subtype)
+            .unwrap_or(AudioKind::WebM)
+            .extension()
+    }
+}
+
+/// checks for an ogg page containing an opus identification header
+fn is_ogg_opus(bytes: &[u8]) -> bool {
+    // the first page header is 27 bytes plus one segment table entry per
+    // segment. the opus header is the first packet in that page
+    if bytes.len() < 27 || !bytes.starts_with(b"OggS") {
+        return false;
+    }
+
+    let segments = bytes[26] as usize;
+    let start = 27 + segments;
+
+    bytes.len() >= start + 8 && &bytes[start..start + 8] == b"OpusHead"
+}
+
+/// checks for an id3 tag or an mpeg audio layer 3 frame header
+fn is_mp3(bytes: &[u8]) -> bool {
+    if bytes.starts_with(b"ID3") {
+        return true;
+    }
+
+    if bytes.len() < 3 || bytes[0] != 0xff || bytes[1] & 0xe0 != 0xe0 {
+        return false;
+    }
+
+    let version = (bytes[1] >> 3) & 0x03;
+    let layer = (bytes[1] >> 1) & 0x03;
+    let bitrate = bytes[2] >> 4;
+    let sample_rate = (bytes[2] >> 2) & 0x03;
+
+    // version 0b01 and bitrate 0b1111 are reserved, layer 0b01 is layer 3,
+    // and sample rate 0b11 is reserved
+    version != 0x01 && layer == 0x01 && bitrate != 0x0f && sample_rate != 0x03
+}
+
+/// checks for a riff container with a wave form type
+fn is_wav(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE"
+}
+
+/// checks for an iso base media file that only holds audio
+///
+/// files with an audio major or compatible brand are audio. files with a
+/// generic brand (isom, mp41, mp42) are audio when every track found has a
+/// sound handler and are otherwise left to [is_mp4] so a file is only ever
+/// detected as one or the other
+fn is_m4a(bytes: &[u8]) -> bool {
+    if bytes.len() < 12 || &bytes[4..8] != b"ftyp" {
+        return false;
+    }
+
+    let is_audio_brand = |brand: &[u8]| brand == b"M4A " || brand == b"M4B ";
+
+    if is_audio_brand(&bytes[8..12]) || compatible_brands(bytes).any(is_audio_brand) {
+        return true;
+    }
+
+    let mut has_sound = false;
+
+    for handler in handler_types(bytes) {
+        match handler {
+            b"soun" => has_sound = true,
+            b"vide" => return false,
+            _ => {}
+        }
+    }
+
+    has_sound
+}
+
+/// detects the format of audio from its leading bytes
+pub fn audio(bytes: &[u8]) -> Option<AudioKind> {
+    if is_webm(bytes) {
+        Some(AudioKind::WebM)
+    } else if is_ogg_opus(bytes) {
+        Some(AudioKind::Ogg)
+    } else if is_wav(bytes) {
+        Some(AudioKind::Wav)
+    } else if is_m4a(bytes) {
+        Some(AudioKind::M4a)
+    } else if is_mp3(bytes) {
+        Some(AudioKind::Mp3)
+    } else {
+        None
+    }
+}
+
+/// the extension for stored data with the given leading bytes
+///
+/// the same data can be uploaded as different kinds of entries so the
+/// extension comes from the data alone. data that is not a known format is
+/// given "bin"
+pub fn extension(bytes: &[u8]) -> &'static str {
+    if let Some(kind) = audio(bytes) {
+        kind.extension()
+    } else if let Some(kind) = video(bytes) {
+        kind.extension()
+    } else if let Some(kind) = ImageKind::sniff(bytes) {
+        kind.extension()
+    } else {
+        "bin"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ftyp(brand: &[u8; 4]) -> Vec<u8> {
+        let mut rtn = vec![0x00, 0x00, 0x00, 0x20];
+        rtn.extend_from_slice(b"ftyp");
+        rtn.extend_from_slice(brand);
+        rtn.extend_from_slice(&[0x00; 4]);
+
+        rtn
+    }
+
+    /// an ftyp box followed by a moov box with a track for each handler
+    fn mp4_with(brand: &[u8; 4], compatible: &[&[u8; 4]], handlers: &[&[u8; 4]]) -> Vec<u8> {
+        let mut rtn = Vec::new();
+        rtn.extend_from_slice(&(16 + compatible.len() as u32 * 4).to_be_bytes());
+        rtn.extend_from_slice(b"ftyp");
+        rtn.extend_from_slice(brand);
+        rtn.extend_from_slice(&[0x00; 4]);
+
+        for brand in compatible {
+            rtn.extend_from_slice(*brand);
+        }
+
+        rtn.extend_from_slice(&[0x00, 0x00, 0x00, 0x08]);
+        rtn.extend_from_slice(b"moov");
+
+        for handler in handlers {
+            rtn.extend_from_slice(&[0x00, 0x00, 0x00, 0x21]);
+            rtn.extend_from_slice(b"hdlr");
+            rtn.extend_from_slice(&[0x00; 8]);
+            rtn.extend_from_slice(*handler);
+            rtn.extend_from_slice(&[0x00; 13]);
+        }
+
+        rtn
+    }
+
+    fn ebml(doctype: &[u8]) -> Vec<u8> {
+        let mut rtn = vec![0x1a, 0x45, 0xdf, 0xa3, 0x9f, 0x42, 0x86, 0x81, 0x01, 0x42, 0x82];
+        rtn.push(0x80 | doctype.len() as u8);
+        rtn.extend_from_slice(doctype);
+
+        rtn
+    }
+
+    fn ogg(packet: &[u8]) -> Vec<u8> {
+        let mut rtn = b"OggS".to_vec();
+        rtn.extend_from_slice(&[0x00; 22]);
+        // one segment in the segment table
+        rtn.push(0x01);
+        rtn.push(packet.len() as u8);
+        rtn.extend_from_slice(packet);
+
+        rtn
+    }
+
+    #[test]
+    fn video_formats() {
+        assert_eq!(video(&ebml(b"webm")), Some(VideoKind::WebM));
+        assert_eq!(video(&ebml(b"matroska")), None);
+
+        for brand in [b"mp41", b"mp42", b"isom", b"iso2", b"avc1", b"M4V ", b"qt  "] {
+            assert_eq!(video(&ftyp(brand)), Some(VideoKind::Mp4), "brand {:?}", brand);
+        }
+
+        assert_eq!(video(&ftyp(b"M4A ")), None);
+        assert_eq!(video(&ftyp(b"heic")), None);
+        assert_eq!(video(b"ftyp"), None);
+        assert_eq!(video(&[]), None);
+    }
+
+    #[test]
+    fn audio_formats() {
+        assert_eq!(audio(&ebml(b"webm")), Some(AudioKind::WebM));
+        assert_eq!(audio(&ogg(b"OpusHead\x01\x02")), Some(AudioKind::Ogg));
+        assert_eq!(audio(&ogg(b"\x01vorbis\x00\x00")), None);
+        assert_eq!(audio(b"RIFF\x24\x00\x00\x00WAVEfmt "), Some(AudioKind::Wav));
+        assert_eq!(audio(b"RIFF\x24\x00\x00\x00WEBPVP8 "), None);
+        assert_eq!(audio(&ftyp(b"M4A ")), Some(AudioKind::M4a));
+        assert_eq!(audio(&ftyp(b"M4B ")), Some(AudioKind::M4a));
+        assert_eq!(audio(b"ID3\x04\x00\x00"), Some(AudioKind::Mp3));
+        assert_eq!(audio(&[]), None);
+    }
+
+    #[test]
+    fn generic_brands_are_video() {
+        for brand in [b"isom", b"mp41", b"mp42"] {
+            assert_eq!(video(&ftyp(brand)), Some(VideoKind::Mp4));
+            assert_eq!(audio(&ftyp(brand)), None);
+        }
+    }
+
+    #[test]
+    fn generic_brand_audio() {
+        // written by ffmpeg and most phones as isom or mp42
+        let isom_m4a = mp4_with(b"isom", &[b"isom", b"iso2", b"mp41"], &[b"soun"]);
+        assert_eq!(audio(&isom_m4a), Some(AudioKind::M4a));
+        assert_eq!(video(&isom_m4a), None);
+        assert_eq!(extension(&isom_m4a), "m4a");
+
+        let mp42_m4a = mp4_with(b"mp42", &[b"mp42", b"M4A "], &[]);
+        assert_eq!(audio(&mp42_m4a), Some(AudioKind::M4a));
+        assert_eq!(video(&mp42_m4a), None);
+
+        let with_video = mp4_with(b"isom", &[b"isom"], &[b"vide", b"soun"]);
+        assert_eq!(audio(&with_video), None);
+        assert_eq!(video(&with_video), Some(VideoKind::Mp4));
+        assert_eq!(extension(&with_video), "mp4");
+
+        // metadata handlers are not tracks
+        let metadata_only = mp4_with(b"isom", &[b"isom"], &[b"mdir"]);
+        assert_eq!(audio(&metadata_only), None);
+        assert_eq!(video(&metadata_only), Some(VideoKind::Mp4));
+    }
+
+    #[test]
+    fn mp3_frame_headers() {
+        // mpeg 1 layer 3, 128kbps, 44.1khz
+        assert!(is_mp3(&[0xff, 0xfb, 0x90, 0x64]));
+        // mpeg 2 layer 3
+        assert!(is_mp3(&[0xff, 0xf3, 0x90, 0x64]));
+        // layer 2
+        assert!(!is_mp3(&[0xff, 0xfd, 0x90, 0x64]));
+        // reserved version
+        assert!(!is_mp3(&[0xff, 0xeb, 0x90, 0x64]));
+        // reserved bitrate
+        assert!(!is_mp3(&[0xff, 0xfb, 0xf0, 0x64]));
+        // reserved sample rate
+        assert!(!is_mp3(&[0xff, 0xfb, 0x9c, 0x64]));
+        assert!(!is_mp3(&[0xff, 0xfb]));
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(AudioKind::extension_for("mpeg"), "mp3");
+        assert_eq!(AudioKind::extension_for("mp4"), "m4a");
+        assert_eq!(AudioKind::extension_for("unknown"), "webm");
+        assert_eq!(AudioKind::from_mime_subtype("wav"), Some(AudioKind::Wav));
+    }
+
+    #[test]
+    fn data_extensions() {
+        // each kind of upload stores its data with the same extension that
+        // the data alone is given
+        let webm = ebml(b"webm");
+        assert_eq!(extension(&webm), video(&webm).unwrap().extension());
+        assert_eq!(extension(&webm), audio(&webm).unwrap().extension());
+
+        let mp4 = ftyp(b"isom");
+        assert_eq!(extension(&mp4), video(&mp4).unwrap().extension());
+
+        let m4a = ftyp(b"M4A ");
+        assert_eq!(extension(&m4a), audio(&m4a).unwrap().extension());
+
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10];
+        assert_eq!(extension(&jpeg), "jpg");
+        assert_eq!(extension(b"RIFF\x24\x00\x00\x00WEBPVP8 "), "webp");
+        assert_eq!(extension(b"RIFF\x24\x00\x00\x00WAVEfmt "), "wav");
+        assert_eq!(extension(b"plain text"), "bin");
+        assert_eq!(extension(&[]), "bin");
+    }
+}
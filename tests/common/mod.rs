@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use reqwest::{Url, StatusCode, cookie::{Jar, CookieStore}, blocking::{Client, RequestBuilder}};
@@ -53,6 +54,87 @@ pub mod json {
     }
 }
 
+/// parses the json body of a response and panics with msg if the status is
+/// not 200
+pub fn expect_ok(res: reqwest::blocking::Response, msg: &str) -> Value {
+    let status = res.status();
+    let json: Value = result::expect_with_err(res.json(), "unknown response body");
+
+    if status != StatusCode::OK {
+        panic!("{}\n{:#?}", msg, json);
+    }
+
+    json
+}
+
+/// retrieves the id of a record returned in a response body
+pub fn get_id(json: &Value) -> i64 {
+    let Some(id) = json["data"]["id"].as_i64() else {
+        panic!("missing id in response. {:#?}", json);
+    };
+
+    id
+}
+
+/// creates a client for the default test user with an active session
+pub fn logged_in_client() -> UserClient {
+    let mut client = UserClient::new(
+        User::with_password("password_only", "password_only"),
+        get_base_url()
+    );
+    client.get_session();
+
+    client
+}
+
+/// creates an empty entry for the current day and returns its id
+pub fn create_entry(client: &UserClient) -> i64 {
+    let now = unix_epoch_sec().expect("failed to get current unix epoch");
+    let entry = expect_ok(
+        result::expect_with_err(
+            client.post("/entries")
+                .json(&serde_json::json!({"entry": {"day": now}}))
+                .send(),
+            "failed to send create entry request"
+        ),
+        "failed to create entry"
+    );
+
+    get_id(&entry)
+}
+
+/// permanently removes an entry created by a test
+///
+/// the first delete moves the entry to the trash and the second purges it.
+/// errors are ignored since this is only cleanup
+pub fn purge_entry(client: &UserClient, entry_id: i64) {
+    for _ in 0..2 {
+        let _ = client.delete(format!("/entries/{}", entry_id)).send();
+    }
+}
+
+/// a word made of letters that no other test run will have used
+///
+/// useful for names that have to be unique and for searching text
+pub fn unique_word() -> String {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let mut value = nanos ^ ((std::process::id() as u64) << 40) ^ count.wrapping_mul(0x9e37_79b9);
+    let mut rtn = String::from("zq");
+
+    while value > 0 {
+        rtn.push((b'a' + (value % 26) as u8) as char);
+        value /= 26;
+    }
+
+    rtn
+}
+
 pub fn unix_epoch_sec() -> Option<u64> {
     match std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH) {
         Ok(d) => Some(d.as_secs()),
@@ -60,6 +142,38 @@ pub fn unix_epoch_sec() -> Option<u64> {
     }
 }
 
+/// the sample rate of the files made by [wav_file]
+pub const WAV_SAMPLE_RATE: u32 = 8000;
+
+/// builds a mono 16 bit wav file of a simple square wave
+pub fn wav_file(seconds: u32) -> Vec<u8> {
+    let samples: Vec<i16> = (0..WAV_SAMPLE_RATE * seconds)
+        .map(|i| if (i / 20) % 2 == 0 { 8_000 } else { -8_000 })
+        .collect();
+    let data_len = samples.len() as u32 * 2;
+    let mut rtn = Vec::with_capacity(44 + data_len as usize);
+
+    rtn.extend_from_slice(b"RIFF");
+    rtn.extend_from_slice(&(36 + data_len).to_le_bytes());
+    rtn.extend_from_slice(b"WAVE");
+    rtn.extend_from_slice(b"fmt ");
+    rtn.extend_from_slice(&16u32.to_le_bytes());
+    rtn.extend_from_slice(&1u16.to_le_bytes());
+    rtn.extend_from_slice(&1u16.to_le_bytes());
+    rtn.extend_from_slice(&WAV_SAMPLE_RATE.to_le_bytes());
+    rtn.extend_from_slice(&(WAV_SAMPLE_RATE * 2).to_le_bytes());
+    rtn.extend_from_slice(&2u16.to_le_bytes());
+    rtn.extend_from_slice(&16u16.to_le_bytes());
+    rtn.extend_from_slice(b"data");
+    rtn.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in samples {
+        rtn.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    rtn
+}
+
 pub fn create_cookie_client_blocking<C>(store: Arc<C>) -> Client
 where
     C: CookieStore + 'static
@@ -80,9 +194,12 @@ pub fn create_client_blocking() -> Client {
 pub struct TestArgs {
     host: Option<String>,
     port: Option<u16>,
+    /// the storage directory of the server for tests that check what is
+    /// kept on disk
+    storage: Option<PathBuf>,
 }
 
-pub fn get_base_url() -> Url {
+pub fn get_test_args() -> TestArgs {
     let mut path = result::expect_with_err(
         std::env::current_dir(),
         "failed to get current working directory"
@@ -91,7 +208,7 @@ pub fn get_base_url() -> Url {
     path.push("test_args");
     path.set_extension("json");
 
-    let args: TestArgs = if result::expect_with_err(path.try_exists(), "failed to check if file exists") {
+    if result::expect_with_err(path.try_exists(), "failed to check if file exists") {
         let file = result::expect_with_err(
             std::fs::OpenOptions::new()
                 .read(true)
@@ -108,9 +225,13 @@ pub fn get_base_url() -> Url {
         TestArgs {
             host: None,
             port: None,
+            storage: None,
         }
-    };
+    }
+}
 
+pub fn get_base_url() -> Url {
+    let args = get_test_args();
     let mut url = Url::parse("http://localhost/").unwrap();
 
     if let Some(host) = args.host {
@@ -125,6 +246,18 @@ pub fn get_base_url() -> Url {
     url
 }
 
+/// the storage directory of the server from test_args.json
+///
+/// panics if it is not given since the tests that need it cannot run
+/// without it
+pub fn get_storage_dir() -> PathBuf {
+    let Some(storage) = get_test_args().storage else {
+        panic!("no storage directory given. set \"storage\" in test_args.json to the storage.directory of the server");
+    };
+
+    storage
+}
+
 #[derive(Serialize)]
 pub struct PasswordLogin {
     username: String,
@@ -151,6 +284,20 @@ pub struct User {
     totp: Option<Totp>,
 }
 
+impl User {
+    pub fn with_password<U, P>(username: U, password: P) -> User
+    where
+        U: Into<String>,
+        P: Into<String>,
+    {
+        User {
+            username: username.into(),
+            password: password.into(),
+            totp: None,
+        }
+    }
+}
+
 pub struct UserClient {
     client: Client,
     cookie_jar: Arc<Jar>,
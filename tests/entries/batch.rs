@@ -0,0 +1,84 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+
+fn post_batch(client: &UserClient, operations: Value) -> Vec<Value> {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.post("/entries/batch")
+                .json(&json!({"operations": operations}))
+                .send(),
+            "failed to send batch request"
+        ),
+        "failed to run batch"
+    );
+
+    let Some(results) = json["data"].as_array() else {
+        panic!("missing batch results. {:#?}", json);
+    };
+
+    results.clone()
+}
+
+#[test]
+fn batch_operations() {
+    let client = common::logged_in_client();
+    let now = common::unix_epoch_sec().unwrap();
+
+    let results = post_batch(&client, json!([
+        {"op": "create", "data": {"entry": {"day": now}}},
+        {"op": "create", "data": {"entry": {"day": now}, "text_entries": [{"thought": "batched", "private": false}]}},
+        {"op": "update", "id": i32::MAX, "data": {"entry": {"day": now}}},
+    ]));
+
+    assert_eq!(results.len(), 3, "every operation should have a result");
+    assert!(results[0]["ok"].as_bool().unwrap() && results[1]["ok"].as_bool().unwrap());
+    assert_eq!(results[1]["entry"]["text"][0]["thought"], json!("batched"));
+    assert_eq!(results[2]["ok"], json!(false), "updating a missing entry should fail");
+    assert_eq!(results[2]["error"]["status"], json!(404));
+    assert_eq!(results[2]["error"]["error"], json!("EntryNotFound"));
+
+    let first = &results[0]["entry"];
+    let second = &results[1]["entry"];
+    let first_id = first["id"].as_i64().unwrap();
+    let second_id = second["id"].as_i64().unwrap();
+    let version = first["version"].as_i64().unwrap();
+
+    let results = post_batch(&client, json!([
+        {"op": "update", "id": first_id, "version": version, "data": {
+            "entry": {"day": now},
+            "text_entries": [{"thought": "updated in a batch", "private": false}]
+        }},
+        {"op": "update", "id": first_id, "version": version, "data": {"entry": {"day": now}}},
+        {"op": "delete", "id": second_id, "version": second["version"]},
+    ]));
+
+    assert_eq!(results[0]["ok"], json!(true), "update with the current version should pass");
+    assert_eq!(results[0]["entry"]["version"].as_i64(), Some(version + 1));
+
+    assert_eq!(results[1]["ok"], json!(false), "update with an old version should fail");
+    assert_eq!(results[1]["error"]["status"], json!(412));
+    assert_eq!(results[1]["error"]["error"], json!("PreconditionFailed"));
+    assert_eq!(results[1]["entry"]["version"].as_i64(), Some(version + 1), "the current entry should be given");
+    assert_eq!(results[1]["entry"]["text"][0]["thought"], json!("updated in a batch"));
+
+    assert_eq!(results[2]["ok"], json!(true));
+    assert_eq!(results[2]["id"].as_i64(), Some(second_id), "deletes should give the deleted id");
+
+    let results = post_batch(&client, json!([
+        {"op": "delete", "id": first_id, "version": version},
+    ]));
+
+    assert_eq!(results[0]["error"]["status"], json!(412), "delete with an old version should fail");
+
+    let res = common::result::expect_with_err(
+        client.get(format!("/entries/{}", first_id)).send(),
+        "failed to send get entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::OK, "failed operations should be rolled back");
+
+    common::purge_entry(&client, first_id);
+    common::purge_entry(&client, second_id);
+}
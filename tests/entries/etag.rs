@@ -0,0 +1,114 @@
+use reqwest::StatusCode;
+use reqwest::blocking::Response;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+
+fn get_etag(res: &Response) -> String {
+    res.headers()
+        .get("etag")
+        .and_then(|v| v.to_str().ok())
+        .expect("missing etag")
+        .to_owned()
+}
+
+fn get_entry(client: &UserClient, entry_id: i64) -> (String, Value) {
+    let res = common::result::expect_with_err(
+        client.get(format!("/entries/{}", entry_id)).send(),
+        "failed to send get entry request"
+    );
+    let etag = get_etag(&res);
+
+    (etag, common::expect_ok(res, "failed to get entry"))
+}
+
+fn put_entry(client: &UserClient, entry_id: i64, if_match: &str) -> Response {
+    common::result::expect_with_err(
+        client.put(format!("/entries/{}", entry_id))
+            .header("if-match", if_match)
+            .json(&json!({
+                "entry": {"day": common::unix_epoch_sec().unwrap()},
+                "text_entries": [{"thought": "matched", "private": false}]
+            }))
+            .send(),
+        "failed to send update entry request"
+    )
+}
+
+#[test]
+fn if_match_updates() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let (etag, entry) = get_entry(&client, entry_id);
+
+    assert_eq!(
+        etag,
+        format!("\"{}.{}\"", entry_id, entry["data"]["version"]),
+        "the etag should be the id and version of the entry"
+    );
+
+    let res = put_entry(&client, entry_id, &etag);
+
+    assert_eq!(res.status(), StatusCode::OK, "updates with the current etag should pass");
+
+    let updated_etag = get_etag(&res);
+
+    assert_ne!(updated_etag, etag, "updates should change the etag");
+    assert_eq!(get_entry(&client, entry_id).0, updated_etag, "the etag should match the stored entry");
+
+    let res = put_entry(&client, entry_id, &etag);
+
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED, "updates with an old etag should fail");
+    assert_eq!(get_etag(&res), updated_etag, "the current etag should be given");
+
+    let json: Value = common::result::expect_with_err(res.json(), "failed to parse precondition json");
+
+    assert_eq!(json["error"], json!("PreconditionFailed"));
+    assert_eq!(json["data"]["text"][0]["thought"], json!("matched"), "the current entry should be given");
+
+    assert_eq!(put_entry(&client, entry_id, "*").status(), StatusCode::OK, "any etag should match");
+
+    let res = put_entry(&client, entry_id, "not an etag");
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "invalid If-Match headers should be rejected");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn attachments_change_the_etag() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let (etag, _) = get_entry(&client, entry_id);
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/files?name=etag.txt", entry_id))
+                .body("changes the entry version")
+                .send(),
+            "failed to send file upload request"
+        ),
+        "failed to upload file"
+    );
+
+    let res = common::result::expect_with_err(
+        client.delete(format!("/entries/{}", entry_id))
+            .header("if-match", etag.as_str())
+            .send(),
+        "failed to send delete entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED, "uploads should change the etag");
+
+    let (etag, _) = get_entry(&client, entry_id);
+    let res = common::result::expect_with_err(
+        client.delete(format!("/entries/{}", entry_id))
+            .header("if-match", etag.as_str())
+            .send(),
+        "failed to send delete entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::OK, "deletes with the current etag should pass");
+
+    common::purge_entry(&client, entry_id);
+}
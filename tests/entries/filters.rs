@@ -0,0 +1,145 @@
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common;
+use super::{
+    post_entry,
+    list_entries,
+    sorted_ids,
+    create_tag,
+    create_field,
+    remove_tags_and_fields,
+};
+
+#[test]
+fn tag_expressions() {
+    let client = common::logged_in_client();
+    let a = create_tag(&client);
+    let b = create_tag(&client);
+    let now = common::unix_epoch_sec().unwrap();
+
+    let mut ids = Vec::new();
+
+    for tags in [vec![a, b], vec![a], vec![b]] {
+        let entry = post_entry(&client, json!({"entry": {"day": now}, "tags": tags}));
+
+        ids.push(entry["id"].as_i64().unwrap());
+    }
+
+    let (both, only_a, only_b) = (ids[0], ids[1], ids[2]);
+    let mut all = ids.clone();
+    all.sort_unstable();
+
+    for (expr, expected) in [
+        (format!("{} and {}", a, b), vec![both]),
+        (format!("{} and not {}", a, b), vec![only_a]),
+        (format!("({0} or {1}) and !{0}", a, b), vec![only_b]),
+        (format!("{},{}", a, b), all.clone()),
+        (format!("{} | {}", a, b), all),
+    ] {
+        let listed = list_entries(&client, &[("tags", expr.as_str())]);
+
+        assert_eq!(sorted_ids(&listed), expected, "tag expression: {}", expr);
+    }
+
+    let res = common::result::expect_with_err(
+        client.get("/entries").query(&[("tags", format!("{} and (", a))]).send(),
+        "failed to send list entries request"
+    );
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "an invalid expression should be rejected");
+
+    for id in ids {
+        common::purge_entry(&client, id);
+    }
+
+    remove_tags_and_fields(&client, &[a, b], &[]);
+}
+
+#[test]
+fn field_filters() {
+    let client = common::logged_in_client();
+    let number = create_field(&client, json!({"type": "Integer"}));
+    let range = create_field(&client, json!({"type": "IntegerRange"}));
+    let now = common::unix_epoch_sec().unwrap();
+
+    let low = post_entry(&client, json!({
+        "entry": {"day": now},
+        "custom_field_entries": [
+            {"field": number, "value": {"type": "Integer", "value": 3}},
+            {"field": range, "value": {"type": "IntegerRange", "low": 1, "high": 5}}
+        ]
+    }))["id"].as_i64().unwrap();
+    let high = post_entry(&client, json!({
+        "entry": {"day": now},
+        "custom_field_entries": [
+            {"field": number, "value": {"type": "Integer", "value": 7}}
+        ]
+    }))["id"].as_i64().unwrap();
+
+    for (filter, expected) in [
+        (format!("{}>5", number), vec![high]),
+        (format!("{}<=3", number), vec![low]),
+        (format!("{}.value!=3", number), vec![high]),
+        (format!("{0}>1,{0}<5", number), vec![low]),
+        (format!("{}.high>=5", range), vec![low]),
+        (format!("{}.low>1", range), vec![]),
+    ] {
+        let listed = list_entries(&client, &[("fields", filter.as_str())]);
+
+        assert_eq!(sorted_ids(&listed), expected, "field filter: {}", filter);
+    }
+
+    let res = common::result::expect_with_err(
+        client.get("/entries").query(&[("fields", format!("{}~3", number))]).send(),
+        "failed to send list entries request"
+    );
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "an invalid filter should be rejected");
+
+    common::purge_entry(&client, low);
+    common::purge_entry(&client, high);
+
+    remove_tags_and_fields(&client, &[], &[number, range]);
+}
+
+#[test]
+fn text_and_date_filters() {
+    let client = common::logged_in_client();
+    let tag = create_tag(&client);
+    let tag_str = tag.to_string();
+    let now = common::unix_epoch_sec().unwrap();
+    let day = 24 * 60 * 60;
+
+    let older = post_entry(&client, json!({
+        "entry": {"day": now - 10 * day},
+        "tags": [tag],
+        "text_entries": [{"thought": "older entry", "private": false}]
+    }))["id"].as_i64().unwrap();
+    let newer = post_entry(&client, json!({
+        "entry": {"day": now},
+        "tags": [tag]
+    }))["id"].as_i64().unwrap();
+
+    let yesterday = (now - day).to_string();
+    let last_week = (now - 5 * day).to_string();
+
+    for (mut query, expected) in [
+        (vec![("has_text", "true")], vec![older]),
+        (vec![("has_text", "false")], vec![newer]),
+        (vec![("has_audio", "true")], vec![]),
+        (vec![("from", yesterday.as_str())], vec![newer]),
+        (vec![("to", last_week.as_str())], vec![older]),
+    ] {
+        query.push(("tags", tag_str.as_str()));
+
+        let listed = list_entries(&client, &query);
+
+        assert_eq!(sorted_ids(&listed), expected, "query: {:?}", query);
+    }
+
+    common::purge_entry(&client, older);
+    common::purge_entry(&client, newer);
+
+    remove_tags_and_fields(&client, &[tag], &[]);
+}
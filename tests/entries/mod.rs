@@ -0,0 +1,105 @@
+//! entry tests
+//!
+//! the entries created are purged at the end of each test but the tests
+//! still expect the test user to not be changed by anything else while they
+//! run
+
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+
+mod search;
+mod filters;
+mod pagination;
+mod trash;
+mod revisions;
+mod per_day;
+mod templates;
+mod batch;
+mod sync;
+mod etag;
+
+/// creates an entry from the given body and returns the created entry
+fn post_entry(client: &UserClient, body: Value) -> Value {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.post("/entries").json(&body).send(),
+            "failed to send create entry request"
+        ),
+        "failed to create entry"
+    );
+
+    json["data"].clone()
+}
+
+/// creates a tag with a unique title and returns its id
+fn create_tag(client: &UserClient) -> i64 {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.post("/tags")
+                .json(&json!({"title": common::unique_word(), "color": "#336699"}))
+                .send(),
+            "failed to send create tag request"
+        ),
+        "failed to create tag"
+    );
+
+    common::get_id(&json)
+}
+
+/// creates a custom field with a unique name and returns its id
+fn create_field(client: &UserClient, config: Value) -> i64 {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.post("/custom_fields")
+                .json(&json!({"name": common::unique_word(), "config": config, "order": 0}))
+                .send(),
+            "failed to send create custom field request"
+        ),
+        "failed to create custom field"
+    );
+
+    common::get_id(&json)
+}
+
+/// removes tags and custom fields created by a test
+///
+/// errors are ignored since this is only cleanup
+fn remove_tags_and_fields(client: &UserClient, tags: &[i64], fields: &[i64]) {
+    for tag in tags {
+        let _ = client.delete(format!("/tags/{}", tag)).send();
+    }
+
+    for field in fields {
+        let _ = client.delete(format!("/custom_fields/{}", field)).send();
+    }
+}
+
+/// lists the entries of the test user with the given query params
+fn list_entries(client: &UserClient, query: &[(&str, &str)]) -> Value {
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.get("/entries").query(query).send(),
+            "failed to send list entries request"
+        ),
+        "failed to list entries"
+    )
+}
+
+/// the sorted ids of the entries in a list response
+fn sorted_ids(json: &Value) -> Vec<i64> {
+    let mut rtn = listed_ids(json);
+    rtn.sort_unstable();
+    rtn
+}
+
+/// the ids of the entries in a list response
+fn listed_ids(json: &Value) -> Vec<i64> {
+    let Some(list) = json["data"].as_array() else {
+        panic!("missing entries list. {:#?}", json);
+    };
+
+    list.iter()
+        .map(|entry| entry["id"].as_i64().expect("missing entry id"))
+        .collect()
+}
@@ -0,0 +1,90 @@
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common;
+use super::{post_entry, list_entries, listed_ids, create_tag, remove_tags_and_fields};
+
+#[test]
+fn cursor_pages_cover_every_entry() {
+    let client = common::logged_in_client();
+    let tag = create_tag(&client);
+    let tag_str = tag.to_string();
+    let now = common::unix_epoch_sec().unwrap();
+
+    // two entries share a day so the id has to break the tie
+    let mut ids = Vec::new();
+
+    for offset in [0, 0, 60, 120, 180] {
+        let entry = post_entry(&client, json!({"entry": {"day": now - offset}, "tags": [tag]}));
+
+        ids.push(entry["id"].as_i64().unwrap());
+    }
+
+    // newest first with the later id first on the same day
+    let expected = vec![ids[1], ids[0], ids[2], ids[3], ids[4]];
+    let mut pages = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let mut query = vec![("tags", tag_str.as_str()), ("limit", "2")];
+
+        if let Some(cursor) = cursor.as_ref() {
+            query.push(("cursor", cursor.as_str()));
+        }
+
+        let page = list_entries(&client, &query);
+        let page_ids = listed_ids(&page);
+
+        assert!(page_ids.len() <= 2, "page should not be larger than the limit. {:#?}", page);
+
+        pages.push(page_ids);
+
+        match page["cursor"]["next"].as_str() {
+            Some(next) => cursor = Some(next.to_owned()),
+            None => break,
+        }
+
+        assert!(pages.len() < 5, "pages did not end. {:#?}", pages);
+    }
+
+    assert_eq!(pages.concat(), expected, "pages should list every entry once in order");
+    assert_eq!(pages.len(), 3);
+
+    // going back from the last page gives the page before it
+    let last = list_entries(&client, &[
+        ("tags", tag_str.as_str()),
+        ("limit", "2"),
+        ("cursor", cursor.as_deref().unwrap()),
+    ]);
+    let Some(prev) = last["cursor"]["prev"].as_str() else {
+        panic!("missing prev cursor on the last page. {:#?}", last);
+    };
+    let back = list_entries(&client, &[("tags", tag_str.as_str()), ("limit", "2"), ("cursor", prev)]);
+
+    assert_eq!(listed_ids(&back), pages[1], "prev should return the previous page");
+
+    for id in ids {
+        common::purge_entry(&client, id);
+    }
+
+    remove_tags_and_fields(&client, &[tag], &[]);
+}
+
+#[test]
+fn invalid_limits_and_cursors() {
+    let client = common::logged_in_client();
+
+    for query in [
+        [("limit", "0")],
+        [("limit", "1001")],
+        [("cursor", "not a cursor")],
+        [("cursor", "bi4xLjI")],
+    ] {
+        let res = common::result::expect_with_err(
+            client.get("/entries").query(&query).send(),
+            "failed to send list entries request"
+        );
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "query should be rejected: {:?}", query);
+    }
+}
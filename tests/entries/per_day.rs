@@ -0,0 +1,69 @@
+use serde_json::json;
+
+use crate::common;
+use super::{post_entry, list_entries, listed_ids, create_tag, remove_tags_and_fields};
+
+#[test]
+fn same_day_entries_keep_their_time() {
+    let client = common::logged_in_client();
+    let tag = create_tag(&client);
+    let tag_str = tag.to_string();
+    let now = common::unix_epoch_sec().unwrap();
+    let hour = 60 * 60;
+    // midnight a few days ago so the times do not depend on when this runs
+    let day = now - now % (24 * hour) - 3 * 24 * hour;
+
+    let yesterday = post_entry(&client, json!({"entry": {"day": day - 12 * hour}, "tags": [tag]}));
+    let morning = post_entry(&client, json!({
+        "entry": {"day": day + 8 * hour},
+        "tags": [tag],
+        "markers": [{"title": "morning"}]
+    }));
+    let evening = post_entry(&client, json!({"entry": {"day": day + 20 * hour}, "tags": [tag]}));
+
+    let yesterday_id = yesterday["id"].as_i64().unwrap();
+    let morning_id = morning["id"].as_i64().unwrap();
+    let evening_id = evening["id"].as_i64().unwrap();
+    let marker_id = morning["markers"][0]["id"].as_i64().expect("missing marker id").to_string();
+
+    let fetched = common::expect_ok(
+        common::result::expect_with_err(
+            client.get(format!("/entries/{}", morning_id)).send(),
+            "failed to send get entry request"
+        ),
+        "failed to get entry"
+    );
+
+    assert_eq!(fetched["data"]["day"], morning["day"], "the time of day should be kept");
+    assert_ne!(morning["day"], evening["day"]);
+
+    let listed = list_entries(&client, &[("tags", tag_str.as_str())]);
+
+    assert_eq!(
+        listed_ids(&listed),
+        vec![evening_id, morning_id, yesterday_id],
+        "entries on the same day should be ordered by time"
+    );
+
+    let from = list_entries(&client, &[("tags", tag_str.as_str()), ("from_marker", marker_id.as_str())]);
+
+    assert_eq!(
+        listed_ids(&from),
+        vec![evening_id, morning_id],
+        "from a marker should include later entries on the same day"
+    );
+
+    let to = list_entries(&client, &[("tags", tag_str.as_str()), ("to_marker", marker_id.as_str())]);
+
+    assert_eq!(
+        listed_ids(&to),
+        vec![morning_id, yesterday_id],
+        "to a marker should not include later entries on the same day"
+    );
+
+    for id in [yesterday_id, morning_id, evening_id] {
+        common::purge_entry(&client, id);
+    }
+
+    remove_tags_and_fields(&client, &[tag], &[]);
+}
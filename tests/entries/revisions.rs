@@ -0,0 +1,131 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+use super::post_entry;
+
+fn get_json(client: &UserClient, url: &str) -> Value {
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.get(url).send(),
+            "failed to send revisions request"
+        ),
+        "failed to retrieve revisions"
+    )
+}
+
+fn update_text(client: &UserClient, entry_id: i64, day: u64, text_id: i64, thought: &str) {
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.put(format!("/entries/{}", entry_id))
+                .json(&json!({
+                    "entry": {"day": day},
+                    "text_entries": [{"id": text_id, "thought": thought, "private": false}]
+                }))
+                .send(),
+            "failed to send update entry request"
+        ),
+        "failed to update entry"
+    );
+}
+
+#[test]
+fn edits_create_revisions() {
+    let client = common::logged_in_client();
+    let now = common::unix_epoch_sec().unwrap();
+    let entry = post_entry(&client, json!({
+        "entry": {"day": now},
+        "text_entries": [{"thought": "the quick brown fox", "private": false}]
+    }));
+    let entry_id = entry["id"].as_i64().unwrap();
+    let text_id = entry["text"][0]["id"].as_i64().expect("missing text entry id");
+    let url = format!("/entries/{}/text/{}/revisions", entry_id, text_id);
+
+    update_text(&client, entry_id, now, text_id, "the slow brown fox");
+    // saving the same text again does not create a revision
+    update_text(&client, entry_id, now, text_id, "the slow brown fox");
+
+    let revisions = get_json(&client, &url);
+    let list = revisions["data"].as_array().cloned().unwrap_or_default();
+    let thoughts: Vec<&str> = list.iter()
+        .map(|rev| rev["thought"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(thoughts, ["the quick brown fox", "the slow brown fox"], "revisions should be oldest first");
+
+    let from = list[0]["id"].as_i64().unwrap();
+    let to = list[1]["id"].as_i64().unwrap();
+    let diff = get_json(&client, &format!("{}/diff?from={}&to={}", url, from, to));
+    let changes = diff["data"]["changes"].as_array().cloned().unwrap_or_default();
+
+    assert!(
+        changes.contains(&json!({"kind": "removed", "value": "quick"})),
+        "the replaced word should be removed. {:#?}",
+        changes
+    );
+    assert!(
+        changes.contains(&json!({"kind": "added", "value": "slow"})),
+        "the new word should be added. {:#?}",
+        changes
+    );
+
+    let rebuilt: String = changes.iter()
+        .filter(|change| change["kind"] != "removed")
+        .map(|change| change["value"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(rebuilt, "the slow brown fox", "kept and added text should make the newer revision");
+
+    let res = common::result::expect_with_err(
+        client.get(format!("{}/diff?from={}&to={}", url, from, to + 1_000_000)).send(),
+        "failed to send revision diff request"
+    );
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "unknown revisions should not be found");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn removed_text_keeps_revisions() {
+    let client = common::logged_in_client();
+    let now = common::unix_epoch_sec().unwrap();
+    let entry = post_entry(&client, json!({
+        "entry": {"day": now},
+        "text_entries": [{"thought": "dropped text", "private": false}]
+    }));
+    let entry_id = entry["id"].as_i64().unwrap();
+    let text_id = entry["text"][0]["id"].as_i64().expect("missing text entry id");
+
+    update_text(&client, entry_id, now, text_id, "dropped text edited");
+
+    // leaving the text entry out of an update removes it
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.put(format!("/entries/{}", entry_id))
+                .json(&json!({
+                    "entry": {"day": now},
+                    "text_entries": [{"thought": "replacement text", "private": false}]
+                }))
+                .send(),
+            "failed to send update entry request"
+        ),
+        "failed to update entry"
+    );
+
+    let revisions = get_json(&client, &format!("/entries/{}/text/{}/revisions", entry_id, text_id));
+    let list = revisions["data"].as_array().cloned().unwrap_or_default();
+    let thoughts: Vec<&str> = list.iter()
+        .map(|rev| rev["thought"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(
+        thoughts,
+        ["dropped text", "dropped text edited", "dropped text edited"],
+        "revisions of removed text should be kept"
+    );
+    assert_eq!(list[1]["removed"], json!(false));
+    assert_eq!(list[2]["removed"], json!(true), "the last revision should mark the text as removed");
+
+    common::purge_entry(&client, entry_id);
+}
@@ -0,0 +1,123 @@
+use serde_json::json;
+
+use crate::common;
+use super::{post_entry, list_entries, listed_ids};
+
+#[test]
+fn search_finds_matching_entries() {
+    let client = common::logged_in_client();
+    let word = common::unique_word();
+    let now = common::unix_epoch_sec().unwrap();
+    let entry = post_entry(&client, json!({
+        "entry": {"day": now},
+        "text_entries": [
+            {"thought": format!("looking for {} in here", word), "private": false},
+            {"thought": "nothing to find here", "private": false}
+        ]
+    }));
+    let other = post_entry(&client, json!({
+        "entry": {"day": now - 60},
+        "text_entries": [{"thought": "something else entirely", "private": false}]
+    }));
+    let entry_id = entry["id"].as_i64().unwrap();
+
+    let found = list_entries(&client, &[("q", word.as_str())]);
+
+    assert_eq!(listed_ids(&found), vec![entry_id], "only the matching entry should be found");
+
+    let search = &found["data"][0]["search"];
+    let matches = search["matches"].as_array().cloned().unwrap_or_default();
+
+    assert!(search["rank"].as_f64().map_or(false, |rank| rank > 0.0), "matches should be ranked. {:#?}", found);
+    assert_eq!(matches.len(), 1, "only the matching text entry should be included. {:#?}", found);
+
+    let snippet = matches[0]["snippet"].as_str().unwrap();
+
+    assert!(snippet.contains(&format!("<mark>{}</mark>", word)), "match should be marked. {}", snippet);
+
+    common::purge_entry(&client, entry_id);
+    common::purge_entry(&client, other["id"].as_i64().unwrap());
+}
+
+#[test]
+fn search_escapes_snippets() {
+    let client = common::logged_in_client();
+    let word = common::unique_word();
+    let now = common::unix_epoch_sec().unwrap();
+    let entry = post_entry(&client, json!({
+        "entry": {"day": now},
+        "text_entries": [
+            {"thought": format!("{} <script>alert(1)</script> & \"quoted\"", word), "private": false},
+            {"thought": "nothing to find here", "private": false}
+        ]
+    }));
+    let entry_id = entry["id"].as_i64().unwrap();
+
+    let found = list_entries(&client, &[("q", word.as_str())]);
+
+    assert_eq!(listed_ids(&found), vec![entry_id], "only the matching entry should be found");
+
+    let matches = found["data"][0]["search"]["matches"].as_array().cloned().unwrap_or_default();
+
+    assert_eq!(matches.len(), 1, "only the matching text entry should be included. {:#?}", found);
+
+    let snippet = matches[0]["snippet"].as_str().unwrap();
+
+    assert!(snippet.contains(&format!("<mark>{}</mark>", word)), "match should be marked. {}", snippet);
+    assert!(snippet.contains("&lt;script&gt;"), "markup should be escaped. {}", snippet);
+    assert!(snippet.contains("&amp;"), "ampersands should be escaped. {}", snippet);
+    assert!(!snippet.contains("<script>"), "markup should not be in the snippet. {}", snippet);
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn search_pages_by_rank() {
+    let client = common::logged_in_client();
+    let word = common::unique_word();
+    let now = common::unix_epoch_sec().unwrap();
+
+    // the newer entry is the weaker match so rank has to come before day
+    let best = post_entry(&client, json!({
+        "entry": {"day": now - 60},
+        "text_entries": [{"thought": format!("{0} {0} {0} {0}", word), "private": false}]
+    }));
+    let weaker = post_entry(&client, json!({
+        "entry": {"day": now},
+        "text_entries": [{"thought": format!("{} among many other unrelated words", word), "private": false}]
+    }));
+    let best_id = best["id"].as_i64().unwrap();
+    let weaker_id = weaker["id"].as_i64().unwrap();
+
+    let first = list_entries(&client, &[("q", word.as_str()), ("limit", "1")]);
+
+    assert_eq!(listed_ids(&first), vec![best_id], "the best match should be first");
+
+    let Some(next) = first["cursor"]["next"].as_str() else {
+        panic!("missing next cursor. {:#?}", first);
+    };
+    let second = list_entries(&client, &[("q", word.as_str()), ("limit", "1"), ("cursor", next)]);
+
+    assert_eq!(listed_ids(&second), vec![weaker_id], "the weaker match should be on the next page");
+    assert!(second["cursor"]["next"].is_null(), "there should be no more pages. {:#?}", second);
+
+    let Some(prev) = second["cursor"]["prev"].as_str() else {
+        panic!("missing prev cursor. {:#?}", second);
+    };
+    let back = list_entries(&client, &[("q", word.as_str()), ("limit", "1"), ("cursor", prev)]);
+
+    assert_eq!(listed_ids(&back), vec![best_id], "the prev cursor should go back to the best match");
+
+    common::purge_entry(&client, best_id);
+    common::purge_entry(&client, weaker_id);
+}
+
+#[test]
+fn search_skips_other_entries() {
+    let client = common::logged_in_client();
+    let word = common::unique_word();
+
+    let found = list_entries(&client, &[("q", word.as_str())]);
+
+    assert!(listed_ids(&found).is_empty(), "nothing should match a new word. {:#?}", found);
+}
@@ -0,0 +1,123 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+use super::post_entry;
+
+fn sync(client: &UserClient, since: Option<&str>) -> Value {
+    let mut req = client.get("/sync");
+
+    if let Some(token) = since {
+        req = req.query(&[("since", token)]);
+    }
+
+    let json = common::expect_ok(
+        common::result::expect_with_err(req.send(), "failed to send sync request"),
+        "failed to sync"
+    );
+
+    json["data"].clone()
+}
+
+fn token(result: &Value) -> String {
+    result["token"].as_str()
+        .expect("missing sync token")
+        .to_owned()
+}
+
+/// the ids of a kind in a sync result that are in the given list
+fn ids_in(result: &Value, kind: &str, list: &str) -> Vec<i64> {
+    let Some(records) = result[kind][list].as_array() else {
+        panic!("missing {} {} in sync result. {:#?}", kind, list, result);
+    };
+
+    records.iter()
+        .map(|record| record.as_i64().or_else(|| record["id"].as_i64()).expect("missing record id"))
+        .collect()
+}
+
+fn changed_entry<'a>(result: &'a Value, entry_id: i64) -> Option<&'a Value> {
+    result["entries"]["changed"].as_array()?
+        .iter()
+        .find(|entry| entry["id"].as_i64() == Some(entry_id))
+}
+
+#[test]
+fn sync_feed_tracks_changes() {
+    let client = common::logged_in_client();
+    let now = common::unix_epoch_sec().unwrap();
+    let initial = sync(&client, None);
+
+    let entry = post_entry(&client, json!({
+        "entry": {"day": now},
+        "text_entries": [{"thought": "synced text", "private": false}],
+        "markers": [{"title": "synced marker"}]
+    }));
+    let entry_id = entry["id"].as_i64().unwrap();
+    let text_id = entry["text"][0]["id"].as_i64().unwrap();
+    let marker_id = entry["markers"][0]["id"].as_i64().unwrap();
+
+    let created = sync(&client, Some(&token(&initial)));
+
+    assert!(changed_entry(&created, entry_id).is_some(), "created entries should be changed");
+    assert!(ids_in(&created, "text_entries", "changed").contains(&text_id));
+    assert!(ids_in(&created, "markers", "changed").contains(&marker_id));
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.delete(format!("/entries/{}", entry_id)).send(),
+            "failed to send delete entry request"
+        ),
+        "failed to delete entry"
+    );
+
+    let trashed = sync(&client, Some(&token(&created)));
+    let Some(synced) = changed_entry(&trashed, entry_id) else {
+        panic!("trashed entries should be changed. {:#?}", trashed);
+    };
+
+    assert!(!synced["deleted"].is_null(), "trashed entries should have a deleted date");
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.delete(format!("/entries/{}", entry_id)).send(),
+            "failed to send delete entry request"
+        ),
+        "failed to purge entry"
+    );
+
+    let purged = sync(&client, Some(&token(&trashed)));
+
+    assert!(changed_entry(&purged, entry_id).is_none(), "purged entries should not be changed");
+    assert!(ids_in(&purged, "entries", "deleted").contains(&entry_id), "purged entries should be deleted");
+
+    // the first sync has everything so nothing is given as deleted
+    let full = sync(&client, None);
+
+    assert!(ids_in(&full, "entries", "deleted").is_empty());
+    assert!(changed_entry(&full, entry_id).is_none());
+}
+
+#[test]
+fn invalid_and_old_tokens() {
+    let client = common::logged_in_client();
+
+    let res = common::result::expect_with_err(
+        client.get("/sync").query(&[("since", "not a token")]).send(),
+        "failed to send sync request"
+    );
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "invalid tokens should be rejected");
+
+    // "dC4w" is the token "t.0" which is always at or before the horizon
+    let res = common::result::expect_with_err(
+        client.get("/sync").query(&[("since", "dC4w")]).send(),
+        "failed to send sync request"
+    );
+
+    assert_eq!(res.status(), StatusCode::GONE, "tokens at the sync horizon should require a full resync");
+
+    let json: Value = common::result::expect_with_err(res.json(), "failed to parse error json");
+
+    assert_eq!(json["error"], json!("FullResyncRequired"));
+}
@@ -0,0 +1,190 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+use super::{post_entry, create_tag, create_field, remove_tags_and_fields};
+
+fn post_template(client: &UserClient, body: Value) -> reqwest::blocking::Response {
+    common::result::expect_with_err(
+        client.post("/entry_templates").json(&body).send(),
+        "failed to send create entry template request"
+    )
+}
+
+fn field_value(entry: &Value, field: i64) -> Value {
+    let Some(fields) = entry["fields"].as_array() else {
+        panic!("missing entry fields. {:#?}", entry);
+    };
+
+    fields.iter()
+        .find(|f| f["field"].as_i64() == Some(field))
+        .map(|f| f["value"]["value"].clone())
+        .unwrap_or(Value::Null)
+}
+
+#[test]
+fn entries_from_templates() {
+    let client = common::logged_in_client();
+    let now = common::unix_epoch_sec().unwrap();
+    let template_tag = create_tag(&client);
+    let given_tag = create_tag(&client);
+    let mood = create_field(&client, json!({"type": "Integer", "minimum": 0, "maximum": 5}));
+    let energy = create_field(&client, json!({"type": "Integer", "minimum": 0, "maximum": 5}));
+
+    let template = common::expect_ok(
+        post_template(&client, json!({
+            "name": common::unique_word(),
+            "data": {
+                "tags": [template_tag],
+                "custom_field_entries": [
+                    {"field": mood, "value": {"type": "Integer", "value": 3}},
+                    {"field": energy, "value": {"type": "Integer", "value": 2}},
+                ],
+                "text_entries": [{"thought": "how was today?", "private": false}]
+            }
+        })),
+        "failed to create entry template"
+    );
+    let template_id = common::get_id(&template);
+
+    let fetched = common::expect_ok(
+        common::result::expect_with_err(
+            client.get(format!("/entry_templates/{}", template_id)).send(),
+            "failed to send get entry template request"
+        ),
+        "failed to get entry template"
+    );
+
+    assert_eq!(fetched["data"]["data"], template["data"]["data"], "the stored template should match");
+
+    let entry = post_entry(&client, json!({
+        "entry": {"day": now},
+        "tags": [given_tag],
+        "custom_field_entries": [{"field": energy, "value": {"type": "Integer", "value": 5}}],
+        "template_id": template_id
+    }));
+    let mut tags: Vec<i64> = entry["tags"].as_array()
+        .expect("missing entry tags")
+        .iter()
+        .map(|tag| tag.as_i64().unwrap())
+        .collect();
+    tags.sort_unstable();
+
+    assert_eq!(tags, vec![template_tag, given_tag], "template tags should be added to the given tags");
+    assert_eq!(field_value(&entry, mood), json!(3), "template defaults should fill in missing fields");
+    assert_eq!(field_value(&entry, energy), json!(5), "given fields should keep their value");
+    assert_eq!(entry["text"][0]["thought"], json!("how was today?"), "starter text should be added");
+
+    let entry_with_text = post_entry(&client, json!({
+        "entry": {"day": now},
+        "text_entries": [{"thought": "my own text", "private": false}],
+        "template_id": template_id
+    }));
+
+    assert_eq!(entry_with_text["text"].as_array().map(Vec::len), Some(1), "starter text is only used without given text");
+    assert_eq!(entry_with_text["text"][0]["thought"], json!("my own text"));
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.delete(format!("/entry_templates/{}", template_id)).send(),
+            "failed to send delete entry template request"
+        ),
+        "failed to delete entry template"
+    );
+
+    let res = common::result::expect_with_err(
+        client.post("/entries")
+            .json(&json!({"entry": {"day": now}, "template_id": template_id}))
+            .send(),
+        "failed to send create entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "deleted templates should not be usable");
+
+    let res = common::result::expect_with_err(
+        client.get(format!("/entries/{}", entry["id"])).send(),
+        "failed to send get entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::OK, "entries should stay after their template is deleted");
+
+    common::purge_entry(&client, entry["id"].as_i64().unwrap());
+    common::purge_entry(&client, entry_with_text["id"].as_i64().unwrap());
+    remove_tags_and_fields(&client, &[template_tag, given_tag], &[mood, energy]);
+}
+
+#[test]
+fn invalid_templates_are_rejected() {
+    let client = common::logged_in_client();
+    let field = create_field(&client, json!({"type": "Integer", "minimum": 0, "maximum": 5}));
+
+    let res = post_template(&client, json!({
+        "name": common::unique_word(),
+        "data": {
+            "custom_field_entries": [{"field": field, "value": {"type": "Integer", "value": 10}}]
+        }
+    }));
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "out of range defaults should be rejected");
+
+    let json: Value = common::result::expect_with_err(res.json(), "failed to parse error json");
+
+    assert_eq!(json["error"], json!("Validation"));
+
+    let res = post_template(&client, json!({
+        "name": common::unique_word(),
+        "data": {"tags": [i32::MAX]}
+    }));
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "unknown tags should be rejected");
+
+    let res = common::result::expect_with_err(
+        client.get(format!("/entry_templates/{}", i32::MAX)).send(),
+        "failed to send get entry template request"
+    );
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    remove_tags_and_fields(&client, &[], &[field]);
+}
+
+#[test]
+fn deleted_tags_and_fields_are_skipped() {
+    let client = common::logged_in_client();
+    let now = common::unix_epoch_sec().unwrap();
+    let kept_tag = create_tag(&client);
+    let removed_tag = create_tag(&client);
+    let kept_field = create_field(&client, json!({"type": "Integer", "minimum": 0, "maximum": 5}));
+    let removed_field = create_field(&client, json!({"type": "Integer", "minimum": 0, "maximum": 5}));
+
+    let template = common::expect_ok(
+        post_template(&client, json!({
+            "name": common::unique_word(),
+            "data": {
+                "tags": [kept_tag, removed_tag],
+                "custom_field_entries": [
+                    {"field": kept_field, "value": {"type": "Integer", "value": 1}},
+                    {"field": removed_field, "value": {"type": "Integer", "value": 4}},
+                ]
+            }
+        })),
+        "failed to create entry template"
+    );
+    let template_id = common::get_id(&template);
+
+    remove_tags_and_fields(&client, &[removed_tag], &[removed_field]);
+
+    let entry = post_entry(&client, json!({
+        "entry": {"day": now},
+        "template_id": template_id
+    }));
+
+    assert_eq!(entry["tags"], json!([kept_tag]), "deleted tags should be skipped");
+    assert_eq!(entry["fields"].as_array().map(Vec::len), Some(1), "deleted fields should be skipped");
+    assert_eq!(field_value(&entry, kept_field), json!(1));
+
+    let _ = client.delete(format!("/entry_templates/{}", template_id)).send();
+
+    common::purge_entry(&client, entry["id"].as_i64().unwrap());
+    remove_tags_and_fields(&client, &[kept_tag], &[kept_field]);
+}
@@ -0,0 +1,143 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+use super::{post_entry, listed_ids, create_tag, remove_tags_and_fields};
+
+fn list(client: &UserClient, url: &str, tag: i64) -> Value {
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.get(url).query(&[("tags", tag.to_string())]).send(),
+            "failed to send list entries request"
+        ),
+        "failed to list entries"
+    )
+}
+
+fn delete_entry(client: &UserClient, entry_id: i64) -> Value {
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.delete(format!("/entries/{}", entry_id)).send(),
+            "failed to send delete entry request"
+        ),
+        "failed to delete entry"
+    )
+}
+
+#[test]
+fn trash_and_restore() {
+    let client = common::logged_in_client();
+    let tag = create_tag(&client);
+    let now = common::unix_epoch_sec().unwrap();
+    let entry_id = post_entry(&client, json!({"entry": {"day": now}, "tags": [tag]}))["id"]
+        .as_i64()
+        .unwrap();
+
+    delete_entry(&client, entry_id);
+
+    assert!(listed_ids(&list(&client, "/entries", tag)).is_empty(), "trashed entries should not be listed");
+
+    let trash = list(&client, "/entries/trash", tag);
+
+    assert_eq!(listed_ids(&trash), vec![entry_id], "the entry should be in the trash");
+    assert!(!trash["data"][0]["deleted"].is_null(), "trashed entries should have a deleted date");
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/restore", entry_id)).send(),
+            "failed to send restore entry request"
+        ),
+        "failed to restore entry"
+    );
+
+    assert_eq!(listed_ids(&list(&client, "/entries", tag)), vec![entry_id], "restored entries should be listed");
+    assert!(listed_ids(&list(&client, "/entries/trash", tag)).is_empty(), "restored entries should leave the trash");
+
+    let res = common::result::expect_with_err(
+        client.post(format!("/entries/{}/restore", entry_id)).send(),
+        "failed to send restore entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "only trashed entries can be restored");
+
+    common::purge_entry(&client, entry_id);
+    remove_tags_and_fields(&client, &[tag], &[]);
+}
+
+#[test]
+fn deleting_trashed_entry_purges_it() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+
+    let file = common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/files?name=trash.txt", entry_id))
+                .body("purged with the entry")
+                .send(),
+            "failed to send file upload request"
+        ),
+        "failed to upload file"
+    );
+    let file_id = common::get_id(&file);
+
+    delete_entry(&client, entry_id);
+
+    let res = common::result::expect_with_err(
+        client.get(format!("/entries/{}/files/{}", entry_id, file_id)).send(),
+        "failed to send file download request"
+    );
+
+    assert_eq!(res.status(), StatusCode::OK, "files should stay while the entry is in the trash");
+
+    delete_entry(&client, entry_id);
+
+    let res = common::result::expect_with_err(
+        client.get(format!("/entries/{}", entry_id)).send(),
+        "failed to send get entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "purged entries should be gone");
+
+    let res = common::result::expect_with_err(
+        client.get(format!("/entries/{}/files/{}", entry_id, file_id)).send(),
+        "failed to send file download request"
+    );
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "files should be removed with the entry");
+}
+
+#[test]
+fn trashed_entries_cannot_change() {
+    let client = common::logged_in_client();
+    let now = common::unix_epoch_sec().unwrap();
+    let entry_id = common::create_entry(&client);
+
+    delete_entry(&client, entry_id);
+
+    let res = common::result::expect_with_err(
+        client.put(format!("/entries/{}", entry_id))
+            .json(&json!({
+                "entry": {"day": now},
+                "text_entries": [{"thought": "changed in the trash", "private": false}]
+            }))
+            .send(),
+        "failed to send update entry request"
+    );
+
+    assert_eq!(res.status(), StatusCode::CONFLICT, "trashed entries should not be updated");
+
+    let res = common::result::expect_with_err(
+        client.post(format!("/entries/{}/files?name=trash.txt", entry_id))
+            .body("uploaded to the trash")
+            .send(),
+        "failed to send file upload request"
+    );
+
+    assert_eq!(res.status(), StatusCode::CONFLICT, "trashed entries should not take uploads");
+
+    let json: Value = common::result::expect_with_err(res.json(), "failed to parse error json");
+
+    assert_eq!(json["error"], json!("EntryInTrash"));
+
+    common::purge_entry(&client, entry_id);
+}
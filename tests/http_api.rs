@@ -4,3 +4,11 @@ mod common;
 mod auth;
 #[cfg(test)]
 mod status;
+#[cfg(test)]
+mod processing;
+#[cfg(test)]
+mod storage;
+#[cfg(test)]
+mod entries;
+#[cfg(test)]
+mod media;
@@ -0,0 +1,123 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common;
+use super::{get, delete, upload, header};
+
+#[test]
+fn upload_list_download_delete() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/files", entry_id);
+    let contents = b"notes attached to the entry".to_vec();
+
+    let json = common::expect_ok(
+        upload(
+            &client,
+            &format!("{}?name=notes.txt&private=true&comment=attached", url),
+            Some("text/plain"),
+            contents.clone()
+        ),
+        "failed to upload file"
+    );
+    let file_id = common::get_id(&json);
+
+    assert_eq!(json["data"]["name"], json!("notes.txt"));
+    assert_eq!(json["data"]["mime_type"], json!("text"));
+    assert_eq!(json["data"]["mime_subtype"], json!("plain"));
+    assert_eq!(json["data"]["file_size"].as_u64(), Some(contents.len() as u64));
+    assert_eq!(json["data"]["private"], json!(true));
+    assert_eq!(json["data"]["comment"], json!("attached"));
+
+    let listed = common::expect_ok(get(&client, &url), "failed to list files");
+    let Some(files) = listed["data"].as_array() else {
+        panic!("missing files list. {:#?}", listed);
+    };
+
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0]["id"].as_i64(), Some(file_id));
+
+    let entry = common::expect_ok(get(&client, &format!("/entries/{}", entry_id)), "failed to get entry");
+
+    assert_eq!(entry["data"]["files"][0]["id"].as_i64(), Some(file_id), "files should be part of the entry");
+
+    let file_url = format!("{}/{}", url, file_id);
+    let res = get(&client, &file_url);
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(header(&res, "content-type"), Some("text/plain"));
+    assert!(
+        header(&res, "content-disposition").map_or(false, |v| v.starts_with("attachment") && v.contains("notes.txt")),
+        "files should be downloaded as attachments with their name"
+    );
+    assert!(header(&res, "etag").is_some(), "downloads should have an etag");
+
+    let body = common::result::expect_with_err(res.bytes(), "failed to read file");
+
+    assert!(body.as_ref() == contents.as_slice(), "the downloaded file should match the upload");
+
+    common::expect_ok(delete(&client, &file_url), "failed to delete file");
+
+    assert_eq!(get(&client, &file_url).status(), StatusCode::NOT_FOUND, "deleted files should be gone");
+
+    let listed = common::expect_ok(get(&client, &url), "failed to list files");
+
+    assert_eq!(listed["data"], Value::Array(Vec::new()));
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn files_are_counted_in_lists() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/files", entry_id);
+
+    for name in ["first.bin", "second.bin"] {
+        common::expect_ok(
+            upload(&client, &format!("{}?name={}", url, name), None, name.as_bytes().to_vec()),
+            "failed to upload file"
+        );
+    }
+
+    let listed = common::expect_ok(get(&client, &url), "failed to list files");
+
+    assert_eq!(listed["data"][0]["mime_type"], json!("application"), "files default to octet-stream");
+    assert_eq!(listed["data"][0]["mime_subtype"], json!("octet-stream"));
+
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.get("/entries").query(&[("limit", "1000")]).send(),
+            "failed to send list entries request"
+        ),
+        "failed to list entries"
+    );
+    let Some(listed) = json["data"].as_array()
+        .and_then(|list| list.iter().find(|entry| entry["id"].as_i64() == Some(entry_id))) else {
+        panic!("missing created entry in list. {:#?}", json);
+    };
+
+    assert_eq!(listed["files"].as_i64(), Some(2), "the list should count files");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn invalid_uploads() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+
+    let res = upload(&client, &format!("/entries/{}/files?name=%20", entry_id), None, b"data".to_vec());
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "empty names should be rejected");
+
+    let res = upload(&client, &format!("/entries/{}/files?name=a.txt", entry_id), Some("not a mime"), b"data".to_vec());
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "invalid content types should be rejected");
+
+    let res = get(&client, &format!("/entries/{}/files/{}", entry_id, i32::MAX));
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    common::purge_entry(&client, entry_id);
+}
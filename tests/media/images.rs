@@ -0,0 +1,173 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common::{self, UserClient};
+use super::{get, delete, upload, header};
+
+/// encodes a simple gradient image of the given size
+fn encode_image(width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+    let img = RgbImage::from_fn(width, height, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, 128]));
+    let mut rtn = Cursor::new(Vec::new());
+
+    common::result::expect_with_err(
+        DynamicImage::ImageRgb8(img).write_to(&mut rtn, format),
+        "failed to encode test image"
+    );
+
+    rtn.into_inner()
+}
+
+/// adds an exif segment to a jpeg with the given orientation
+fn with_exif_orientation(jpeg: Vec<u8>, orientation: u16) -> Vec<u8> {
+    let mut tiff = Vec::new();
+    tiff.extend_from_slice(b"II*\0");
+    tiff.extend_from_slice(&8u32.to_le_bytes());
+    tiff.extend_from_slice(&1u16.to_le_bytes());
+    // orientation tag as a single short
+    tiff.extend_from_slice(&0x0112u16.to_le_bytes());
+    tiff.extend_from_slice(&3u16.to_le_bytes());
+    tiff.extend_from_slice(&1u32.to_le_bytes());
+    tiff.extend_from_slice(&orientation.to_le_bytes());
+    tiff.extend_from_slice(&[0, 0]);
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut rtn = Vec::with_capacity(jpeg.len() + tiff.len() + 10);
+    rtn.extend_from_slice(&jpeg[..2]);
+    rtn.extend_from_slice(&[0xff, 0xe1]);
+    rtn.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+    rtn.extend_from_slice(b"Exif\0\0");
+    rtn.extend_from_slice(&tiff);
+    rtn.extend_from_slice(&jpeg[2..]);
+    rtn
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+fn download(client: &UserClient, url: &str) -> (Option<String>, Option<String>, Vec<u8>) {
+    let res = get(client, url);
+
+    assert_eq!(res.status(), StatusCode::OK, "failed to download image. {}", url);
+
+    let content_type = header(&res, "content-type").map(str::to_owned);
+    let etag = header(&res, "etag").map(str::to_owned);
+    let body = common::result::expect_with_err(res.bytes(), "failed to read image");
+
+    (content_type, etag, body.to_vec())
+}
+
+#[test]
+fn png_with_thumbnail() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/images", entry_id);
+
+    let json = common::expect_ok(
+        upload(&client, &url, None, encode_image(600, 300, ImageOutputFormat::Png)),
+        "failed to upload image"
+    );
+    let image_id = common::get_id(&json);
+
+    assert_eq!(json["data"]["mime_subtype"], json!("png"));
+    assert_eq!(json["data"]["width"], json!(600));
+    assert_eq!(json["data"]["height"], json!(300));
+
+    let entry = common::expect_ok(get(&client, &format!("/entries/{}", entry_id)), "failed to get entry");
+
+    assert_eq!(entry["data"]["images"][0]["id"].as_i64(), Some(image_id), "images should be part of the entry");
+
+    let image_url = format!("{}/{}", url, image_id);
+    let (content_type, etag, image) = download(&client, &image_url);
+    let (thumb_type, thumb_etag, thumbnail) = download(&client, &format!("{}/thumbnail", image_url));
+    let hash = etag.expect("missing image etag");
+
+    assert_eq!(content_type.as_deref(), Some("image/png"));
+    assert_eq!(thumb_type.as_deref(), Some("image/png"));
+    assert_eq!(thumb_etag, Some(format!("{}-thumb\"", hash.trim_end_matches('"'))));
+
+    let image = common::result::expect_with_err(image::load_from_memory(&image), "failed to decode image");
+    let thumbnail = common::result::expect_with_err(image::load_from_memory(&thumbnail), "failed to decode thumbnail");
+
+    assert_eq!((image.width(), image.height()), (600, 300));
+    assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128), "thumbnails should keep the aspect ratio");
+
+    common::expect_ok(delete(&client, &image_url), "failed to delete image");
+
+    assert_eq!(get(&client, &image_url).status(), StatusCode::NOT_FOUND, "deleted images should be gone");
+    assert_eq!(get(&client, &format!("{}/thumbnail", image_url)).status(), StatusCode::NOT_FOUND);
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn jpeg_metadata() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/images", entry_id);
+    // rotated 90 degrees so the stored image is taller than it is wide
+    let jpeg = with_exif_orientation(encode_image(40, 20, ImageOutputFormat::Jpeg(90)), 6);
+
+    let json = common::expect_ok(upload(&client, &url, None, jpeg.clone()), "failed to upload image");
+
+    assert_eq!(json["data"]["mime_subtype"], json!("jpeg"));
+    assert_eq!((json["data"]["width"].as_i64(), json["data"]["height"].as_i64()), (Some(20), Some(40)));
+
+    let (_, _, stripped) = download(&client, &format!("{}/{}", url, common::get_id(&json)));
+
+    assert!(!contains(&stripped, b"Exif\0\0"), "exif data should be removed by default");
+
+    let decoded = common::result::expect_with_err(image::load_from_memory(&stripped), "failed to decode image");
+
+    assert_eq!((decoded.width(), decoded.height()), (20, 40), "the orientation should be applied");
+
+    let json = common::expect_ok(
+        upload(&client, &format!("{}?keep_metadata=true", url), None, jpeg),
+        "failed to upload image"
+    );
+    let (_, _, kept) = download(&client, &format!("{}/{}", url, common::get_id(&json)));
+
+    assert!(contains(&kept, b"Exif\0\0"), "exif data should be kept when asked");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn upright_jpeg_is_not_re_encoded() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/images", entry_id);
+    let original = encode_image(40, 20, ImageOutputFormat::Jpeg(90));
+    let jpeg = with_exif_orientation(original.clone(), 1);
+
+    let json = common::expect_ok(upload(&client, &url, None, jpeg), "failed to upload image");
+    let (_, _, stripped) = download(&client, &format!("{}/{}", url, common::get_id(&json)));
+
+    assert!(stripped == original, "only the exif segment should be removed from an upright image");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn unsupported_images() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/images", entry_id);
+
+    for body in [b"GIF89a\x01\x00\x01\x00".to_vec(), b"not an image".to_vec()] {
+        let res = upload(&client, &url, Some("image/png"), body);
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "the format should be checked from the data");
+    }
+
+    // looks like a png but cannot be decoded
+    let mut broken = encode_image(10, 10, ImageOutputFormat::Png);
+    broken.truncate(24);
+
+    assert_eq!(upload(&client, &url, None, broken).status(), StatusCode::BAD_REQUEST);
+
+    common::purge_entry(&client, entry_id);
+}
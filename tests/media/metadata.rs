@@ -0,0 +1,102 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common;
+use super::{get, upload, upload_wav};
+
+fn assert_wav_metadata(audio: &Value, duration_ms: i64, msg: &str) {
+    assert_eq!(audio["duration_ms"].as_i64(), Some(duration_ms), "{}", msg);
+    assert_eq!(audio["codec"], json!("pcm"), "{}", msg);
+    assert_eq!(audio["sample_rate"].as_u64(), Some(common::WAV_SAMPLE_RATE as u64), "{}", msg);
+    assert_eq!(audio["channels"], json!(1), "{}", msg);
+}
+
+#[test]
+fn wav_metadata() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let (audio, _) = upload_wav(&client, entry_id, 2);
+
+    assert_wav_metadata(&audio, 2_000, "the upload should have the metadata");
+
+    let listed = common::expect_ok(get(&client, &format!("/entries/{}/audio", entry_id)), "failed to list audio");
+
+    assert_wav_metadata(&listed["data"][0], 2_000, "the audio list should have the metadata");
+
+    let entry = common::expect_ok(get(&client, &format!("/entries/{}", entry_id)), "failed to get entry");
+
+    assert_wav_metadata(&entry["data"]["audio"][0], 2_000, "the entry should have the metadata");
+
+    let replaced = common::expect_ok(
+        common::result::expect_with_err(
+            client.put(format!("/entries/{}/audio/{}/file", entry_id, audio["id"]))
+                .header("content-type", "audio/wav")
+                .body(common::wav_file(3))
+                .send(),
+            "failed to send replace audio request"
+        ),
+        "failed to replace audio"
+    );
+
+    assert_wav_metadata(&replaced["data"], 3_000, "replaced files should have new metadata");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn unreadable_metadata() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+
+    // passes sniffing but has nothing to read metadata from
+    let mut m4a = vec![0x00, 0x00, 0x00, 0x10];
+    m4a.extend_from_slice(b"ftypM4A ");
+    m4a.extend_from_slice(&[0x00; 4]);
+
+    let json = common::expect_ok(
+        upload(&client, &format!("/entries/{}/audio", entry_id), None, m4a),
+        "files without readable metadata should still be accepted"
+    );
+
+    for key in ["duration_ms", "codec", "sample_rate", "channels"] {
+        assert!(json["data"][key].is_null(), "{} should be missing", key);
+    }
+
+    common::purge_entry(&client, entry_id);
+}
+
+/// an audio only mp4 with a generic brand the way ffmpeg and most phones
+/// write it. the moov box is at the start so the sound track can be found
+fn isom_m4a() -> Vec<u8> {
+    let mut rtn = vec![0x00, 0x00, 0x00, 0x1c];
+    rtn.extend_from_slice(b"ftypisom");
+    rtn.extend_from_slice(&[0x00, 0x00, 0x02, 0x00]);
+    rtn.extend_from_slice(b"isomiso2mp41");
+    rtn.extend_from_slice(&[0x00, 0x00, 0x00, 0x29]);
+    rtn.extend_from_slice(b"moov");
+    rtn.extend_from_slice(&[0x00, 0x00, 0x00, 0x21]);
+    rtn.extend_from_slice(b"hdlr");
+    rtn.extend_from_slice(&[0x00; 8]);
+    rtn.extend_from_slice(b"soun");
+    rtn.extend_from_slice(&[0x00; 13]);
+    rtn
+}
+
+#[test]
+fn isom_m4a_is_audio() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+
+    let json = common::expect_ok(
+        upload(&client, &format!("/entries/{}/audio", entry_id), None, isom_m4a()),
+        "m4a files with a generic brand should be accepted as audio"
+    );
+
+    assert_eq!(json["data"]["mime_subtype"], json!("mp4"));
+
+    let res = upload(&client, &format!("/entries/{}/video", entry_id), Some("video/mp4"), isom_m4a());
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "audio only files should not be accepted as video");
+
+    common::purge_entry(&client, entry_id);
+}
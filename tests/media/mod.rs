@@ -0,0 +1,66 @@
+//! media attachment tests
+//!
+//! each test uploads to its own entry and purges the entry when done which
+//! also removes everything that was uploaded to it
+
+use reqwest::blocking::Response;
+use serde_json::Value;
+
+use crate::common::{self, UserClient};
+
+mod files;
+mod images;
+mod video;
+mod range;
+mod multipart;
+mod metadata;
+mod peaks;
+
+/// the value of a response header
+fn header<'a>(res: &'a Response, name: &str) -> Option<&'a str> {
+    res.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+}
+
+/// sends a GET request for the given url
+fn get(client: &UserClient, url: &str) -> Response {
+    common::result::expect_with_err(
+        client.get(url).send(),
+        &format!("failed to send request. GET {}", url)
+    )
+}
+
+/// sends a DELETE request for the given url
+fn delete(client: &UserClient, url: &str) -> Response {
+    common::result::expect_with_err(
+        client.delete(url).send(),
+        &format!("failed to send request. DELETE {}", url)
+    )
+}
+
+/// uploads the given body to a url with an optional content-type
+fn upload(client: &UserClient, url: &str, content_type: Option<&str>, body: Vec<u8>) -> Response {
+    let mut req = client.post(url);
+
+    if let Some(content_type) = content_type {
+        req = req.header("content-type", content_type);
+    }
+
+    common::result::expect_with_err(
+        req.body(body).send(),
+        &format!("failed to send upload request. POST {}", url)
+    )
+}
+
+/// uploads a wav file of the given length as an audio entry and returns the
+/// audio entry along with the uploaded file
+fn upload_wav(client: &UserClient, entry_id: i64, seconds: u32) -> (Value, Vec<u8>) {
+    let file = common::wav_file(seconds);
+    let json = common::expect_ok(
+        upload(client, &format!("/entries/{}/audio", entry_id), Some("audio/wav"), file.clone()),
+        "failed to upload audio"
+    );
+
+    (json["data"].clone(), file)
+}
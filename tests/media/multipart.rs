@@ -0,0 +1,188 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+use super::upload;
+
+const BOUNDARY: &str = "audio-test-boundary";
+
+/// a part of a multipart form. parts with a file name are sent as files
+struct Part<'a> {
+    name: &'a str,
+    file_name: Option<&'a str>,
+    data: Vec<u8>,
+}
+
+fn text<'a>(name: &'a str, value: &str) -> Part<'a> {
+    Part { name, file_name: None, data: value.as_bytes().to_vec() }
+}
+
+fn file(data: Vec<u8>) -> Part<'static> {
+    Part { name: "file", file_name: Some("recording"), data }
+}
+
+/// builds a multipart/form-data body from the given parts
+fn form_body(parts: Vec<Part<'_>>) -> Vec<u8> {
+    let mut rtn = Vec::new();
+
+    for part in parts {
+        rtn.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+
+        if let Some(file_name) = part.file_name {
+            rtn.extend_from_slice(format!(
+                "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                Content-Type: application/octet-stream\r\n\r\n",
+                part.name,
+                file_name
+            ).as_bytes());
+        } else {
+            rtn.extend_from_slice(format!(
+                "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                part.name
+            ).as_bytes());
+        }
+
+        rtn.extend_from_slice(&part.data);
+        rtn.extend_from_slice(b"\r\n");
+    }
+
+    rtn.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+    rtn
+}
+
+fn post_form(client: &UserClient, entry_id: i64, parts: Vec<Part<'_>>) -> reqwest::blocking::Response {
+    upload(
+        client,
+        &format!("/entries/{}/audio", entry_id),
+        Some(&format!("multipart/form-data; boundary={}", BOUNDARY)),
+        form_body(parts)
+    )
+}
+
+/// an ogg page holding an opus identification header
+fn ogg_opus() -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(1);
+    head.extend_from_slice(&0u16.to_le_bytes());
+    head.extend_from_slice(&48000u32.to_le_bytes());
+    head.extend_from_slice(&0u16.to_le_bytes());
+    head.push(0);
+
+    let mut rtn = b"OggS".to_vec();
+    rtn.push(0);
+    rtn.push(0x02);
+    rtn.extend_from_slice(&[0x00; 20]);
+    rtn.push(1);
+    rtn.push(head.len() as u8);
+    rtn.extend_from_slice(&head);
+    rtn
+}
+
+/// mpeg 1 layer 3 frames at 128 kbps and 44.1 kHz
+fn mp3() -> Vec<u8> {
+    let mut rtn = Vec::new();
+
+    for _ in 0..8 {
+        rtn.extend_from_slice(&[0xff, 0xfb, 0x90, 0x64]);
+        rtn.extend_from_slice(&[0x00; 413]);
+    }
+
+    rtn
+}
+
+fn m4a() -> Vec<u8> {
+    let mut rtn = vec![0x00, 0x00, 0x00, 0x14];
+    rtn.extend_from_slice(b"ftypM4A ");
+    rtn.extend_from_slice(&[0x00; 4]);
+    rtn.extend_from_slice(b"isom");
+    rtn
+}
+
+fn webm() -> Vec<u8> {
+    let mut rtn = vec![0x1a, 0x45, 0xdf, 0xa3, 0x8b, 0x42, 0x86, 0x81, 0x01, 0x42, 0x82, 0x84];
+    rtn.extend_from_slice(b"webm");
+    rtn
+}
+
+#[test]
+fn form_uploads() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let wav = common::wav_file(1);
+
+    let json = common::expect_ok(
+        post_form(&client, entry_id, vec![
+            text("private", "on"),
+            text("comment", "  from a form  "),
+            file(wav.clone()),
+        ]),
+        "failed to upload audio form"
+    );
+
+    assert_eq!(json["data"]["mime_subtype"], json!("wav"));
+    assert_eq!(json["data"]["private"], json!(true));
+    assert_eq!(json["data"]["comment"], json!("from a form"));
+    assert_eq!(json["data"]["file_size"].as_u64(), Some(wav.len() as u64));
+
+    // fields after the file are still read
+    let json = common::expect_ok(
+        post_form(&client, entry_id, vec![file(wav.clone()), text("comment", "after the file")]),
+        "failed to upload audio form"
+    );
+
+    assert_eq!(json["data"]["private"], json!(false));
+    assert_eq!(json["data"]["comment"], json!("after the file"));
+
+    for (parts, reason) in [
+        (vec![text("comment", "no file")], "forms without a file should be rejected"),
+        (vec![file(wav.clone()), file(wav.clone())], "only one file should be accepted"),
+        (vec![text("private", "maybe"), file(wav.clone())], "invalid booleans should be rejected"),
+        (vec![file(b"not audio".to_vec())], "the file format should be checked"),
+    ] {
+        assert_eq!(post_form(&client, entry_id, parts).status(), StatusCode::BAD_REQUEST, "{}", reason);
+    }
+
+    let listed = common::expect_ok(
+        common::result::expect_with_err(
+            client.get(format!("/entries/{}/audio", entry_id)).send(),
+            "failed to send list audio request"
+        ),
+        "failed to list audio"
+    );
+
+    assert_eq!(listed["data"].as_array().map(Vec::len), Some(2), "rejected forms should not be stored");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn formats_are_sniffed() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/audio", entry_id);
+
+    for (data, subtype) in [
+        (webm(), "webm"),
+        (ogg_opus(), "ogg"),
+        (mp3(), "mpeg"),
+        (common::wav_file(1), "wav"),
+        (m4a(), "mp4"),
+    ] {
+        // the content-type is ignored in favor of the data
+        let json = common::expect_ok(
+            upload(&client, &url, Some("audio/webm"), data),
+            &format!("failed to upload {} audio", subtype)
+        );
+        let audio: &Value = &json["data"];
+
+        assert_eq!(audio["mime_type"], json!("audio"));
+        assert_eq!(audio["mime_subtype"], json!(subtype));
+    }
+
+    let res = upload(&client, &url, Some("audio/webm"), b"plain text pretending to be audio".to_vec());
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "unknown formats should be rejected");
+
+    common::purge_entry(&client, entry_id);
+}
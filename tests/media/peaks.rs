@@ -0,0 +1,94 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::common::{self, UserClient};
+use super::{get, upload, upload_wav};
+
+fn get_peaks(client: &UserClient, url: &str, resolution: Option<&str>) -> reqwest::blocking::Response {
+    let mut req = client.get(url);
+
+    if let Some(resolution) = resolution {
+        req = req.query(&[("resolution", resolution)]);
+    }
+
+    common::result::expect_with_err(req.send(), "failed to send peaks request")
+}
+
+fn peaks_of(json: &Value) -> Vec<f64> {
+    let Some(peaks) = json["data"]["peaks"].as_array() else {
+        panic!("missing peaks. {:#?}", json);
+    };
+
+    peaks.iter()
+        .map(|peak| peak.as_f64().expect("peaks should be numbers"))
+        .collect()
+}
+
+#[test]
+fn wav_peaks() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let (audio, _) = upload_wav(&client, entry_id, 2);
+    let url = format!("/entries/{}/audio/{}/peaks", entry_id, audio["id"]);
+    // the square wave of the test file peaks at 8000 out of 32768
+    let amplitude = 8_000.0 / 32_768.0;
+
+    let json = common::expect_ok(get_peaks(&client, &url, None), "failed to get peaks");
+    let peaks = peaks_of(&json);
+
+    assert_eq!(json["data"]["duration_ms"], json!(2_000));
+    assert_eq!(json["data"]["resolution"], json!(200), "the default resolution should be used");
+    assert_eq!(peaks.len(), 200);
+    assert!(
+        peaks.iter().all(|peak| (peak - amplitude).abs() < 0.01),
+        "peaks should match the amplitude of the file. {:?}", peaks
+    );
+
+    let json = common::expect_ok(get_peaks(&client, &url, Some("50")), "failed to get peaks");
+
+    assert_eq!(json["data"]["resolution"], json!(50));
+    assert_eq!(peaks_of(&json).len(), 50);
+
+    // two seconds only has 200 peaks to give
+    let json = common::expect_ok(get_peaks(&client, &url, Some("10000")), "failed to get peaks");
+
+    assert_eq!(json["data"]["resolution"], json!(200), "short recordings should give every peak they have");
+    assert_eq!(peaks_of(&json).len(), 200);
+
+    for resolution in ["0", "10001"] {
+        assert_eq!(
+            get_peaks(&client, &url, Some(resolution)).status(),
+            StatusCode::BAD_REQUEST,
+            "resolution should be rejected: {}", resolution
+        );
+    }
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn peaks_not_available() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let mut m4a = vec![0x00, 0x00, 0x00, 0x10];
+    m4a.extend_from_slice(b"ftypM4A ");
+    m4a.extend_from_slice(&[0x00; 4]);
+
+    let json = common::expect_ok(
+        upload(&client, &format!("/entries/{}/audio", entry_id), None, m4a),
+        "failed to upload audio"
+    );
+    let res = get(&client, &format!("/entries/{}/audio/{}/peaks", entry_id, json["data"]["id"]));
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "peaks should only be given for wav audio");
+
+    let json: Value = common::result::expect_with_err(res.json(), "failed to parse error json");
+
+    assert_eq!(json["error"], json!("AudioPeaksNotAvailable"));
+
+    let res = get(&client, &format!("/entries/{}/audio/{}/peaks", entry_id, i32::MAX));
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "missing audio should not have peaks");
+
+    common::purge_entry(&client, entry_id);
+}
@@ -0,0 +1,121 @@
+use reqwest::StatusCode;
+use reqwest::blocking::Response;
+
+use crate::common::{self, UserClient};
+use super::{get, header, upload};
+
+/// uploads a webm file of the given size as an audio entry and returns the
+/// id of the audio entry along with the uploaded file
+fn upload_webm(client: &UserClient, entry_id: i64, size: usize) -> (i64, Vec<u8>) {
+    let mut file = vec![0x1a, 0x45, 0xdf, 0xa3, 0x8b, 0x42, 0x86, 0x81, 0x01, 0x42, 0x82, 0x84];
+    file.extend_from_slice(b"webm");
+
+    while file.len() < size {
+        file.push((file.len() % 251) as u8);
+    }
+
+    let url = format!("/entries/{}/audio", entry_id);
+
+    common::expect_ok(upload(client, &url, Some("audio/webm"), file.clone()), "failed to upload audio");
+
+    let listed = common::expect_ok(get(client, &url), "failed to list audio");
+    let Some(id) = listed["data"][0]["id"].as_i64() else {
+        panic!("missing uploaded audio. {:#?}", listed);
+    };
+
+    (id, file)
+}
+
+fn get_with(client: &UserClient, url: &str, headers: &[(&str, &str)]) -> Response {
+    let mut req = client.get(url);
+
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+
+    common::result::expect_with_err(req.send(), "failed to send audio request")
+}
+
+fn body(res: Response) -> Vec<u8> {
+    common::result::expect_with_err(res.bytes(), "failed to read audio").to_vec()
+}
+
+#[test]
+fn audio_ranges() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let (audio_id, file) = upload_webm(&client, entry_id, 4096);
+    let url = format!("/entries/{}/audio/{}", entry_id, audio_id);
+    let size = file.len();
+
+    let res = get_with(&client, &url, &[]);
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(header(&res, "accept-ranges"), Some("bytes"));
+    assert_eq!(header(&res, "content-length").map(str::to_owned), Some(size.to_string()));
+
+    assert!(header(&res, "etag").is_some(), "audio should have an etag");
+    assert!(body(res) == file, "the full download should match the upload");
+
+    for (range, start, end) in [
+        ("bytes=0-99", 0, 99),
+        ("bytes=100-", 100, size - 1),
+        ("bytes=-50", size - 50, size - 1),
+        ("bytes=1000-999999999", 1000, size - 1),
+    ] {
+        let res = get_with(&client, &url, &[("range", range)]);
+
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT, "range should be partial: {}", range);
+        assert_eq!(
+            header(&res, "content-range").map(str::to_owned),
+            Some(format!("bytes {}-{}/{}", start, end, size)),
+            "unexpected content range for: {}", range
+        );
+        assert_eq!(
+            header(&res, "content-length").map(str::to_owned),
+            Some((end - start + 1).to_string())
+        );
+        assert!(body(res) == file[start..=end], "the partial body should match the range: {}", range);
+    }
+
+    let res = get_with(&client, &url, &[("range", &format!("bytes={}-", size))]);
+
+    assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(header(&res, "content-range").map(str::to_owned), Some(format!("bytes */{}", size)));
+
+    // multiple ranges are not supported so the whole file is sent
+    let res = get_with(&client, &url, &[("range", "bytes=0-1,5-6")]);
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(body(res).len(), size);
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn audio_conditional_requests() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let (audio_id, file) = upload_webm(&client, entry_id, 1024);
+    let url = format!("/entries/{}/audio/{}", entry_id, audio_id);
+
+    let res = get_with(&client, &url, &[]);
+    let etag = header(&res, "etag").expect("missing audio etag").to_owned();
+
+    let res = get_with(&client, &url, &[("range", "bytes=0-9"), ("if-range", &etag)]);
+
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT, "matching If-Range should send the range");
+    assert!(body(res) == file[..10]);
+
+    let res = get_with(&client, &url, &[("range", "bytes=0-9"), ("if-range", "\"changed\"")]);
+
+    assert_eq!(res.status(), StatusCode::OK, "stale If-Range should send the whole file");
+    assert_eq!(body(res).len(), file.len());
+
+    let res = get_with(&client, &url, &[("if-none-match", &etag)]);
+
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "a matching If-None-Match should not send the file");
+    assert_eq!(header(&res, "etag"), Some(etag.as_str()));
+
+    common::purge_entry(&client, entry_id);
+}
@@ -0,0 +1,110 @@
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common;
+use super::{get, delete, upload, header};
+
+/// an mp4 file type box with the given brand followed by some data
+fn mp4(brand: &[u8; 4]) -> Vec<u8> {
+    let mut rtn = vec![0x00, 0x00, 0x00, 0x14];
+    rtn.extend_from_slice(b"ftyp");
+    rtn.extend_from_slice(brand);
+    rtn.extend_from_slice(&[0x00; 4]);
+    rtn.extend_from_slice(b"isom");
+    rtn.extend((0..4096u32).map(|i| (i % 251) as u8));
+    rtn
+}
+
+/// an ebml header with the given doctype followed by some data
+fn ebml(doctype: &[u8]) -> Vec<u8> {
+    let mut rtn = vec![0x1a, 0x45, 0xdf, 0xa3, 0x9f, 0x42, 0x86, 0x81, 0x01, 0x42, 0x82];
+    rtn.push(0x80 | doctype.len() as u8);
+    rtn.extend_from_slice(doctype);
+    rtn.extend((0..4096u32).map(|i| (i % 241) as u8));
+    rtn
+}
+
+#[test]
+fn upload_stream_update_delete() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/video", entry_id);
+
+    for (body, subtype) in [(ebml(b"webm"), "webm"), (mp4(b"mp42"), "mp4")] {
+        // the content-type is not trusted
+        let json = common::expect_ok(
+            upload(&client, &format!("{}?comment=clip", url), Some("application/octet-stream"), body.clone()),
+            "failed to upload video"
+        );
+        let video_id = common::get_id(&json);
+
+        assert_eq!(json["data"]["mime_type"], json!("video"));
+        assert_eq!(json["data"]["mime_subtype"], json!(subtype));
+        assert_eq!(json["data"]["file_size"].as_u64(), Some(body.len() as u64));
+        assert_eq!(json["data"]["comment"], json!("clip"));
+
+        let video_url = format!("{}/{}", url, video_id);
+        let res = get(&client, &video_url);
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, "content-type").map(str::to_owned), Some(format!("video/{}", subtype)));
+        assert_eq!(header(&res, "accept-ranges"), Some("bytes"), "videos should support seeking");
+
+        let streamed = common::result::expect_with_err(res.bytes(), "failed to read video");
+
+        assert!(streamed.as_ref() == body.as_slice(), "the streamed video should match the upload");
+    }
+
+    let listed = common::expect_ok(get(&client, &url), "failed to list videos");
+    let Some(videos) = listed["data"].as_array() else {
+        panic!("missing videos list. {:#?}", listed);
+    };
+
+    assert_eq!(videos.len(), 2);
+
+    let video_url = format!("{}/{}", url, videos[0]["id"]);
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.put(&video_url)
+                .json(&json!({"private": true, "comment": "updated"}))
+                .send(),
+            "failed to send update video request"
+        ),
+        "failed to update video"
+    );
+
+    let listed = common::expect_ok(get(&client, &url), "failed to list videos");
+    let Some(updated) = listed["data"].as_array()
+        .and_then(|list| list.iter().find(|video| video["id"] == videos[0]["id"])) else {
+        panic!("missing updated video. {:#?}", listed);
+    };
+
+    assert_eq!(updated["private"], json!(true));
+    assert_eq!(updated["comment"], json!("updated"));
+
+    common::expect_ok(delete(&client, &video_url), "failed to delete video");
+
+    assert_eq!(get(&client, &video_url).status(), StatusCode::NOT_FOUND, "deleted videos should be gone");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn unsupported_containers() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let url = format!("/entries/{}/video", entry_id);
+
+    for body in [ebml(b"matroska"), mp4(b"M4A "), b"not a video".to_vec()] {
+        let res = upload(&client, &url, Some("video/mp4"), body);
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "the container should be checked from the data");
+    }
+
+    let listed = common::expect_ok(get(&client, &url), "failed to list videos");
+
+    assert_eq!(listed["data"], json!([]), "rejected uploads should not be stored");
+
+    common::purge_entry(&client, entry_id);
+}
@@ -0,0 +1,225 @@
+//! processing tests
+//!
+//! the server must be running with tests/processing/config.yaml included so
+//! that wav uploads are given to the stub transcript command
+
+use std::time::{Duration, Instant};
+
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common;
+
+#[test]
+fn wav_upload_creates_transcript() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+
+    let file = common::wav_file(1);
+    let file_len = file.len();
+    let audio = common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/audio", entry_id))
+                .header("content-type", "audio/wav")
+                .body(file)
+                .send(),
+            "failed to send audio upload request"
+        ),
+        "failed to upload audio"
+    );
+    let audio_id = common::get_id(&audio);
+
+    assert_eq!(audio["data"]["mime_subtype"], "wav");
+    assert_eq!(audio["data"]["duration_ms"], 1000);
+
+    let processing_url = format!("/entries/{}/audio/{}/processing", entry_id, audio_id);
+    let started = Instant::now();
+
+    let job = loop {
+        let jobs = common::expect_ok(
+            common::result::expect_with_err(
+                client.get(&processing_url).send(),
+                "failed to send processing status request"
+            ),
+            "failed to get processing status"
+        );
+
+        let Some(job) = jobs["data"].as_array().and_then(|list| list.first()).cloned() else {
+            panic!("no processing job was created for the upload. {:#?}", jobs);
+        };
+
+        match job["status"].as_str() {
+            Some("done") => break job,
+            Some("failed") => panic!("processing job failed. {:#?}", job),
+            _ => {}
+        }
+
+        if started.elapsed() > Duration::from_secs(30) {
+            panic!("processing job did not finish in time. {:#?}", job);
+        }
+
+        std::thread::sleep(Duration::from_millis(500));
+    };
+
+    let expected = format!("stub transcript ({} bytes)", file_len);
+    assert_eq!(job["command"], "stub_transcript");
+    assert_eq!(job["output"].as_str().map(str::trim), Some(expected.as_str()));
+
+    let Some(text_id) = job["text_entry"].as_i64() else {
+        panic!("processing job did not create a text entry. {:#?}", job);
+    };
+
+    let entry = common::expect_ok(
+        common::result::expect_with_err(
+            client.get(format!("/entries/{}", entry_id)).send(),
+            "failed to send get entry request"
+        ),
+        "failed to get entry"
+    );
+    let texts = entry["data"]["text"].as_array().cloned().unwrap_or_default();
+
+    assert!(
+        texts.iter().any(|text| text["id"].as_i64() == Some(text_id) && text["thought"] == expected.as_str()),
+        "transcript text entry is missing from the entry. {:#?}",
+        entry
+    );
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn wav_replace_and_delete() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+
+    let audio = common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/audio?comment=first", entry_id))
+                .header("content-type", "audio/wav")
+                .body(common::wav_file(1))
+                .send(),
+            "failed to send audio upload request"
+        ),
+        "failed to upload audio"
+    );
+    let audio_id = common::get_id(&audio);
+    let audio_url = format!("/entries/{}/audio/{}", entry_id, audio_id);
+
+    let replaced_file = common::wav_file(2);
+    let replaced_len = replaced_file.len();
+    let replaced = common::expect_ok(
+        common::result::expect_with_err(
+            client.put(format!("{}/file", audio_url))
+                .header("content-type", "audio/wav")
+                .body(replaced_file)
+                .send(),
+            "failed to send audio replace request"
+        ),
+        "failed to replace audio"
+    );
+
+    assert_eq!(replaced["data"]["duration_ms"], 2000);
+    assert_eq!(replaced["data"]["file_size"].as_u64(), Some(replaced_len as u64));
+    assert_eq!(replaced["data"]["comment"], "first");
+
+    let downloaded = common::result::expect_with_err(
+        client.get(&audio_url).send(),
+        "failed to send audio download request"
+    );
+    assert_eq!(downloaded.status(), StatusCode::OK);
+    assert_eq!(
+        downloaded.bytes().expect("failed to read audio body").len(),
+        replaced_len
+    );
+
+    let updated = common::expect_ok(
+        common::result::expect_with_err(
+            client.put(&audio_url)
+                .json(&json!({"private": true, "comment": "  second  "}))
+                .send(),
+            "failed to send audio update request"
+        ),
+        "failed to update audio"
+    );
+
+    assert_eq!(updated["data"]["private"], true);
+    assert_eq!(updated["data"]["comment"], "second");
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.delete(&audio_url).send(),
+            "failed to send audio delete request"
+        ),
+        "failed to delete audio"
+    );
+
+    let missing = common::result::expect_with_err(
+        client.get(&audio_url).send(),
+        "failed to send audio download request"
+    );
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn duplicate_uploads_share_file() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let file = common::wav_file(3);
+    let mut audio_urls = Vec::new();
+
+    for _ in 0..2 {
+        let audio = common::expect_ok(
+            common::result::expect_with_err(
+                client.post(format!("/entries/{}/audio", entry_id))
+                    .header("content-type", "audio/wav")
+                    .body(file.clone())
+                    .send(),
+                "failed to send audio upload request"
+            ),
+            "failed to upload audio"
+        );
+
+        audio_urls.push(format!("/entries/{}/audio/{}", entry_id, common::get_id(&audio)));
+    }
+
+    let etags: Vec<String> = audio_urls.iter()
+        .map(|url| {
+            let res = common::result::expect_with_err(
+                client.get(url).send(),
+                "failed to send audio download request"
+            );
+            assert_eq!(res.status(), StatusCode::OK);
+
+            res.headers()
+                .get("etag")
+                .and_then(|v| v.to_str().ok())
+                .expect("missing etag header")
+                .to_owned()
+        })
+        .collect();
+
+    assert_eq!(etags[0], etags[1], "duplicate uploads should be stored as the same file");
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.delete(&audio_urls[0]).send(),
+            "failed to send audio delete request"
+        ),
+        "failed to delete audio"
+    );
+
+    let remaining = common::result::expect_with_err(
+        client.get(&audio_urls[1]).send(),
+        "failed to send audio download request"
+    );
+    assert_eq!(remaining.status(), StatusCode::OK);
+    assert_eq!(
+        remaining.bytes().expect("failed to read audio body").as_ref(),
+        file.as_slice(),
+        "the file should remain for the other audio entry"
+    );
+
+    common::purge_entry(&client, entry_id);
+}
@@ -0,0 +1,11 @@
+#!/bin/sh
+# stub speech-to-text command used by the processing tests. prints a fake
+# transcript that includes the size of the given file so the test can check
+# that the right file was passed in
+
+if [ ! -f "$1" ]; then
+    echo "missing audio file: $1" >&2
+    exit 1
+fi
+
+echo "stub transcript ($(wc -c < "$1" | tr -d ' ') bytes)"
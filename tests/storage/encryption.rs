@@ -0,0 +1,153 @@
+//! encryption at rest tests
+//!
+//! expects the server to be started with tests/storage/encryption.yaml
+//! included using the local storage backend and the storage directory of the
+//! server given as "storage" in test_args.json
+
+use std::path::PathBuf;
+
+use reqwest::StatusCode;
+
+use crate::common::{self, UserClient};
+
+/// marks the start of an encrypted object
+const MAGIC: &[u8] = b"TSE1";
+
+/// a 1x1 transparent png
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// downloads media returning its etag and contents
+fn download(client: &UserClient, url: String) -> (String, Vec<u8>) {
+    let res = common::result::expect_with_err(
+        client.get(url).send(),
+        "failed to send download request"
+    );
+
+    assert_eq!(res.status(), StatusCode::OK, "failed to download media");
+
+    let etag = res.headers()
+        .get("etag")
+        .and_then(|v| v.to_str().ok())
+        .expect("missing etag on download")
+        .trim_matches('"')
+        .to_owned();
+    let body = common::result::expect_with_err(res.bytes(), "failed to read download");
+
+    (etag, body.to_vec())
+}
+
+/// the id of the logged in user
+fn user_id(client: &UserClient) -> i64 {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.get("/account").send(),
+            "failed to send account request"
+        ),
+        "failed to retrieve account"
+    );
+
+    common::get_id(&json)
+}
+
+/// the path of a blob of a user in the storage directory of the server
+fn blob_path(user_id: i64, hash: &str, extension: &str) -> PathBuf {
+    common::get_storage_dir()
+        .join("users")
+        .join(user_id.to_string())
+        .join("blobs")
+        .join(&hash[..2])
+        .join(format!("{}.{}", hash, extension))
+}
+
+/// reads a stored object making sure that it is encrypted and does not hold
+/// the given plain data
+fn assert_encrypted(path: PathBuf, plain: &[u8]) {
+    let stored = common::result::expect_with_err(
+        std::fs::read(&path),
+        &format!("failed to read stored object {}", path.display())
+    );
+
+    assert!(
+        stored.starts_with(MAGIC),
+        "stored object is not encrypted. include tests/storage/encryption.yaml in the server config. {}",
+        path.display()
+    );
+    assert!(
+        !stored.windows(plain.len()).any(|window| window == plain),
+        "stored object contains the plain data. {}",
+        path.display()
+    );
+}
+
+#[test]
+fn stored_file_is_encrypted() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    // unique per run so the blob is written by this upload
+    let data = format!(
+        "encrypted file {} {}",
+        std::process::id(),
+        common::unix_epoch_sec().unwrap()
+    ).repeat(64).into_bytes();
+
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/files?name=encrypted.bin", entry_id))
+                .body(data.clone())
+                .send(),
+            "failed to send file upload request"
+        ),
+        "failed to upload file"
+    );
+    let file_id = common::get_id(&json);
+
+    let (hash, body) = download(&client, format!("/entries/{}/files/{}", entry_id, file_id));
+
+    assert!(body == data, "the downloaded file should be decrypted");
+
+    assert_encrypted(blob_path(user_id(&client), &hash, "bin"), &data);
+
+    let shared = common::get_storage_dir()
+        .join("blobs")
+        .join(&hash[..2])
+        .join(format!("{}.bin", hash));
+
+    assert!(!shared.exists(), "blobs should not be stored where other users could share them");
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn stored_image_is_encrypted() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+
+    // the same image is uploaded each run so the blob may already exist from
+    // an earlier run. blobs are kept per user so it was still written by this
+    // user and has to have been encrypted
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/images", entry_id))
+                .body(PNG.to_vec())
+                .send(),
+            "failed to send image upload request"
+        ),
+        "failed to upload image"
+    );
+    let image_id = common::get_id(&json);
+
+    let url = format!("/entries/{}/images/{}", entry_id, image_id);
+    let (hash, image) = download(&client, url.clone());
+    let (_, thumbnail) = download(&client, format!("{}/thumbnail", url));
+
+    assert_encrypted(blob_path(user_id(&client), &hash, "png"), &image);
+    assert_encrypted(blob_path(user_id(&client), &hash, "thumb"), &thumbnail);
+
+    common::purge_entry(&client, entry_id);
+}
@@ -0,0 +1,237 @@
+//! storage usage tests
+//!
+//! the configs in this directory can be included to run the other tests
+//! against a different storage setup. the quota tests expect the server to
+//! be started with tests/storage/quota.yaml included
+
+mod encryption;
+
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::common::{self, UserClient};
+
+fn get_usage(client: &UserClient) -> Value {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.get("/account/storage").send(),
+            "failed to send storage usage request"
+        ),
+        "failed to retrieve storage usage"
+    );
+
+    json["data"]["usage"].clone()
+}
+
+#[test]
+fn account_storage_counts_uploads() {
+    let client = common::logged_in_client();
+    let entry_id = common::create_entry(&client);
+    let file = vec![7u8; 4096];
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/files?name=usage.bin", entry_id))
+                .body(file.clone())
+                .send(),
+            "failed to send file upload request"
+        ),
+        "failed to upload file"
+    );
+
+    let usage = get_usage(&client);
+    let parts: i64 = ["audio", "images", "video", "files"].iter()
+        .map(|key| usage[key].as_i64().expect("missing usage for media type"))
+        .sum();
+
+    assert!(
+        usage["files"].as_i64().unwrap_or(0) >= file.len() as i64,
+        "uploaded file should count towards usage. {:#?}",
+        usage
+    );
+    assert_eq!(usage["total"].as_i64(), Some(parts), "total should be the sum of each media type");
+
+    common::purge_entry(&client, entry_id);
+}
+
+/// retrieves the storage info of the current user making sure that the
+/// server was started with the quota config
+fn get_quota(client: &UserClient) -> Value {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.get("/account/storage").send(),
+            "failed to send storage usage request"
+        ),
+        "failed to retrieve storage usage"
+    );
+
+    if json["data"]["remaining"].as_u64().is_none() {
+        panic!("no quota applies. include tests/storage/quota.yaml in the server config. {:#?}", json);
+    }
+
+    json["data"].clone()
+}
+
+/// uploads a file of the given size expecting it to go over the quota
+fn expect_over_quota(client: &UserClient, entry_id: i64, size: usize) -> Value {
+    let res = common::result::expect_with_err(
+        client.post(format!("/entries/{}/files?name=quota.bin", entry_id))
+            .body(vec![0u8; size])
+            .send(),
+        "failed to send file upload request"
+    );
+    let status = res.status();
+    let json: Value = common::result::expect_with_err(res.json(), "unknown response body");
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "upload should be over quota. {:#?}", json);
+    assert_eq!(json["error"], "StorageQuotaExceeded");
+
+    json
+}
+
+#[test]
+fn over_quota_upload_is_rejected() {
+    let client = common::logged_in_client();
+    let quota = get_quota(&client);
+    let remaining = quota["remaining"].as_u64().unwrap();
+    let entry_id = common::create_entry(&client);
+
+    expect_over_quota(&client, entry_id, remaining as usize + 1);
+
+    let after = get_quota(&client);
+
+    assert_eq!(
+        after["usage"]["total"], quota["usage"]["total"],
+        "a rejected upload should not count towards usage"
+    );
+
+    let files = common::expect_ok(
+        common::result::expect_with_err(
+            client.get(format!("/entries/{}/files", entry_id)).send(),
+            "failed to send entry files request"
+        ),
+        "failed to retrieve entry files"
+    );
+
+    assert_eq!(
+        files["data"].as_array().map(Vec::len),
+        Some(0),
+        "a rejected upload should not create a file. {:#?}",
+        files
+    );
+
+    common::purge_entry(&client, entry_id);
+}
+
+#[test]
+fn group_quota_applies() {
+    let client = common::logged_in_client();
+    let quota = get_quota(&client);
+    let limits = quota["limits"].as_array().cloned().unwrap_or_default();
+
+    let Some(group) = limits.iter().find(|limit| limit["group"].as_i64() == Some(1)) else {
+        panic!("no quota for group 1. the test user has to be a member of group 1. {:#?}", quota);
+    };
+    let Some(user) = limits.iter().find(|limit| limit["group"].is_null()) else {
+        panic!("no quota for the user. {:#?}", quota);
+    };
+
+    let remaining_for = |limit: &Value| {
+        limit["limit"].as_u64().unwrap()
+            .saturating_sub(limit["used"].as_i64().unwrap().max(0) as u64)
+    };
+    let group_remaining = remaining_for(group);
+
+    assert!(
+        group_remaining < remaining_for(user),
+        "the group quota should be smaller than the user quota. {:#?}",
+        quota
+    );
+    assert_eq!(
+        quota["remaining"].as_u64(),
+        Some(group_remaining),
+        "the smallest remaining limit should apply"
+    );
+
+    let entry_id = common::create_entry(&client);
+    let rejected = expect_over_quota(&client, entry_id, group_remaining as usize + 1);
+    let expected = format!("remaining: {} bytes", group_remaining);
+
+    assert!(
+        rejected["message"].as_str().map(|msg| msg.contains(&expected)).unwrap_or(false),
+        "the group quota should limit the upload. {:#?}",
+        rejected
+    );
+
+    common::purge_entry(&client, entry_id);
+}
+
+/// uploads a file to an entry and returns its id
+fn upload_file(client: &UserClient, entry_id: i64, data: &[u8]) -> i64 {
+    let json = common::expect_ok(
+        common::result::expect_with_err(
+            client.post(format!("/entries/{}/files?name=shared.bin", entry_id))
+                .body(data.to_vec())
+                .send(),
+            "failed to send file upload request"
+        ),
+        "failed to upload file"
+    );
+
+    common::get_id(&json)
+}
+
+/// downloads a file returning its etag and contents
+fn download_file(client: &UserClient, entry_id: i64, file_id: i64) -> (String, Vec<u8>) {
+    let res = common::result::expect_with_err(
+        client.get(format!("/entries/{}/files/{}", entry_id, file_id)).send(),
+        "failed to send file download request"
+    );
+
+    assert_eq!(res.status(), StatusCode::OK, "failed to download file");
+
+    let etag = res.headers()
+        .get("etag")
+        .and_then(|v| v.to_str().ok())
+        .expect("missing etag on file download")
+        .to_owned();
+    let body = common::result::expect_with_err(res.bytes(), "failed to read file download");
+
+    (etag, body.to_vec())
+}
+
+#[test]
+fn shared_blob_outlives_one_delete() {
+    let client = common::logged_in_client();
+    let first_entry = common::create_entry(&client);
+    let second_entry = common::create_entry(&client);
+    // unique per run so an earlier run does not already hold the blob
+    let data = format!(
+        "shared blob {} {}",
+        std::process::id(),
+        common::unix_epoch_sec().unwrap()
+    ).repeat(64).into_bytes();
+
+    let first_file = upload_file(&client, first_entry, &data);
+    let second_file = upload_file(&client, second_entry, &data);
+
+    let (first_etag, _) = download_file(&client, first_entry, first_file);
+    let (second_etag, _) = download_file(&client, second_entry, second_file);
+
+    assert_eq!(first_etag, second_etag, "the same data should be stored as the same blob");
+
+    common::expect_ok(
+        common::result::expect_with_err(
+            client.delete(format!("/entries/{}/files/{}", first_entry, first_file)).send(),
+            "failed to send file delete request"
+        ),
+        "failed to delete file"
+    );
+
+    let (_, body) = download_file(&client, second_entry, second_file);
+
+    assert!(body == data, "the other file should still be readable after one is deleted");
+
+    common::purge_entry(&client, first_entry);
+    common::purge_entry(&client, second_entry);
+}